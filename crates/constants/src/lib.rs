@@ -5,11 +5,29 @@ use serde::{Deserialize, Serialize};
 /// the number of views to gather information for ahead of time
 pub const LOOK_AHEAD: u64 = 5;
 
+/// the number of views a proposal can be ahead of the last decided view before a node syncs the
+/// decided chain in batches instead of fetching the missing leaves one by one
+pub const SYNC_VIEW_THRESHOLD: u64 = 20;
+
+/// the maximum number of decided leaves to request from a peer at a time when syncing
+pub const SYNC_BATCH_SIZE: u64 = 100;
+
+/// the number of views a node keeps its VID share after the view is decided, so peers that missed
+/// the payload can recover it
+pub const VID_SHARE_RETENTION_VIEWS: u64 = 100;
+
+/// the maximum number of VID shares a payload is dispersed into when storage nodes get shares in
+/// proportion to their stake; larger stake tables fall back to one share per storage node
+pub const VID_MAX_SHARES: usize = 1024;
+
 /// the default kademlia record republication interval (in seconds)
 pub const KAD_DEFAULT_REPUB_INTERVAL_SEC: u64 = 28800;
 
-/// the number of messages to cache in the combined network
-pub const COMBINED_NETWORK_CACHE_SIZE: usize = 1000;
+/// the number of views before the latest one whose received messages are deduplicated
+pub const NETWORK_DEDUP_VIEW_WINDOW: u64 = 10;
+
+/// the maximum number of received messages remembered for deduplication
+pub const NETWORK_DEDUP_MAX_DIGESTS: usize = 100_000;
 
 /// the number of messages to attempt to send over the primary network before switching to prefer the secondary network
 pub const COMBINED_NETWORK_MIN_PRIMARY_FAILURES: u64 = 5;
@@ -29,5 +47,15 @@ pub struct Version {
 /// Constant for protocol version 0.1.
 pub const VERSION_0_1: Version = Version { major: 0, minor: 1 };
 
+/// Constant for protocol version 0.2.
+pub const VERSION_0_2: Version = Version { major: 0, minor: 2 };
+
+/// The protocol versions this node can encode messages for, and so can upgrade to.
+pub const SUPPORTED_VERSIONS: [Version; 2] = [VERSION_0_1, VERSION_0_2];
+
+/// the minimum number of views between proposing an upgrade and the first view of the new
+/// version, which leaves time for the upgrade certificate to be formed and decided
+pub const UPGRADE_PROPOSAL_LEAD_VIEWS: u64 = 10;
+
 /// Default Channel Size for consensus event sharing
 pub const EVENT_CHANNEL_SIZE: usize = 100_000;
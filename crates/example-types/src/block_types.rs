@@ -5,7 +5,7 @@ use std::{
 
 use commit::{Commitment, Committable, RawCommitmentBuilder};
 use hotshot_types::{
-    data::{BlockError, VidCommitment, VidScheme, VidSchemeTrait},
+    data::{BlockError, VidCommitment, VidParams, VidScheme, VidSchemeTrait},
     traits::{
         block_contents::{vid_commitment, BlockHeader, TestableBlock, Transaction},
         BlockPayload, ValidatedState,
@@ -175,9 +175,13 @@ impl BlockPayload for TestBlockPayload {
 /// to the genesis payload.
 ///
 /// In that case, the payloads may mismatch and cause problems.
+///
+/// # Panics
+/// If no trusted setup for VID is available, see [`Leaf::genesis`](hotshot_types::data::Leaf::genesis).
 #[must_use]
 pub fn genesis_vid_commitment() -> <VidScheme as VidSchemeTrait>::Commit {
-    vid_commitment(&vec![], 8)
+    vid_commitment(&vec![], VidParams::for_num_nodes(8))
+        .expect("No trusted setup for VID to commit to the genesis payload")
 }
 
 /// A [`BlockHeader`] that commits to [`TestBlockPayload`].
@@ -29,7 +29,7 @@ use hotshot_types::traits::network::ConnectedNetwork;
 use hotshot_types::ValidatorConfig;
 use hotshot_types::{
     consensus::ConsensusMetricsValue,
-    data::{Leaf, TestableLeaf},
+    data::{load_vid_srs, Leaf, TestableLeaf},
     event::{Event, EventType},
     traits::{
         block_contents::TestableBlock,
@@ -168,7 +168,13 @@ fn webserver_network_from_config<TYPES: NodeType>(
         wait_between_polls,
     }: WebServerConfig = config.web_server_config.unwrap();
 
-    WebServerNetwork::create(url, wait_between_polls, pub_key, false)
+    WebServerNetwork::create(
+        url,
+        wait_between_polls,
+        pub_key,
+        false,
+        NetworkingMetricsValue::default(),
+    )
 }
 
 #[allow(clippy::cast_possible_truncation)]
@@ -276,6 +282,7 @@ async fn libp2p_network_from_config<TYPES: NodeType>(
         NetworkingMetricsValue::default(),
         node_config,
         pub_key.clone(),
+        &config.config.my_own_validator_config.private_key,
         Arc::new(RwLock::new(
             bootstrap_nodes
                 .iter()
@@ -324,11 +331,15 @@ pub trait RunDA<
     /// get the anchored view
     /// Note: sequencing leaf does not have state, so does not return state
     async fn initialize_state_and_hotshot(&self) -> SystemContextHandle<TYPES, NODE> {
+        let config = self.get_config();
+
+        // The genesis block commits to its payload with VID, so the trusted setup is needed first.
+        if let Some(path) = &config.config.vid_srs_path {
+            load_vid_srs(path).expect("Couldn't load the KZG SRS for VID");
+        }
         let initializer = hotshot::HotShotInitializer::<TYPES>::from_genesis(&TestInstanceState {})
             .expect("Couldn't generate genesis block");
 
-        let config = self.get_config();
-
         // Get KeyPair for certificate Aggregation
         let pk = config.config.my_own_validator_config.public_key.clone();
         let sk = config.config.my_own_validator_config.private_key.clone();
@@ -351,24 +362,12 @@ pub trait RunDA<
             _pd: PhantomData,
         };
 
-        let memberships = Memberships {
-            quorum_membership: <TYPES as NodeType>::Membership::create_election(
-                known_nodes_with_stake.clone(),
-                quorum_election_config.clone(),
-            ),
-            da_membership: <TYPES as NodeType>::Membership::create_election(
-                known_nodes_with_stake.clone(),
-                committee_election_config,
-            ),
-            vid_membership: <TYPES as NodeType>::Membership::create_election(
-                known_nodes_with_stake.clone(),
-                quorum_election_config.clone(),
-            ),
-            view_sync_membership: <TYPES as NodeType>::Membership::create_election(
-                known_nodes_with_stake.clone(),
-                quorum_election_config,
-            ),
-        };
+        let memberships = Memberships::create(
+            known_nodes_with_stake,
+            quorum_election_config,
+            committee_election_config,
+        )
+        .expect("Could not create the memberships");
 
         SystemContext::init(
             pk,
@@ -380,6 +379,7 @@ pub trait RunDA<
             networks_bundle,
             initializer,
             ConsensusMetricsValue::default(),
+            None,
         )
         .await
         .expect("Could not init hotshot")
@@ -541,8 +541,13 @@ where
 
         underlying_quorum_network.wait_for_ready().await;
 
-        let da_channel: WebServerNetwork<TYPES> =
-            WebServerNetwork::create(url.clone(), wait_between_polls, pub_key.clone(), true);
+        let da_channel: WebServerNetwork<TYPES> = WebServerNetwork::create(
+            url.clone(),
+            wait_between_polls,
+            pub_key.clone(),
+            true,
+            NetworkingMetricsValue::default(),
+        );
 
         WebServerDARun {
             config,
@@ -693,7 +698,13 @@ where
         let web_quorum_network =
             webserver_network_from_config::<TYPES>(config.clone(), pub_key.clone());
 
-        let web_da_network = WebServerNetwork::create(url, wait_between_polls, pub_key, true);
+        let web_da_network = WebServerNetwork::create(
+            url,
+            wait_between_polls,
+            pub_key,
+            true,
+            NetworkingMetricsValue::default(),
+        );
 
         web_quorum_network.wait_for_ready().await;
 
@@ -1,5 +1,717 @@
 //! This is a `SNARKed` QC implemenation, see more in the `HotShot` paper.
+//!
+//! A QC consists of an aggregated signature, the aggregated verification key of the signers, and
+//! a constant-size Plonk proof that this key aggregates the keys of a subset of a committed stake
+//! table whose stake passes the threshold. Checking a QC thus only requires the stake table
+//! digest, and neither the signers nor the stake table keys.
 
 mod circuit;
 
-// TODO: add impl: https://github.com/EspressoSystems/hotshot-primitives/issues/5
+use self::circuit::{compute_stake_table_hash, QCKeyAggregateGadget};
+use ark_bn254::{Bn254, Fr as Fr254};
+use ark_ec::{
+    short_weierstrass::{Affine, Projective, SWCurveConfig},
+    CurveGroup,
+};
+use ark_ff::{BigInteger, PrimeField};
+use ark_std::{
+    format,
+    iter::repeat,
+    marker::PhantomData,
+    rand::{CryptoRng, RngCore},
+    string::ToString,
+    vec::Vec,
+    Zero,
+};
+use bitvec::prelude::*;
+use ethereum_types::U256;
+use generic_array::GenericArray;
+use hotshot_types::traits::qc::QuorumCertificateScheme;
+use jf_plonk::{
+    proof_system::{
+        structs::{Proof, ProvingKey, UniversalSrs, VerifyingKey},
+        PlonkKzgSnark, UniversalSNARK,
+    },
+    transcript::StandardTranscript,
+};
+use jf_primitives::{
+    errors::{PrimitivesError, PrimitivesError::ParameterError},
+    signatures::AggregateableSignatureSchemes,
+};
+use jf_relation::{
+    errors::CircuitError,
+    gadgets::{
+        ecc::emulated::{EmulatedSWPointVariable, SWPoint},
+        EmulationConfig, SerializableEmulatedStruct,
+    },
+    Arithmetization, BoolVar, Circuit, PlonkCircuit, Variable,
+};
+use jf_utils::canonical;
+use serde::{Deserialize, Serialize};
+use typenum::U32;
+
+/// Bit length of the range lookup table of the QC circuit
+const RANGE_BIT_LEN: usize = 16;
+
+/// Verification keys which are points on the short Weierstrass curve `P`, so that they can be
+/// aggregated inside the QC circuit.
+pub trait SWVerKey<P: SWCurveConfig>: Sized {
+    /// Returns the point of this key
+    fn to_point(&self) -> Affine<P>;
+
+    /// Returns the key of a given point
+    fn from_point(point: Affine<P>) -> Self;
+}
+
+/// An implementation of QC using an aggregatable signature scheme whose keys live on the curve
+/// `P`, and Plonk proofs over BN254.
+pub struct SnarkedQC<A, P>(PhantomData<A>, PhantomData<P>);
+
+/// Prover parameters of [`SnarkedQC`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct QCProverParams<A: AggregateableSignatureSchemes> {
+    /// the stake table (snapshot) the QCs are assembled against
+    pub stake_table: Vec<(A::VerificationKey, U256)>,
+    /// the number of entries the circuit supports, the stake table is padded to this size
+    pub capacity: usize,
+    /// threshold for the accumulated stake of the signers
+    pub threshold: U256,
+    /// Plonk proving key of the QC circuit
+    #[serde(with = "canonical")]
+    pub proving_key: ProvingKey<Bn254>,
+    /// public parameter for the aggregated signature scheme
+    pub agg_sig_pp: A::PublicParameter,
+}
+
+/// Verifier parameters of [`SnarkedQC`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct QCVerifierParams<A: AggregateableSignatureSchemes> {
+    /// digest of the padded stake table the QCs are checked against
+    #[serde(with = "canonical")]
+    pub stake_table_digest: Fr254,
+    /// threshold for the accumulated stake of the signers
+    pub threshold: U256,
+    /// Plonk verifying key of the QC circuit
+    #[serde(with = "canonical")]
+    pub verifying_key: VerifyingKey<Bn254>,
+    /// public parameter for the aggregated signature scheme
+    pub agg_sig_pp: A::PublicParameter,
+    /// the keys of the stake table, which are only needed to trace the signers of a QC
+    pub ver_keys: Option<Vec<A::VerificationKey>>,
+}
+
+/// A quorum certificate of [`SnarkedQC`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SnarkedQCData<A: AggregateableSignatureSchemes> {
+    /// the aggregated signature
+    pub sig: A::Signature,
+    /// the aggregated verification key of the signers
+    pub agg_ver_key: A::VerificationKey,
+    /// proof that `agg_ver_key` aggregates signers whose stake passes the threshold
+    #[serde(with = "canonical")]
+    pub proof: Proof<Bn254>,
+    /// the signers, which are only needed to trace them, see [`SnarkedQCData::without_signers`]
+    pub signers: Option<BitVec>,
+}
+
+impl<A: AggregateableSignatureSchemes> SnarkedQCData<A> {
+    /// Drop the signers, leaving the constant-size part of the QC which is enough to check it.
+    #[must_use]
+    pub fn without_signers(self) -> Self {
+        Self {
+            signers: None,
+            ..self
+        }
+    }
+}
+
+/// Convert a stake amount to a field element
+fn u256_to_field<F: PrimeField>(amount: &U256) -> F {
+    let mut bytes = [0u8; 32];
+    amount.to_little_endian(&mut bytes);
+    F::from_le_bytes_mod_order(&bytes)
+}
+
+/// Whether `amount` is below the modulus of `F`, so that [`u256_to_field`] keeps it as is
+fn fits_in_field<F: PrimeField>(amount: &U256) -> bool {
+    *amount < U256::from_little_endian(&F::MODULUS.to_bytes_le())
+}
+
+/// Converts a circuit error to a [`PrimitivesError`]
+fn circuit_error(err: CircuitError) -> PrimitivesError {
+    ParameterError(format!("QC circuit error: {err}"))
+}
+
+/// Build the QC circuit over `stake_table` padded to `capacity` entries, proving that the keys
+/// selected by `signers` aggregate to a key whose signers' stake is at least `threshold`.
+/// Returns the circuit together with its public input.
+fn build_qc_circuit<P>(
+    stake_table: &[(Affine<P>, U256)],
+    capacity: usize,
+    signers: &BitSlice,
+    threshold: &U256,
+) -> Result<(PlonkCircuit<Fr254>, Vec<Fr254>), CircuitError>
+where
+    P: SWCurveConfig,
+    P::BaseField: EmulationConfig<Fr254>,
+{
+    if stake_table.len() > capacity || signers.len() != stake_table.len() {
+        return Err(CircuitError::ParameterError(format!(
+            "stake table len {} and bit vector len {} should be equal and at most {}",
+            stake_table.len(),
+            signers.len(),
+            capacity,
+        )));
+    }
+    let padding_len = capacity - stake_table.len();
+    let keys: Vec<SWPoint<P::BaseField>> = stake_table
+        .iter()
+        .map(|(key, _)| (*key).into())
+        .chain(repeat(SWPoint::default()).take(padding_len))
+        .collect();
+    let amounts: Vec<Fr254> = stake_table
+        .iter()
+        .map(|(_, amount)| u256_to_field(amount))
+        .chain(repeat(Fr254::zero()).take(padding_len))
+        .collect();
+    let selectors: Vec<bool> = signers
+        .iter()
+        .by_vals()
+        .chain(repeat(false).take(padding_len))
+        .collect();
+    let agg_key: SWPoint<P::BaseField> = stake_table
+        .iter()
+        .zip(signers.iter().by_vals())
+        .filter(|(_, signed)| *signed)
+        .fold(Projective::<P>::zero(), |acc, ((key, _), _)| acc + key)
+        .into_affine()
+        .into();
+    let digest = compute_stake_table_hash::<Fr254, SWPoint<P::BaseField>>(&amounts, &keys);
+    let threshold = u256_to_field::<Fr254>(threshold);
+
+    let mut circuit = PlonkCircuit::<Fr254>::new_ultra_plonk(RANGE_BIT_LEN);
+    // public input
+    let agg_key_var = circuit.create_public_emulated_sw_point_variable(agg_key)?;
+    let digest_var = circuit.create_public_variable(digest)?;
+    let threshold_var = circuit.create_public_variable(threshold)?;
+    // witness
+    let key_vars = keys
+        .iter()
+        .map(|key| circuit.create_emulated_sw_point_variable(*key))
+        .collect::<Result<Vec<_>, _>>()?;
+    let amount_vars = amounts
+        .iter()
+        .map(|amount| circuit.create_variable(*amount))
+        .collect::<Result<Vec<Variable>, _>>()?;
+    let selector_vars = selectors
+        .iter()
+        .map(|selected| circuit.create_boolean_variable(*selected))
+        .collect::<Result<Vec<BoolVar>, _>>()?;
+
+    circuit.check_aggregate_vk::<P::BaseField, EmulatedSWPointVariable<P::BaseField>>(
+        &key_vars,
+        &selector_vars,
+        &agg_key_var,
+        P::COEFF_A,
+    )?;
+    circuit.check_stake_table_digest::<P::BaseField, EmulatedSWPointVariable<P::BaseField>>(
+        &key_vars,
+        &amount_vars,
+        digest_var,
+    )?;
+    circuit.check_threshold(&amount_vars, &selector_vars, threshold_var)?;
+    circuit.finalize_for_arithmetization()?;
+
+    let mut public_input: Vec<Fr254> = agg_key.serialize_to_native_elements();
+    public_input.push(digest);
+    public_input.push(threshold);
+    Ok((circuit, public_input))
+}
+
+impl<A, P> SnarkedQC<A, P>
+where
+    A: AggregateableSignatureSchemes,
+    A::VerificationKey: SWVerKey<P>,
+    P: SWCurveConfig,
+    P::BaseField: EmulationConfig<Fr254>,
+{
+    /// Returns the size of the universal SRS needed to preprocess the circuit for a stake table
+    /// of `capacity` entries.
+    ///
+    /// # Errors
+    /// Errors if the circuit cannot be built.
+    pub fn srs_size(capacity: usize) -> Result<usize, PrimitivesError> {
+        let (circuit, _) = build_qc_circuit::<P>(&[], capacity, BitSlice::empty(), &U256::zero())
+            .map_err(circuit_error)?;
+        circuit.srs_size().map_err(circuit_error)
+    }
+
+    /// Preprocess the QC circuit for `stake_table` padded to `capacity` entries, returning the
+    /// parameters to assemble and check QCs against it.
+    ///
+    /// # Errors
+    /// Errors if the stake table exceeds the capacity or the SRS is too small, or if the total
+    /// stake or the threshold is not below the BN254 scalar field modulus.
+    pub fn preprocess(
+        srs: &UniversalSrs<Bn254>,
+        stake_table: Vec<(A::VerificationKey, U256)>,
+        capacity: usize,
+        threshold: U256,
+        agg_sig_pp: A::PublicParameter,
+    ) -> Result<(QCProverParams<A>, QCVerifierParams<A>), PrimitivesError> {
+        // The circuit sums the amounts in the scalar field, which must not wrap around.
+        let total_stake = stake_table
+            .iter()
+            .try_fold(U256::zero(), |total, (_, amount)| {
+                total.checked_add(*amount)
+            });
+        if !total_stake.is_some_and(|total| fits_in_field::<Fr254>(&total)) {
+            return Err(ParameterError(
+                "total stake must be below the scalar field modulus".to_string(),
+            ));
+        }
+        if !fits_in_field::<Fr254>(&threshold) {
+            return Err(ParameterError(
+                "threshold must be below the scalar field modulus".to_string(),
+            ));
+        }
+        let points: Vec<(Affine<P>, U256)> = stake_table
+            .iter()
+            .map(|(key, amount)| (key.to_point(), *amount))
+            .collect();
+        // The circuit shape does not depend on the signers, so none are selected here.
+        let no_signers = bitvec![0; stake_table.len()];
+        let (circuit, public_input) =
+            build_qc_circuit(&points, capacity, &no_signers, &U256::zero())
+                .map_err(circuit_error)?;
+        let (proving_key, verifying_key) = PlonkKzgSnark::<Bn254>::preprocess(srs, &circuit)
+            .map_err(|err| ParameterError(err.to_string()))?;
+        // The digest is the public input right after the aggregated key.
+        let stake_table_digest = public_input[public_input.len() - 2];
+        let ver_keys = stake_table.iter().map(|(key, _)| key.clone()).collect();
+        Ok((
+            QCProverParams {
+                stake_table,
+                capacity,
+                threshold,
+                proving_key,
+                agg_sig_pp: agg_sig_pp.clone(),
+            },
+            QCVerifierParams {
+                stake_table_digest,
+                threshold,
+                verifying_key,
+                agg_sig_pp,
+                ver_keys: Some(ver_keys),
+            },
+        ))
+    }
+}
+
+impl<A, P> QuorumCertificateScheme<A> for SnarkedQC<A, P>
+where
+    A: AggregateableSignatureSchemes + Serialize + for<'a> Deserialize<'a>,
+    A::VerificationKey: SWVerKey<P>,
+    P: SWCurveConfig,
+    P::BaseField: EmulationConfig<Fr254>,
+{
+    type QCProverParams = QCProverParams<A>;
+    type QCVerifierParams = QCVerifierParams<A>;
+    type QC = SnarkedQCData<A>;
+    type MessageLength = U32;
+    type QuorumSize = U256;
+
+    fn sign<R: CryptoRng + RngCore>(
+        agg_sig_pp: &A::PublicParameter,
+        message: &GenericArray<A::MessageUnit, Self::MessageLength>,
+        sk: &A::SigningKey,
+        prng: &mut R,
+    ) -> Result<A::Signature, PrimitivesError> {
+        A::sign(agg_sig_pp, sk, message, prng)
+    }
+
+    fn assemble(
+        qc_pp: &Self::QCProverParams,
+        signers: &BitSlice,
+        sigs: &[A::Signature],
+    ) -> Result<Self::QC, PrimitivesError> {
+        if signers.len() != qc_pp.stake_table.len() {
+            return Err(ParameterError(format!(
+                "bit vector len {} != the number of stake entries {}",
+                signers.len(),
+                qc_pp.stake_table.len(),
+            )));
+        }
+        let total_weight = qc_pp
+            .stake_table
+            .iter()
+            .zip(signers.iter().by_vals())
+            .filter(|(_, signed)| *signed)
+            .fold(U256::zero(), |acc, ((_, amount), _)| acc + amount);
+        if total_weight < qc_pp.threshold {
+            return Err(ParameterError(format!(
+                "total_weight {} less than threshold {}",
+                total_weight, qc_pp.threshold,
+            )));
+        }
+        let ver_keys: Vec<A::VerificationKey> = qc_pp
+            .stake_table
+            .iter()
+            .zip(signers.iter().by_vals())
+            .filter(|(_, signed)| *signed)
+            .map(|((key, _), _)| key.clone())
+            .collect();
+        if ver_keys.len() != sigs.len() {
+            return Err(ParameterError(format!(
+                "the number of ver_keys {} != the number of partial signatures {}",
+                ver_keys.len(),
+                sigs.len(),
+            )));
+        }
+        let sig = A::aggregate(&qc_pp.agg_sig_pp, &ver_keys[..], sigs)?;
+        let agg_ver_key = A::VerificationKey::from_point(
+            ver_keys
+                .iter()
+                .fold(Projective::<P>::zero(), |acc, key| acc + key.to_point())
+                .into_affine(),
+        );
+
+        let points: Vec<(Affine<P>, U256)> = qc_pp
+            .stake_table
+            .iter()
+            .map(|(key, amount)| (key.to_point(), *amount))
+            .collect();
+        let (circuit, _) = build_qc_circuit(&points, qc_pp.capacity, signers, &qc_pp.threshold)
+            .map_err(circuit_error)?;
+        let proof = PlonkKzgSnark::<Bn254>::prove::<_, _, StandardTranscript>(
+            &mut rand::thread_rng(),
+            &circuit,
+            &qc_pp.proving_key,
+            None,
+        )
+        .map_err(|err| ParameterError(err.to_string()))?;
+
+        Ok(SnarkedQCData {
+            sig,
+            agg_ver_key,
+            proof,
+            signers: Some(signers.into()),
+        })
+    }
+
+    /// The returned quorum size is the threshold, since the proof only shows that the stake of
+    /// the signers passes it.
+    fn check(
+        qc_vp: &Self::QCVerifierParams,
+        message: &GenericArray<A::MessageUnit, Self::MessageLength>,
+        qc: &Self::QC,
+    ) -> Result<Self::QuorumSize, PrimitivesError> {
+        let agg_key: SWPoint<P::BaseField> = qc.agg_ver_key.to_point().into();
+        let mut public_input: Vec<Fr254> = agg_key.serialize_to_native_elements();
+        public_input.push(qc_vp.stake_table_digest);
+        public_input.push(u256_to_field(&qc_vp.threshold));
+        PlonkKzgSnark::<Bn254>::verify::<StandardTranscript>(
+            &qc_vp.verifying_key,
+            &public_input,
+            &qc.proof,
+            None,
+        )
+        .map_err(|err| PrimitivesError::VerificationError(err.to_string()))?;
+        A::verify(&qc_vp.agg_sig_pp, &qc.agg_ver_key, message, &qc.sig)?;
+
+        Ok(qc_vp.threshold)
+    }
+
+    fn trace(
+        qc_vp: &Self::QCVerifierParams,
+        message: &GenericArray<<A>::MessageUnit, Self::MessageLength>,
+        qc: &Self::QC,
+    ) -> Result<Vec<<A>::VerificationKey>, PrimitivesError> {
+        let (Some(ver_keys), Some(signers)) = (&qc_vp.ver_keys, &qc.signers) else {
+            return Err(ParameterError(
+                "tracing needs both the stake table keys and the signers".to_string(),
+            ));
+        };
+        if signers.len() != ver_keys.len() {
+            return Err(ParameterError(format!(
+                "signers bit vector len {} != the number of stake entries {}",
+                signers.len(),
+                ver_keys.len(),
+            )));
+        }
+
+        Self::check(qc_vp, message, qc)?;
+
+        let signer_pks: Vec<_> = ver_keys
+            .iter()
+            .zip(signers.iter().by_vals())
+            .filter(|(_, signed)| *signed)
+            .map(|(key, _)| key.clone())
+            .collect();
+        // The signers must be the ones whose keys were aggregated by the proof.
+        let agg_point = signer_pks
+            .iter()
+            .fold(Projective::<P>::zero(), |acc, key| acc + key.to_point())
+            .into_affine();
+        if agg_point != qc.agg_ver_key.to_point() {
+            return Err(ParameterError(
+                "signers do not match the aggregated verification key".to_string(),
+            ));
+        }
+        Ok(signer_pks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_377::{
+        g1::Config as Param377, Bls12_377, Fr as Fr377, G1Affine, G1Projective, G2Affine,
+        G2Projective,
+    };
+    use ark_ec::{pairing::Pairing, AffineRepr, Group};
+    use ark_std::{vec, UniformRand};
+    use jf_primitives::signatures::SignatureScheme;
+    use zeroize::Zeroize;
+
+    /// A BLS-like signature scheme over BLS12-377 whose verification keys are in G1, so that they
+    /// can be aggregated in the QC circuit. Messages are hashed to G2 insecurely, this is only
+    /// meant for testing.
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct TestAggSig;
+
+    /// Signing key of [`TestAggSig`]
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct TestSignKey(Fr377);
+
+    impl Zeroize for TestSignKey {
+        fn zeroize(&mut self) {
+            self.0 = Fr377::zero();
+        }
+    }
+
+    /// Verification key of [`TestAggSig`]
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct TestVerKey(#[serde(with = "canonical")] G1Affine);
+
+    impl SWVerKey<Param377> for TestVerKey {
+        fn to_point(&self) -> G1Affine {
+            self.0
+        }
+
+        fn from_point(point: G1Affine) -> Self {
+            Self(point)
+        }
+    }
+
+    /// Signature of [`TestAggSig`]
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct TestSig(#[serde(with = "canonical")] G2Affine);
+
+    /// Insecurely hash a message to G2
+    fn hash_to_g2(msg: &[u8]) -> G2Projective {
+        G2Projective::generator() * Fr377::from_le_bytes_mod_order(msg)
+    }
+
+    impl SignatureScheme for TestAggSig {
+        const CS_ID: &'static str = "TEST_AGG_SIG";
+        type SigningKey = TestSignKey;
+        type VerificationKey = TestVerKey;
+        type PublicParameter = ();
+        type Signature = TestSig;
+        type MessageUnit = u8;
+
+        fn param_gen<R: CryptoRng + RngCore>(
+            _prng: Option<&mut R>,
+        ) -> Result<Self::PublicParameter, PrimitivesError> {
+            Ok(())
+        }
+
+        fn key_gen<R: CryptoRng + RngCore>(
+            _pp: &Self::PublicParameter,
+            prng: &mut R,
+        ) -> Result<(Self::SigningKey, Self::VerificationKey), PrimitivesError> {
+            let sk = Fr377::rand(prng);
+            Ok((
+                TestSignKey(sk),
+                TestVerKey((G1Projective::generator() * sk).into_affine()),
+            ))
+        }
+
+        fn sign<R: CryptoRng + RngCore, M: AsRef<[Self::MessageUnit]>>(
+            _pp: &Self::PublicParameter,
+            sk: &Self::SigningKey,
+            msg: M,
+            _prng: &mut R,
+        ) -> Result<Self::Signature, PrimitivesError> {
+            Ok(TestSig((hash_to_g2(msg.as_ref()) * sk.0).into_affine()))
+        }
+
+        fn verify<M: AsRef<[Self::MessageUnit]>>(
+            _pp: &Self::PublicParameter,
+            vk: &Self::VerificationKey,
+            msg: M,
+            sig: &Self::Signature,
+        ) -> Result<(), PrimitivesError> {
+            if Bls12_377::pairing(vk.0, hash_to_g2(msg.as_ref()))
+                == Bls12_377::pairing(G1Affine::generator(), sig.0)
+            {
+                Ok(())
+            } else {
+                Err(PrimitivesError::VerificationError(
+                    "invalid signature".to_string(),
+                ))
+            }
+        }
+    }
+
+    impl AggregateableSignatureSchemes for TestAggSig {
+        fn aggregate(
+            _pp: &Self::PublicParameter,
+            _vks: &[Self::VerificationKey],
+            sigs: &[Self::Signature],
+        ) -> Result<Self::Signature, PrimitivesError> {
+            Ok(TestSig(
+                sigs.iter()
+                    .fold(G2Projective::zero(), |acc, sig| acc + sig.0)
+                    .into_affine(),
+            ))
+        }
+
+        fn aggregate_verify<M: AsRef<[Self::MessageUnit]>>(
+            _pp: &Self::PublicParameter,
+            vks: &[Self::VerificationKey],
+            msgs: &[M],
+            sig: &Self::Signature,
+        ) -> Result<(), PrimitivesError> {
+            let lhs = Bls12_377::multi_pairing(
+                vks.iter().map(|vk| vk.0),
+                msgs.iter().map(|msg| hash_to_g2(msg.as_ref())),
+            );
+            if lhs == Bls12_377::pairing(G1Affine::generator(), sig.0) {
+                Ok(())
+            } else {
+                Err(PrimitivesError::VerificationError(
+                    "invalid signature".to_string(),
+                ))
+            }
+        }
+
+        fn multi_sig_verify(
+            pp: &Self::PublicParameter,
+            vks: &[Self::VerificationKey],
+            msg: &[Self::MessageUnit],
+            sig: &Self::Signature,
+        ) -> Result<(), PrimitivesError> {
+            let agg_vk = TestVerKey(
+                vks.iter()
+                    .fold(G1Projective::zero(), |acc, vk| acc + vk.0)
+                    .into_affine(),
+            );
+            Self::verify(pp, &agg_vk, msg, sig)
+        }
+    }
+
+    type QC = SnarkedQC<TestAggSig, Param377>;
+
+    #[test]
+    fn crypto_test_snarked_qc() {
+        let mut rng = jf_utils::test_rng();
+        let keys = (0..3)
+            .map(|_| TestAggSig::key_gen(&(), &mut rng).unwrap())
+            .collect::<Vec<_>>();
+        let stake_table = keys
+            .iter()
+            .zip([3u8, 5, 7])
+            .map(|((_, vk), amount)| (vk.clone(), U256::from(amount)))
+            .collect();
+        let capacity = 4;
+        let srs = PlonkKzgSnark::<Bn254>::universal_setup_for_testing(
+            QC::srs_size(capacity).unwrap(),
+            &mut rng,
+        )
+        .unwrap();
+        let (qc_pp, qc_vp) =
+            QC::preprocess(&srs, stake_table, capacity, U256::from(10u8), ()).unwrap();
+
+        let msg = [72u8; 32];
+        let sigs = keys
+            .iter()
+            .map(|(sk, _)| QC::sign(&(), &msg.into(), sk, &mut rng).unwrap())
+            .collect::<Vec<_>>();
+
+        // happy path
+        let signers = bitvec![0, 1, 1];
+        let qc = QC::assemble(&qc_pp, signers.as_bitslice(), &sigs[1..]).unwrap();
+        assert_eq!(
+            QC::check(&qc_vp, &msg.into(), &qc).unwrap(),
+            U256::from(10u8)
+        );
+        assert_eq!(
+            QC::trace(&qc_vp, &msg.into(), &qc).unwrap(),
+            vec![keys[1].1.clone(), keys[2].1.clone()],
+        );
+        assert_eq!(
+            qc,
+            bincode::deserialize(&bincode::serialize(&qc).unwrap()).unwrap()
+        );
+
+        // The QC can be checked without its signers, but not traced
+        let light_qc = qc.clone().without_signers();
+        assert!(QC::check(&qc_vp, &msg.into(), &light_qc).is_ok());
+        assert!(QC::trace(&qc_vp, &msg.into(), &light_qc).is_err());
+
+        // bad paths
+        // total weight under threshold
+        assert!(QC::assemble(&qc_pp, bitvec![1, 1, 0].as_bitslice(), &sigs[..2]).is_err());
+        // number of signatures unmatch
+        assert!(QC::assemble(&qc_pp, signers.as_bitslice(), &sigs[1..2]).is_err());
+        // wrong bool vector length
+        assert!(QC::assemble(&qc_pp, bitvec![0, 1, 1, 0].as_bitslice(), &sigs[1..]).is_err());
+
+        let bad_msg = [70u8; 32];
+        assert!(QC::check(&qc_vp, &bad_msg.into(), &qc).is_err());
+        // The aggregated key must be the one the proof was made for
+        let bad_key_qc = SnarkedQCData {
+            sig: sigs[0].clone(),
+            agg_ver_key: keys[0].1.clone(),
+            ..qc.clone()
+        };
+        assert!(QC::check(&qc_vp, &msg.into(), &bad_key_qc).is_err());
+        // The signers must be the ones whose keys were aggregated
+        let bad_signers_qc = SnarkedQCData {
+            signers: Some(bitvec![1, 0, 1]),
+            ..qc
+        };
+        assert!(QC::trace(&qc_vp, &msg.into(), &bad_signers_qc).is_err());
+    }
+
+    #[test]
+    fn crypto_test_snarked_qc_rejects_amounts_outside_field() {
+        let mut rng = jf_utils::test_rng();
+        let keys = (0..2)
+            .map(|_| TestAggSig::key_gen(&(), &mut rng).unwrap().1)
+            .collect::<Vec<_>>();
+        let capacity = 4;
+        let srs = PlonkKzgSnark::<Bn254>::universal_setup_for_testing(
+            QC::srs_size(capacity).unwrap(),
+            &mut rng,
+        )
+        .unwrap();
+        let modulus = U256::from_little_endian(&Fr254::MODULUS.to_bytes_le());
+
+        // a single amount equal to the modulus
+        let stake_table = vec![(keys[0].clone(), modulus), (keys[1].clone(), U256::one())];
+        assert!(QC::preprocess(&srs, stake_table, capacity, U256::one(), ()).is_err());
+        // amounts below the modulus whose total is not
+        let half = modulus / 2 + 1;
+        let stake_table = vec![(keys[0].clone(), half), (keys[1].clone(), half)];
+        assert!(QC::preprocess(&srs, stake_table, capacity, U256::one(), ()).is_err());
+        // a threshold equal to the modulus
+        let stake_table = vec![
+            (keys[0].clone(), U256::one()),
+            (keys[1].clone(), U256::one()),
+        ];
+        assert!(QC::preprocess(&srs, stake_table, capacity, modulus, ()).is_err());
+    }
+}
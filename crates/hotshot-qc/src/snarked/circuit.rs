@@ -21,7 +21,6 @@ use jf_relation::{
 /// Digest a list of verification keys and their associated stake amounts
 /// * `stack_amts` - stake amounts
 /// * `keys` - list of verification keys
-#[allow(dead_code)]
 pub fn compute_stake_table_hash<F: RescueParameter, T: SerializableEmulatedStruct<F>>(
     stake_amts: &[F],
     keys: &[T],
@@ -2,3 +2,6 @@
 
 /// Capacity of a stake table
 pub const STAKE_TABLE_CAPACITY: usize = 200;
+
+/// Default number of past blocks whose stake table snapshots are retained
+pub const SNAPSHOT_HISTORY_WINDOW: u64 = 1000;
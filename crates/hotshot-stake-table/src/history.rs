@@ -0,0 +1,179 @@
+//! Bounded history of stake table snapshots, indexed by block height.
+
+use ark_std::collections::BTreeMap;
+use hotshot_types::traits::stake_table::StakeTableError;
+use serde::{Deserialize, Serialize};
+
+/// Snapshots of a stake table at past block heights, answering `SnapshotVersion::BlockNum`.
+///
+/// A snapshot is only stored when the table changed since the previously recorded block, and a
+/// block is answered with the latest snapshot recorded at or before it. Blocks more than
+/// `window` blocks older than the last recorded one are pruned.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotHistory<S> {
+    /// Stored snapshots, keyed by the block height at which they were recorded
+    snapshots: BTreeMap<u64, S>,
+    /// Number of blocks before the last recorded one which can still be queried
+    window: u64,
+    /// Oldest block height which can be queried
+    first_block: u64,
+    /// Last recorded block height, if any
+    last_block: Option<u64>,
+}
+
+impl<S: PartialEq> SnapshotHistory<S> {
+    /// Create an empty history retaining `window` blocks.
+    #[must_use]
+    pub fn new(window: u64) -> Self {
+        Self {
+            snapshots: BTreeMap::new(),
+            window,
+            first_block: 0,
+            last_block: None,
+        }
+    }
+
+    /// Record `snapshot` as the state of the stake table at block height `block_num`.
+    ///
+    /// # Errors
+    /// Return err if `block_num` is not after the last recorded block.
+    pub fn record(&mut self, block_num: u64, snapshot: S) -> Result<(), StakeTableError> {
+        if matches!(self.last_block, Some(last) if block_num <= last) {
+            return Err(StakeTableError::StaleBlockNum);
+        }
+        if self.last_block.is_none() {
+            self.first_block = block_num;
+        }
+        if self.snapshots.values().next_back() != Some(&snapshot) {
+            self.snapshots.insert(block_num, snapshot);
+        }
+        self.last_block = Some(block_num);
+        self.prune();
+        Ok(())
+    }
+
+    /// Returns the snapshot in effect at block height `block_num`.
+    ///
+    /// # Errors
+    /// Return err if `block_num` has been pruned or is not recorded yet.
+    pub fn get(&self, block_num: u64) -> Result<&S, StakeTableError> {
+        match self.last_block {
+            Some(last) if self.first_block <= block_num && block_num <= last => self
+                .snapshots
+                .range(..=block_num)
+                .next_back()
+                .map(|(_, snapshot)| snapshot)
+                .ok_or(StakeTableError::SnapshotUnsupported),
+            _ => Err(StakeTableError::SnapshotUnsupported),
+        }
+    }
+
+    /// Returns the number of blocks retained before the last recorded one.
+    #[must_use]
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    /// Change the number of blocks retained before the last recorded one.
+    /// Shrinking the window prunes the snapshots which fall out of it.
+    pub fn set_window(&mut self, window: u64) {
+        self.window = window;
+        self.prune();
+    }
+
+    /// Drop the snapshots which are no longer needed to answer a block in the window.
+    fn prune(&mut self) {
+        let Some(last) = self.last_block else {
+            return;
+        };
+        self.first_block = self.first_block.max(last.saturating_sub(self.window));
+        // Keep the snapshot in effect at `first_block`, which may have been recorded before it.
+        if let Some(&start) = self
+            .snapshots
+            .range(..=self.first_block)
+            .next_back()
+            .map(|(k, _)| k)
+        {
+            self.snapshots = self.snapshots.split_off(&start);
+        }
+    }
+}
+
+impl<S> SnapshotHistory<S> {
+    /// Returns the stored snapshots, ordered by the block height at which they were recorded.
+    pub(crate) fn snapshots(&self) -> impl Iterator<Item = &S> {
+        self.snapshots.values()
+    }
+
+    /// Convert every stored snapshot with `f`, keeping the recorded block heights.
+    pub(crate) fn map<T>(&self, mut f: impl FnMut(&S) -> T) -> SnapshotHistory<T> {
+        SnapshotHistory {
+            snapshots: self
+                .snapshots
+                .iter()
+                .map(|(block_num, snapshot)| (*block_num, f(snapshot)))
+                .collect(),
+            window: self.window,
+            first_block: self.first_block,
+            last_block: self.last_block,
+        }
+    }
+
+    /// Convert every stored snapshot with the fallible `f`, keeping the recorded block heights.
+    ///
+    /// # Errors
+    /// Return the first error returned by `f`.
+    pub(crate) fn try_map<T, E>(
+        &self,
+        mut f: impl FnMut(&S) -> Result<T, E>,
+    ) -> Result<SnapshotHistory<T>, E> {
+        Ok(SnapshotHistory {
+            snapshots: self
+                .snapshots
+                .iter()
+                .map(|(block_num, snapshot)| Ok((*block_num, f(snapshot)?)))
+                .collect::<Result<_, E>>()?,
+            window: self.window,
+            first_block: self.first_block,
+            last_block: self.last_block,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SnapshotHistory;
+
+    #[test]
+    fn test_snapshot_history() {
+        let mut history = SnapshotHistory::new(10);
+        assert!(history.get(0).is_err());
+
+        history.record(5, 'a').unwrap();
+        // Unchanged snapshots are not stored again
+        history.record(6, 'a').unwrap();
+        history.record(8, 'b').unwrap();
+        assert!(history.record(8, 'c').is_err());
+        assert!(history.get(4).is_err());
+        assert_eq!(history.get(5).unwrap(), &'a');
+        assert_eq!(history.get(7).unwrap(), &'a');
+        assert_eq!(history.get(8).unwrap(), &'b');
+        // Blocks after the last recorded one are unknown
+        assert!(history.get(9).is_err());
+        assert_eq!(history.snapshots.len(), 2);
+
+        // Block 7 is still answered by the snapshot recorded at block 5
+        history.record(17, 'c').unwrap();
+        assert!(history.get(6).is_err());
+        assert_eq!(history.get(7).unwrap(), &'a');
+        history.record(18, 'c').unwrap();
+        assert!(history.get(7).is_err());
+        assert_eq!(history.get(8).unwrap(), &'b');
+        assert_eq!(history.snapshots.len(), 2);
+
+        history.set_window(0);
+        assert!(history.get(17).is_err());
+        assert_eq!(history.get(18).unwrap(), &'c');
+        assert_eq!(history.snapshots.len(), 1);
+    }
+}
@@ -1,5 +1,7 @@
 //! This crate contains some stake table implementations for `HotShot` system.
 pub mod config;
+pub mod history;
 pub mod mt_based;
+pub mod persistence;
 pub mod utils;
 pub mod vec_based;
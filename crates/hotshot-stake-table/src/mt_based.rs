@@ -3,16 +3,30 @@
 mod config;
 mod internal;
 
-use self::internal::{to_merkle_path, Key, MerkleCommitment, MerkleProof, PersistentMerkleNode};
+use self::internal::{
+    to_merkle_path, Key, MerkleCommitment, MerkleNodeArena, MerkleProof, PersistentMerkleNode,
+};
+use crate::{
+    config::SNAPSHOT_HISTORY_WINDOW,
+    history::SnapshotHistory,
+    persistence::{self, PersistenceError},
+};
 use ark_std::{collections::HashMap, rand::SeedableRng, sync::Arc};
 use digest::crypto_common::rand_core::CryptoRngCore;
 use ethereum_types::{U256, U512};
 use hotshot_types::traits::stake_table::{SnapshotVersion, StakeTableError, StakeTableScheme};
+use jf_utils::canonical;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 
 /// Locally maintained stake table, generic over public key type `K`.
+/// It is serialized as a [`SerializedStakeTable`].
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(bound = "K: Key")]
+#[serde(
+    bound = "K: Key",
+    into = "SerializedStakeTable<K>",
+    try_from = "SerializedStakeTable<K>"
+)]
 pub struct StakeTable<K: Key> {
     /// The most up-to-date stake table, where the incoming transactions shall be performed on.
     head: Arc<PersistentMerkleNode<K>>,
@@ -20,16 +34,94 @@ pub struct StakeTable<K: Key> {
     epoch_start: Arc<PersistentMerkleNode<K>>,
     /// The stake table used for leader election.
     last_epoch_start: Arc<PersistentMerkleNode<K>>,
+    /// Snapshots of the stake table at recent block heights
+    history: SnapshotHistory<Arc<PersistentMerkleNode<K>>>,
 
     /// Height of the underlying merkle tree, determines the capacity.
     /// The capacity is `TREE_BRANCH.pow(height)`.
     height: usize,
 
     /// The mapping from public keys to their location in the Merkle tree.
-    #[serde(skip)]
     mapping: HashMap<K, usize>,
 }
 
+/// Serialized form of [`StakeTable`]. All the snapshots are flattened into a single arena, so
+/// that the subtrees they share are only stored once.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "K: Key")]
+struct SerializedStakeTable<K: Key> {
+    /// Nodes of all the snapshots
+    nodes: MerkleNodeArena<K>,
+    /// Index of the root of `head` in `nodes`
+    head: usize,
+    /// Index of the root of `epoch_start` in `nodes`
+    epoch_start: usize,
+    /// Index of the root of `last_epoch_start` in `nodes`
+    last_epoch_start: usize,
+    /// Snapshots at recent block heights, given as the index of their root in `nodes`
+    history: SnapshotHistory<usize>,
+    /// Height of the underlying merkle tree
+    height: usize,
+    /// Every key ever registered, ordered by its location in the Merkle tree
+    #[serde(with = "canonical")]
+    keys: Vec<K>,
+}
+
+impl<K: Key> From<StakeTable<K>> for SerializedStakeTable<K> {
+    fn from(table: StakeTable<K>) -> Self {
+        let mut nodes = MerkleNodeArena::new();
+        let mut stored = HashMap::new();
+        let head = nodes.insert(&table.head, &mut stored);
+        let epoch_start = nodes.insert(&table.epoch_start, &mut stored);
+        let last_epoch_start = nodes.insert(&table.last_epoch_start, &mut stored);
+        let history = table.history.map(|root| nodes.insert(root, &mut stored));
+        let mut keys = table.mapping.into_iter().collect::<Vec<_>>();
+        keys.sort_unstable_by_key(|(_, pos)| *pos);
+        Self {
+            nodes,
+            head,
+            epoch_start,
+            last_epoch_start,
+            history,
+            height: table.height,
+            keys: keys.into_iter().map(|(key, _)| key).collect(),
+        }
+    }
+}
+
+impl<K: Key> TryFrom<SerializedStakeTable<K>> for StakeTable<K> {
+    type Error = StakeTableError;
+
+    fn try_from(table: SerializedStakeTable<K>) -> Result<Self, Self::Error> {
+        let nodes = table.nodes.unflatten()?;
+        let root = |index: &usize| {
+            nodes
+                .get(*index)
+                .cloned()
+                .ok_or(StakeTableError::MalformedSnapshot)
+        };
+        let num_keys = table.keys.len();
+        let mapping = table
+            .keys
+            .into_iter()
+            .enumerate()
+            .map(|(pos, key)| (key, pos))
+            .collect::<HashMap<_, _>>();
+        // A key registered twice would be given two locations
+        if mapping.len() != num_keys {
+            return Err(StakeTableError::MalformedSnapshot);
+        }
+        Ok(Self {
+            head: root(&table.head)?,
+            epoch_start: root(&table.epoch_start)?,
+            last_epoch_start: root(&table.last_epoch_start)?,
+            history: table.history.try_map(root)?,
+            height: table.height,
+            mapping,
+        })
+    }
+}
+
 impl<K: Key> StakeTableScheme for StakeTable<K> {
     type Key = K;
     type Amount = U256;
@@ -44,24 +136,46 @@ impl<K: Key> StakeTableScheme for StakeTable<K> {
         amount: Self::Amount,
         (): Self::Aux,
     ) -> Result<(), StakeTableError> {
-        if self.mapping.get(&new_key).is_some() {
-            Err(StakeTableError::ExistingKey)
-        } else {
-            let pos = self.mapping.len();
-            self.head = self.head.register(
-                self.height,
-                &to_merkle_path(pos, self.height),
-                &new_key,
-                amount,
-            )?;
-            self.mapping.insert(new_key, pos);
-            Ok(())
+        match self.mapping.get(&new_key) {
+            // A deregistered key is registered again at its reserved position
+            Some(pos) if !self.contains_key(&new_key) => {
+                self.head = self.head.register(
+                    self.height,
+                    &to_merkle_path(*pos, self.height),
+                    &new_key,
+                    amount,
+                )?;
+                Ok(())
+            }
+            Some(_) => Err(StakeTableError::ExistingKey),
+            None => {
+                let pos = self.mapping.len();
+                self.head = self.head.register(
+                    self.height,
+                    &to_merkle_path(pos, self.height),
+                    &new_key,
+                    amount,
+                )?;
+                self.mapping.insert(new_key, pos);
+                Ok(())
+            }
         }
     }
 
-    fn deregister(&mut self, _existing_key: &Self::Key) -> Result<(), StakeTableError> {
-        // TODO: (alex) work on this in a future PR
-        unimplemented!()
+    /// The leaf of `existing_key` is replaced by an empty node in the head version. Its position
+    /// stays reserved in `mapping`, so the key can still be looked up in older snapshots.
+    fn deregister(&mut self, existing_key: &Self::Key) -> Result<(), StakeTableError> {
+        match self.mapping.get(existing_key) {
+            Some(pos) => {
+                (self.head, _) = self.head.deregister(
+                    self.height,
+                    &to_merkle_path(*pos, self.height),
+                    existing_key,
+                )?;
+                Ok(())
+            }
+            None => Err(StakeTableError::KeyNotFound),
+        }
     }
 
     fn commitment(&self, version: SnapshotVersion) -> Result<Self::Commitment, StakeTableError> {
@@ -84,7 +198,7 @@ impl<K: Key> StakeTableScheme for StakeTable<K> {
     }
 
     fn contains_key(&self, key: &Self::Key) -> bool {
-        self.mapping.contains_key(key)
+        self.lookup(SnapshotVersion::Head, key).is_ok()
     }
 
     fn lookup(&self, version: SnapshotVersion, key: &K) -> Result<Self::Amount, StakeTableError> {
@@ -165,6 +279,16 @@ impl<K: Key> StakeTableScheme for StakeTable<K> {
         let root = Self::get_root(self, &version)?;
         Ok(internal::IntoIter::new(root))
     }
+
+    fn advance(&mut self) {
+        self.last_epoch_start = self.epoch_start.clone();
+        self.epoch_start = self.head.clone();
+    }
+
+    fn record_block(&mut self, block_num: u64) -> Result<(), StakeTableError> {
+        // Snapshots share their unchanged subtrees with the head, so recording is cheap.
+        self.history.record(block_num, Arc::clone(&self.head))
+    }
 }
 
 impl<K: Key> StakeTable<K> {
@@ -175,6 +299,7 @@ impl<K: Key> StakeTable<K> {
             head: Arc::new(PersistentMerkleNode::Empty),
             epoch_start: Arc::new(PersistentMerkleNode::Empty),
             last_epoch_start: Arc::new(PersistentMerkleNode::Empty),
+            history: SnapshotHistory::new(SNAPSHOT_HISTORY_WINDOW),
             height,
             mapping: HashMap::new(),
         }
@@ -189,14 +314,28 @@ impl<K: Key> StakeTable<K> {
             SnapshotVersion::Head => Ok(Arc::clone(&self.head)),
             SnapshotVersion::EpochStart => Ok(Arc::clone(&self.epoch_start)),
             SnapshotVersion::LastEpochStart => Ok(Arc::clone(&self.last_epoch_start)),
-            SnapshotVersion::BlockNum(_) => Err(StakeTableError::SnapshotUnsupported),
+            SnapshotVersion::BlockNum(block_num) => self.history.get(*block_num).cloned(),
         }
     }
 
-    /// Update the stake table when the epoch number advances, should be manually called.
-    pub fn advance(&mut self) {
-        self.last_epoch_start = self.epoch_start.clone();
-        self.epoch_start = self.head.clone();
+    /// Set the number of past blocks whose snapshots are retained, see
+    /// [`StakeTableScheme::record_block`].
+    pub fn set_history_window(&mut self, window: u64) {
+        self.history.set_window(window);
+    }
+
+    /// Write the stake table, including all of its snapshots, to the file at `path`.
+    /// # Errors
+    /// Errors if the file cannot be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PersistenceError> {
+        persistence::save(self, path.as_ref())
+    }
+
+    /// Load a stake table written by [`StakeTable::save`].
+    /// # Errors
+    /// Errors if the file cannot be read, or is incomplete or corrupted.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PersistenceError> {
+        persistence::load(path.as_ref())
     }
 
     /// Set the stake withheld by `key` to be `value`.
@@ -338,4 +477,211 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn crypto_test_stake_table_deregister() -> Result<(), StakeTableError> {
+        let mut st = StakeTable::<Key>::new(3);
+        let keys = (0..10).map(Key::from).collect::<Vec<_>>();
+        keys.iter()
+            .for_each(|key| st.register(*key, U256::from(100), ()).unwrap());
+        st.advance();
+
+        // Deregistering keys only affects the head version
+        st.deregister(&keys[3])?;
+        st.deregister(&keys[9])?;
+        assert!(st.deregister(&keys[3]).is_err());
+        assert!(st.deregister(&Key::from(10u64)).is_err());
+        assert!(!st.contains_key(&keys[3]));
+        assert!(st.contains_key(&keys[4]));
+        assert_eq!(st.total_stake(SnapshotVersion::Head)?, U256::from(800));
+        assert_eq!(st.len(SnapshotVersion::Head)?, 8);
+        assert_eq!(
+            st.total_stake(SnapshotVersion::EpochStart)?,
+            U256::from(1000)
+        );
+        assert!(st.lookup(SnapshotVersion::Head, &keys[3]).is_err());
+        assert!(st
+            .lookup_with_proof(SnapshotVersion::Head, &keys[3])
+            .is_err());
+        assert_eq!(
+            st.lookup(SnapshotVersion::EpochStart, &keys[3])?,
+            U256::from(100)
+        );
+        // Updates on a deregistered key fail
+        assert!(st.update(&keys[3], U256::from(1), false).is_err());
+        assert!(st.set_value(&keys[3], U256::from(1)).is_err());
+
+        st.advance();
+        st.advance();
+        // The deregistration is visible in every snapshot after advancing
+        for version in [
+            SnapshotVersion::Head,
+            SnapshotVersion::EpochStart,
+            SnapshotVersion::LastEpochStart,
+        ] {
+            let comm = st.commitment(version)?;
+            assert_eq!(comm.size(), 8);
+            for (i, key) in keys.iter().enumerate() {
+                match st.lookup_with_proof(version, key) {
+                    Ok((amount, proof)) => {
+                        assert!(i != 3 && i != 9);
+                        assert_eq!(amount, U256::from(100));
+                        assert!(proof.verify(&comm).is_ok());
+                    }
+                    Err(_) => assert!(i == 3 || i == 9),
+                }
+            }
+        }
+        // Deregistered keys are skipped by the iterator
+        let remaining = st
+            .try_iter(SnapshotVersion::LastEpochStart)?
+            .map(|(key, _, ())| key)
+            .collect::<Vec<_>>();
+        let expected = keys
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != 3 && *i != 9)
+            .map(|(_, key)| *key)
+            .collect::<Vec<_>>();
+        assert_eq!(remaining, expected);
+        // Deregistered keys are never sampled
+        let mut rng = rand_chacha::ChaCha20Rng::seed_from_u64(41u64);
+        for _ in 0..100 {
+            let (key, value) = st.sample(&mut rng).unwrap();
+            assert!(*key != keys[3] && *key != keys[9]);
+            assert_eq!(value, &U256::from(100));
+        }
+
+        // A deregistered key can register again at its previous position
+        st.register(keys[3], U256::from(50), ())?;
+        assert_eq!(st.lookup(SnapshotVersion::Head, &keys[3])?, U256::from(50));
+        assert!(st.register(keys[3], U256::from(50), ()).is_err());
+        assert_eq!(
+            st.try_iter(SnapshotVersion::Head)?
+                .nth(3)
+                .map(|(key, ..)| key),
+            Some(keys[3])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn crypto_test_stake_table_history() -> Result<(), StakeTableError> {
+        let mut st = StakeTable::<Key>::new(3);
+        st.set_history_window(10);
+        let keys = (0..10).map(Key::from).collect::<Vec<_>>();
+        keys.iter()
+            .take(5)
+            .for_each(|key| st.register(*key, U256::from(100), ()).unwrap());
+        st.record_block(1)?;
+        st.update(&keys[0], U256::from(50), false)?;
+        st.deregister(&keys[1])?;
+        st.register(keys[5], U256::from(100), ())?;
+        st.record_block(4)?;
+        assert!(st.record_block(4).is_err());
+
+        // Blocks outside of the recorded range are not supported
+        assert!(st.lookup(SnapshotVersion::BlockNum(0), &keys[0]).is_err());
+        assert!(st.lookup(SnapshotVersion::BlockNum(5), &keys[0]).is_err());
+        // Blocks between two records are answered by the earlier one
+        for block_num in 1..4 {
+            let version = SnapshotVersion::BlockNum(block_num);
+            assert_eq!(st.total_stake(version)?, U256::from(500));
+            assert_eq!(st.lookup(version, &keys[0])?, U256::from(100));
+            assert!(st.lookup(version, &keys[5]).is_err());
+            let (amount, proof) = st.lookup_with_proof(version, &keys[1])?;
+            assert_eq!(amount, U256::from(100));
+            assert!(proof.verify(&st.commitment(version)?).is_ok());
+        }
+        let version = SnapshotVersion::BlockNum(4);
+        assert_eq!(st.total_stake(version)?, U256::from(550));
+        assert!(st.lookup_with_proof(version, &keys[1]).is_err());
+        let (amount, proof) = st.lookup_with_proof(version, &keys[0])?;
+        assert_eq!(amount, U256::from(150));
+        assert!(proof.verify(&st.commitment(version)?).is_ok());
+        assert!(proof
+            .verify(&st.commitment(SnapshotVersion::BlockNum(1))?)
+            .is_err());
+        // A recorded block commits to the same table as the head it was taken from
+        assert_eq!(
+            st.commitment(version)?,
+            st.commitment(SnapshotVersion::Head)?
+        );
+        let historical_keys = st
+            .try_iter(version)?
+            .map(|(key, _, ())| key)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            historical_keys,
+            vec![keys[0], keys[2], keys[3], keys[4], keys[5]]
+        );
+
+        // Snapshots older than the window are pruned
+        st.record_block(12)?;
+        assert!(st.lookup(SnapshotVersion::BlockNum(1), &keys[0]).is_err());
+        assert_eq!(
+            st.lookup(SnapshotVersion::BlockNum(2), &keys[0])?,
+            U256::from(100)
+        );
+        st.set_history_window(0);
+        assert!(st.lookup(SnapshotVersion::BlockNum(4), &keys[0]).is_err());
+        assert_eq!(
+            st.lookup(SnapshotVersion::BlockNum(12), &keys[0])?,
+            U256::from(150)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn crypto_test_stake_table_persistence() -> Result<(), StakeTableError> {
+        let mut st = StakeTable::<Key>::new(3);
+        let keys = (0..10).map(Key::from).collect::<Vec<_>>();
+        keys.iter()
+            .take(6)
+            .for_each(|key| st.register(*key, U256::from(100), ()).unwrap());
+        st.record_block(1)?;
+        st.advance();
+        st.deregister(&keys[2])?;
+        st.update(&keys[0], U256::from(50), false)?;
+        st.record_block(2)?;
+        st.advance();
+        keys.iter()
+            .skip(6)
+            .for_each(|key| st.register(*key, U256::from(100), ()).unwrap());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stake_table");
+        st.save(&path).unwrap();
+        let mut loaded = StakeTable::<Key>::load(&path).unwrap();
+        assert_eq!(loaded, st);
+        for version in [
+            SnapshotVersion::Head,
+            SnapshotVersion::EpochStart,
+            SnapshotVersion::LastEpochStart,
+            SnapshotVersion::BlockNum(1),
+            SnapshotVersion::BlockNum(2),
+        ] {
+            assert_eq!(loaded.commitment(version)?, st.commitment(version)?);
+            assert_eq!(loaded.total_stake(version)?, st.total_stake(version)?);
+        }
+
+        // The key mapping is restored, including the positions of deregistered keys
+        let proof = loaded
+            .lookup_with_proof(SnapshotVersion::LastEpochStart, &keys[2])?
+            .1;
+        assert!(proof
+            .verify(&st.commitment(SnapshotVersion::LastEpochStart)?)
+            .is_ok());
+        assert!(loaded.lookup(SnapshotVersion::Head, &keys[2]).is_err());
+        loaded.register(keys[2], U256::from(100), ())?;
+        st.register(keys[2], U256::from(100), ())?;
+        assert_eq!(
+            loaded.commitment(SnapshotVersion::Head)?,
+            st.commitment(SnapshotVersion::Head)?
+        );
+
+        Ok(())
+    }
 }
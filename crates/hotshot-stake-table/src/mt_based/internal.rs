@@ -3,7 +3,7 @@
 use super::config::{Digest, FieldType, TREE_BRANCH};
 use crate::utils::{u256_to_field, ToFields};
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
-use ark_std::{hash::Hash, sync::Arc, vec, vec::Vec};
+use ark_std::{collections::HashMap, hash::Hash, sync::Arc, vec, vec::Vec};
 use ethereum_types::U256;
 use hotshot_types::traits::stake_table::StakeTableError;
 use jf_primitives::crhf::CRHF;
@@ -537,6 +537,215 @@ impl<K: Key> PersistentMerkleNode<K> {
             }
         }
     }
+
+    /// Remove `key` from the Merkle tree, replacing its leaf with an empty node.
+    /// Subtrees left without any key collapse into an empty node, so the commitment is the same
+    /// as if the key had never been registered at this position.
+    /// Return the stake the key withheld
+    pub fn deregister(
+        &self,
+        height: usize,
+        path: &[usize],
+        key: &K,
+    ) -> Result<(Arc<Self>, U256), StakeTableError> {
+        match self {
+            PersistentMerkleNode::Empty => Err(StakeTableError::KeyNotFound),
+            PersistentMerkleNode::Branch {
+                comm: _,
+                children,
+                num_keys: _,
+                total_stakes: _,
+            } => {
+                let mut children = children.clone();
+                let old_value: U256;
+                (children[path[height - 1]], old_value) =
+                    children[path[height - 1]].deregister(height - 1, path, key)?;
+                let num_keys = children.iter().map(|child| child.num_keys()).sum();
+                if num_keys == 0 {
+                    Ok((Arc::new(PersistentMerkleNode::Empty), old_value))
+                } else {
+                    let total_stakes = children
+                        .iter()
+                        .map(|child| child.total_stakes())
+                        .fold(U256::zero(), |sum, val| sum + val);
+                    let comm = Digest::evaluate(children.clone().map(|child| child.commitment()))
+                        .map_err(|_| StakeTableError::RescueError)?[0];
+                    Ok((
+                        Arc::new(PersistentMerkleNode::Branch {
+                            comm,
+                            children,
+                            num_keys,
+                            total_stakes,
+                        }),
+                        old_value,
+                    ))
+                }
+            }
+            PersistentMerkleNode::Leaf {
+                comm: _,
+                key: cur_key,
+                value,
+            } => {
+                if key == cur_key {
+                    Ok((Arc::new(PersistentMerkleNode::Empty), *value))
+                } else {
+                    Err(StakeTableError::MismatchedKey)
+                }
+            }
+        }
+    }
+}
+
+/// A node of a [`MerkleNodeArena`], which refers to its children by their index in the arena
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "K: Key")]
+enum ArenaNode<K: Key> {
+    /// Empty
+    Empty,
+    /// A branch
+    Branch {
+        /// field type
+        #[serde(with = "canonical")]
+        comm: FieldType,
+        /// indices of the children
+        children: [usize; TREE_BRANCH],
+        /// number of keys
+        num_keys: usize,
+        /// total stake
+        total_stakes: U256,
+    },
+    /// A leaf
+    Leaf {
+        /// field type
+        #[serde(with = "canonical")]
+        comm: FieldType,
+        /// the key
+        #[serde(with = "canonical")]
+        key: K,
+        /// the value
+        value: U256,
+    },
+}
+
+/// Persistent Merkle trees flattened into a list of nodes, which is how they are serialized.
+/// A subtree shared by several trees is only stored once, and children always precede their
+/// parent. The stored commitments and aggregates are checked against the nodes when unflattening.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "K: Key")]
+pub(crate) struct MerkleNodeArena<K: Key> {
+    /// The flattened nodes
+    nodes: Vec<ArenaNode<K>>,
+}
+
+impl<K: Key> MerkleNodeArena<K> {
+    /// Create an empty arena
+    pub fn new() -> Self {
+        Self { nodes: vec![] }
+    }
+
+    /// Store the tree rooted at `root` and return the index of its root.
+    /// `stored` maps the address of every node already in the arena to its index, so that shared
+    /// subtrees are not stored twice.
+    pub fn insert(
+        &mut self,
+        root: &Arc<PersistentMerkleNode<K>>,
+        stored: &mut HashMap<*const PersistentMerkleNode<K>, usize>,
+    ) -> usize {
+        if let Some(index) = stored.get(&Arc::as_ptr(root)) {
+            return *index;
+        }
+        let node = match root.as_ref() {
+            PersistentMerkleNode::Empty => ArenaNode::Empty,
+            PersistentMerkleNode::Branch {
+                comm,
+                children,
+                num_keys,
+                total_stakes,
+            } => ArenaNode::Branch {
+                comm: *comm,
+                children: children.clone().map(|child| self.insert(&child, stored)),
+                num_keys: *num_keys,
+                total_stakes: *total_stakes,
+            },
+            PersistentMerkleNode::Leaf { comm, key, value } => ArenaNode::Leaf {
+                comm: *comm,
+                key: key.clone(),
+                value: *value,
+            },
+        };
+        self.nodes.push(node);
+        stored.insert(Arc::as_ptr(root), self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// Rebuild all the nodes of the arena, in the same order, with shared subtrees shared again.
+    /// # Errors
+    /// Errors if a node refers to a child which does not precede it, or if its commitment, number
+    /// of keys or total stake does not match its contents.
+    pub fn unflatten(&self) -> Result<Vec<Arc<PersistentMerkleNode<K>>>, StakeTableError> {
+        let mut nodes: Vec<Arc<PersistentMerkleNode<K>>> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let node = match node {
+                ArenaNode::Empty => PersistentMerkleNode::Empty,
+                ArenaNode::Branch {
+                    comm,
+                    children,
+                    num_keys,
+                    total_stakes,
+                } => {
+                    let children: [Arc<PersistentMerkleNode<K>>; TREE_BRANCH] = children
+                        .iter()
+                        .map(|index| nodes.get(*index).cloned())
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or(StakeTableError::MalformedSnapshot)?
+                        .try_into()
+                        .map_err(|_| StakeTableError::MalformedSnapshot)?;
+                    // Children are checked before their parent, so their aggregates can be trusted.
+                    let expected_num_keys: usize =
+                        children.iter().map(|child| child.num_keys()).sum();
+                    let expected_total_stakes = children
+                        .iter()
+                        .try_fold(U256::zero(), |sum, child| {
+                            sum.checked_add(child.total_stakes())
+                        })
+                        .ok_or(StakeTableError::MalformedSnapshot)?;
+                    let expected_comm =
+                        Digest::evaluate(children.clone().map(|child| child.commitment()))
+                            .map_err(|_| StakeTableError::RescueError)?[0];
+                    if *num_keys != expected_num_keys
+                        || *total_stakes != expected_total_stakes
+                        || *comm != expected_comm
+                    {
+                        return Err(StakeTableError::MalformedSnapshot);
+                    }
+                    PersistentMerkleNode::Branch {
+                        comm: *comm,
+                        children,
+                        num_keys: *num_keys,
+                        total_stakes: *total_stakes,
+                    }
+                }
+                ArenaNode::Leaf { comm, key, value } => {
+                    let mut input = [FieldType::default(); 3];
+                    input[..<K as ToFields<FieldType>>::SIZE]
+                        .copy_from_slice(&(*key).clone().to_fields()[..]);
+                    input[2] = u256_to_field(value);
+                    let expected_comm =
+                        Digest::evaluate(input).map_err(|_| StakeTableError::RescueError)?[0];
+                    if *comm != expected_comm {
+                        return Err(StakeTableError::MalformedSnapshot);
+                    }
+                    PersistentMerkleNode::Leaf {
+                        comm: *comm,
+                        key: key.clone(),
+                        value: *value,
+                    }
+                }
+            };
+            nodes.push(Arc::new(node));
+        }
+        Ok(nodes)
+    }
 }
 
 /// An owning iterator over the (key, value) entries of a `PersistentMerkleNode`
@@ -567,7 +776,8 @@ impl<K: Key> Iterator for IntoIter<K> {
         // This unwrap always succeed because `unvisited` is nonempty
         let visiting = (*self.unvisited.pop().unwrap()).clone();
         match visiting {
-            PersistentMerkleNode::Empty => None,
+            // Empty subtrees are either unused or left by deregistered keys
+            PersistentMerkleNode::Empty => self.next(),
             PersistentMerkleNode::Branch {
                 comm: _,
                 children,
@@ -618,7 +828,7 @@ pub fn from_merkle_path(path: &[usize]) -> usize {
 
 #[cfg(test)]
 mod tests {
-    use super::{super::config, to_merkle_path, PersistentMerkleNode};
+    use super::{super::config, to_merkle_path, ArenaNode, MerkleNodeArena, PersistentMerkleNode};
     use ark_std::{
         rand::{Rng, RngCore},
         sync::Arc,
@@ -769,4 +979,44 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn crypto_test_arena_rejects_tampered_nodes() {
+        let height = 2;
+        let mut root = Arc::new(PersistentMerkleNode::<Key>::Empty);
+        for idx in 0..3 {
+            root = root
+                .register(
+                    height,
+                    &to_merkle_path(idx, height),
+                    &Key::from(idx as u64),
+                    U256::from(100),
+                )
+                .unwrap();
+        }
+        let mut arena = MerkleNodeArena::new();
+        let root_index = arena.insert(&root, &mut ark_std::collections::HashMap::new());
+        assert_eq!(arena.unflatten().unwrap()[root_index], root);
+
+        let mut leaf_value = arena.clone();
+        for node in &mut leaf_value.nodes {
+            if let ArenaNode::Leaf { value, .. } = node {
+                *value = U256::from(1000);
+                break;
+            }
+        }
+        assert!(leaf_value.unflatten().is_err());
+
+        let mut total_stakes = arena.clone();
+        if let Some(ArenaNode::Branch { total_stakes, .. }) = total_stakes.nodes.last_mut() {
+            *total_stakes = U256::from(1000);
+        }
+        assert!(total_stakes.unflatten().is_err());
+
+        let mut num_keys = arena;
+        if let Some(ArenaNode::Branch { num_keys, .. }) = num_keys.nodes.last_mut() {
+            *num_keys = 1;
+        }
+        assert!(num_keys.unflatten().is_err());
+    }
 }
@@ -0,0 +1,202 @@
+//! Versioned on-disk format for stake tables.
+//!
+//! A stake table file starts with a fixed-size header, followed by the serialized stake table:
+//!
+//! | bytes  | content                                   |
+//! |--------|-------------------------------------------|
+//! | 8      | [`MAGIC`]                                 |
+//! | 4      | format version, little endian             |
+//! | 8      | length of the serialized table, little endian |
+//! | 32     | SHA-256 digest of the serialized table    |
+//! | ...    | the serialized table                      |
+//!
+//! Files are written to a temporary file which is then renamed, so an interrupted write leaves
+//! the previous file in place. The directory is synced after the rename, so the new file survives
+//! a crash once [`save`] returns. A file which is nonetheless incomplete or corrupted is rejected by
+//! [`load`] thanks to the recorded length and digest.
+
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use snafu::{ensure, ResultExt, Snafu};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
+
+/// Bytes every stake table file starts with
+pub const MAGIC: [u8; 8] = *b"HSSTAKE\0";
+
+/// Version of the format written by [`save`]
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Length of the header preceding the serialized table
+const HEADER_LEN: usize = 8 + 4 + 8 + 32;
+
+/// Error type for saving and loading stake tables
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum PersistenceError {
+    /// Failed to read or write the file
+    #[snafu(display("Failed to access the stake table file: {source}"))]
+    Io {
+        /// The underlying IO error
+        source: std::io::Error,
+    },
+    /// Failed to serialize or deserialize the stake table
+    #[snafu(display("Failed to (de)serialize the stake table: {source}"))]
+    Serialization {
+        /// The underlying serialization error
+        source: bincode::Error,
+    },
+    /// The file does not start with [`MAGIC`]
+    #[snafu(display("Not a stake table file"))]
+    NotAStakeTable,
+    /// The file was written in a format version this build cannot read
+    #[snafu(display("Unsupported stake table format version {version}"))]
+    UnsupportedVersion {
+        /// The version found in the file
+        version: u32,
+    },
+    /// The file is shorter or longer than its header says, e.g. because it was only partially
+    /// written
+    #[snafu(display("Expected {expected} bytes of stake table data, found {found}"))]
+    LengthMismatch {
+        /// The length recorded in the header
+        expected: u64,
+        /// The length actually found
+        found: u64,
+    },
+    /// The serialized table does not match the digest recorded in the header
+    #[snafu(display("Stake table file is corrupted"))]
+    ChecksumMismatch,
+}
+
+/// Write `table` to the file at `path`, replacing the file if it exists.
+///
+/// # Errors
+/// Errors if `table` cannot be serialized or the file cannot be written.
+pub fn save<T: Serialize>(table: &T, path: &Path) -> Result<(), PersistenceError> {
+    let payload = bincode::serialize(table).context(SerializationSnafu)?;
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&Sha256::digest(&payload));
+    bytes.extend_from_slice(&payload);
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let mut file = File::create(&tmp_path).context(IoSnafu)?;
+    file.write_all(&bytes).context(IoSnafu)?;
+    file.sync_all().context(IoSnafu)?;
+    fs::rename(&tmp_path, path).context(IoSnafu)?;
+    sync_parent_dir(path)
+}
+
+/// Make the rename of the file at `path` durable by syncing the directory containing it.
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> Result<(), PersistenceError> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .context(IoSnafu)
+}
+
+/// Directories cannot be opened to sync them on this platform, so only the file itself is synced.
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+fn sync_parent_dir(_path: &Path) -> Result<(), PersistenceError> {
+    Ok(())
+}
+
+/// Load a table written by [`save`] from the file at `path`.
+///
+/// # Errors
+/// Errors if the file cannot be read, is not a stake table file of a supported version, or is
+/// incomplete or corrupted.
+pub fn load<T: DeserializeOwned>(path: &Path) -> Result<T, PersistenceError> {
+    decode(&fs::read(path).context(IoSnafu)?)
+}
+
+/// Decode the content of a stake table file.
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, PersistenceError> {
+    ensure!(
+        bytes.len() >= HEADER_LEN,
+        LengthMismatchSnafu {
+            expected: HEADER_LEN as u64,
+            found: bytes.len() as u64,
+        }
+    );
+    ensure!(bytes.starts_with(&MAGIC), NotAStakeTableSnafu);
+    let (header, payload) = bytes.split_at(HEADER_LEN);
+    let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    match version {
+        1 => {
+            let expected = u64::from_le_bytes(header[12..20].try_into().unwrap());
+            ensure!(
+                payload.len() as u64 == expected,
+                LengthMismatchSnafu {
+                    expected,
+                    found: payload.len() as u64,
+                }
+            );
+            ensure!(
+                header[20..] == Sha256::digest(payload)[..],
+                ChecksumMismatchSnafu
+            );
+            bincode::deserialize(payload).context(SerializationSnafu)
+        }
+        version => UnsupportedVersionSnafu { version }.fail(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, save, PersistenceError, FORMAT_VERSION};
+    use std::fs;
+
+    #[test]
+    fn test_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stake_table");
+        let table = (0..100u64).collect::<Vec<_>>();
+        save(&table, &path).unwrap();
+        assert_eq!(load::<Vec<u64>>(&path).unwrap(), table);
+        let bytes = fs::read(&path).unwrap();
+
+        // Partially written files are detected, whether the header or the table is incomplete
+        for len in [4, 30, bytes.len() - 1] {
+            fs::write(&path, &bytes[..len]).unwrap();
+            assert!(matches!(
+                load::<Vec<u64>>(&path),
+                Err(PersistenceError::LengthMismatch { .. })
+            ));
+        }
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        fs::write(&path, corrupted).unwrap();
+        assert!(matches!(
+            load::<Vec<u64>>(&path),
+            Err(PersistenceError::ChecksumMismatch)
+        ));
+
+        let mut future = bytes.clone();
+        future[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        fs::write(&path, future).unwrap();
+        assert!(matches!(
+            load::<Vec<u64>>(&path),
+            Err(PersistenceError::UnsupportedVersion { .. })
+        ));
+
+        fs::write(&path, &bytes[8..]).unwrap();
+        assert!(matches!(
+            load::<Vec<u64>>(&path),
+            Err(PersistenceError::NotAStakeTable)
+        ));
+    }
+}
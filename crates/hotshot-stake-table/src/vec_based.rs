@@ -1,7 +1,9 @@
 //! A vector based stake table implementation. The commitment is the rescue hash of the list of (key, amount) pairs;
 
 use crate::{
-    config::STAKE_TABLE_CAPACITY,
+    config::{SNAPSHOT_HISTORY_WINDOW, STAKE_TABLE_CAPACITY},
+    history::SnapshotHistory,
+    persistence::{self, PersistenceError},
     utils::{u256_to_field, ToFields},
 };
 use ark_std::{collections::HashMap, hash::Hash, rand::SeedableRng};
@@ -12,7 +14,9 @@ use jf_primitives::{
     crhf::{VariableLengthRescueCRHF, CRHF},
     rescue::RescueParameter,
 };
+use jf_utils::canonical;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
 
 pub mod config;
 
@@ -27,6 +31,23 @@ struct StakeTableSnapshot<K1, K2> {
     pub stake_amount: Vec<U256>,
 }
 
+impl<K1, K2> StakeTableSnapshot<K1, K2> {
+    /// Whether the snapshot holds at most `capacity` entries, each with a key, an auxiliary key
+    /// and an amount of stake
+    fn is_well_formed(&self, capacity: usize) -> bool {
+        self.bls_keys.len() <= capacity
+            && self.schnorr_keys.len() == self.bls_keys.len()
+            && self.stake_amount.len() == self.bls_keys.len()
+    }
+
+    /// The total stake of the snapshot, or `None` if it does not fit in a `U256`
+    fn total_stake(&self) -> Option<U256> {
+        self.stake_amount
+            .iter()
+            .try_fold(U256::zero(), |total, amount| total.checked_add(*amount))
+    }
+}
+
 impl<K1, K2> Default for StakeTableSnapshot<K1, K2> {
     fn default() -> Self {
         Self {
@@ -41,7 +62,16 @@ impl<K1, K2> Default for StakeTableSnapshot<K1, K2> {
 /// Whose commitment is a rescue hash of all key-value pairs over field `F`.
 /// NOTE: the commitment is only available for the finalized versions, and is
 /// computed only once when it's finalized.
+/// It is serialized as a [`SerializedStakeTable`].
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    bound(
+        serialize = "K1: Serialize, K2: Serialize",
+        deserialize = "K1: Deserialize<'de>, K2: Deserialize<'de>"
+    ),
+    into = "SerializedStakeTable<K1, K2, F>",
+    try_from = "SerializedStakeTable<K1, K2, F>"
+)]
 pub struct StakeTable<K1, K2, F>
 where
     K1: Eq + Hash + Clone + ToFields<F>,
@@ -75,11 +105,142 @@ where
     /// Commitment of the stake table snapshot version `LastEpochStart`
     last_epoch_start_comm: (F, F, F),
 
+    /// Snapshots of the stake table at recent block heights, along with their total stakes.
+    /// Their commitments are computed when requested.
+    history: SnapshotHistory<(StakeTableSnapshot<K1, K2>, U256)>,
+
     /// The mapping from public keys to their location in the Merkle tree.
-    #[serde(skip)]
     bls_mapping: HashMap<K1, usize>,
 }
 
+/// Serialized form of [`StakeTable`]. The commitments and total stakes are stored, and checked
+/// against the snapshots when loading. The key mapping is rebuilt from the head snapshot, where
+/// keys are never removed.
+#[derive(Serialize, Deserialize)]
+struct SerializedStakeTable<K1, K2, F: RescueParameter> {
+    /// upper bound on table size
+    capacity: usize,
+    /// The most up-to-date stake table
+    head: StakeTableSnapshot<K1, K2>,
+    /// The snapshot of stake table at the beginning of the current epoch
+    epoch_start: StakeTableSnapshot<K1, K2>,
+    /// The stake table used for leader election
+    last_epoch_start: StakeTableSnapshot<K1, K2>,
+    /// Total stakes in the most update-to-date stake table
+    head_total_stake: U256,
+    /// Total stakes in the snapshot version `EpochStart`
+    epoch_start_total_stake: U256,
+    /// Total stakes in the snapshot version `LastEpochStart`
+    last_epoch_start_total_stake: U256,
+    /// Commitment of the stake table snapshot version `EpochStart`
+    #[serde(with = "canonical")]
+    epoch_start_comm: [F; 3],
+    /// Commitment of the stake table snapshot version `LastEpochStart`
+    #[serde(with = "canonical")]
+    last_epoch_start_comm: [F; 3],
+    /// Snapshots of the stake table at recent block heights, along with their total stakes
+    history: SnapshotHistory<(StakeTableSnapshot<K1, K2>, U256)>,
+}
+
+impl<K1, K2, F> From<StakeTable<K1, K2, F>> for SerializedStakeTable<K1, K2, F>
+where
+    K1: Eq + Hash + Clone + ToFields<F>,
+    K2: Eq + Hash + Clone + Default + ToFields<F>,
+    F: RescueParameter,
+{
+    fn from(table: StakeTable<K1, K2, F>) -> Self {
+        let (a, b, c) = table.epoch_start_comm;
+        let (d, e, f) = table.last_epoch_start_comm;
+        Self {
+            capacity: table.capacity,
+            head: table.head,
+            epoch_start: table.epoch_start,
+            last_epoch_start: table.last_epoch_start,
+            head_total_stake: table.head_total_stake,
+            epoch_start_total_stake: table.epoch_start_total_stake,
+            last_epoch_start_total_stake: table.last_epoch_start_total_stake,
+            epoch_start_comm: [a, b, c],
+            last_epoch_start_comm: [d, e, f],
+            history: table.history,
+        }
+    }
+}
+
+impl<K1, K2, F> TryFrom<SerializedStakeTable<K1, K2, F>> for StakeTable<K1, K2, F>
+where
+    K1: Eq + Hash + Clone + ToFields<F>,
+    K2: Eq + Hash + Clone + Default + ToFields<F>,
+    F: RescueParameter,
+{
+    type Error = StakeTableError;
+
+    fn try_from(table: SerializedStakeTable<K1, K2, F>) -> Result<Self, Self::Error> {
+        let bls_mapping = table
+            .head
+            .bls_keys
+            .iter()
+            .enumerate()
+            .map(|(pos, key)| (key.clone(), pos))
+            .collect::<HashMap<_, _>>();
+        // Every snapshot must fit in the table, and a key registered twice would be given two
+        // locations
+        let well_formed = [&table.head, &table.epoch_start, &table.last_epoch_start]
+            .into_iter()
+            .all(|snapshot| snapshot.is_well_formed(table.capacity))
+            && table
+                .history
+                .snapshots()
+                .all(|(snapshot, _)| snapshot.is_well_formed(table.capacity))
+            && bls_mapping.len() == table.head.bls_keys.len();
+        if !well_formed {
+            return Err(StakeTableError::MalformedSnapshot);
+        }
+        let [a, b, c] = table.epoch_start_comm;
+        let [d, e, f] = table.last_epoch_start_comm;
+        let table = Self {
+            capacity: table.capacity,
+            head: table.head,
+            epoch_start: table.epoch_start,
+            last_epoch_start: table.last_epoch_start,
+            head_total_stake: table.head_total_stake,
+            epoch_start_total_stake: table.epoch_start_total_stake,
+            last_epoch_start_total_stake: table.last_epoch_start_total_stake,
+            epoch_start_comm: (a, b, c),
+            last_epoch_start_comm: (d, e, f),
+            history: table.history,
+            bls_mapping,
+        };
+        let totals_match = [
+            (&table.head, table.head_total_stake),
+            (&table.epoch_start, table.epoch_start_total_stake),
+            (&table.last_epoch_start, table.last_epoch_start_total_stake),
+        ]
+        .into_iter()
+        .chain(
+            table
+                .history
+                .snapshots()
+                .map(|(snapshot, total_stake)| (snapshot, *total_stake)),
+        )
+        .all(|(snapshot, total_stake)| snapshot.total_stake() == Some(total_stake));
+        // A snapshot which was never advanced into keeps the commitment of an empty table.
+        let default_comm = Self::default_comm(table.capacity);
+        let comms_match = [
+            (&table.epoch_start, table.epoch_start_comm),
+            (&table.last_epoch_start, table.last_epoch_start_comm),
+        ]
+        .into_iter()
+        .all(|(snapshot, comm)| {
+            (snapshot.bls_keys.is_empty() && comm == default_comm)
+                || table.compute_comm(snapshot) == comm
+        });
+        if !totals_match || !comms_match {
+            return Err(StakeTableError::MalformedSnapshot);
+        }
+        Ok(table)
+    }
+}
+
 impl<K1, K2, F> StakeTableScheme for StakeTable<K1, K2, F>
 where
     K1: Eq + Hash + Clone + ToFields<F>,
@@ -131,7 +292,11 @@ where
             // IMPORTANT: we don't support committing the head version b/c it's not finalized.
             SnapshotVersion::EpochStart => Ok(self.epoch_start_comm),
             SnapshotVersion::LastEpochStart => Ok(self.last_epoch_start_comm),
-            _ => Err(StakeTableError::SnapshotUnsupported),
+            SnapshotVersion::BlockNum(block_num) => {
+                let (snapshot, _) = self.history.get(block_num)?;
+                Ok(self.compute_comm(snapshot))
+            }
+            SnapshotVersion::Head => Err(StakeTableError::SnapshotUnsupported),
         }
     }
 
@@ -140,7 +305,7 @@ where
             SnapshotVersion::Head => Ok(self.head_total_stake),
             SnapshotVersion::EpochStart => Ok(self.epoch_start_total_stake),
             SnapshotVersion::LastEpochStart => Ok(self.last_epoch_start_total_stake),
-            SnapshotVersion::BlockNum(_) => Err(StakeTableError::SnapshotUnsupported),
+            SnapshotVersion::BlockNum(block_num) => Ok(self.history.get(block_num)?.1),
         }
     }
 
@@ -241,6 +406,21 @@ where
             .collect::<Vec<_>>();
         Ok(owned.into_iter())
     }
+
+    fn advance(&mut self) {
+        // Could we avoid this `clone()`?
+        self.last_epoch_start = self.epoch_start.clone();
+        self.last_epoch_start_total_stake = self.epoch_start_total_stake;
+        self.last_epoch_start_comm = self.epoch_start_comm;
+        self.epoch_start = self.head.clone();
+        self.epoch_start_total_stake = self.head_total_stake;
+        self.epoch_start_comm = self.compute_comm(&self.head);
+    }
+
+    fn record_block(&mut self, block_num: u64) -> Result<(), StakeTableError> {
+        self.history
+            .record(block_num, (self.head.clone(), self.head_total_stake))
+    }
 }
 
 impl<K1, K2, F> StakeTable<K1, K2, F>
@@ -254,16 +434,7 @@ where
     /// If unable to evaluate a preimage
     #[must_use]
     pub fn new(capacity: usize) -> Self {
-        let bls_comm_preimage = vec![F::default(); capacity * <K1 as ToFields<F>>::SIZE];
-        let default_bls_comm =
-            VariableLengthRescueCRHF::<F, 1>::evaluate(&bls_comm_preimage).unwrap()[0];
-        let schnorr_comm_preimage = vec![F::default(); capacity * <K2 as ToFields<F>>::SIZE];
-        let default_schnorr_comm =
-            VariableLengthRescueCRHF::<F, 1>::evaluate(&schnorr_comm_preimage).unwrap()[0];
-        let stake_comm_preimage = vec![F::default(); capacity];
-        let default_stake_comm =
-            VariableLengthRescueCRHF::<F, 1>::evaluate(&stake_comm_preimage).unwrap()[0];
-        let default_comm = (default_bls_comm, default_schnorr_comm, default_stake_comm);
+        let default_comm = Self::default_comm(capacity);
         Self {
             capacity,
             head: StakeTableSnapshot::default(),
@@ -275,20 +446,10 @@ where
             bls_mapping: HashMap::new(),
             epoch_start_comm: default_comm,
             last_epoch_start_comm: default_comm,
+            history: SnapshotHistory::new(SNAPSHOT_HISTORY_WINDOW),
         }
     }
 
-    /// Update the stake table when the epoch number advances, should be manually called.
-    pub fn advance(&mut self) {
-        // Could we avoid this `clone()`?
-        self.last_epoch_start = self.epoch_start.clone();
-        self.last_epoch_start_total_stake = self.epoch_start_total_stake;
-        self.last_epoch_start_comm = self.epoch_start_comm;
-        self.epoch_start = self.head.clone();
-        self.epoch_start_total_stake = self.head_total_stake;
-        self.epoch_start_comm = self.compute_head_comm();
-    }
-
     /// Set the stake withheld by `key` to be `value`.
     /// Return the previous stake if succeed.
     /// # Errors
@@ -306,14 +467,56 @@ where
         }
     }
 
-    /// Helper function to recompute the stake table commitment for head version
+    /// Set the number of past blocks whose snapshots are retained, see
+    /// [`StakeTableScheme::record_block`].
+    pub fn set_history_window(&mut self, window: u64) {
+        self.history.set_window(window);
+    }
+
+    /// Write the stake table, including all of its snapshots and commitments, to the file at
+    /// `path`.
+    /// # Errors
+    /// Errors if the file cannot be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PersistenceError>
+    where
+        K1: Serialize,
+        K2: Serialize,
+    {
+        persistence::save(self, path.as_ref())
+    }
+
+    /// Load a stake table written by [`StakeTable::save`].
+    /// # Errors
+    /// Errors if the file cannot be read, or is incomplete or corrupted.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PersistenceError>
+    where
+        K1: for<'de> Deserialize<'de>,
+        K2: for<'de> Deserialize<'de>,
+    {
+        persistence::load(path.as_ref())
+    }
+
+    /// Helper function to compute the commitment of an empty stake table of `capacity` entries
+    fn default_comm(capacity: usize) -> (F, F, F) {
+        let bls_comm_preimage = vec![F::default(); capacity * <K1 as ToFields<F>>::SIZE];
+        let default_bls_comm =
+            VariableLengthRescueCRHF::<F, 1>::evaluate(&bls_comm_preimage).unwrap()[0];
+        let schnorr_comm_preimage = vec![F::default(); capacity * <K2 as ToFields<F>>::SIZE];
+        let default_schnorr_comm =
+            VariableLengthRescueCRHF::<F, 1>::evaluate(&schnorr_comm_preimage).unwrap()[0];
+        let stake_comm_preimage = vec![F::default(); capacity];
+        let default_stake_comm =
+            VariableLengthRescueCRHF::<F, 1>::evaluate(&stake_comm_preimage).unwrap()[0];
+        (default_bls_comm, default_schnorr_comm, default_stake_comm)
+    }
+
+    /// Helper function to compute the commitment of a stake table snapshot
     /// Commitment of a stake table is a triple `(bls_keys_comm, schnorr_keys_comm, stake_amount_comm)`
     /// TODO(Chengyu): The BLS verification keys doesn't implement Default. Thus we directly pad with `F::default()`.
-    fn compute_head_comm(&mut self) -> (F, F, F) {
-        let padding_len = self.capacity - self.head.bls_keys.len();
+    fn compute_comm(&self, snapshot: &StakeTableSnapshot<K1, K2>) -> (F, F, F) {
+        let padding_len = self.capacity - snapshot.bls_keys.len();
         // Compute rescue hash for bls keys
-        let mut bls_comm_preimage = self
-            .head
+        let mut bls_comm_preimage = snapshot
             .bls_keys
             .iter()
             .flat_map(ToFields::to_fields)
@@ -322,8 +525,7 @@ where
         let bls_comm = VariableLengthRescueCRHF::<F, 1>::evaluate(bls_comm_preimage).unwrap()[0];
 
         // Compute rescue hash for Schnorr keys
-        let schnorr_comm_preimage = self
-            .head
+        let schnorr_comm_preimage = snapshot
             .schnorr_keys
             .iter()
             .chain(ark_std::iter::repeat(&K2::default()).take(padding_len))
@@ -333,8 +535,7 @@ where
             VariableLengthRescueCRHF::<F, 1>::evaluate(schnorr_comm_preimage).unwrap()[0];
 
         // Compute rescue hash for stake amounts
-        let mut stake_comm_preimage = self
-            .head
+        let mut stake_comm_preimage = snapshot
             .stake_amount
             .iter()
             .map(|x| u256_to_field(x))
@@ -363,7 +564,7 @@ where
             SnapshotVersion::Head => Ok(&self.head),
             SnapshotVersion::EpochStart => Ok(&self.epoch_start),
             SnapshotVersion::LastEpochStart => Ok(&self.last_epoch_start),
-            SnapshotVersion::BlockNum(_) => Err(StakeTableError::SnapshotUnsupported),
+            SnapshotVersion::BlockNum(block_num) => Ok(&self.history.get(*block_num)?.0),
         }
     }
 }
@@ -382,7 +583,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::config::{FieldType as F, QCVerKey, StateVerKey};
-    use super::StakeTable;
+    use super::{SerializedStakeTable, StakeTable};
     use ark_std::{rand::SeedableRng, vec::Vec};
     use ethereum_types::U256;
     use hotshot_types::traits::stake_table::{SnapshotVersion, StakeTableError, StakeTableScheme};
@@ -508,4 +709,166 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn crypto_test_stake_table_history() -> Result<(), StakeTableError> {
+        let mut st = StakeTable::<QCVerKey, StateVerKey, F>::new(10);
+        st.set_history_window(10);
+        let mut pseudo_rng = jf_utils::test_rng();
+        let keys = (0..6)
+            .map(|_| {
+                (
+                    BLSOverBN254CurveSignatureScheme::key_gen(&(), &mut pseudo_rng)
+                        .unwrap()
+                        .1,
+                    SchnorrSignatureScheme::key_gen(&(), &mut pseudo_rng)
+                        .unwrap()
+                        .1,
+                )
+            })
+            .collect::<Vec<_>>();
+        keys.iter()
+            .take(5)
+            .for_each(|key| st.register(key.0, U256::from(100), key.1.clone()).unwrap());
+        st.record_block(1)?;
+        st.advance();
+        st.update(&keys[0].0, U256::from(50), false)?;
+        st.deregister(&keys[1].0)?;
+        st.register(keys[5].0, U256::from(100), keys[5].1.clone())?;
+        st.record_block(4)?;
+        assert!(st.record_block(3).is_err());
+
+        // Blocks outside of the recorded range are not supported
+        assert!(st.lookup(SnapshotVersion::BlockNum(0), &keys[0].0).is_err());
+        assert!(st.commitment(SnapshotVersion::BlockNum(5)).is_err());
+        // Blocks between two records are answered by the earlier one, and commit to the same
+        // table as the epoch snapshot taken at that time
+        for block_num in 1..4 {
+            let version = SnapshotVersion::BlockNum(block_num);
+            assert_eq!(st.total_stake(version)?, U256::from(500));
+            assert_eq!(st.lookup(version, &keys[1].0)?, U256::from(100));
+            assert!(st.lookup(version, &keys[5].0).is_err());
+            assert_eq!(
+                st.commitment(version)?,
+                st.commitment(SnapshotVersion::EpochStart)?
+            );
+        }
+        let version = SnapshotVersion::BlockNum(4);
+        assert_eq!(st.total_stake(version)?, U256::from(550));
+        assert_eq!(st.lookup(version, &keys[0].0)?, U256::from(150));
+        assert_eq!(st.lookup(version, &keys[1].0)?, U256::from(0));
+        assert_eq!(st.len(version)?, 6);
+        st.advance();
+        assert_eq!(
+            st.commitment(version)?,
+            st.commitment(SnapshotVersion::EpochStart)?
+        );
+
+        // Snapshots older than the window are pruned
+        st.record_block(12)?;
+        assert!(st.lookup(SnapshotVersion::BlockNum(1), &keys[0].0).is_err());
+        assert_eq!(
+            st.lookup(SnapshotVersion::BlockNum(2), &keys[0].0)?,
+            U256::from(100)
+        );
+        st.set_history_window(0);
+        assert!(st.total_stake(SnapshotVersion::BlockNum(4)).is_err());
+        assert_eq!(
+            st.total_stake(SnapshotVersion::BlockNum(12))?,
+            U256::from(550)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn crypto_test_stake_table_persistence() -> Result<(), StakeTableError> {
+        let mut st = StakeTable::<QCVerKey, StateVerKey, F>::new(10);
+        let mut pseudo_rng = jf_utils::test_rng();
+        let keys = (0..6)
+            .map(|_| {
+                (
+                    BLSOverBN254CurveSignatureScheme::key_gen(&(), &mut pseudo_rng)
+                        .unwrap()
+                        .1,
+                    SchnorrSignatureScheme::key_gen(&(), &mut pseudo_rng)
+                        .unwrap()
+                        .1,
+                )
+            })
+            .collect::<Vec<_>>();
+        keys.iter()
+            .take(4)
+            .for_each(|key| st.register(key.0, U256::from(100), key.1.clone()).unwrap());
+        st.record_block(1)?;
+        st.advance();
+        st.deregister(&keys[1].0)?;
+        st.register(keys[4].0, U256::from(100), keys[4].1.clone())?;
+        st.record_block(2)?;
+        st.advance();
+        st.register(keys[5].0, U256::from(100), keys[5].1.clone())?;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stake_table");
+        st.save(&path).unwrap();
+        let mut loaded = StakeTable::<QCVerKey, StateVerKey, F>::load(&path).unwrap();
+        assert_eq!(loaded, st);
+        for version in [
+            SnapshotVersion::EpochStart,
+            SnapshotVersion::LastEpochStart,
+            SnapshotVersion::BlockNum(1),
+            SnapshotVersion::BlockNum(2),
+        ] {
+            assert_eq!(loaded.commitment(version)?, st.commitment(version)?);
+            assert_eq!(loaded.total_stake(version)?, st.total_stake(version)?);
+        }
+
+        // The key mapping is rebuilt, so the loaded table keeps working
+        assert_eq!(
+            loaded.lookup(SnapshotVersion::Head, &keys[5].0)?,
+            U256::from(100)
+        );
+        assert!(loaded
+            .register(keys[0].0, U256::from(100), keys[0].1.clone())
+            .is_err());
+        loaded.advance();
+        st.advance();
+        assert_eq!(
+            loaded.commitment(SnapshotVersion::EpochStart)?,
+            st.commitment(SnapshotVersion::EpochStart)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn crypto_test_stake_table_rejects_tampered_snapshot() -> Result<(), StakeTableError> {
+        let mut st = StakeTable::<QCVerKey, StateVerKey, F>::new(10);
+        let mut pseudo_rng = jf_utils::test_rng();
+        for _ in 0..3 {
+            let bls_key = BLSOverBN254CurveSignatureScheme::key_gen(&(), &mut pseudo_rng)
+                .unwrap()
+                .1;
+            let schnorr_key = SchnorrSignatureScheme::key_gen(&(), &mut pseudo_rng)
+                .unwrap()
+                .1;
+            st.register(bls_key, U256::from(100), schnorr_key)?;
+        }
+        st.advance();
+
+        let mut total_stake = SerializedStakeTable::from(st.clone());
+        total_stake.epoch_start_total_stake = U256::from(1000);
+        assert!(StakeTable::try_from(total_stake).is_err());
+
+        let mut stake = SerializedStakeTable::from(st.clone());
+        stake.epoch_start.stake_amount[0] = U256::from(1);
+        stake.epoch_start_total_stake = U256::from(201);
+        assert!(StakeTable::try_from(stake).is_err());
+
+        assert_eq!(
+            StakeTable::try_from(SerializedStakeTable::from(st.clone()))?,
+            st
+        );
+        Ok(())
+    }
 }
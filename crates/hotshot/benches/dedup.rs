@@ -0,0 +1,89 @@
+//! Throughput of the deduplication of received messages
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use hotshot::traits::implementations::{digest_of, MessageDedup, MessageDigest};
+
+/// The number of messages received per view in the benchmarks, about a vote from each node of a
+/// large network
+const MESSAGES_PER_VIEW: u64 = 1000;
+
+/// The number of views the benchmarks receive messages for
+const VIEWS: u64 = 20;
+
+/// The number of messages remembered when the dedup layer is bounded by capacity rather than
+/// views
+const MAX_DIGESTS: usize = 1000;
+
+/// The digests of the messages received in each view
+fn digests() -> Vec<(u64, MessageDigest)> {
+    (0..VIEWS)
+        .flat_map(|view| {
+            (0..MESSAGES_PER_VIEW).map(move |i| {
+                (
+                    view,
+                    digest_of(&(view * MESSAGES_PER_VIEW + i).to_le_bytes()),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Benchmark the digest of messages of a typical size
+fn bench_digest(c: &mut Criterion) {
+    let message = vec![7u8; 512];
+    let mut group = c.benchmark_group("dedup_digest");
+    group.throughput(Throughput::Bytes(message.len() as u64));
+    group.bench_function("blake3_512_bytes", |b| {
+        b.iter(|| digest_of(black_box(&message)));
+    });
+    group.finish();
+}
+
+/// Benchmark recording new messages as views advance, and dropping messages received twice
+fn bench_insert(c: &mut Criterion) {
+    let digests = digests();
+    let mut group = c.benchmark_group("dedup_insert");
+    group.throughput(Throughput::Elements(digests.len() as u64));
+    group.bench_function("unique", |b| {
+        b.iter_batched(
+            MessageDedup::default,
+            |mut dedup| {
+                for (view, digest) in &digests {
+                    black_box(dedup.insert(*view, *digest));
+                }
+                dedup
+            },
+            BatchSize::SmallInput,
+        );
+    });
+    group.bench_function("duplicated", |b| {
+        b.iter_batched(
+            MessageDedup::default,
+            |mut dedup| {
+                // every message arrives over both networks of a combined network
+                for (view, digest) in &digests {
+                    black_box(dedup.insert(*view, *digest));
+                    black_box(dedup.insert(*view, *digest));
+                }
+                dedup
+            },
+            BatchSize::SmallInput,
+        );
+    });
+    group.bench_function("capacity_bound", |b| {
+        b.iter_batched(
+            || MessageDedup::new(u64::MAX, MAX_DIGESTS),
+            |mut dedup| {
+                for (view, digest) in &digests {
+                    black_box(dedup.insert(*view, *digest));
+                }
+                dedup
+            },
+            BatchSize::SmallInput,
+        );
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_digest, bench_insert);
+criterion_main!(benches);
@@ -15,6 +15,7 @@ pub mod tasks;
 use crate::{
     tasks::{
         add_consensus_task, add_da_task, add_network_event_task, add_network_message_task,
+        add_payload_recovery_task, add_request_task, add_state_signing_task, add_sync_task,
         add_transaction_task, add_upgrade_task, add_view_sync_task,
     },
     traits::{NodeImplementation, Storage},
@@ -22,7 +23,7 @@ use crate::{
 };
 use async_broadcast::{broadcast, InactiveReceiver, Receiver, Sender};
 use async_compatibility_layer::art::async_spawn;
-use async_lock::RwLock;
+use async_lock::{Mutex, RwLock};
 use async_trait::async_trait;
 use commit::Committable;
 use custom_debug::Debug;
@@ -35,19 +36,19 @@ use hotshot_task_impls::network;
 use hotshot_task::task::TaskRegistry;
 use hotshot_types::{
     consensus::{Consensus, ConsensusMetricsValue, View, ViewInner},
-    data::Leaf,
-    error::StorageSnafu,
+    data::{check_vid_srs, load_vid_srs, Leaf, VidParams},
+    error::{StorageSnafu, VidSrsSnafu},
     event::EventType,
-    message::{DataMessage, Message, MessageKind},
-    simple_certificate::QuorumCertificate,
+    light_client::StateSigningConfig,
+    simple_certificate::{DACertificate, QuorumCertificate},
     traits::{
         consensus_api::ConsensusApi,
-        election::Membership,
+        election::{ElectionError, Membership},
         network::ConnectedNetwork,
         node_implementation::{ConsensusTime, NodeType},
         signature_key::SignatureKey,
         states::ValidatedState,
-        storage::StoredView,
+        storage::{SafetyRecord, SafetyVote, StoredView, ViewEntry},
         BlockPayload,
     },
     HotShotConfig,
@@ -114,6 +115,32 @@ pub struct Memberships<TYPES: NodeType> {
     pub view_sync_membership: TYPES::Membership,
 }
 
+impl<TYPES: NodeType> Memberships<TYPES> {
+    /// Create the memberships of a node from the nodes it knows about.
+    ///
+    /// The DA membership uses `committee_config` and the others use `quorum_config`. They are all
+    /// derived from the quorum membership, so they follow the same stake table.
+    ///
+    /// # Errors
+    /// If the known nodes cannot form a committee.
+    pub fn create(
+        known_nodes_with_stake: Vec<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry>,
+        quorum_config: TYPES::ElectionConfigType,
+        committee_config: TYPES::ElectionConfigType,
+    ) -> Result<Self, ElectionError> {
+        let quorum_membership =
+            TYPES::Membership::create_election(known_nodes_with_stake.clone(), quorum_config)?;
+        let da_membership =
+            quorum_membership.derive_election(known_nodes_with_stake, committee_config)?;
+        Ok(Self {
+            vid_membership: quorum_membership.clone(),
+            view_sync_membership: quorum_membership.clone(),
+            quorum_membership,
+            da_membership,
+        })
+    }
+}
+
 /// Holds the state needed to participate in `HotShot` consensus
 pub struct SystemContextInner<TYPES: NodeType, I: NodeImplementation<TYPES>> {
     /// The public key of this node
@@ -135,11 +162,17 @@ pub struct SystemContextInner<TYPES: NodeType, I: NodeImplementation<TYPES>> {
     pub memberships: Arc<Memberships<TYPES>>,
 
     /// the metrics that the implementor is using.
-    _metrics: Arc<ConsensusMetricsValue>,
+    metrics: Arc<ConsensusMetricsValue>,
+
+    /// How to sign and publish the light client state on every decide, if at all
+    pub state_signing: Option<StateSigningConfig>,
 
     /// The hotstuff implementation
     consensus: Arc<RwLock<Consensus<TYPES>>>,
 
+    /// The votes this node has sent, kept in sync with the one in storage
+    safety_record: Mutex<SafetyRecord<TYPES>>,
+
     // global_registry: GlobalRegistry,
     /// Access to the output event stream.
     pub output_event_stream: (Sender<Event<TYPES>>, InactiveReceiver<Event<TYPES>>),
@@ -169,7 +202,15 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
     /// To do a full initialization, use `fn init` instead, which will set up background tasks as
     /// well.
     #[allow(clippy::too_many_arguments)]
-    #[instrument(skip(private_key, storage, memberships, networks, initializer, metrics))]
+    #[instrument(skip(
+        private_key,
+        storage,
+        memberships,
+        networks,
+        initializer,
+        metrics,
+        state_signing
+    ))]
     pub async fn new(
         public_key: TYPES::SignatureKey,
         private_key: <TYPES::SignatureKey as SignatureKey>::PrivateKey,
@@ -180,12 +221,24 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
         networks: Networks<TYPES, I>,
         initializer: HotShotInitializer<TYPES>,
         metrics: ConsensusMetricsValue,
+        state_signing: Option<StateSigningConfig>,
     ) -> Result<Self, HotShotError<TYPES>> {
         debug!("Creating a new hotshot");
 
+        // The VID, DA and consensus tasks all share the trusted setup loaded here.
+        if let Some(path) = &config.vid_srs_path {
+            load_vid_srs(path).context(VidSrsSnafu)?;
+        }
+        let vid_params = VidParams::from_membership::<TYPES>(
+            &memberships.quorum_membership,
+            TYPES::Time::genesis(),
+        );
+        check_vid_srs(vid_params.num_shares).context(VidSrsSnafu)?;
+
         let consensus_metrics = Arc::new(metrics);
         let anchored_leaf = initializer.inner;
         let instance_state = initializer.instance_state;
+        let undecided_leaves = initializer.undecided_leaves;
 
         // insert to storage
         storage
@@ -209,6 +262,21 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
         let mut saved_leaves = HashMap::new();
         let mut saved_payloads = BTreeMap::new();
         saved_leaves.insert(anchored_leaf.commit(), anchored_leaf.clone());
+
+        // restore the leaves that were not decided before a restart
+        for leaf in &undecided_leaves {
+            let state = Arc::new(TYPES::ValidatedState::from_header(leaf.get_block_header()));
+            validated_state_map.insert(
+                leaf.get_view_number(),
+                View {
+                    view_inner: ViewInner::Leaf {
+                        leaf: leaf.commit(),
+                        state,
+                    },
+                },
+            );
+            saved_leaves.insert(leaf.commit(), leaf.clone());
+        }
         if let Some(payload) = anchored_leaf.get_block_payload() {
             let encoded_txns: Vec<u8> = match payload.encode() {
                 // TODO (Keyao) [VALIDATED_STATE] - Avoid collect/copy on the encoded transaction bytes.
@@ -222,7 +290,11 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
             saved_payloads.insert(TYPES::Time::new(1), encoded_txns);
         }
 
-        let start_view = anchored_leaf.get_view_number();
+        let start_view = undecided_leaves
+            .iter()
+            .map(Leaf::get_view_number)
+            .chain(initializer.safety_record.highest_view())
+            .fold(anchored_leaf.get_view_number(), std::cmp::max);
 
         let consensus = Consensus {
             instance_state,
@@ -231,11 +303,13 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
             last_decided_view: anchored_leaf.get_view_number(),
             saved_leaves,
             saved_payloads,
-            saved_da_certs: HashMap::new(),
+            saved_vid_shares: BTreeMap::new(),
+            saved_da_certs: initializer.saved_da_certs,
             // TODO this is incorrect
             // https://github.com/EspressoSystems/HotShot/issues/560
-            locked_view: anchored_leaf.get_view_number(),
-            high_qc: anchored_leaf.get_justify_qc(),
+            locked_view: initializer.locked_view,
+            high_qc: initializer.high_qc,
+            version: VERSION_0_1,
             metrics: consensus_metrics.clone(),
         };
         let consensus = Arc::new(RwLock::new(consensus));
@@ -250,13 +324,15 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
         let inner: Arc<SystemContextInner<TYPES, I>> = Arc::new(SystemContextInner {
             id: nonce,
             consensus,
+            safety_record: Mutex::new(initializer.safety_record),
             public_key,
             private_key,
             config,
             storage,
             networks: Arc::new(networks),
             memberships: Arc::new(memberships),
-            _metrics: consensus_metrics.clone(),
+            metrics: consensus_metrics.clone(),
+            state_signing,
             internal_event_stream: (internal_tx, internal_rx.deactivate()),
             output_event_stream: (external_tx, external_rx.deactivate()),
         });
@@ -266,10 +342,25 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
 
     /// "Starts" consensus by sending a `QCFormed` event
     ///
+    /// A node restarted from storage instead moves its tasks to the view it stopped in.
+    ///
     /// # Panics
     /// Panics if sending genesis fails
     pub async fn start_consensus(&self) {
         debug!("Starting Consensus");
+        let (high_qc, cur_view) = {
+            let consensus = self.inner.consensus.read().await;
+            (consensus.high_qc.clone(), consensus.cur_view)
+        };
+        if !high_qc.is_genesis {
+            debug!("Resuming consensus in view {:?}", cur_view);
+            broadcast_event(
+                HotShotEvent::ViewChange(cur_view),
+                &self.inner.internal_event_stream.0,
+            )
+            .await;
+            return;
+        }
         self.inner
             .internal_event_stream
             .0
@@ -299,31 +390,16 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
         transaction: TYPES::Transaction,
     ) -> Result<(), HotShotError<TYPES>> {
         trace!("Adding transaction to our own queue");
-        // Wrap up a message
-        // TODO place a view number here that makes sense
-        // we haven't worked out how this will work yet
-        let message = DataMessage::SubmitTransaction(transaction.clone(), TYPES::Time::new(0));
         let api = self.clone();
 
         async_spawn(async move {
-            let da_membership = &api.inner.memberships.da_membership.clone();
             join! {
-                // TODO We should have a function that can return a network error if there is one
-                // but first we'd need to ensure our network implementations can support that
-                // (and not hang instead)
-                //
-                api
-                    .inner
-                    .networks
-                    .da_network
-                    .broadcast_message(
-                        Message {
-                            version: VERSION_0_1,
-                            sender: api.inner.public_key.clone(),
-                            kind: MessageKind::from(message),
-                        },
-                        da_membership.get_committee(TYPES::Time::new(0)),
-                    ),
+                // The DA network task sends the transaction, stamped with the protocol version in
+                // use.
+                broadcast_event(
+                    HotShotEvent::TransactionSend(transaction.clone(), api.inner.public_key.clone()),
+                    &api.inner.internal_event_stream.0,
+                ),
                 api
                     .send_external_event(Event {
                         view_number: api.inner.consensus.read().await.cur_view,
@@ -411,6 +487,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
         networks: Networks<TYPES, I>,
         initializer: HotShotInitializer<TYPES>,
         metrics: ConsensusMetricsValue,
+        state_signing: Option<StateSigningConfig>,
     ) -> Result<
         (
             SystemContextHandle<TYPES, I>,
@@ -430,6 +507,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
             networks,
             initializer,
             metrics,
+            state_signing,
         )
         .await?;
         let handle = hotshot.clone().run_tasks().await;
@@ -479,8 +557,26 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
             storage: self.inner.storage.clone(),
         };
 
-        add_network_message_task(registry.clone(), event_tx.clone(), quorum_network.clone()).await;
-        add_network_message_task(registry.clone(), event_tx.clone(), da_network.clone()).await;
+        // Messages are signed with our key only if peers require them to be.
+        let authenticate_messages = self.inner.config.authenticate_messages;
+        let private_key = authenticate_messages.then(|| self.inner.private_key.clone());
+
+        add_network_message_task(
+            registry.clone(),
+            event_tx.clone(),
+            quorum_network.clone(),
+            authenticate_messages,
+            self.inner.metrics.clone(),
+        )
+        .await;
+        add_network_message_task(
+            registry.clone(),
+            event_tx.clone(),
+            da_network.clone(),
+            authenticate_messages,
+            self.inner.metrics.clone(),
+        )
+        .await;
 
         add_network_event_task(
             registry.clone(),
@@ -489,6 +585,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
             quorum_network.clone(),
             quorum_membership,
             network::quorum_filter,
+            private_key.clone(),
         )
         .await;
         add_network_event_task(
@@ -498,6 +595,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
             da_network.clone(),
             da_membership,
             network::committee_filter,
+            private_key.clone(),
         )
         .await;
         add_network_event_task(
@@ -507,6 +605,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
             quorum_network.clone(),
             view_sync_membership,
             network::view_sync_filter,
+            private_key.clone(),
         )
         .await;
         add_network_event_task(
@@ -516,6 +615,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
             quorum_network.clone(),
             vid_membership,
             network::vid_filter,
+            private_key.clone(),
         )
         .await;
         add_consensus_task(
@@ -560,6 +660,37 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> SystemContext<TYPES, I> {
             &handle,
         )
         .await;
+        add_request_task(
+            registry.clone(),
+            event_tx.clone(),
+            event_rx.activate_cloned(),
+            &handle,
+        )
+        .await;
+        add_sync_task(
+            registry.clone(),
+            event_tx.clone(),
+            event_rx.activate_cloned(),
+            &handle,
+        )
+        .await;
+        add_payload_recovery_task(
+            registry.clone(),
+            event_tx.clone(),
+            event_rx.activate_cloned(),
+            &handle,
+        )
+        .await;
+        if let Some(state_signing) = self.inner.state_signing.clone() {
+            add_state_signing_task(
+                registry.clone(),
+                event_tx.clone(),
+                event_rx.activate_cloned(),
+                &handle,
+                state_signing,
+            )
+            .await;
+        }
         handle
     }
 }
@@ -608,16 +739,88 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>> ConsensusApi<TYPES, I>
         &self.inner.private_key
     }
 
-    async fn store_leaf(
+    async fn store_decided_leaves(
+        &self,
+        leaves: Vec<Leaf<TYPES>>,
+    ) -> std::result::Result<(), hotshot_types::traits::storage::StorageError> {
+        let storage = &self.inner.storage;
+        let newest_view = leaves.iter().map(Leaf::get_view_number).max();
+        storage
+            .append(leaves.into_iter().map(ViewEntry::from).collect())
+            .await?;
+        // Nodes which do not serve the decided chain to syncing peers may only keep a window of it.
+        if let (Some(newest_view), Some(retention)) =
+            (newest_view, self.inner.config.decided_view_retention)
+        {
+            storage
+                .cleanup_storage_up_to_view(TYPES::Time::new(newest_view.saturating_sub(retention)))
+                .await?;
+        }
+        storage.commit().await?;
+        Ok(())
+    }
+
+    async fn get_decided_leaves(
+        &self,
+        after: TYPES::Time,
+        limit: usize,
+    ) -> std::result::Result<Vec<Leaf<TYPES>>, hotshot_types::traits::storage::StorageError> {
+        let views = self.inner.storage.get_decided_views(after, limit).await?;
+        Ok(views.into_iter().map(Leaf::from_stored_view).collect())
+    }
+
+    async fn store_undecided_leaf(
         &self,
-        old_anchor_view: TYPES::Time,
         leaf: Leaf<TYPES>,
     ) -> std::result::Result<(), hotshot_types::traits::storage::StorageError> {
-        let view_to_insert = StoredView::from(leaf);
         let storage = &self.inner.storage;
-        storage.append_single_view(view_to_insert).await?;
-        storage.cleanup_storage_up_to_view(old_anchor_view).await?;
+        storage
+            .append(vec![ViewEntry::Undecided(StoredView::from(leaf))])
+            .await?;
+        storage.commit().await?;
+        Ok(())
+    }
+
+    async fn store_da_cert(
+        &self,
+        cert: DACertificate<TYPES>,
+    ) -> std::result::Result<(), hotshot_types::traits::storage::StorageError> {
+        let storage = &self.inner.storage;
+        storage.append_da_cert(cert).await?;
+        storage.commit().await?;
+        Ok(())
+    }
+
+    async fn record_vote(
+        &self,
+        vote: SafetyVote<TYPES>,
+    ) -> std::result::Result<bool, hotshot_types::traits::storage::StorageError> {
+        // Hold the lock until the record is committed, so concurrent votes are recorded in order.
+        let mut safety_record = self.inner.safety_record.lock().await;
+        let mut new_record = safety_record.clone();
+        if !new_record.record_vote(vote) {
+            return Ok(false);
+        }
+        let storage = &self.inner.storage;
+        storage.update_safety_record(new_record.clone()).await?;
+        storage.commit().await?;
+        *safety_record = new_record;
+        Ok(true)
+    }
+
+    async fn store_locked_qc(
+        &self,
+        qc: QuorumCertificate<TYPES>,
+    ) -> std::result::Result<(), hotshot_types::traits::storage::StorageError> {
+        let mut safety_record = self.inner.safety_record.lock().await;
+        let mut new_record = safety_record.clone();
+        if !new_record.update_locked_qc(qc) {
+            return Ok(());
+        }
+        let storage = &self.inner.storage;
+        storage.update_safety_record(new_record.clone()).await?;
         storage.commit().await?;
+        *safety_record = new_record;
         Ok(())
     }
 }
@@ -629,26 +832,109 @@ pub struct HotShotInitializer<TYPES: NodeType> {
 
     /// Instance-level state.
     instance_state: TYPES::InstanceState,
+
+    /// Leaves after the anchor leaf which have not been decided yet.
+    undecided_leaves: Vec<Leaf<TYPES>>,
+
+    /// Highest QC seen in the anchor leaf and the undecided leaves.
+    high_qc: QuorumCertificate<TYPES>,
+
+    /// The view of the leaf this node is locked on.
+    locked_view: TYPES::Time,
+
+    /// DA certificates for the views after the anchor leaf.
+    saved_da_certs: HashMap<TYPES::Time, DACertificate<TYPES>>,
+
+    /// The votes this node has already sent, and the QC it is locked on.
+    safety_record: SafetyRecord<TYPES>,
 }
 
 impl<TYPES: NodeType> HotShotInitializer<TYPES> {
     /// initialize from genesis
     /// # Errors
-    /// If we are unable to apply the genesis block to the default state
+    /// If we are unable to apply the genesis block to the default state, or no trusted setup for
+    /// VID is available to build it
     pub fn from_genesis(
         instance_state: &TYPES::InstanceState,
     ) -> Result<Self, HotShotError<TYPES>> {
-        Ok(Self {
-            inner: Leaf::genesis(instance_state),
-            instance_state: instance_state.clone(),
-        })
+        Ok(Self::from_reload(
+            Leaf::genesis(instance_state).context(VidSrsSnafu)?,
+            instance_state.clone(),
+        ))
     }
 
     /// reload previous state based on most recent leaf and the instance-level state.
     pub fn from_reload(anchor_leaf: Leaf<TYPES>, instance_state: TYPES::InstanceState) -> Self {
         Self {
+            high_qc: anchor_leaf.get_justify_qc(),
+            locked_view: anchor_leaf.get_view_number(),
             inner: anchor_leaf,
             instance_state,
+            undecided_leaves: Vec::new(),
+            saved_da_certs: HashMap::new(),
+            safety_record: SafetyRecord::default(),
         }
     }
+
+    /// Restart from the state saved in `storage`.
+    ///
+    /// The anchor leaf is the latest anchored view, and the undecided leaves, DA certificates and
+    /// safety record are restored on top of it, so the node resumes voting in the right view.
+    ///
+    /// # Errors
+    /// If the storage could not be read, or does not contain an anchored view.
+    pub async fn from_storage<S: Storage<TYPES>>(
+        storage: &S,
+        instance_state: TYPES::InstanceState,
+    ) -> Result<Self, HotShotError<TYPES>> {
+        let anchor_leaf =
+            Leaf::from_stored_view(storage.get_anchored_view().await.context(StorageSnafu)?);
+        let anchor_view = anchor_leaf.get_view_number();
+        let undecided_leaves: Vec<_> = storage
+            .get_undecided_views()
+            .await
+            .context(StorageSnafu)?
+            .into_iter()
+            .map(Leaf::from_stored_view)
+            .collect();
+
+        let safety_record = storage.get_safety_record().await.context(StorageSnafu)?;
+
+        let mut high_qc = anchor_leaf.get_justify_qc();
+        let mut locked_view = anchor_view;
+        if let Some(locked_qc) = &safety_record.locked_qc {
+            locked_view = locked_view.max(locked_qc.get_view_number());
+            if locked_qc.get_view_number() > high_qc.get_view_number() {
+                high_qc = locked_qc.clone();
+            }
+        }
+        for leaf in &undecided_leaves {
+            let justify_qc = leaf.get_justify_qc();
+            // A leaf directly extending its parent forms a two-chain, which locks on the parent.
+            if justify_qc.get_view_number() + 1 == leaf.get_view_number() {
+                locked_view = locked_view.max(justify_qc.get_view_number());
+            }
+            if justify_qc.get_view_number() > high_qc.get_view_number() {
+                high_qc = justify_qc;
+            }
+        }
+
+        let saved_da_certs = storage
+            .get_da_certs()
+            .await
+            .context(StorageSnafu)?
+            .into_iter()
+            .filter(|(view, _)| *view > anchor_view)
+            .collect();
+
+        Ok(Self {
+            inner: anchor_leaf,
+            instance_state,
+            undecided_leaves,
+            high_qc,
+            locked_view,
+            saved_da_certs,
+            safety_record,
+        })
+    }
 }
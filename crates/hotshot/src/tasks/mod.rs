@@ -11,19 +11,27 @@ use hotshot_task_impls::{
     da::DATaskState,
     events::HotShotEvent,
     network::{NetworkEventTaskState, NetworkMessageTaskState},
+    recovery::PayloadRecoveryTaskState,
+    request::RequestTaskState,
+    state_signing::StateSigningTaskState,
+    sync::SyncTaskState,
     transactions::TransactionTaskState,
     upgrade::UpgradeTaskState,
     vid::VIDTaskState,
     view_sync::ViewSyncTaskState,
 };
 use hotshot_types::{
+    consensus::ConsensusMetricsValue,
+    data::VidParams,
     event::Event,
+    light_client::StateSigningConfig,
     message::Messages,
     traits::{
         block_contents::vid_commitment,
         consensus_api::ConsensusApi,
         network::{ConsensusIntentEvent, TransmitType},
         node_implementation::{ConsensusTime, NodeImplementation, NodeType},
+        signature_key::SignatureKey,
         BlockPayload,
     },
 };
@@ -56,10 +64,14 @@ pub async fn add_network_message_task<
     task_reg: Arc<TaskRegistry>,
     event_stream: Sender<HotShotEvent<TYPES>>,
     channel: Arc<NET>,
+    authenticate_messages: bool,
+    metrics: Arc<ConsensusMetricsValue>,
 ) {
     let net = channel.clone();
     let network_state: NetworkMessageTaskState<_> = NetworkMessageTaskState {
         event_stream: event_stream.clone(),
+        authenticate_messages,
+        metrics,
     };
 
     // TODO we don't need two async tasks for this, we should combine the
@@ -122,10 +134,13 @@ pub async fn add_network_event_task<
     channel: Arc<NET>,
     membership: TYPES::Membership,
     filter: fn(&HotShotEvent<TYPES>) -> bool,
+    private_key: Option<<TYPES::SignatureKey as SignatureKey>::PrivateKey>,
 ) {
     let network_state: NetworkEventTaskState<_, _> = NetworkEventTaskState {
         channel,
         view: TYPES::Time::genesis(),
+        version: VERSION_0_1,
+        private_key,
         membership,
         filter,
     };
@@ -135,7 +150,8 @@ pub async fn add_network_event_task<
 
 /// Create the consensus task state
 /// # Panics
-/// If genesis payload can't be encoded.  This should not be possible
+/// If genesis payload can't be encoded, which should not be possible, or no trusted setup for VID
+/// is available, which `SystemContext::init` checks for
 pub async fn create_consensus_state<TYPES: NodeType, I: NodeImplementation<TYPES>>(
     output_stream: Sender<Event<TYPES>>,
     handle: &SystemContextHandle<TYPES, I>,
@@ -149,13 +165,12 @@ pub async fn create_consensus_state<TYPES: NodeType, I: NodeImplementation<TYPES
     // Impossible for `unwrap` to fail on the genesis payload.
     let payload_commitment = vid_commitment(
         &payload.encode().unwrap().collect(),
-        handle
-            .hotshot
-            .inner
-            .memberships
-            .quorum_membership
-            .total_nodes(),
-    );
+        VidParams::from_membership::<TYPES>(
+            &handle.hotshot.inner.memberships.quorum_membership,
+            TYPES::Time::genesis(),
+        ),
+    )
+    .expect("No trusted setup for VID, which `SystemContext::init` checks for");
     // build the consensus task
     let consensus_state = ConsensusTaskState {
         consensus,
@@ -174,9 +189,8 @@ pub async fn create_consensus_state<TYPES: NodeType, I: NodeImplementation<TYPES
         timeout_cert: None,
         upgrade_cert: None,
         decided_upgrade_cert: None,
-        current_network_version: VERSION_0_1,
         output_event_stream: output_stream,
-        vid_shares: BTreeMap::new(),
+        signed_proposals: BTreeMap::new(),
         current_proposal: None,
         id: handle.hotshot.inner.id,
         public_key: c_api.public_key().clone(),
@@ -277,6 +291,7 @@ pub async fn add_upgrade_task<TYPES: NodeType, I: NodeImplementation<TYPES>>(
         quorum_membership: c_api.inner.memberships.quorum_membership.clone().into(),
         quorum_network: c_api.inner.networks.quorum_network.clone(),
         should_vote: |_upgrade_proposal| false,
+        upgrade_target: None,
         vote_collector: None.into(),
         public_key: c_api.public_key().clone(),
         private_key: c_api.private_key().clone(),
@@ -341,6 +356,110 @@ pub async fn add_transaction_task<TYPES: NodeType, I: NodeImplementation<TYPES>>
     let task = Task::new(tx, rx, task_reg.clone(), transactions_state);
     task_reg.run_task(task).await;
 }
+
+/// add the request task
+pub async fn add_request_task<TYPES: NodeType, I: NodeImplementation<TYPES>>(
+    task_reg: Arc<TaskRegistry>,
+    tx: Sender<HotShotEvent<TYPES>>,
+    rx: Receiver<HotShotEvent<TYPES>>,
+    handle: &SystemContextHandle<TYPES, I>,
+) {
+    let c_api: HotShotConsensusApi<TYPES, I> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let request_state = RequestTaskState {
+        consensus: handle.hotshot.get_consensus(),
+        cur_view: TYPES::Time::new(0),
+        quorum_membership: c_api.inner.memberships.quorum_membership.clone().into(),
+        outstanding_requests: HashMap::new(),
+        unvalidated_leaves: HashMap::new(),
+        public_key: c_api.public_key().clone(),
+        id: handle.hotshot.inner.id,
+    };
+
+    let task = Task::new(tx, rx, task_reg.clone(), request_state);
+    task_reg.run_task(task).await;
+}
+
+/// add the sync task, which syncs the decided chain when far behind and serves it to peers
+pub async fn add_sync_task<TYPES: NodeType, I: NodeImplementation<TYPES>>(
+    task_reg: Arc<TaskRegistry>,
+    tx: Sender<HotShotEvent<TYPES>>,
+    rx: Receiver<HotShotEvent<TYPES>>,
+    handle: &SystemContextHandle<TYPES, I>,
+) {
+    let c_api: HotShotConsensusApi<TYPES, I> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let sync_state = SyncTaskState {
+        consensus: handle.hotshot.get_consensus(),
+        cur_view: TYPES::Time::new(0),
+        quorum_membership: c_api.inner.memberships.quorum_membership.clone().into(),
+        sync: None,
+        public_key: c_api.public_key().clone(),
+        id: handle.hotshot.inner.id,
+        api: c_api,
+        _pd: PhantomData,
+    };
+
+    let task = Task::new(tx, rx, task_reg.clone(), sync_state);
+    task_reg.run_task(task).await;
+}
+
+/// add the payload recovery task, which rebuilds missing block payloads from VID shares of peers
+pub async fn add_payload_recovery_task<TYPES: NodeType, I: NodeImplementation<TYPES>>(
+    task_reg: Arc<TaskRegistry>,
+    tx: Sender<HotShotEvent<TYPES>>,
+    rx: Receiver<HotShotEvent<TYPES>>,
+    handle: &SystemContextHandle<TYPES, I>,
+) {
+    let c_api: HotShotConsensusApi<TYPES, I> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let recovery_state = PayloadRecoveryTaskState {
+        consensus: handle.hotshot.get_consensus(),
+        cur_view: TYPES::Time::new(0),
+        quorum_membership: c_api.inner.memberships.quorum_membership.clone().into(),
+        recoveries: BTreeMap::new(),
+        public_key: c_api.public_key().clone(),
+        id: handle.hotshot.inner.id,
+        api: c_api,
+        _pd: PhantomData,
+    };
+
+    let task = Task::new(tx, rx, task_reg.clone(), recovery_state);
+    task_reg.run_task(task).await;
+}
+
+/// add the state signing task, which signs the light client state on every decide
+pub async fn add_state_signing_task<TYPES: NodeType, I: NodeImplementation<TYPES>>(
+    task_reg: Arc<TaskRegistry>,
+    tx: Sender<HotShotEvent<TYPES>>,
+    rx: Receiver<HotShotEvent<TYPES>>,
+    handle: &SystemContextHandle<TYPES, I>,
+    config: StateSigningConfig,
+) {
+    let c_api: HotShotConsensusApi<TYPES, I> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let state_signing_state = StateSigningTaskState {
+        state_key_pair: c_api
+            .inner
+            .config
+            .my_own_validator_config
+            .state_key_pair
+            .clone(),
+        config,
+        last_signed_view: TYPES::Time::new(0),
+        id: handle.hotshot.inner.id,
+        api: c_api,
+        _pd: PhantomData,
+    };
+
+    let task = Task::new(tx, rx, task_reg.clone(), state_signing_state);
+    task_reg.run_task(task).await;
+}
+
 /// add the view sync task
 pub async fn add_view_sync_task<TYPES: NodeType, I: NodeImplementation<TYPES>>(
     task_reg: Arc<TaskRegistry>,
@@ -2,6 +2,7 @@
 pub mod election;
 mod networking;
 mod node_implementation;
+mod state_relay;
 mod storage;
 
 pub use hotshot_types::traits::{BlockPayload, ValidatedState};
@@ -14,13 +15,21 @@ pub mod implementations {
     pub use super::{
         networking::{
             combined_network::{
-                calculate_hash_of, Cache, CombinedNetworks, UnderlyingCombinedNetworks,
+                CombinedNetworks, CombinedRoute, ConsecutiveFailures, FailoverStrategy,
+                UnderlyingCombinedNetworks,
+            },
+            dedup::{digest_of, MessageDedup, MessageDigest},
+            libp2p_network::{
+                peer_record::{
+                    PeerRecord, PeerRecordValidator, RecordVersionCounter, SignedPeerRecord,
+                },
+                Libp2pNetwork, PeerInfoVec,
             },
-            libp2p_network::{Libp2pNetwork, PeerInfoVec},
             memory_network::{MasterMap, MemoryNetwork},
             web_server_network::WebServerNetwork,
             NetworkingMetricsValue,
         },
-        storage::memory_storage::MemoryStorage, // atomic_storage::AtomicStorage,
+        state_relay::InProcessStateRelay,
+        storage::{atomic_storage::AtomicStorage, memory_storage::MemoryStorage},
     };
 }
@@ -2,3 +2,6 @@
 
 /// static (round robin) committee election
 pub mod static_committee;
+
+/// epoch-based committee read from a stake table
+pub mod stake_table_committee;
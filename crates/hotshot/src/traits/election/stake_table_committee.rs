@@ -0,0 +1,482 @@
+//! Epoch-based committee backed by a [`StakeTableScheme`].
+//!
+//! The committee changes at epoch boundaries. Registrations, deregistrations and stake updates
+//! are applied to the head of the stake table, and become effective two epochs after the epoch in
+//! which they were made, as the head only becomes a snapshot when the next epoch starts:
+//!  - the committee of the current epoch is the `LastEpochStart` snapshot of the stake table,
+//!  - the committee of the next epoch is the `EpochStart` snapshot of the stake table,
+//!  - the committee of the epoch after that is the head of the stake table.
+//!
+//! The stake table advances when a leaf of a new epoch is decided (see
+//! [`Membership::set_decided_view`]), so changes to the stake table must be applied at the same
+//! point of the chain by every node.
+
+use ethereum_types::U256;
+use hotshot_types::{
+    stake_table::StakeTableEntry,
+    traits::{
+        election::{ElectionConfig, ElectionError, Membership},
+        node_implementation::NodeType,
+        signature_key::SignatureKey,
+        stake_table::{SnapshotVersion, StakeTableError, StakeTableScheme},
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    sync::{Arc, RwLock},
+};
+use tracing::{debug, error, warn};
+
+/// Default number of views in an epoch
+pub const DEFAULT_EPOCH_LENGTH: u64 = 1000;
+
+/// Number of past epochs whose committee is kept, so that certificates of recent views remain
+/// verifiable after an epoch change.
+const RETAINED_EPOCHS: u64 = 2;
+
+/// configuration for a committee backed by a stake table
+#[derive(Clone, Serialize, Deserialize, core::fmt::Debug)]
+pub struct StakeTableElectionConfig {
+    /// Maximum number of nodes on the committee
+    pub num_nodes: u64,
+    /// Number of views in an epoch
+    pub epoch_length: u64,
+}
+
+impl Default for StakeTableElectionConfig {
+    fn default() -> Self {
+        Self {
+            num_nodes: 0,
+            epoch_length: DEFAULT_EPOCH_LENGTH,
+        }
+    }
+}
+
+impl ElectionConfig for StakeTableElectionConfig {}
+
+/// The committee in effect during one epoch
+#[derive(Debug)]
+struct EpochCommittee<PUBKEY: SignatureKey> {
+    /// The nodes on the committee and their stake, in stake table order
+    nodes_with_stake: Vec<StakeTableEntry<PUBKEY>>,
+    /// Total stake of the committee
+    total_stake: U256,
+}
+
+impl<PUBKEY: SignatureKey> EpochCommittee<PUBKEY> {
+    /// Read the committee from the `version` snapshot of `stake_table`, keeping at most
+    /// `committee_size` nodes with non-zero stake.
+    fn from_snapshot<ST>(stake_table: &ST, version: SnapshotVersion, committee_size: usize) -> Self
+    where
+        ST: StakeTableScheme<Key = PUBKEY, Amount = U256>,
+    {
+        let nodes_with_stake: Vec<_> = match stake_table.try_iter(version) {
+            Ok(entries) => entries
+                .filter(|(_, stake_amount, _)| !stake_amount.is_zero())
+                .take(committee_size)
+                .map(|(stake_key, stake_amount, _)| StakeTableEntry {
+                    stake_key,
+                    stake_amount,
+                })
+                .collect(),
+            Err(e) => {
+                error!("Failed to read stake table snapshot: {}", e);
+                Vec::new()
+            }
+        };
+        let total_stake = nodes_with_stake
+            .iter()
+            .fold(U256::zero(), |total, entry| total + entry.stake_amount);
+        Self {
+            nodes_with_stake,
+            total_stake,
+        }
+    }
+
+    /// `numerator / denominator` of the total stake plus one
+    fn threshold(&self, numerator: u64, denominator: u64) -> U256 {
+        self.total_stake * numerator / denominator + 1
+    }
+}
+
+/// State shared between all clones of a [`StakeTableCommittee`], and the memberships derived from
+/// it with [`Membership::derive_election`]
+#[derive(Debug)]
+struct CommitteeState<PUBKEY: SignatureKey, ST> {
+    /// The stake table, new changes are applied to its head
+    stake_table: ST,
+    /// Number of views in an epoch
+    epoch_length: u64,
+    /// The epoch of the last decided leaf
+    current_epoch: u64,
+    /// Committee of each known epoch, for each committee size in use
+    committees: BTreeMap<usize, BTreeMap<u64, Arc<EpochCommittee<PUBKEY>>>>,
+}
+
+impl<PUBKEY: SignatureKey, ST> CommitteeState<PUBKEY, ST>
+where
+    ST: StakeTableScheme<Key = PUBKEY, Amount = U256>,
+{
+    /// Record the committee of `epoch` from the `version` snapshot of the stake table, for every
+    /// committee size in use.
+    ///
+    /// An epoch without any stake keeps the committee of the epoch before it.
+    fn record_committee(&mut self, epoch: u64, version: SnapshotVersion) {
+        for (committee_size, committees) in &mut self.committees {
+            let committee =
+                EpochCommittee::from_snapshot(&self.stake_table, version, *committee_size);
+            if committee.nodes_with_stake.is_empty() {
+                if let Some((_, previous)) = committees.range(..epoch).next_back() {
+                    warn!("Stake table is empty at epoch {epoch}, keeping the previous committee");
+                    let previous = Arc::clone(previous);
+                    committees.insert(epoch, previous);
+                    continue;
+                }
+            }
+            committees.insert(epoch, Arc::new(committee));
+        }
+    }
+
+    /// Start recording committees of `committee_size` nodes, from the current epoch on.
+    ///
+    /// # Errors
+    /// If no key has stake in the committee of the current epoch.
+    fn add_committee_size(&mut self, committee_size: usize) -> Result<(), StakeTableError> {
+        if self.committees.contains_key(&committee_size) {
+            return Ok(());
+        }
+        let mut committees = BTreeMap::new();
+        for (epoch, version) in [
+            (self.current_epoch, SnapshotVersion::LastEpochStart),
+            (self.current_epoch + 1, SnapshotVersion::EpochStart),
+        ] {
+            let committee =
+                EpochCommittee::from_snapshot(&self.stake_table, version, committee_size);
+            if committee.nodes_with_stake.is_empty() {
+                // Later epochs without stake keep the committee before them, so no committee is
+                // empty as long as the first one is not.
+                let Some((_, previous)) = committees.range(..epoch).next_back() else {
+                    return Err(StakeTableError::EmptyCommittee);
+                };
+                let previous = Arc::clone(previous);
+                committees.insert(epoch, previous);
+            } else {
+                committees.insert(epoch, Arc::new(committee));
+            }
+        }
+        self.committees.insert(committee_size, committees);
+        Ok(())
+    }
+
+    /// Advance the stake table until `epoch` is the current epoch.
+    fn advance_to(&mut self, epoch: u64) {
+        while self.current_epoch < epoch {
+            self.stake_table.advance();
+            self.current_epoch += 1;
+            // After advancing, `LastEpochStart` is the committee of the current epoch, which we
+            // already recorded, and `EpochStart` is the committee of the next one.
+            let next_epoch = self.current_epoch + 1;
+            self.record_committee(next_epoch, SnapshotVersion::EpochStart);
+            debug!("Stake table advanced to epoch {}", self.current_epoch);
+        }
+        let oldest_retained = self.current_epoch.saturating_sub(RETAINED_EPOCHS);
+        for committees in self.committees.values_mut() {
+            *committees = committees.split_off(&oldest_retained);
+        }
+    }
+
+    /// The committee of `committee_size` nodes of `epoch`.
+    ///
+    /// Epochs after the last known one use the last known committee, and epochs which have been
+    /// garbage collected use the oldest retained committee.
+    fn committee(&self, committee_size: usize, epoch: u64) -> Arc<EpochCommittee<PUBKEY>> {
+        let committees = &self.committees[&committee_size];
+        let committee = committees
+            .range(..=epoch)
+            .next_back()
+            .or_else(|| committees.iter().next())
+            .map(|(_, committee)| committee);
+        Arc::clone(committee.expect("Committee map is never empty"))
+    }
+}
+
+/// A [`Membership`] whose committee is read from a [`StakeTableScheme`] and switches at epoch
+/// boundaries.
+///
+/// Clones, and the memberships derived from one with [`Membership::derive_election`], share the
+/// same stake table, so that changes made through one of them (e.g. with
+/// [`StakeTableCommittee::register`]) are seen by all of them.
+#[derive(Debug)]
+pub struct StakeTableCommittee<T, PUBKEY: SignatureKey, ST> {
+    /// The stake table and the committees read from it
+    state: Arc<RwLock<CommitteeState<PUBKEY, ST>>>,
+    /// Maximum number of nodes on the committee
+    committee_size: usize,
+    /// If set, the epoch whose committee this membership answers for, instead of the current one
+    epoch: Option<u64>,
+    /// Node type phantom
+    _type_phantom: PhantomData<T>,
+}
+
+impl<T, PUBKEY: SignatureKey, ST> Clone for StakeTableCommittee<T, PUBKEY, ST> {
+    fn clone(&self) -> Self {
+        Self {
+            state: Arc::clone(&self.state),
+            committee_size: self.committee_size,
+            epoch: self.epoch,
+            _type_phantom: PhantomData,
+        }
+    }
+}
+
+impl<T, PUBKEY: SignatureKey, ST> PartialEq for StakeTableCommittee<T, PUBKEY, ST> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+            && self.committee_size == other.committee_size
+            && self.epoch == other.epoch
+    }
+}
+
+impl<T, PUBKEY: SignatureKey, ST> Eq for StakeTableCommittee<T, PUBKEY, ST> {}
+
+impl<T, PUBKEY: SignatureKey, ST> Hash for StakeTableCommittee<T, PUBKEY, ST> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.state).hash(state);
+        self.committee_size.hash(state);
+        self.epoch.hash(state);
+    }
+}
+
+impl<T, PUBKEY: SignatureKey, ST> StakeTableCommittee<T, PUBKEY, ST>
+where
+    ST: StakeTableScheme<Key = PUBKEY, Amount = U256>,
+{
+    /// Creates a committee from `stake_table`, whose head is the committee of the first two
+    /// epochs.
+    ///
+    /// # Errors
+    /// If no key in `stake_table` has stake, as the committee would have no leader.
+    pub fn new(
+        mut stake_table: ST,
+        config: &StakeTableElectionConfig,
+    ) -> Result<Self, StakeTableError> {
+        debug!("Election Membership Size: {}", config.num_nodes);
+        stake_table.advance();
+        stake_table.advance();
+        let committee_size = config.num_nodes.try_into().unwrap_or(usize::MAX);
+        let mut state = CommitteeState {
+            stake_table,
+            epoch_length: config.epoch_length.max(1),
+            current_epoch: 0,
+            committees: BTreeMap::new(),
+        };
+        state.add_committee_size(committee_size)?;
+        Ok(Self {
+            state: Arc::new(RwLock::new(state)),
+            committee_size,
+            epoch: None,
+            _type_phantom: PhantomData,
+        })
+    }
+
+    /// A committee of at most `config.num_nodes` nodes, sharing the stake table of this one.
+    ///
+    /// The epoch length of the stake table is kept, as all memberships sharing it must change
+    /// epochs together.
+    ///
+    /// # Errors
+    /// If no key has stake in the committee of the current epoch.
+    ///
+    /// # Panics
+    /// If the stake table lock is poisoned.
+    pub fn with_committee_size(
+        &self,
+        config: &StakeTableElectionConfig,
+    ) -> Result<Self, StakeTableError> {
+        let committee_size = config.num_nodes.try_into().unwrap_or(usize::MAX);
+        self.state
+            .write()
+            .unwrap()
+            .add_committee_size(committee_size)?;
+        Ok(Self {
+            state: Arc::clone(&self.state),
+            committee_size,
+            epoch: None,
+            _type_phantom: PhantomData,
+        })
+    }
+
+    /// Register a new key in the stake table. It joins the committee two epochs after the current
+    /// one.
+    ///
+    /// # Errors
+    /// If the key is already registered.
+    ///
+    /// # Panics
+    /// If the stake table lock is poisoned.
+    pub fn register(&self, key: PUBKEY, amount: U256, aux: ST::Aux) -> Result<(), StakeTableError> {
+        self.state
+            .write()
+            .unwrap()
+            .stake_table
+            .register(key, amount, aux)
+    }
+
+    /// Deregister a key from the stake table. It leaves the committee two epochs after the
+    /// current one.
+    ///
+    /// # Errors
+    /// If the key is not registered.
+    ///
+    /// # Panics
+    /// If the stake table lock is poisoned.
+    pub fn deregister(&self, key: &PUBKEY) -> Result<(), StakeTableError> {
+        self.state.write().unwrap().stake_table.deregister(key)
+    }
+
+    /// The epoch of the last decided leaf.
+    ///
+    /// # Panics
+    /// If the stake table lock is poisoned.
+    #[must_use]
+    pub fn current_epoch(&self) -> u64 {
+        self.state.read().unwrap().current_epoch
+    }
+
+    /// The epoch containing view `view_number`.
+    ///
+    /// # Panics
+    /// If the stake table lock is poisoned.
+    #[must_use]
+    pub fn epoch_of(&self, view_number: u64) -> u64 {
+        view_number / self.state.read().unwrap().epoch_length
+    }
+
+    /// The committee this membership answers for when no view is given.
+    fn committee(&self) -> Arc<EpochCommittee<PUBKEY>> {
+        let state = self.state.read().unwrap();
+        state.committee(
+            self.committee_size,
+            self.epoch.unwrap_or(state.current_epoch),
+        )
+    }
+
+    /// The committee of the epoch containing view `view_number`.
+    fn committee_for_view(&self, view_number: u64) -> Arc<EpochCommittee<PUBKEY>> {
+        let state = self.state.read().unwrap();
+        state.committee(self.committee_size, view_number / state.epoch_length)
+    }
+}
+
+impl<TYPES, PUBKEY, ST> Membership<TYPES> for StakeTableCommittee<TYPES, PUBKEY, ST>
+where
+    TYPES: NodeType<SignatureKey = PUBKEY, ElectionConfigType = StakeTableElectionConfig>,
+    PUBKEY: SignatureKey<StakeTableEntry = StakeTableEntry<PUBKEY>> + 'static,
+    ST: StakeTableScheme<Key = PUBKEY, Amount = U256>
+        + Default
+        + core::fmt::Debug
+        + Send
+        + Sync
+        + 'static,
+    ST::Aux: Default,
+{
+    fn get_committee_qc_stake_table(&self) -> Vec<StakeTableEntry<PUBKEY>> {
+        self.committee().nodes_with_stake.clone()
+    }
+
+    /// Index the committee of the epoch of `view_number` with `view_number`
+    fn get_leader(&self, view_number: TYPES::Time) -> PUBKEY {
+        // Committees are never empty, see `StakeTableCommittee::new`
+        let committee = self.committee_for_view(*view_number);
+        let index =
+            usize::try_from(*view_number % committee.nodes_with_stake.len() as u64).unwrap();
+        committee.nodes_with_stake[index].stake_key.clone()
+    }
+
+    fn has_stake(&self, pub_key: &PUBKEY) -> bool {
+        self.get_stake(pub_key).is_some()
+    }
+
+    fn get_stake(&self, pub_key: &PUBKEY) -> Option<StakeTableEntry<PUBKEY>> {
+        self.committee()
+            .nodes_with_stake
+            .iter()
+            .find(|entry| entry.stake_key == *pub_key)
+            .cloned()
+    }
+
+    fn default_election_config(num_nodes: u64) -> StakeTableElectionConfig {
+        StakeTableElectionConfig {
+            num_nodes,
+            epoch_length: DEFAULT_EPOCH_LENGTH,
+        }
+    }
+
+    fn create_election(
+        entries: Vec<StakeTableEntry<PUBKEY>>,
+        config: StakeTableElectionConfig,
+    ) -> Result<Self, ElectionError> {
+        let mut stake_table = ST::default();
+        for entry in entries {
+            if let Err(e) =
+                stake_table.register(entry.stake_key, entry.stake_amount, ST::Aux::default())
+            {
+                error!("Failed to register key in the stake table: {}", e);
+            }
+        }
+        Self::new(stake_table, &config).map_err(|error| ElectionError::InvalidStakeTable { error })
+    }
+
+    /// Share the stake table of this committee, the keys in `_entries` are already registered
+    fn derive_election(
+        &self,
+        _entries: Vec<StakeTableEntry<PUBKEY>>,
+        config: StakeTableElectionConfig,
+    ) -> Result<Self, ElectionError> {
+        self.with_committee_size(&config)
+            .map_err(|error| ElectionError::InvalidStakeTable { error })
+    }
+
+    fn total_nodes(&self) -> usize {
+        self.committee().nodes_with_stake.len()
+    }
+
+    fn success_threshold(&self) -> U256 {
+        self.committee().threshold(2, 3)
+    }
+
+    fn failure_threshold(&self) -> U256 {
+        self.committee().threshold(1, 3)
+    }
+
+    fn upgrade_threshold(&self) -> U256 {
+        self.committee().threshold(9, 10)
+    }
+
+    fn get_committee(&self, view_number: TYPES::Time) -> BTreeSet<PUBKEY> {
+        self.committee_for_view(*view_number)
+            .nodes_with_stake
+            .iter()
+            .map(|entry| entry.stake_key.clone())
+            .collect()
+    }
+
+    fn for_view(&self, view_number: TYPES::Time) -> Cow<'_, Self> {
+        Cow::Owned(Self {
+            state: Arc::clone(&self.state),
+            committee_size: self.committee_size,
+            epoch: Some(self.epoch_of(*view_number)),
+            _type_phantom: PhantomData,
+        })
+    }
+
+    fn set_decided_view(&self, view_number: TYPES::Time) {
+        let mut state = self.state.write().unwrap();
+        let epoch = *view_number / state.epoch_length;
+        state.advance_to(epoch);
+    }
+}
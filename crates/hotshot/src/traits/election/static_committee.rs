@@ -1,34 +1,101 @@
 // use ark_bls12_381::Parameters as Param381;
+use ethereum_types::{U256, U512};
 use hotshot_types::signature_key::BLSPubKey;
 use hotshot_types::traits::{
-    election::{ElectionConfig, Membership},
+    election::{ElectionConfig, ElectionError, Membership},
     node_implementation::NodeType,
-    signature_key::SignatureKey,
+    signature_key::{SignatureKey, StakeTableEntryType},
 };
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 #[allow(deprecated)]
 use serde::{Deserialize, Serialize};
-use std::{marker::PhantomData, num::NonZeroU64};
+use std::{fmt::Debug, hash::Hash, marker::PhantomData};
 use tracing::debug;
 
+/// How a [`GeneralStaticCommittee`] chooses the leader of each view
+pub trait LeaderElection: Clone + Debug + Eq + Hash + Send + Sync + 'static {
+    /// The index of the leader of view `view_number` in `stakes`, the stake of each node in stake
+    /// table order. `stakes` is never empty.
+    fn leader_index(view_number: u64, stakes: &[U256]) -> usize;
+}
+
+/// Samples the leader of each view with probability proportional to its stake, seeded by the view
+/// number so every node agrees on it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct StakeWeighted;
+
+impl LeaderElection for StakeWeighted {
+    fn leader_index(view_number: u64, stakes: &[U256]) -> usize {
+        let total_stake = stakes
+            .iter()
+            .fold(U256::zero(), |total, stake| total + *stake);
+        if total_stake.is_zero() {
+            // There is no stake to weigh the nodes by, so they take turns.
+            return RoundRobin::leader_index(view_number, stakes);
+        }
+        let mut rng = ChaCha20Rng::seed_from_u64(view_number);
+        let mut bytes = [0u8; 64];
+        rng.fill_bytes(&mut bytes);
+        // A 512-bit sample keeps the modulo bias negligible for any 256-bit total stake.
+        let sample = U512::from_big_endian(&bytes) % U512::from(total_stake);
+        let mut remaining: U256 = sample.try_into().unwrap();
+        stakes
+            .iter()
+            .position(|stake| {
+                if remaining < *stake {
+                    return true;
+                }
+                remaining -= *stake;
+                false
+            })
+            .unwrap()
+    }
+}
+
+/// Lets the nodes take turns in stake table order, regardless of their stake
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RoundRobin;
+
+impl LeaderElection for RoundRobin {
+    fn leader_index(view_number: u64, stakes: &[U256]) -> usize {
+        usize::try_from(view_number % stakes.len() as u64).unwrap()
+    }
+}
+
+/// The [`LeaderElection`] of a [`GeneralStaticCommittee`] unless another one is given: the nodes
+/// take turns, or leaders are sampled by stake with the `randomized-leader-election` feature
+#[cfg(not(feature = "randomized-leader-election"))]
+pub type DefaultLeaderElection = RoundRobin;
+
+/// The [`LeaderElection`] of a [`GeneralStaticCommittee`] unless another one is given: the nodes
+/// take turns, or leaders are sampled by stake with the `randomized-leader-election` feature
 #[cfg(feature = "randomized-leader-election")]
-use rand::{rngs::StdRng, Rng};
+pub type DefaultLeaderElection = StakeWeighted;
 
-/// Dummy implementation of [`Membership`]
+/// Static implementation of [`Membership`], where votes and thresholds are weighted by stake, and
+/// leaders are chosen by `LEADER`
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
-pub struct GeneralStaticCommittee<T, PUBKEY: SignatureKey> {
+pub struct GeneralStaticCommittee<
+    T,
+    PUBKEY: SignatureKey,
+    LEADER: LeaderElection = DefaultLeaderElection,
+> {
     /// All the nodes participating and their stake
     nodes_with_stake: Vec<PUBKEY::StakeTableEntry>,
     /// The nodes on the static committee and their stake
     committee_nodes_with_stake: Vec<PUBKEY::StakeTableEntry>,
     /// Node type phantom
     _type_phantom: PhantomData<T>,
+    /// Leader election phantom
+    _leader_phantom: PhantomData<LEADER>,
 }
 
 /// static committee using a vrf kp
 pub type StaticCommittee<T> = GeneralStaticCommittee<T, BLSPubKey>;
 
-impl<T, PUBKEY: SignatureKey> GeneralStaticCommittee<T, PUBKEY> {
+impl<T, PUBKEY: SignatureKey, LEADER: LeaderElection> GeneralStaticCommittee<T, PUBKEY, LEADER> {
     /// Creates a new dummy elector
     #[must_use]
     pub fn new(_nodes: &[PUBKEY], nodes_with_stake: Vec<PUBKEY::StakeTableEntry>) -> Self {
@@ -36,8 +103,21 @@ impl<T, PUBKEY: SignatureKey> GeneralStaticCommittee<T, PUBKEY> {
             nodes_with_stake: nodes_with_stake.clone(),
             committee_nodes_with_stake: nodes_with_stake,
             _type_phantom: PhantomData,
+            _leader_phantom: PhantomData,
         }
     }
+
+    /// Total stake of the nodes on the committee
+    fn committee_total_stake(&self) -> U256 {
+        self.committee_nodes_with_stake
+            .iter()
+            .fold(U256::zero(), |total, entry| total + entry.get_stake())
+    }
+
+    /// `numerator / denominator` of the committee's total stake plus one
+    fn stake_threshold(&self, numerator: u64, denominator: u64) -> U256 {
+        self.committee_total_stake() * numerator / denominator + 1
+    }
 }
 
 /// configuration for static committee. stub for now
@@ -49,8 +129,8 @@ pub struct StaticElectionConfig {
 
 impl ElectionConfig for StaticElectionConfig {}
 
-impl<TYPES, PUBKEY: SignatureKey + 'static> Membership<TYPES>
-    for GeneralStaticCommittee<TYPES, PUBKEY>
+impl<TYPES, PUBKEY: SignatureKey + 'static, LEADER: LeaderElection> Membership<TYPES>
+    for GeneralStaticCommittee<TYPES, PUBKEY, LEADER>
 where
     TYPES: NodeType<SignatureKey = PUBKEY, ElectionConfigType = StaticElectionConfig>,
 {
@@ -59,39 +139,29 @@ where
         self.committee_nodes_with_stake.clone()
     }
 
-    #[cfg(not(feature = "randomized-leader-election"))]
-    /// Index the vector of public keys with the current view number
+    /// Choose the leader among all the nodes participating with `LEADER`
     fn get_leader(&self, view_number: TYPES::Time) -> PUBKEY {
-        let index = usize::try_from(*view_number % self.nodes_with_stake.len() as u64).unwrap();
-        let res = self.nodes_with_stake[index].clone();
-        TYPES::SignatureKey::get_public_key(&res)
-    }
-
-    #[cfg(feature = "randomized-leader-election")]
-    /// Index the vector of public keys with a random number generated using the current view number as a seed
-    fn get_leader(&self, view_number: TYPES::Time) -> PUBKEY {
-        let mut rng: StdRng = rand::SeedableRng::seed_from_u64(*view_number as u64);
-        let randomized_view_number: u64 = rng.gen();
-        let index = (randomized_view_number % self.nodes_with_stake.len() as u64) as usize;
-        let res = self.nodes_with_stake[index].clone();
-        TYPES::SignatureKey::get_public_key(&res)
+        let stakes: Vec<U256> = self
+            .nodes_with_stake
+            .iter()
+            .map(StakeTableEntryType::get_stake)
+            .collect();
+        let index = LEADER::leader_index(*view_number, &stakes);
+        TYPES::SignatureKey::get_public_key(&self.nodes_with_stake[index])
     }
 
     fn has_stake(&self, pub_key: &PUBKEY) -> bool {
-        let entry = pub_key.get_stake_table_entry(1u64);
-        self.committee_nodes_with_stake.contains(&entry)
+        self.get_stake(pub_key).is_some()
     }
 
     fn get_stake(
         &self,
         pub_key: &<TYPES as NodeType>::SignatureKey,
     ) -> Option<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry> {
-        let entry = pub_key.get_stake_table_entry(1u64);
-        if self.committee_nodes_with_stake.contains(&entry) {
-            Some(entry)
-        } else {
-            None
-        }
+        self.committee_nodes_with_stake
+            .iter()
+            .find(|entry| TYPES::SignatureKey::get_public_key(entry) == *pub_key)
+            .cloned()
     }
 
     fn default_election_config(num_nodes: u64) -> TYPES::ElectionConfigType {
@@ -101,31 +171,35 @@ where
     fn create_election(
         keys_qc: Vec<PUBKEY::StakeTableEntry>,
         config: TYPES::ElectionConfigType,
-    ) -> Self {
+    ) -> Result<Self, ElectionError> {
         let mut committee_nodes_with_stake = keys_qc.clone();
         debug!("Election Membership Size: {}", config.num_nodes);
         committee_nodes_with_stake.truncate(config.num_nodes.try_into().unwrap());
-        Self {
+        Ok(Self {
             nodes_with_stake: keys_qc,
             committee_nodes_with_stake,
             _type_phantom: PhantomData,
-        }
+            _leader_phantom: PhantomData,
+        })
     }
 
     fn total_nodes(&self) -> usize {
         self.committee_nodes_with_stake.len()
     }
 
-    fn success_threshold(&self) -> NonZeroU64 {
-        NonZeroU64::new(((self.committee_nodes_with_stake.len() as u64 * 2) / 3) + 1).unwrap()
+    /// More than two thirds of the committee's stake
+    fn success_threshold(&self) -> U256 {
+        self.stake_threshold(2, 3)
     }
 
-    fn failure_threshold(&self) -> NonZeroU64 {
-        NonZeroU64::new(((self.committee_nodes_with_stake.len() as u64) / 3) + 1).unwrap()
+    /// More than one third of the committee's stake
+    fn failure_threshold(&self) -> U256 {
+        self.stake_threshold(1, 3)
     }
 
-    fn upgrade_threshold(&self) -> NonZeroU64 {
-        NonZeroU64::new(((self.committee_nodes_with_stake.len() as u64 * 9) / 10) + 1).unwrap()
+    /// More than nine tenths of the committee's stake
+    fn upgrade_threshold(&self) -> U256 {
+        self.stake_threshold(9, 10)
     }
 
     fn get_committee(
@@ -6,6 +6,7 @@
 //! - [`Libp2pNetwork`](libp2p_network::Libp2pNetwork), a production-ready networking implementation built on top of libp2p-rs.
 
 pub mod combined_network;
+pub mod dedup;
 pub mod libp2p_network;
 pub mod memory_network;
 pub mod web_server_network;
@@ -36,6 +37,9 @@ pub struct NetworkingMetricsValue {
     pub outgoing_broadcast_message_count: Box<dyn Counter>,
     /// A [`Counter`] which tracks how many messages failed to send
     pub message_failed_to_send: Box<dyn Counter>,
+    /// A [`Counter`] which tracks how many received messages were dropped because their protocol
+    /// version is not supported
+    pub unsupported_version_message_count: Box<dyn Counter>,
     // A [`Gauge`] which tracks how many connected entries there are in the gossipsub mesh
     // pub gossipsub_mesh_connected: Box<dyn Gauge>,
     // A [`Gauge`] which tracks how many kademlia entries there are
@@ -173,6 +177,8 @@ impl NetworkingMetricsValue {
                 .create_counter(String::from("outgoing_broadcast_message_count"), None),
             message_failed_to_send: metrics
                 .create_counter(String::from("message_failed_to_send"), None),
+            unsupported_version_message_count: metrics
+                .create_counter(String::from("unsupported_version_message_count"), None),
         }
     }
 }
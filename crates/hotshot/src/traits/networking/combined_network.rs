@@ -1,15 +1,16 @@
 //! Networking Implementation that has a primary and a fallback newtork.  If the primary
-//! Errors we will use the backup to send or receive
-use super::NetworkError;
+//! Errors we will use the backup to send or receive. Any two networks can be combined, and when
+//! the primary is presumed down is up to a [`FailoverStrategy`].
+use super::{dedup::MessageDedup, NetworkError};
 use crate::traits::implementations::{Libp2pNetwork, WebServerNetwork};
 use async_lock::RwLock;
 use hotshot_constants::{
-    COMBINED_NETWORK_CACHE_SIZE, COMBINED_NETWORK_MIN_PRIMARY_FAILURES,
-    COMBINED_NETWORK_PRIMARY_CHECK_INTERVAL,
+    COMBINED_NETWORK_MIN_PRIMARY_FAILURES, COMBINED_NETWORK_PRIMARY_CHECK_INTERVAL,
 };
 use std::{
-    collections::{BTreeSet, HashSet},
-    hash::Hasher,
+    collections::{BTreeSet, HashMap},
+    fmt::Debug,
+    marker::PhantomData,
     sync::atomic::{AtomicU64, Ordering},
 };
 use tracing::warn;
@@ -24,134 +25,197 @@ use hotshot_types::traits::network::{NetworkReliability, TestableNetworkingImple
 use hotshot_types::{
     boxed_sync,
     data::ViewNumber,
-    message::Message,
+    message::{Message, MessagePurpose},
     traits::{
-        network::{ConnectedNetwork, ConsensusIntentEvent, TransmitType},
+        network::{ConnectedNetwork, ConsensusIntentEvent, TransmitType, ViewMessage},
         node_implementation::NodeType,
     },
     BoxSyncFuture,
 };
-use std::{collections::hash_map::DefaultHasher, sync::Arc};
+use std::sync::Arc;
 
-use std::hash::Hash;
+/// Decides when a combined network sends on its primary network, and when it presumes the
+/// primary down and relies on the secondary network alone
+pub trait FailoverStrategy: Debug + Send + Sync + 'static {
+    /// Whether the next message should be attempted on the primary network
+    fn should_try_primary(&self) -> bool;
 
-/// A cache to keep track of the last n messages we've seen, avoids reprocessing duplicates
-/// from multiple networks
-#[derive(Clone, Debug)]
-pub struct Cache {
-    /// The maximum number of items to store in the cache
-    capacity: usize,
-    /// The cache itself
-    inner: HashSet<u64>,
-    /// The hashes of the messages in the cache, in order of insertion
-    hashes: Vec<u64>,
+    /// Record that a message was delivered to the primary network
+    fn primary_succeeded(&self);
+
+    /// Record that a message failed to be delivered to the primary network
+    fn primary_failed(&self);
 }
 
-impl Cache {
-    /// Create a new cache with the given capacity
+/// Presumes the primary network down after a number of consecutive failures, then only retries
+/// it every so many messages until a message gets through again
+#[derive(Debug)]
+pub struct ConsecutiveFailures {
+    /// The number of consecutive failures after which the primary is presumed down
+    min_failures: u64,
+    /// The number of messages to send over the secondary network alone before retrying the
+    /// primary
+    check_interval: u64,
+    /// The number of consecutive messages the primary network failed to deliver
+    failures: AtomicU64,
+    /// The number of messages skipped on the primary network since it was last attempted
+    skipped: AtomicU64,
+}
+
+impl ConsecutiveFailures {
+    /// Create a strategy presuming the primary down after `min_failures` consecutive failures,
+    /// and retrying it every `check_interval` messages from then on
     #[must_use]
-    pub fn new(capacity: usize) -> Self {
+    pub fn new(min_failures: u64, check_interval: u64) -> Self {
         Self {
-            capacity,
-            inner: HashSet::with_capacity(capacity),
-            hashes: Vec::with_capacity(capacity),
+            min_failures,
+            check_interval,
+            failures: AtomicU64::new(0),
+            skipped: AtomicU64::new(0),
         }
     }
 
-    /// Insert a hash into the cache
-    pub fn insert(&mut self, hash: u64) {
-        if self.inner.contains(&hash) {
-            return;
-        }
-
-        // calculate how much we are over and remove that many elements from the cache. deal with overflow
-        let over = (self.hashes.len() + 1).saturating_sub(self.capacity);
-        if over > 0 {
-            for _ in 0..over {
-                let hash = self.hashes.remove(0);
-                self.inner.remove(&hash);
-            }
-        }
+    /// The number of consecutive messages the primary network failed to deliver
+    #[must_use]
+    pub fn consecutive_failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+}
 
-        self.inner.insert(hash);
-        self.hashes.push(hash);
+impl Default for ConsecutiveFailures {
+    fn default() -> Self {
+        Self::new(
+            COMBINED_NETWORK_MIN_PRIMARY_FAILURES,
+            COMBINED_NETWORK_PRIMARY_CHECK_INTERVAL,
+        )
     }
+}
 
-    /// Check if the cache contains a hash
-    #[must_use]
-    pub fn contains(&self, hash: u64) -> bool {
-        self.inner.contains(&hash)
+impl FailoverStrategy for ConsecutiveFailures {
+    fn should_try_primary(&self) -> bool {
+        if self.failures.load(Ordering::Relaxed) < self.min_failures {
+            return true;
+        }
+        // the primary is presumed down, check whether it is back up every `check_interval` messages
+        let skipped = self.skipped.fetch_add(1, Ordering::Relaxed) + 1;
+        if skipped < self.check_interval {
+            return false;
+        }
+        self.skipped.store(0, Ordering::Relaxed);
+        true
     }
 
-    /// Get the number of items in the cache
-    #[must_use]
-    pub fn len(&self) -> usize {
-        self.inner.len()
+    fn primary_succeeded(&self) {
+        self.failures.store(0, Ordering::Relaxed);
+        self.skipped.store(0, Ordering::Relaxed);
     }
 
-    /// True if the cache is empty false otherwise
-    #[must_use]
-    pub fn is_empty(&self) -> bool {
-        self.inner.is_empty()
+    fn primary_failed(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
     }
 }
 
-/// Helper function to calculate a hash of a type that implements Hash
-pub fn calculate_hash_of<T: Hash>(t: &T) -> u64 {
-    let mut s = DefaultHasher::new();
-    t.hash(&mut s);
-    s.finish()
+/// The networks a combined network sends a kind of message on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CombinedRoute {
+    /// Send on the primary network, unless it is presumed down, and always on the secondary
+    #[default]
+    Both,
+    /// Send on the primary network, falling back to the secondary if the primary is presumed
+    /// down or fails
+    Primary,
+    /// Only send on the secondary network
+    Secondary,
 }
 
 /// A communication channel with 2 networks, where we can fall back to the slower network if the
 /// primary fails
 #[derive(Clone, Debug)]
-pub struct CombinedNetworks<TYPES: NodeType> {
+pub struct CombinedNetworks<
+    TYPES: NodeType,
+    PRIMARY = WebServerNetwork<TYPES>,
+    SECONDARY = Libp2pNetwork<Message<TYPES>, <TYPES as NodeType>::SignatureKey>,
+> {
     /// The two networks we'll use for send/recv
-    networks: Arc<UnderlyingCombinedNetworks<TYPES>>,
+    networks: Arc<UnderlyingCombinedNetworks<PRIMARY, SECONDARY>>,
+
+    /// Messages of the latest views we received, to prevent processing duplicates
+    message_dedup: Arc<RwLock<MessageDedup>>,
+
+    /// Decides whether the primary network is down
+    failover: Arc<dyn FailoverStrategy>,
 
-    /// Last n seen messages to prevent processing duplicates
-    message_cache: Arc<RwLock<Cache>>,
+    /// The networks to send each kind of message on, [`CombinedRoute::Both`] if absent
+    routes: HashMap<MessagePurpose, CombinedRoute>,
 
-    /// If the primary network is down (0) or not, and for how many messages
-    primary_down: Arc<AtomicU64>,
+    /// phantom
+    _pd: PhantomData<TYPES>,
 }
 
-impl<TYPES: NodeType> CombinedNetworks<TYPES> {
+impl<TYPES: NodeType, PRIMARY, SECONDARY> CombinedNetworks<TYPES, PRIMARY, SECONDARY> {
     /// Constructor
     #[must_use]
-    pub fn new(networks: Arc<UnderlyingCombinedNetworks<TYPES>>) -> Self {
+    pub fn new(networks: Arc<UnderlyingCombinedNetworks<PRIMARY, SECONDARY>>) -> Self {
         Self {
             networks,
-            message_cache: Arc::new(RwLock::new(Cache::new(COMBINED_NETWORK_CACHE_SIZE))),
-            primary_down: Arc::new(AtomicU64::new(0)),
+            message_dedup: Arc::new(RwLock::new(MessageDedup::default())),
+            failover: Arc::new(ConsecutiveFailures::default()),
+            routes: HashMap::new(),
+            _pd: PhantomData,
         }
     }
 
+    /// Decide when the primary network is down with the given strategy
+    #[must_use]
+    pub fn with_failover_strategy(mut self, failover: Arc<dyn FailoverStrategy>) -> Self {
+        self.failover = failover;
+        self
+    }
+
+    /// Send messages with the given purpose on the given networks
+    #[must_use]
+    pub fn with_route(mut self, purpose: MessagePurpose, route: CombinedRoute) -> Self {
+        self.routes.insert(purpose, route);
+        self
+    }
+
     /// Get a ref to the primary network
     #[must_use]
-    pub fn primary(&self) -> &WebServerNetwork<TYPES> {
+    pub fn primary(&self) -> &PRIMARY {
         &self.networks.0
     }
 
     /// Get a ref to the backup network
     #[must_use]
-    pub fn secondary(&self) -> &Libp2pNetwork<Message<TYPES>, TYPES::SignatureKey> {
+    pub fn secondary(&self) -> &SECONDARY {
         &self.networks.1
     }
+
+    /// The networks to send a message on
+    fn route(&self, message: &Message<TYPES>) -> CombinedRoute {
+        self.routes
+            .get(&message.purpose())
+            .copied()
+            .unwrap_or_default()
+    }
 }
 
-/// Wrapper for the tuple of `WebServerNetwork` and `Libp2pNetwork`
+/// Wrapper for the tuple of the primary and secondary networks
 /// We need this so we can impl `TestableNetworkingImplementation`
 /// on the tuple
 #[derive(Debug, Clone)]
-pub struct UnderlyingCombinedNetworks<TYPES: NodeType>(
-    pub WebServerNetwork<TYPES>,
-    pub Libp2pNetwork<Message<TYPES>, TYPES::SignatureKey>,
-);
+pub struct UnderlyingCombinedNetworks<PRIMARY, SECONDARY>(pub PRIMARY, pub SECONDARY);
 
 #[cfg(feature = "hotshot-testing")]
-impl<TYPES: NodeType> TestableNetworkingImplementation<TYPES> for CombinedNetworks<TYPES> {
+impl<TYPES, PRIMARY, SECONDARY> TestableNetworkingImplementation<TYPES>
+    for CombinedNetworks<TYPES, PRIMARY, SECONDARY>
+where
+    TYPES: NodeType,
+    PRIMARY: ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>
+        + TestableNetworkingImplementation<TYPES>,
+    SECONDARY: ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>
+        + TestableNetworkingImplementation<TYPES>,
+{
     fn generator(
         expected_node_count: usize,
         num_bootstrap: usize,
@@ -161,9 +225,7 @@ impl<TYPES: NodeType> TestableNetworkingImplementation<TYPES> for CombinedNetwor
         reliability_config: Option<Box<dyn NetworkReliability>>,
     ) -> Box<dyn Fn(u64) -> (Arc<Self>, Arc<Self>) + 'static> {
         let generators = (
-            <WebServerNetwork<
-                TYPES,
-            > as TestableNetworkingImplementation<_>>::generator(
+            <PRIMARY as TestableNetworkingImplementation<_>>::generator(
                 expected_node_count,
                 num_bootstrap,
                 network_id,
@@ -171,38 +233,28 @@ impl<TYPES: NodeType> TestableNetworkingImplementation<TYPES> for CombinedNetwor
                 is_da,
                 None,
             ),
-            <Libp2pNetwork<Message<TYPES>, TYPES::SignatureKey> as TestableNetworkingImplementation<_>>::generator(
+            <SECONDARY as TestableNetworkingImplementation<_>>::generator(
                 expected_node_count,
                 num_bootstrap,
                 network_id,
                 da_committee_size,
                 is_da,
                 reliability_config,
-            )
+            ),
         );
         Box::new(move |node_id| {
-            let (quorum_web, da_web) = generators.0(node_id);
-            let (quorum_p2p, da_p2p) = generators.1(node_id);
+            let (quorum_primary, da_primary) = generators.0(node_id);
+            let (quorum_secondary, da_secondary) = generators.1(node_id);
             let da_networks = UnderlyingCombinedNetworks(
-                Arc::<WebServerNetwork<TYPES>>::into_inner(da_web).unwrap(),
-                Arc::<Libp2pNetwork<Message<TYPES>, TYPES::SignatureKey>>::unwrap_or_clone(da_p2p),
+                Arc::unwrap_or_clone(da_primary),
+                Arc::unwrap_or_clone(da_secondary),
             );
             let quorum_networks = UnderlyingCombinedNetworks(
-                Arc::<WebServerNetwork<TYPES>>::into_inner(quorum_web).unwrap(),
-                Arc::<Libp2pNetwork<Message<TYPES>, TYPES::SignatureKey>>::unwrap_or_clone(
-                    quorum_p2p,
-                ),
+                Arc::unwrap_or_clone(quorum_primary),
+                Arc::unwrap_or_clone(quorum_secondary),
             );
-            let quorum_net = Self {
-                networks: Arc::new(quorum_networks),
-                message_cache: Arc::new(RwLock::new(Cache::new(COMBINED_NETWORK_CACHE_SIZE))),
-                primary_down: Arc::new(AtomicU64::new(0)),
-            };
-            let da_net = Self {
-                networks: Arc::new(da_networks),
-                message_cache: Arc::new(RwLock::new(Cache::new(COMBINED_NETWORK_CACHE_SIZE))),
-                primary_down: Arc::new(AtomicU64::new(0)),
-            };
+            let quorum_net = Self::new(Arc::new(quorum_networks));
+            let da_net = Self::new(Arc::new(da_networks));
             (quorum_net.into(), da_net.into())
         })
     }
@@ -216,15 +268,19 @@ impl<TYPES: NodeType> TestableNetworkingImplementation<TYPES> for CombinedNetwor
 }
 
 #[async_trait]
-impl<TYPES: NodeType> ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>
-    for CombinedNetworks<TYPES>
+impl<TYPES, PRIMARY, SECONDARY> ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>
+    for CombinedNetworks<TYPES, PRIMARY, SECONDARY>
+where
+    TYPES: NodeType,
+    PRIMARY: ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>,
+    SECONDARY: ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>,
 {
     fn pause(&self) {
-        self.networks.0.pause();
+        self.primary().pause();
     }
 
     fn resume(&self) {
-        self.networks.0.resume();
+        self.primary().resume();
     }
 
     async fn wait_for_ready(&self) {
@@ -255,10 +311,8 @@ impl<TYPES: NodeType> ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>
         recipients: BTreeSet<TYPES::SignatureKey>,
     ) -> Result<(), NetworkError> {
         // broadcast optimistically on both networks, but if the primary network is down, skip it
-        let primary_down = self.primary_down.load(Ordering::Relaxed);
-        if primary_down < COMBINED_NETWORK_MIN_PRIMARY_FAILURES
-            || primary_down % COMBINED_NETWORK_PRIMARY_CHECK_INTERVAL == 0
-        {
+        let route = self.route(&message);
+        if route != CombinedRoute::Secondary && self.failover.should_try_primary() {
             // broadcast on the primary network as it is not down, or we are checking if it is back up
             match self
                 .primary()
@@ -266,11 +320,14 @@ impl<TYPES: NodeType> ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>
                 .await
             {
                 Ok(()) => {
-                    self.primary_down.store(0, Ordering::Relaxed);
+                    self.failover.primary_succeeded();
+                    if route == CombinedRoute::Primary {
+                        return Ok(());
+                    }
                 }
                 Err(e) => {
                     warn!("Error on primary network: {}", e);
-                    self.primary_down.fetch_add(1, Ordering::Relaxed);
+                    self.failover.primary_failed();
                 }
             };
         }
@@ -286,10 +343,8 @@ impl<TYPES: NodeType> ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>
         recipient: TYPES::SignatureKey,
     ) -> Result<(), NetworkError> {
         // DM optimistically on both networks, but if the primary network is down, skip it
-        let primary_down = self.primary_down.load(Ordering::Relaxed);
-        if primary_down < COMBINED_NETWORK_MIN_PRIMARY_FAILURES
-            || primary_down % COMBINED_NETWORK_PRIMARY_CHECK_INTERVAL == 0
-        {
+        let route = self.route(&message);
+        if route != CombinedRoute::Secondary && self.failover.should_try_primary() {
             // message on the primary network as it is not down, or we are checking if it is back up
             match self
                 .primary()
@@ -297,11 +352,14 @@ impl<TYPES: NodeType> ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>
                 .await
             {
                 Ok(()) => {
-                    self.primary_down.store(0, Ordering::Relaxed);
+                    self.failover.primary_succeeded();
+                    if route == CombinedRoute::Primary {
+                        return Ok(());
+                    }
                 }
                 Err(e) => {
                     warn!("Error on primary network: {}", e);
-                    self.primary_down.fetch_add(1, Ordering::Relaxed);
+                    self.failover.primary_failed();
                 }
             };
         }
@@ -318,31 +376,17 @@ impl<TYPES: NodeType> ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>
         Self: 'b,
     {
         // recv on both networks because nodes may be accessible only on either. discard duplicates
-        // TODO: improve this algorithm: https://github.com/EspressoSystems/HotShot/issues/2089
         let closure = async move {
             let mut primary_msgs = self.primary().recv_msgs(transmit_type).await?;
             let mut secondary_msgs = self.secondary().recv_msgs(transmit_type).await?;
 
             primary_msgs.append(secondary_msgs.as_mut());
 
-            let mut filtered_msgs = Vec::with_capacity(primary_msgs.len());
-            for msg in primary_msgs {
-                // see if we've already seen this message
-                if !self
-                    .message_cache
-                    .read()
-                    .await
-                    .contains(calculate_hash_of(&msg))
-                {
-                    filtered_msgs.push(msg.clone());
-                    self.message_cache
-                        .write()
-                        .await
-                        .insert(calculate_hash_of(&msg));
-                }
-            }
-
-            Ok(filtered_msgs)
+            Ok(self
+                .message_dedup
+                .write()
+                .await
+                .filter::<TYPES, _>(primary_msgs))
         };
 
         boxed_sync(closure)
@@ -360,10 +404,13 @@ impl<TYPES: NodeType> ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>
     }
 
     async fn inject_consensus_info(&self, event: ConsensusIntentEvent<TYPES::SignatureKey>) {
-        <WebServerNetwork<_> as ConnectedNetwork<Message<TYPES>,TYPES::SignatureKey>>::
-            inject_consensus_info(self.primary(), event.clone()).await;
+        <PRIMARY as ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>>::inject_consensus_info(
+            self.primary(),
+            event.clone(),
+        )
+        .await;
 
-        <Libp2pNetwork<_, _> as ConnectedNetwork<Message<TYPES>,TYPES::SignatureKey>>::
+        <SECONDARY as ConnectedNetwork<Message<TYPES>,TYPES::SignatureKey>>::
             inject_consensus_info(self.secondary(), event).await;
     }
 }
@@ -371,30 +418,32 @@ impl<TYPES: NodeType> ConnectedNetwork<Message<TYPES>, TYPES::SignatureKey>
 #[cfg(test)]
 mod test {
     use super::*;
-    use tracing::instrument;
-
-    /// cache eviction test
-    #[cfg_attr(
-        async_executor_impl = "tokio",
-        tokio::test(flavor = "multi_thread", worker_threads = 2)
-    )]
-    #[cfg_attr(async_executor_impl = "async-std", async_std::test)]
-    #[instrument]
-    async fn test_cache_eviction() {
-        let mut cache = Cache::new(3);
-        cache.insert(1);
-        cache.insert(2);
-        cache.insert(3);
-        cache.insert(4);
-        assert_eq!(cache.inner.len(), 3);
-        assert_eq!(cache.hashes.len(), 3);
-        assert!(!cache.inner.contains(&1));
-        assert!(cache.inner.contains(&2));
-        assert!(cache.inner.contains(&3));
-        assert!(cache.inner.contains(&4));
-        assert!(!cache.hashes.contains(&1));
-        assert!(cache.hashes.contains(&2));
-        assert!(cache.hashes.contains(&3));
-        assert!(cache.hashes.contains(&4));
+
+    /// failover strategy test
+    #[test]
+    fn test_consecutive_failures() {
+        let strategy = ConsecutiveFailures::new(2, 3);
+        // the primary is tried until it fails twice in a row
+        assert!(strategy.should_try_primary());
+        strategy.primary_failed();
+        assert!(strategy.should_try_primary());
+        strategy.primary_failed();
+        assert_eq!(strategy.consecutive_failures(), 2);
+
+        // then only every third message
+        for _ in 0..2 {
+            assert!(!strategy.should_try_primary());
+            assert!(!strategy.should_try_primary());
+            assert!(strategy.should_try_primary());
+            strategy.primary_failed();
+        }
+
+        // until it delivers a message again
+        assert!(!strategy.should_try_primary());
+        assert!(!strategy.should_try_primary());
+        assert!(strategy.should_try_primary());
+        strategy.primary_succeeded();
+        assert_eq!(strategy.consecutive_failures(), 0);
+        assert!(strategy.should_try_primary());
     }
 }
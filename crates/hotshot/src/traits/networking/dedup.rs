@@ -0,0 +1,153 @@
+//! Deduplication of received messages, for networks which may receive a message more than once,
+//! such as a combined network receiving it over both of its networks
+//!
+//! Messages are keyed by a cryptographic digest of their wire encoding, so distinct messages
+//! cannot collide, and forgotten by view number as consensus advances, so messages still in
+//! flight for a recent view are never evicted by a burst of newer ones. Transactions carry no
+//! meaningful view, so they are forgotten oldest first instead.
+
+use hotshot_constants::{NETWORK_DEDUP_MAX_DIGESTS, NETWORK_DEDUP_VIEW_WINDOW};
+use hotshot_types::{
+    message::MessagePurpose,
+    traits::{
+        network::{NetworkMsg, ViewMessage},
+        node_implementation::NodeType,
+    },
+};
+use lru::LruCache;
+use std::{
+    collections::{BTreeMap, HashSet},
+    num::NonZeroUsize,
+};
+
+/// The digest a message is deduplicated by
+pub type MessageDigest = [u8; 32];
+
+/// Compute the digest of the wire encoding of a message
+#[must_use]
+pub fn digest_of(bytes: &[u8]) -> MessageDigest {
+    *blake3::hash(bytes).as_bytes()
+}
+
+/// Remembers the digests of the messages received in the latest views, to drop the ones received
+/// again
+#[derive(Clone, Debug)]
+pub struct MessageDedup {
+    /// The number of views before the latest one to remember messages of
+    view_window: u64,
+    /// The maximum number of digests to remember, the oldest views are forgotten first
+    max_digests: usize,
+    /// The digests of the messages received in each view
+    views: BTreeMap<u64, HashSet<MessageDigest>>,
+    /// The number of digests in `views`
+    len: usize,
+    /// The view the window ends at, which follows the views messages are received for
+    latest_view: u64,
+    /// The digests of the messages received without a view, at most `max_digests` of them
+    viewless: LruCache<MessageDigest, ()>,
+}
+
+impl Default for MessageDedup {
+    fn default() -> Self {
+        Self::new(NETWORK_DEDUP_VIEW_WINDOW, NETWORK_DEDUP_MAX_DIGESTS)
+    }
+}
+
+impl MessageDedup {
+    /// Create a dedup layer remembering the messages of the `view_window` views before the latest
+    /// one, and at most `max_digests` messages
+    #[must_use]
+    pub fn new(view_window: u64, max_digests: usize) -> Self {
+        Self {
+            view_window,
+            max_digests,
+            views: BTreeMap::new(),
+            len: 0,
+            latest_view: 0,
+            viewless: LruCache::new(NonZeroUsize::new(max_digests).unwrap_or(NonZeroUsize::MIN)),
+        }
+    }
+
+    /// Record the digest of a message received for a view, returning whether the message is new.
+    ///
+    /// Messages for views older than the window are always considered new, as they may have been
+    /// forgotten; consensus discards stale messages by itself. Each message moves the window at
+    /// most one view ahead, so a message claiming a view far ahead cannot make us forget the
+    /// current views.
+    pub fn insert(&mut self, view: u64, digest: MessageDigest) -> bool {
+        if view.saturating_add(self.view_window) < self.latest_view {
+            return true;
+        }
+        if !self.views.entry(view).or_default().insert(digest) {
+            return false;
+        }
+        self.len += 1;
+
+        if view > self.latest_view {
+            self.latest_view += 1;
+            let retained = self
+                .views
+                .split_off(&self.latest_view.saturating_sub(self.view_window));
+            self.len -= self.views.values().map(HashSet::len).sum::<usize>();
+            self.views = retained;
+        }
+        while self.len > self.max_digests {
+            let Some((_, digests)) = self.views.pop_first() else {
+                break;
+            };
+            self.len -= digests.len();
+        }
+        true
+    }
+
+    /// Record the digest of a message received without a view, returning whether the message is
+    /// new.
+    pub fn insert_viewless(&mut self, digest: MessageDigest) -> bool {
+        self.viewless.put(digest, ()).is_none()
+    }
+
+    /// Whether a message was received for a view
+    #[must_use]
+    pub fn contains(&self, view: u64, digest: &MessageDigest) -> bool {
+        self.views
+            .get(&view)
+            .is_some_and(|digests| digests.contains(digest))
+    }
+
+    /// Drop the messages which were already received, and record the others
+    pub fn filter<TYPES: NodeType, M: NetworkMsg + ViewMessage<TYPES>>(
+        &mut self,
+        messages: Vec<M>,
+    ) -> Vec<M> {
+        messages
+            .into_iter()
+            .filter(|message| match message.encode() {
+                // transactions are submitted without a view
+                Ok(bytes) if message.purpose() == MessagePurpose::Data => {
+                    self.insert_viewless(digest_of(&bytes))
+                }
+                Ok(bytes) => self.insert(*message.get_view_number(), digest_of(&bytes)),
+                // a message we cannot encode was never recorded either
+                Err(_) => true,
+            })
+            .collect()
+    }
+
+    /// The view the window of remembered messages ends at
+    #[must_use]
+    pub fn latest_view(&self) -> u64 {
+        self.latest_view
+    }
+
+    /// Get the number of digests remembered
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len + self.viewless.len()
+    }
+
+    /// True if no digest is remembered false otherwise
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
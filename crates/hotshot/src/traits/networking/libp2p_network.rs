@@ -1,6 +1,11 @@
 //! Libp2p based/production networking implementation
 //! This module provides a libp2p based networking implementation where each node in the
 //! network forms a tcp or udp connection to a subset of other nodes in the network
+
+/// Signed records of the peer id of each node, published to the DHT
+pub mod peer_record;
+
+use self::peer_record::{PeerRecordValidator, RecordVersionCounter, SignedPeerRecord};
 use super::NetworkingMetricsValue;
 #[cfg(feature = "hotshot-testing")]
 use async_compatibility_layer::art::async_block_on;
@@ -11,7 +16,7 @@ use async_compatibility_layer::{
 use async_lock::RwLock;
 use async_trait::async_trait;
 use bimap::BiHashMap;
-use bincode::Options;
+use futures::future::join_all;
 use hotshot_constants::{Version, LOOK_AHEAD, VERSION_0_1};
 #[cfg(feature = "hotshot-testing")]
 use hotshot_types::traits::network::{NetworkReliability, TestableNetworkingImplementation};
@@ -21,42 +26,41 @@ use hotshot_types::{
     message::{Message, MessageKind},
     traits::{
         network::{
-            ConnectedNetwork, ConsensusIntentEvent, FailedToSerializeSnafu, NetworkError,
-            NetworkMsg, TransmitType, ViewMessage,
+            ConnectedNetwork, ConsensusIntentEvent, NetworkError, NetworkMsg, TransmitType,
+            ViewMessage,
         },
         node_implementation::{ConsensusTime, NodeType},
         signature_key::SignatureKey,
     },
     BoxSyncFuture,
 };
-use hotshot_utils::{bincode::bincode_opts, version::read_version};
-use libp2p_identity::PeerId;
+use libp2p_identity::{Keypair, PeerId};
 #[cfg(feature = "hotshot-testing")]
 use libp2p_networking::network::{MeshParams, NetworkNodeConfigBuilder};
 
 use libp2p_networking::{
     network::{
         NetworkEvent::{self, DirectRequest, DirectResponse, GossipMsg},
-        NetworkNodeConfig, NetworkNodeHandle, NetworkNodeHandleError, NetworkNodeType,
+        NetworkNodeConfig, NetworkNodeHandle, NetworkNodeType,
     },
     reexport::Multiaddr,
 };
 
 use serde::Serialize;
-use snafu::ResultExt;
 #[cfg(feature = "hotshot-testing")]
 use std::{collections::HashSet, num::NonZeroUsize, str::FromStr};
 
 use std::{
     collections::BTreeSet,
     fmt::Debug,
+    path::PathBuf,
     sync::{
         atomic::{AtomicBool, AtomicU64, Ordering},
         Arc,
     },
     time::Duration,
 };
-use tracing::{error, info, instrument, warn};
+use tracing::{debug, error, info, instrument, warn};
 
 /// convienence alias for the type for bootstrap addresses
 /// concurrency primitives are needed for having tests
@@ -65,6 +69,9 @@ pub type BootstrapAddrs = Arc<RwLock<Vec<(Option<PeerId>, Multiaddr)>>>;
 /// hardcoded topic of QC used
 pub const QC_TOPIC: &str = "global";
 
+/// The number of direct messages sent at once when broadcasting to recipients without a topic
+pub const DIRECT_BROADCAST_BATCH_SIZE: usize = 16;
+
 /// Stubbed out Ack
 ///
 /// Note: as part of versioning for upgradability,
@@ -131,6 +138,12 @@ struct Libp2pNetworkInner<M: NetworkMsg, K: SignatureKey + 'static> {
     reliability_config: Option<Box<dyn NetworkReliability>>,
     /// if we're a member of the DA committee or not
     is_da: bool,
+    /// the record mapping our key to our peer id, which we publish to the DHT
+    peer_record: SignedPeerRecord<K>,
+    /// our libp2p identity, which signs our peer records along with our key
+    identity: Keypair,
+    /// the versions of the peer records we publish
+    record_versions: RecordVersionCounter,
 }
 
 /// Networking implementation that uses libp2p
@@ -255,6 +268,7 @@ where
                         NetworkingMetricsValue::default(),
                         config,
                         pubkey.clone(),
+                        &privkey,
                         bootstrap_addrs_ref,
                         num_bootstrap,
                         usize::try_from(node_id).unwrap(),
@@ -298,6 +312,7 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
     /// One must call `connect` in order to connect.
     /// * `config`: the configuration of the node
     /// * `pk`: public key associated with the node
+    /// * `private_key`: private key of the node, signing the records of its peer id in the DHT
     /// * `bootstrap_addrs`: rwlock containing the bootstrap addrs
     /// # Errors
     /// Returns error in the event that the underlying libp2p network
@@ -309,8 +324,9 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
     #[allow(clippy::too_many_arguments)]
     pub async fn new(
         metrics: NetworkingMetricsValue,
-        config: NetworkNodeConfig,
+        mut config: NetworkNodeConfig,
         pk: K,
+        private_key: &K::PrivateKey,
         bootstrap_addrs: BootstrapAddrs,
         bootstrap_addrs_len: usize,
         id: usize,
@@ -321,6 +337,14 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
         is_da: bool,
     ) -> Result<Libp2pNetwork<M, K>, NetworkError> {
         assert!(bootstrap_addrs_len > 4, "Need at least 5 bootstrap nodes");
+        // only accept the records of peers signed by the key and the peer they map
+        config.record_validator = Some(Arc::new(PeerRecordValidator::<K>::default()));
+        let identity = config
+            .identity
+            .get_or_insert_with(Keypair::generate_ed25519)
+            .clone();
+        let record_versions =
+            RecordVersionCounter::new(config.record_version_location.clone().map(PathBuf::from))?;
         let network_handle = Arc::new(
             Box::pin(NetworkNodeHandle::<()>::new(config, id))
                 .await
@@ -339,6 +363,9 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
             drop(bs_cp);
         }
 
+        let peer_record =
+            SignedPeerRecord::new(private_key, &identity, record_versions.next_version()?)?;
+
         let mut pubkey_pid_map = BiHashMap::new();
         pubkey_pid_map.insert(pk.clone(), network_handle.peer_id());
 
@@ -379,6 +406,9 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
                 #[cfg(feature = "hotshot-testing")]
                 reliability_config,
                 is_da,
+                peer_record,
+                identity,
+                record_versions,
             }),
         };
 
@@ -409,7 +439,7 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
                 // only run if we are not too close to the next view number
                 if latest_seen_view.load(Ordering::Relaxed) + THRESHOLD <= *view_number {
                     // look up
-                    if let Err(err) = handle.lookup_node::<K>(pk.clone(), dht_timeout).await {
+                    if let Err(err) = Self::lookup_peer_id(&handle, &pk, dht_timeout).await {
                         error!("Failed to perform lookup for key {:?}: {}", pk, err);
                     };
                 }
@@ -419,7 +449,7 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
 
     /// Initiates connection to the outside world
     fn spawn_connect(&mut self, id: usize) {
-        let pk = self.inner.pk.clone();
+        let peer_record = self.inner.peer_record.clone();
         let bootstrap_ref = self.inner.bootstrap_addrs.clone();
         let num_bootstrap = self.inner.bootstrap_addrs_len;
         let handle = self.inner.handle.clone();
@@ -484,7 +514,11 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
 
                 // we want our records published before
                 // we begin participating in consensus
-                while handle.put_record(&pk, &handle.peer_id()).await.is_err() {
+                while handle
+                    .put_record(&peer_record.record.public_key, &peer_record)
+                    .await
+                    .is_err()
+                {
                     async_sleep(Duration::from_secs(1)).await;
                 }
 
@@ -494,7 +528,11 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
                     node_type
                 );
 
-                while handle.put_record(&handle.peer_id(), &pk).await.is_err() {
+                while handle
+                    .put_record(&handle.peer_id(), &peer_record)
+                    .await
+                    .is_err()
+                {
                     async_sleep(Duration::from_secs(1)).await;
                 }
 
@@ -511,6 +549,163 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
         });
     }
 
+    /// Look up the peer id of a node in the DHT, only trusting a record signed by the node
+    ///
+    /// # Errors
+    /// Returns an error if no record is found, or the record is not signed by the node
+    async fn lookup_peer_id(
+        handle: &NetworkNodeHandle<()>,
+        pk: &K,
+        dht_timeout: Duration,
+    ) -> Result<PeerId, NetworkError> {
+        let peer_record = handle
+            .get_record_timeout::<SignedPeerRecord<K>>(pk, dht_timeout)
+            .await
+            .map_err(|source| NetworkError::Libp2p { source })?;
+        if peer_record.record.public_key != *pk || !peer_record.is_valid() {
+            return Err(NetworkError::InvalidPeerRecord);
+        }
+        Ok(peer_record.record.peer_id)
+    }
+
+    /// Publish the records of a node restarted with a new consensus key, signed by the key it
+    /// rotated from, so that its peer id maps to the new key. Nodes keep trusting the records of
+    /// the old key until this is published.
+    ///
+    /// # Errors
+    /// Returns an error if `private_key` is not the key of this node, or the records cannot be
+    /// signed or published
+    pub async fn publish_key_rotation(
+        &self,
+        private_key: &K::PrivateKey,
+        old_private_key: &K::PrivateKey,
+    ) -> Result<(), NetworkError> {
+        if K::from_private(private_key) != self.inner.pk {
+            return Err(NetworkError::InvalidPeerRecord);
+        }
+        let handle = &self.inner.handle;
+        let peer_record = SignedPeerRecord::<K>::rotated(
+            private_key,
+            old_private_key,
+            &self.inner.identity,
+            self.inner.record_versions.next_version()?,
+        )?;
+        handle
+            .put_record(&self.inner.pk, &peer_record)
+            .await
+            .map_err(|source| NetworkError::Libp2p { source })?;
+        handle
+            .put_record(&handle.peer_id(), &peer_record)
+            .await
+            .map_err(|source| NetworkError::Libp2p { source })
+    }
+
+    /// Derive the gossip topic of a committee from its members, so every node agrees on it. The
+    /// topic changes along with the membership, e.g. when an epoch starts with a new committee.
+    #[must_use]
+    pub fn committee_topic(members: &BTreeSet<K>) -> String {
+        let mut hasher = blake3::Hasher::new();
+        for member in members {
+            let bytes = member.to_bytes();
+            hasher.update(&(bytes.len() as u64).to_le_bytes());
+            hasher.update(&bytes);
+        }
+        format!("committee-{}", hasher.finalize().to_hex())
+    }
+
+    /// Register the topic of a committee, so broadcasts to exactly its members are gossiped, and
+    /// subscribe to it if we are a member. Returns the topic of the committee.
+    ///
+    /// # Errors
+    /// Returns an error if we fail to subscribe to the topic
+    pub async fn add_committee(&self, members: BTreeSet<K>) -> Result<String, NetworkError> {
+        let is_member = members.contains(&self.inner.pk);
+        let topic = {
+            let mut topic_map = self.inner.topic_map.write().await;
+            if let Some(topic) = topic_map.get_by_left(&members) {
+                return Ok(topic.clone());
+            }
+            let topic = Self::committee_topic(&members);
+            topic_map.insert(members, topic.clone());
+            topic
+        };
+        if is_member {
+            info!("subscribing to committee topic: {}", topic);
+            self.inner
+                .handle
+                .subscribe(topic.clone())
+                .await
+                .map_err(Into::<NetworkError>::into)?;
+        }
+        Ok(topic)
+    }
+
+    /// Forget the topic of a committee which no longer takes part in consensus, unsubscribing
+    /// from it if we were a member. Broadcasts to its members fall back to direct messages.
+    ///
+    /// The topics registered at startup, [`QC_TOPIC`] and the DA topic, are kept.
+    ///
+    /// # Errors
+    /// Returns an error if we fail to unsubscribe from the topic
+    pub async fn remove_committee(&self, members: &BTreeSet<K>) -> Result<(), NetworkError> {
+        let topic = {
+            let mut topic_map = self.inner.topic_map.write().await;
+            match topic_map.get_by_left(members) {
+                Some(topic) if topic != QC_TOPIC && topic != "DA" => {}
+                _ => return Ok(()),
+            }
+            let Some((_, topic)) = topic_map.remove_by_left(members) else {
+                return Ok(());
+            };
+            topic
+        };
+        if members.contains(&self.inner.pk) {
+            info!("unsubscribing from committee topic: {}", topic);
+            self.inner
+                .handle
+                .unsubscribe(topic)
+                .await
+                .map_err(Into::<NetworkError>::into)?;
+        }
+        Ok(())
+    }
+
+    /// Broadcast to recipients without a topic, such as small or one-off sets, by messaging each
+    /// of them directly, a batch at a time. Recipients receive the message as a direct message.
+    ///
+    /// # Errors
+    /// Returns the last error if any recipient could not be messaged
+    async fn broadcast_directly(
+        &self,
+        message: M,
+        recipients: &BTreeSet<K>,
+    ) -> Result<(), NetworkError> {
+        let recipients: Vec<_> = recipients
+            .iter()
+            .filter(|recipient| **recipient != self.inner.pk)
+            .collect();
+        info!("broadcasting directly to {} recipients", recipients.len());
+
+        let mut result = Ok(());
+        for batch in recipients.chunks(DIRECT_BROADCAST_BATCH_SIZE) {
+            let results = join_all(batch.iter().map(|recipient| {
+                <Self as ConnectedNetwork<M, K>>::direct_message(
+                    self,
+                    message.clone(),
+                    (*recipient).clone(),
+                )
+            }))
+            .await;
+            for res in results {
+                if let Err(e) = res {
+                    warn!("Failed to broadcast directly: {}", e);
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
     /// make network aware of known peers
     async fn _add_known_peers(
         &self,
@@ -523,8 +718,23 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
             .map_err(Into::<NetworkError>::into)
     }
 
-    /// Handle events for Version 0.1 of the protocol.
-    async fn handle_recvd_events_0_1(
+    /// Decode a message received from the network, counting messages of versions we do not
+    /// support.
+    fn decode_message(&self, raw: &[u8]) -> Option<M> {
+        match M::decode(raw) {
+            Ok(message) => Some(message),
+            Err(e) => {
+                if matches!(e, NetworkError::UnsupportedVersion { .. }) {
+                    self.inner.metrics.unsupported_version_message_count.add(1);
+                }
+                warn!("Failed to decode incoming message: {:?}", e);
+                None
+            }
+        }
+    }
+
+    /// Handle events received from the network.
+    async fn handle_recvd_events(
         &self,
         msg: NetworkEvent,
         direct_send: &UnboundedSender<M>,
@@ -532,8 +742,7 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
     ) -> Result<(), NetworkError> {
         match msg {
             GossipMsg(msg, _topic) => {
-                let result: Result<M, _> = bincode_opts().deserialize(&msg);
-                if let Ok(result) = result {
+                if let Some(result) = self.decode_message(&msg) {
                     broadcast_send
                         .send(result)
                         .await
@@ -541,10 +750,7 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
                 }
             }
             DirectRequest(msg, _pid, chan) => {
-                let result: Result<M, _> = bincode_opts()
-                    .deserialize(&msg)
-                    .context(FailedToSerializeSnafu);
-                if let Ok(result) = result {
+                if let Some(result) = self.decode_message(&msg) {
                     direct_send
                         .send(result)
                         .await
@@ -565,13 +771,9 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
                     error!("failed to ack!");
                 };
             }
-            DirectResponse(msg, _) => {
-                let _result: Result<M, _> = bincode_opts()
-                    .deserialize(&msg)
-                    .context(FailedToSerializeSnafu);
-            }
+            DirectResponse(_msg, _) => {}
             NetworkEvent::IsBootstrapped => {
-                error!("handle_recvd_events_0_1 received `NetworkEvent::IsBootstrapped`, which should be impossible.");
+                error!("handle_recvd_events received `NetworkEvent::IsBootstrapped`, which should be impossible.");
             }
         }
         Ok::<(), NetworkError>(())
@@ -592,27 +794,10 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> Libp2pNetwork<M, K> {
                     NetworkEvent::IsBootstrapped => {
                         is_bootstrapped.store(true, Ordering::Relaxed);
                     }
-                    GossipMsg(raw, _) | DirectRequest(raw, _, _) | DirectResponse(raw, _) => {
-                        let message_version = read_version(raw);
-                        match message_version {
-                            Some(VERSION_0_1) => {
-                                let _ = handle
-                                    .handle_recvd_events_0_1(message, &direct_send, &broadcast_send)
-                                    .await;
-                            }
-                            Some(version) => {
-                                warn!(
-                            "Received message with unsupported version: {:?}.\n\nPayload:\n\n{:?}",
-                            version, message
-                        );
-                            }
-                            _ => {
-                                warn!(
-                            "Received message with unreadable version number.\n\nPayload:\n\n{:?}",
-                            message
-                        );
-                            }
-                        }
+                    GossipMsg(..) | DirectRequest(..) | DirectResponse(..) => {
+                        let _ = handle
+                            .handle_recvd_events(message, &direct_send, &broadcast_send)
+                            .await;
                     }
                 }
             }
@@ -675,14 +860,13 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Libp2p
             self.inner.handle.connected_pids().await
         );
 
-        let topic_map = self.inner.topic_map.read().await;
-        let topic = topic_map
+        let topic = self
+            .inner
+            .topic_map
+            .read()
+            .await
             .get_by_left(&recipients)
-            .ok_or(NetworkError::Libp2p {
-                source: NetworkNodeHandleError::NoSuchTopic,
-            })?
-            .clone();
-        info!("broadcasting to topic: {}", topic);
+            .cloned();
 
         // gossip doesn't broadcast from itself, so special case
         if recipients.contains(&self.inner.pk) {
@@ -694,6 +878,13 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Libp2p
                 .map_err(|_| NetworkError::ShutDown)?;
         }
 
+        let Some(topic) = topic else {
+            return self.broadcast_directly(message, &recipients).await;
+        };
+        info!("broadcasting to topic: {}", topic);
+
+        let serialized_msg = message.encode()?;
+
         // NOTE: metrics is threadsafe, so clone is fine (and lightweight)
         #[cfg(feature = "hotshot-testing")]
         {
@@ -701,9 +892,6 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Libp2p
             if let Some(ref config) = &self.inner.reliability_config {
                 let handle = self.inner.handle.clone();
 
-                let serialized_msg = bincode_opts()
-                    .serialize(&message)
-                    .context(FailedToSerializeSnafu)?;
                 let fut = config.clone().chaos_send_msg(
                     serialized_msg,
                     Arc::new(move |msg: Vec<u8>| {
@@ -728,7 +916,12 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Libp2p
             }
         }
 
-        match self.inner.handle.gossip(topic, &message).await {
+        match self
+            .inner
+            .handle
+            .gossip_no_serialize(topic, serialized_msg)
+            .await
+        {
             Ok(()) => {
                 self.inner.metrics.outgoing_broadcast_message_count.add(1);
                 Ok(())
@@ -759,10 +952,7 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Libp2p
 
         self.wait_for_ready().await;
 
-        let pid = match self
-            .inner
-            .handle
-            .lookup_node::<K>(recipient.clone(), self.inner.dht_timeout)
+        let pid = match Self::lookup_peer_id(&self.inner.handle, &recipient, self.inner.dht_timeout)
             .await
         {
             Ok(pid) => pid,
@@ -772,9 +962,10 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Libp2p
                     "Failed to message {:?} because could not find recipient peer id for pk {:?}",
                     message, recipient
                 );
-                return Err(NetworkError::Libp2p { source: err });
+                return Err(err);
             }
         };
+        let serialized_msg = message.encode()?;
 
         #[cfg(feature = "hotshot-testing")]
         {
@@ -782,9 +973,6 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Libp2p
             if let Some(ref config) = &self.inner.reliability_config {
                 let handle = self.inner.handle.clone();
 
-                let serialized_msg = bincode_opts()
-                    .serialize(&message)
-                    .context(FailedToSerializeSnafu)?;
                 let fut = config.clone().chaos_send_msg(
                     serialized_msg,
                     Arc::new(move |msg: Vec<u8>| {
@@ -808,7 +996,12 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Libp2p
             }
         }
 
-        match self.inner.handle.direct_request(pid, &message).await {
+        match self
+            .inner
+            .handle
+            .direct_request_no_serialize(pid, serialized_msg)
+            .await
+        {
             Ok(()) => Ok(()),
             Err(e) => Err(e.into()),
         }
@@ -889,6 +1082,16 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Libp2p
                 }
             }
 
+            ConsensusIntentEvent::CommitteeChanged(view, previous, current) => {
+                debug!("committee changed in view {}", view);
+                if let Err(err) = self.remove_committee(&previous).await {
+                    warn!("failed to remove the previous committee: {}", err);
+                }
+                if let Err(err) = self.add_committee(current).await {
+                    warn!("failed to add the new committee: {}", err);
+                }
+            }
+
             _ => {}
         }
     }
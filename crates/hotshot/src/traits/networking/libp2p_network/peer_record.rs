@@ -0,0 +1,247 @@
+//! Records mapping the consensus key of a node to its libp2p peer id, which nodes publish to the
+//! DHT under both, signed with their consensus key and their libp2p identity so no other peer can
+//! redirect their messages or claim their peer id
+
+use bincode::Options;
+use hotshot_types::traits::{
+    network::{FailedToDeserializeSnafu, FailedToSerializeSnafu, NetworkError},
+    signature_key::SignatureKey,
+};
+use hotshot_utils::bincode::bincode_opts;
+use libp2p_identity::{Keypair, PeerId, PublicKey};
+use libp2p_networking::network::behaviours::dht::RecordValidator;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use std::{
+    cmp::Ordering,
+    fs,
+    io::ErrorKind,
+    marker::PhantomData,
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+};
+
+/// The mapping between the consensus key of a node and its peer id
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(bound(deserialize = ""))]
+pub struct PeerRecord<K: SignatureKey> {
+    /// the consensus key of the node
+    pub public_key: K,
+    /// the peer id of the node
+    pub peer_id: PeerId,
+    /// the version of the record, a record supersedes those of lower versions
+    pub version: u64,
+}
+
+impl<K: SignatureKey> PeerRecord<K> {
+    /// The bytes the signatures of the record are over
+    fn signed_bytes(&self) -> Option<Vec<u8>> {
+        bincode_opts().serialize(self).ok()
+    }
+}
+
+/// A [`PeerRecord`] signed by the consensus key and the peer it maps
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(bound(deserialize = ""))]
+pub struct SignedPeerRecord<K: SignatureKey> {
+    /// the record
+    pub record: PeerRecord<K>,
+    /// signature of the record by its consensus key
+    pub signature: K::PureAssembledSignatureType,
+    /// if the node rotated its consensus key, the key it rotated from and its signature of the
+    /// record, which lets the record take over the peer id of the old key
+    pub rotated_from: Option<(K, K::PureAssembledSignatureType)>,
+    /// the protobuf encoding of the libp2p public key the peer id is derived from
+    pub peer_public_key: Vec<u8>,
+    /// signature of the record by the libp2p identity of the peer
+    pub peer_signature: Vec<u8>,
+}
+
+impl<K: SignatureKey> SignedPeerRecord<K> {
+    /// Sign the record mapping the key of `private_key` to the peer id of `identity`
+    ///
+    /// # Errors
+    /// Returns an error if the record cannot be signed
+    pub fn new(
+        private_key: &K::PrivateKey,
+        identity: &Keypair,
+        version: u64,
+    ) -> Result<Self, NetworkError> {
+        let record = PeerRecord {
+            public_key: K::from_private(private_key),
+            peer_id: identity.public().to_peer_id(),
+            version,
+        };
+        let signature = Self::sign_record(private_key, &record)?;
+        let peer_signature = record
+            .signed_bytes()
+            .and_then(|bytes| identity.sign(&bytes).ok())
+            .ok_or(NetworkError::FailedToSign)?;
+        Ok(Self {
+            record,
+            signature,
+            rotated_from: None,
+            peer_public_key: identity.public().encode_protobuf(),
+            peer_signature,
+        })
+    }
+
+    /// Sign the record of a node which rotated its consensus key from the key of
+    /// `old_private_key` to that of `private_key`
+    ///
+    /// # Errors
+    /// Returns an error if the record cannot be signed
+    pub fn rotated(
+        private_key: &K::PrivateKey,
+        old_private_key: &K::PrivateKey,
+        identity: &Keypair,
+        version: u64,
+    ) -> Result<Self, NetworkError> {
+        let mut signed = Self::new(private_key, identity, version)?;
+        let old_signature = Self::sign_record(old_private_key, &signed.record)?;
+        signed.rotated_from = Some((K::from_private(old_private_key), old_signature));
+        Ok(signed)
+    }
+
+    /// Sign a record
+    fn sign_record(
+        private_key: &K::PrivateKey,
+        record: &PeerRecord<K>,
+    ) -> Result<K::PureAssembledSignatureType, NetworkError> {
+        let bytes = record.signed_bytes().ok_or(NetworkError::FailedToSign)?;
+        K::sign(private_key, &bytes).map_err(|_| NetworkError::FailedToSign)
+    }
+
+    /// Whether the record is signed by the key it maps, by the key it was rotated from if any, and
+    /// by the peer it maps the key to
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        let Some(bytes) = self.record.signed_bytes() else {
+            return false;
+        };
+        self.record.public_key.validate(&self.signature, &bytes)
+            && self
+                .rotated_from
+                .as_ref()
+                .map_or(true, |(old_key, signature)| {
+                    old_key.validate(signature, &bytes)
+                })
+            && PublicKey::try_decode_protobuf(&self.peer_public_key).is_ok_and(|peer_key| {
+                peer_key.to_peer_id() == self.record.peer_id
+                    && peer_key.verify(&bytes, &self.peer_signature)
+            })
+    }
+
+    /// Whether the record may be stored under the DHT key `key`, which is either its consensus
+    /// key or its peer id
+    #[must_use]
+    pub fn is_stored_under(&self, key: &[u8]) -> bool {
+        [
+            bincode_opts().serialize(&self.record.public_key).ok(),
+            bincode_opts().serialize(&self.record.peer_id).ok(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|record_key| record_key == key)
+    }
+
+    /// Whether the record should replace `existing`, the record stored under the same DHT key.
+    ///
+    /// Records of the same consensus key are ordered by version, and conflicting records of the
+    /// same version are rejected. A peer id only maps to another consensus key once the node
+    /// publishes a newer record rotated from the key it currently maps to.
+    #[must_use]
+    pub fn supersedes(&self, existing: &Self) -> bool {
+        if self.record.public_key != existing.record.public_key {
+            return self.record.version > existing.record.version
+                && self
+                    .rotated_from
+                    .as_ref()
+                    .is_some_and(|(old_key, _)| *old_key == existing.record.public_key);
+        }
+        match self.record.version.cmp(&existing.record.version) {
+            Ordering::Greater => true,
+            Ordering::Equal => self == existing,
+            Ordering::Less => false,
+        }
+    }
+}
+
+/// Counts the versions of the peer records a node publishes. The latest version is persisted to a
+/// file if one is given, so the records a node publishes after a restart supersede the earlier
+/// ones.
+#[derive(Debug)]
+pub struct RecordVersionCounter {
+    /// the file the latest version is persisted to
+    location: Option<PathBuf>,
+    /// the latest version
+    latest: Mutex<u64>,
+}
+
+impl RecordVersionCounter {
+    /// Create a counter resuming from the version persisted at `location`, if any
+    ///
+    /// # Errors
+    /// Returns an error if the persisted version cannot be read
+    pub fn new(location: Option<PathBuf>) -> Result<Self, NetworkError> {
+        let latest = match location.as_ref().map(fs::read) {
+            Some(Ok(bytes)) => bincode_opts()
+                .deserialize(&bytes)
+                .context(FailedToDeserializeSnafu)?,
+            Some(Err(source)) if source.kind() != ErrorKind::NotFound => {
+                return Err(NetworkError::PeerRecordVersion { source });
+            }
+            _ => 0,
+        };
+        Ok(Self {
+            location,
+            latest: Mutex::new(latest),
+        })
+    }
+
+    /// Take the next version, persisting it before it is used
+    ///
+    /// # Errors
+    /// Returns an error if the version cannot be persisted
+    pub fn next_version(&self) -> Result<u64, NetworkError> {
+        let mut latest = self.latest.lock().unwrap_or_else(PoisonError::into_inner);
+        let next = *latest + 1;
+        if let Some(location) = &self.location {
+            let bytes = bincode_opts()
+                .serialize(&next)
+                .context(FailedToSerializeSnafu)?;
+            fs::write(location, bytes)
+                .map_err(|source| NetworkError::PeerRecordVersion { source })?;
+        }
+        *latest = next;
+        Ok(next)
+    }
+}
+
+/// Only lets the DHT store the [`SignedPeerRecord`]s signed by the node and peer they map
+#[derive(Debug)]
+pub struct PeerRecordValidator<K: SignatureKey>(PhantomData<K>);
+
+impl<K: SignatureKey> Default for PeerRecordValidator<K> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<K: SignatureKey + 'static> RecordValidator for PeerRecordValidator<K> {
+    fn validate(&self, key: &[u8], value: &[u8]) -> bool {
+        bincode_opts()
+            .deserialize::<SignedPeerRecord<K>>(value)
+            .is_ok_and(|record| record.is_valid() && record.is_stored_under(key))
+    }
+
+    fn supersedes(&self, _key: &[u8], new: &[u8], existing: &[u8]) -> bool {
+        let Ok(new) = bincode_opts().deserialize::<SignedPeerRecord<K>>(new) else {
+            return false;
+        };
+        // a valid record replaces anything that is not one
+        bincode_opts()
+            .deserialize::<SignedPeerRecord<K>>(existing)
+            .map_or(true, |existing| new.supersedes(&existing))
+    }
+}
@@ -3,14 +3,13 @@
 //! This module provides an in-memory only simulation of an actual network, useful for unit and
 //! integration tests.
 
-use super::{FailedToSerializeSnafu, NetworkError, NetworkReliability, NetworkingMetricsValue};
+use super::{NetworkError, NetworkReliability, NetworkingMetricsValue};
 use async_compatibility_layer::{
     art::async_spawn,
     channel::{bounded, Receiver, SendError, Sender},
 };
 use async_lock::{Mutex, RwLock};
 use async_trait::async_trait;
-use bincode::Options;
 use dashmap::DashMap;
 use futures::StreamExt;
 use hotshot_types::{
@@ -23,9 +22,7 @@ use hotshot_types::{
     },
     BoxSyncFuture,
 };
-use hotshot_utils::bincode::bincode_opts;
 use rand::Rng;
-use snafu::ResultExt;
 use std::{
     collections::BTreeSet,
     fmt::Debug,
@@ -128,6 +125,7 @@ impl<M: NetworkMsg, K: SignatureKey> MemoryNetwork<M, K> {
         let (broadcast_task_send, broadcast_output) = bounded(128);
         let (direct_task_send, direct_output) = bounded(128);
         let in_flight_message_count = AtomicUsize::new(0);
+        let unsupported_version_message_count = metrics.unsupported_version_message_count.clone();
         trace!("Channels open, spawning background task");
 
         async_spawn(
@@ -147,7 +145,7 @@ impl<M: NetworkMsg, K: SignatureKey> MemoryNetwork<M, K> {
                         Combo::Direct(vec) => {
                             trace!(?vec, "Incoming direct message");
                             // Attempt to decode message
-                            let x = bincode_opts().deserialize(&vec);
+                            let x = M::decode(&vec);
                             match x {
                                 Ok(x) => {
                                     let dts = direct_task_send.clone();
@@ -159,6 +157,9 @@ impl<M: NetworkMsg, K: SignatureKey> MemoryNetwork<M, K> {
                                     }
                                 }
                                 Err(e) => {
+                                    if matches!(e, NetworkError::UnsupportedVersion { .. }) {
+                                        unsupported_version_message_count.add(1);
+                                    }
                                     warn!(?e, "Failed to decode incoming message, skipping");
                                 }
                             }
@@ -166,7 +167,7 @@ impl<M: NetworkMsg, K: SignatureKey> MemoryNetwork<M, K> {
                         Combo::Broadcast(vec) => {
                             trace!(?vec, "Incoming broadcast message");
                             // Attempt to decode message
-                            let x = bincode_opts().deserialize(&vec);
+                            let x = M::decode(&vec);
                             match x {
                                 Ok(x) => {
                                     let bts = broadcast_task_send.clone();
@@ -178,6 +179,9 @@ impl<M: NetworkMsg, K: SignatureKey> MemoryNetwork<M, K> {
                                     }
                                 }
                                 Err(e) => {
+                                    if matches!(e, NetworkError::UnsupportedVersion { .. }) {
+                                        unsupported_version_message_count.add(1);
+                                    }
                                     warn!(?e, "Failed to decode incoming message, skipping");
                                 }
                             }
@@ -307,11 +311,9 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Memory
         recipients: BTreeSet<K>,
     ) -> Result<(), NetworkError> {
         trace!(?message, "Broadcasting message");
-        // Bincode the message
-        let vec = bincode_opts()
-            .serialize(&message)
-            .context(FailedToSerializeSnafu)?;
-        trace!("Message bincoded, sending");
+        // Encode the message
+        let vec = message.encode()?;
+        trace!("Message encoded, sending");
         for node in &self.inner.master_map.map {
             // TODO delay/drop etc here
             let (key, node) = node.pair();
@@ -355,11 +357,9 @@ impl<M: NetworkMsg, K: SignatureKey + 'static> ConnectedNetwork<M, K> for Memory
     #[instrument(name = "MemoryNetwork::direct_message")]
     async fn direct_message(&self, message: M, recipient: K) -> Result<(), NetworkError> {
         // debug!(?message, ?recipient, "Sending direct message");
-        // Bincode the message
-        let vec = bincode_opts()
-            .serialize(&message)
-            .context(FailedToSerializeSnafu)?;
-        trace!("Message bincoded, finding recipient");
+        // Encode the message
+        let vec = message.encode()?;
+        trace!("Message encoded, finding recipient");
         if let Some(node) = self.inner.master_map.map.get(&recipient) {
             let node = node.value().clone();
             if let Some(ref config) = &self.inner.reliability_config {
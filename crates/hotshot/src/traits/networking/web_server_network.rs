@@ -5,14 +5,16 @@
 
 use async_compatibility_layer::channel::{unbounded, UnboundedReceiver, UnboundedSender};
 
+use super::NetworkingMetricsValue;
 use async_compatibility_layer::{
     art::{async_sleep, async_spawn},
     channel::{oneshot, OneShotSender},
 };
 use async_lock::RwLock;
 use async_trait::async_trait;
+use bincode::Options;
 use derive_more::{Deref, DerefMut};
-use hotshot_constants::VERSION_0_1;
+use hotshot_constants::{VERSION_0_1, VERSION_0_2};
 use hotshot_types::{
     boxed_sync,
     message::{Message, MessagePurpose},
@@ -26,7 +28,7 @@ use hotshot_types::{
     },
     BoxSyncFuture,
 };
-use hotshot_utils::version::read_version;
+use hotshot_utils::{bincode::bincode_opts, version::read_version};
 use hotshot_web_server::{self, config};
 use lru::LruCache;
 use serde::{Deserialize, Serialize};
@@ -61,6 +63,10 @@ fn hash<T: Hash>(t: &T) -> u64 {
 }
 
 /// The web server network state
+///
+/// The web server has no routes for request/response messages, so nodes on this network alone
+/// cannot fetch missing leaves from their peers; sending such a message fails with
+/// [`NetworkError::UnimplementedFeature`].
 #[derive(Clone, Debug)]
 pub struct WebServerNetwork<TYPES: NodeType> {
     /// The inner, core state of the web server network
@@ -75,11 +81,14 @@ impl<TYPES: NodeType> WebServerNetwork<TYPES> {
         &self,
         message: SendMsg<Message<TYPES>>,
     ) -> Result<(), NetworkError> {
+        // Messages are posted in their versioned encoding, so the version can be read before the
+        // rest of the message is decoded.
+        let encoded = message.get_message().map(|m| m.encode()).transpose()?;
         let result: Result<(), ClientError> = self
             .inner
             .client
             .post(&message.get_endpoint())
-            .body_binary(&message.get_message())
+            .body_binary(&encoded)
             .unwrap()
             .send()
             .await;
@@ -168,7 +177,7 @@ impl<K: SignatureKey> TaskMap<K> {
 #[derive(Debug)]
 struct Inner<TYPES: NodeType> {
     /// Our own key
-    _own_key: TYPES::SignatureKey,
+    own_key: TYPES::SignatureKey,
     /// Queue for broadcasted messages
     broadcast_poll_queue_0_1: Arc<RwLock<Vec<RecvMsg<Message<TYPES>>>>>,
     /// Queue for direct messages
@@ -183,6 +192,8 @@ struct Inner<TYPES: NodeType> {
     wait_between_polls: Duration,
     /// Whether we are connecting to a DA server
     is_da: bool,
+    /// The networking metrics we're keeping track of
+    metrics: NetworkingMetricsValue,
     /// The last tx_index we saw from the web server
     tx_index: Arc<RwLock<u64>>,
     /// Task map for quorum proposals.
@@ -227,7 +238,7 @@ impl<TYPES: NodeType> Inner<TYPES> {
 
         *tx_index += 1;
 
-        if let Ok(deserialized_message_inner) = bincode::deserialize::<Message<TYPES>>(&tx) {
+        if let Ok(deserialized_message_inner) = Message::<TYPES>::decode(&tx) {
             let deserialized_message = RecvMsg {
                 message: Some(deserialized_message_inner),
             };
@@ -259,7 +270,7 @@ impl<TYPES: NodeType> Inner<TYPES> {
     ) -> bool {
         let broadcast_poll_queue = &self.broadcast_poll_queue_0_1;
         let direct_poll_queue = &self.direct_poll_queue_0_1;
-        if let Ok(deserialized_message_inner) = bincode::deserialize::<Message<TYPES>>(&message) {
+        if let Ok(deserialized_message_inner) = Message::<TYPES>::decode(&message) {
             let deserialized_message = RecvMsg {
                 message: Some(deserialized_message_inner),
             };
@@ -316,14 +327,12 @@ impl<TYPES: NodeType> Inner<TYPES> {
                     return true;
                 }
                 MessagePurpose::VidDisperse => {
-                    // TODO copy-pasted from `MessagePurpose::Proposal` https://github.com/EspressoSystems/HotShot/issues/1690
-
-                    self.broadcast_poll_queue_0_1
+                    // The leader posts only one share per node and view.
+                    direct_poll_queue
                         .write()
                         .await
                         .push(deserialized_message.clone());
 
-                    // Only pushing the first proposal since we will soon only be allowing 1 proposal per view
                     return true;
                 }
                 MessagePurpose::ViewSyncCertificate => {
@@ -342,6 +351,12 @@ impl<TYPES: NodeType> Inner<TYPES> {
                     return false;
                 }
 
+                MessagePurpose::RequestResponse => {
+                    error!("Received request/response message in web server network");
+
+                    return false;
+                }
+
                 MessagePurpose::Upgrade => {
                     broadcast_poll_queue
                         .write()
@@ -385,7 +400,13 @@ impl<TYPES: NodeType> Inner<TYPES> {
                 }
                 MessagePurpose::Vote => config::get_vote_route(view_number, vote_index),
                 MessagePurpose::Data => config::get_transactions_route(tx_index),
-                MessagePurpose::Internal => unimplemented!(),
+                MessagePurpose::Internal | MessagePurpose::RequestResponse => {
+                    error!(
+                        "Cannot poll the web server for {:?} messages",
+                        message_purpose
+                    );
+                    return Err(NetworkError::UnimplementedFeature);
+                }
                 MessagePurpose::ViewSyncCertificate => {
                     config::get_view_sync_certificate_route(view_number, vote_index)
                 }
@@ -393,7 +414,9 @@ impl<TYPES: NodeType> Inner<TYPES> {
                     config::get_view_sync_vote_route(view_number, vote_index)
                 }
                 MessagePurpose::DAC => config::get_da_certificate_route(view_number),
-                MessagePurpose::VidDisperse => config::get_vid_disperse_route(view_number), // like `Proposal`
+                MessagePurpose::VidDisperse => {
+                    config::get_vid_disperse_route(view_number, &self.own_key.to_string())
+                }
                 MessagePurpose::Upgrade => config::get_upgrade_route(view_number),
             };
 
@@ -404,32 +427,20 @@ impl<TYPES: NodeType> Inner<TYPES> {
                 // then try again next time.
                 if let Ok(Some((first_tx_index, txs))) = possible_message {
                     for tx_raw in txs {
-                        // This is very hacky.
-                        //
-                        // Fundamentally, tx_raw is a serialized Option(Message<TYPES>).
-                        // The problem is, we want to extract the serialized Message<TYPES>
-                        // *without* deserializing the entire tx_raw
-                        // (because, a priori, the serialization of Message<TYPES> might depend on the version number,
-                        // which we have not yet read at this point).
-                        //
-                        // So we use the fact that the bincode serialization of Option(_) is a single leading byte
-                        // (0 for None and 1 for Some). Dropping the first byte then yields the serialized Message<TYPES>.
-                        //
-                        // It would be nice to do this with serde primitives, but I'm not sure how.
-
-                        match tx_raw.first() {
-                            Some(0) => {
+                        // Each transaction is an optional message in its versioned encoding.
+                        match bincode_opts().deserialize::<Option<Vec<u8>>>(&tx_raw) {
+                            Ok(None) => {
                                 continue;
                             }
-                            Some(1) => {
-                                let tx = tx_raw[1..].to_vec();
+                            Ok(Some(tx)) => {
                                 let tx_version = read_version(&tx);
 
                                 match tx_version {
-                                    Some(VERSION_0_1) => {
+                                    Some(VERSION_0_1 | VERSION_0_2) => {
                                         self.handle_tx_0_1(tx, first_tx_index, &mut tx_index).await;
                                     }
                                     Some(version) => {
+                                        self.metrics.unsupported_version_message_count.add(1);
                                         warn!(
                                       "Received message with unsupported version: {:?}.\n\nPayload:\n\n{:?}",
                                       version,
@@ -444,7 +455,7 @@ impl<TYPES: NodeType> Inner<TYPES> {
                                     }
                                 }
                             }
-                            _ => {
+                            Err(_) => {
                                 warn!("Could not deserialize transaction: {:?}", tx_raw);
                             }
                         }
@@ -457,31 +468,18 @@ impl<TYPES: NodeType> Inner<TYPES> {
                     self.client.get(&endpoint).send().await;
                 if let Ok(Some(messages)) = possible_message {
                     for message_raw in messages {
-                        // This is very hacky.
-                        //
-                        // Fundamentally, message_raw is a serialized Option(Message<TYPES>).
-                        // The problem is, we want to extract the serialized Message<TYPES>
-                        // *without* deserializing the entire message_raw
-                        // (because, a priori, the serialization of Message<TYPES> might depend on the version number,
-                        // which we have not yet read at this point).
-                        //
-                        // So we use the fact that the bincode serialization of Option(_) is a single leading byte
-                        // (0 for None and 1 for Some). Dropping the first byte then yields the serialized Message<TYPES>.
-                        //
-                        // It would be nice to do this with serde primitives, but I'm not sure how.
-
-                        match message_raw.first() {
-                            Some(0) => {
+                        // Each message is an optional message in its versioned encoding.
+                        match bincode_opts().deserialize::<Option<Vec<u8>>>(&message_raw) {
+                            Ok(None) => {
                                 continue;
                             }
-                            Some(1) => {
-                                let message = message_raw[1..].to_vec();
+                            Ok(Some(message)) => {
                                 let message_version = read_version(&message);
 
                                 let should_return;
 
                                 match message_version {
-                                    Some(VERSION_0_1) => {
+                                    Some(VERSION_0_1 | VERSION_0_2) => {
                                         should_return = self
                                             .handle_message_0_1(
                                                 message,
@@ -498,6 +496,7 @@ impl<TYPES: NodeType> Inner<TYPES> {
                                         }
                                     }
                                     Some(version) => {
+                                        self.metrics.unsupported_version_message_count.add(1);
                                         warn!(
                                       "Received message with unsupported version: {:?}.\n\nPayload:\n\n{:?}",
                                       version,
@@ -512,7 +511,7 @@ impl<TYPES: NodeType> Inner<TYPES> {
                                     }
                                 }
                             }
-                            _ => {
+                            Err(_) => {
                                 warn!("Could not deserialize message: {:?}", message_raw);
                             }
                         }
@@ -629,6 +628,7 @@ impl<TYPES: NodeType + 'static> WebServerNetwork<TYPES> {
         wait_between_polls: Duration,
         key: TYPES::SignatureKey,
         is_da_server: bool,
+        metrics: NetworkingMetricsValue,
     ) -> Self {
         info!("Connecting to web server at {url:?} is da: {is_da_server}");
 
@@ -642,8 +642,9 @@ impl<TYPES: NodeType + 'static> WebServerNetwork<TYPES> {
             connected: AtomicBool::new(false),
             client,
             wait_between_polls,
-            _own_key: key,
+            own_key: key,
             is_da: is_da_server,
+            metrics,
             tx_index: Arc::default(),
             proposal_task_map: Arc::default(),
             vote_task_map: Arc::default(),
@@ -668,17 +669,22 @@ impl<TYPES: NodeType + 'static> WebServerNetwork<TYPES> {
     /// Returns a `SendMsg` containing the endpoint
     fn parse_post_message(
         message: Message<TYPES>,
-    ) -> Result<SendMsg<Message<TYPES>>, WebServerNetworkError> {
+        recipient: Option<&TYPES::SignatureKey>,
+    ) -> Result<SendMsg<Message<TYPES>>, NetworkError> {
         let view_number: TYPES::Time = message.get_view_number();
 
         let endpoint = match &message.purpose() {
             MessagePurpose::Proposal => config::post_proposal_route(*view_number),
             MessagePurpose::Vote => config::post_vote_route(*view_number),
             MessagePurpose::Data => config::post_transactions_route(),
+            // There are no web server routes for request/response messages.
+            MessagePurpose::RequestResponse => return Err(NetworkError::UnimplementedFeature),
             MessagePurpose::Internal
             | MessagePurpose::LatestProposal
             | MessagePurpose::LatestViewSyncCertificate => {
-                return Err(WebServerNetworkError::EndpointError)
+                return Err(NetworkError::WebServer {
+                    source: WebServerNetworkError::EndpointError,
+                })
             }
             MessagePurpose::ViewSyncCertificate => {
                 // error!("Posting view sync proposal route is: {}", config::post_view_sync_certificate_route(*view_number));
@@ -686,7 +692,17 @@ impl<TYPES: NodeType + 'static> WebServerNetwork<TYPES> {
             }
             MessagePurpose::ViewSyncVote => config::post_view_sync_vote_route(*view_number),
             MessagePurpose::DAC => config::post_da_certificate_route(*view_number),
-            MessagePurpose::VidDisperse => config::post_vid_disperse_route(*view_number),
+            // VID shares are only sent directly to the storage node they are for.
+            MessagePurpose::VidDisperse => match recipient {
+                Some(recipient) => {
+                    config::post_vid_disperse_route(*view_number, &recipient.to_string())
+                }
+                None => {
+                    return Err(NetworkError::WebServer {
+                        source: WebServerNetworkError::EndpointError,
+                    })
+                }
+            },
             MessagePurpose::Upgrade => config::post_upgrade_route(*view_number),
         };
 
@@ -745,6 +761,7 @@ impl<TYPES: NodeType + 'static> WebServerNetwork<TYPES> {
                 Duration::from_millis(100),
                 known_nodes[usize::try_from(id).unwrap()].clone(),
                 is_da,
+                NetworkingMetricsValue::default(),
             );
             network.server_shutdown_signal = Some(sender);
             network
@@ -816,13 +833,8 @@ impl<TYPES: NodeType + 'static> ConnectedNetwork<Message<TYPES>, TYPES::Signatur
             return Err(NetworkError::ShutDown);
         }
 
-        let network_msg = Self::parse_post_message(message);
-        match network_msg {
-            Ok(network_msg) => self.post_message_to_web_server(network_msg).await,
-            Err(network_msg) => Err(NetworkError::WebServer {
-                source: network_msg,
-            }),
-        }
+        let network_msg = Self::parse_post_message(message, None)?;
+        self.post_message_to_web_server(network_msg).await
     }
 
     /// Sends a direct message to a specific node
@@ -830,24 +842,15 @@ impl<TYPES: NodeType + 'static> ConnectedNetwork<Message<TYPES>, TYPES::Signatur
     async fn direct_message(
         &self,
         message: Message<TYPES>,
-        _recipient: TYPES::SignatureKey,
+        recipient: TYPES::SignatureKey,
     ) -> Result<(), NetworkError> {
         // short circuit if we are shut down
         #[cfg(feature = "hotshot-testing")]
         if !self.inner.running.load(Ordering::Relaxed) {
             return Err(NetworkError::ShutDown);
         }
-        let network_msg = Self::parse_post_message(message);
-        match network_msg {
-            Ok(network_msg) => {
-                // error!("network msg is {:?}", network_msg.clone());
-
-                self.post_message_to_web_server(network_msg).await
-            }
-            Err(network_msg) => Err(NetworkError::WebServer {
-                source: network_msg,
-            }),
-        }
+        let network_msg = Self::parse_post_message(message, Some(&recipient))?;
+        self.post_message_to_web_server(network_msg).await
     }
 
     /// Moves out the entire queue of received messages of 'transmit_type`
@@ -0,0 +1,71 @@
+//! An in-process state relay, which collects signed light client states in memory
+
+use async_lock::RwLock;
+use async_trait::async_trait;
+use ethereum_types::U256;
+use hotshot_types::{
+    light_client::{
+        LightClientState, StateSignatureRequestBody, StateSignaturesBundle, StateVerKey,
+    },
+    traits::state_relay::{StateRelay, StateRelayError},
+};
+use std::collections::{BTreeMap, HashMap};
+
+/// A [`StateRelay`] which collects the signatures of each light client state into a
+/// [`StateSignaturesBundle`], weighted by the stake of each signer.
+///
+/// Useful for tests and for nodes that run in the same process as the prover.
+#[derive(Debug)]
+pub struct InProcessStateRelay {
+    /// The stake of each key allowed to sign states
+    stake_table: HashMap<StateVerKey, U256>,
+    /// The signatures collected so far for each state
+    bundles: RwLock<BTreeMap<LightClientState, StateSignaturesBundle>>,
+}
+
+impl InProcessStateRelay {
+    /// Create a relay collecting signatures from the given stake table
+    #[must_use]
+    pub fn new(stake_table: HashMap<StateVerKey, U256>) -> Self {
+        Self {
+            stake_table,
+            bundles: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// The signatures collected so far for a state, if any
+    pub async fn get_bundle(&self, state: &LightClientState) -> Option<StateSignaturesBundle> {
+        self.bundles.read().await.get(state).cloned()
+    }
+}
+
+#[async_trait]
+impl StateRelay for InProcessStateRelay {
+    async fn publish(&self, request: StateSignatureRequestBody) -> Result<(), StateRelayError> {
+        if !request.verify() {
+            return Err(StateRelayError::InvalidSignature);
+        }
+        let Some(stake) = self.stake_table.get(&request.key) else {
+            return Err(StateRelayError::UnknownSigner);
+        };
+
+        let mut bundles = self.bundles.write().await;
+        let bundle =
+            bundles
+                .entry(request.state.clone())
+                .or_insert_with(|| StateSignaturesBundle {
+                    state: request.state,
+                    signatures: HashMap::new(),
+                    accumulated_weight: U256::zero(),
+                });
+        // A signer resending its signature does not count twice.
+        if bundle
+            .signatures
+            .insert(request.key, request.signature)
+            .is_none()
+        {
+            bundle.accumulated_weight += *stake;
+        }
+        Ok(())
+    }
+}
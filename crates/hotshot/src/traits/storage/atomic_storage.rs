@@ -1,80 +1,83 @@
 //! On-disk storage of node state. Based on [`atomic_store`](https://github.com/EspressoSystems/atomicstore).
+//!
+//! Every call to [`Storage::append`] and [`Storage::cleanup_storage_up_to_view`] is kept in memory
+//! and only written to disk, and made durable, once [`Storage::commit`] is called. If the process is
+//! killed before that, re-opening the storage will return the state of the last commit.
 
 mod dual_key_value_store;
 mod hash_map_store;
+mod value_store;
 
-use self::{dual_key_value_store::DualKeyValueStore, hash_map_store::HashMapStore};
-use crate::{data::Leaf, traits::StateContents};
-use async_std::sync::Mutex;
+use self::{
+    dual_key_value_store::DualKeyValueStore, hash_map_store::HashMapStore, value_store::ValueStore,
+};
+use async_lock::Mutex;
 use async_trait::async_trait;
-use atomic_store::{AtomicStore, AtomicStoreLoader};
+use atomic_store::{AtomicStore, AtomicStoreLoader, PersistenceError};
 use commit::Commitment;
-use hotshot_types::traits::storage::{
-    AtomicStoreSnafu, Storage, StorageError, StorageResult, StorageState, StorageUpdater,
-    TestableStorage,
+use hotshot_types::{
+    data::Leaf,
+    simple_certificate::{DACertificate, QuorumCertificate},
+    traits::{
+        node_implementation::NodeType,
+        storage::{
+            Result, SafetyRecord, Storage, StorageError, StorageState, StoredView, TestableStorage,
+            ViewEntry,
+        },
+    },
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::Path,
+    sync::Arc,
 };
-use serde::{de::DeserializeOwned, Serialize};
-use snafu::ResultExt;
-use std::{path::Path, sync::Arc};
 use tempfile::{tempdir, TempDir};
 use tracing::{instrument, trace};
 
+/// Convert an [`atomic_store`] error into a [`StorageError`]
+#[allow(clippy::needless_pass_by_value)]
+fn persistence_error(e: PersistenceError) -> StorageError {
+    StorageError::PersistenceError {
+        description: e.to_string(),
+    }
+}
+
 /// Inner state of an atomic storage
-struct AtomicStorageInner<STATE>
-where
-    STATE: DeserializeOwned + Serialize + StateContents,
-{
+struct AtomicStorageInner<TYPES: NodeType> {
     /// Temporary directory storage might live in
     /// (we want to delete the temporary directory when storage is droppped)
     _temp_dir: Option<TempDir>,
     /// The atomic store loader
     atomic_store: Mutex<AtomicStore>,
 
-    /// The Blocks stored by this [`AtomicStorage`]
-    blocks: HashMapStore<Commitment<STATE::BlockPayload>, STATE::BlockPayload>,
+    /// The views that have been stored, and have not been cleaned up yet
+    stored: HashMapStore<TYPES::Time, StoredView<TYPES>>,
 
-    /// The [`QuorumCertificate`]s stored by this [`AtomicStorage`]
-    qcs: DualKeyValueStore<QuorumCertificate<STATE>>,
+    /// The views that have failed, and have not been cleaned up yet
+    failed: HashMapStore<TYPES::Time, ()>,
 
-    /// The [`Leaf`s stored by this [`AtomicStorage`]
-    ///
-    /// In order to maintain the struct constraints, this list must be append only. Once a QC is
-    /// inserted, it index _must not_ change
-    leaves: DualKeyValueStore<Leaf<STATE>>,
+    /// The views that have not been decided yet, and have not been cleaned up yet
+    undecided: HashMapStore<TYPES::Time, StoredView<TYPES>>,
+
+    /// The DA certificates that have not been cleaned up yet
+    da_certs: HashMapStore<TYPES::Time, DACertificate<TYPES>>,
 
-    /// The store of states
-    states: HashMapStore<Commitment<Leaf<STATE>>, STATE>,
+    /// The votes this node has sent
+    safety_record: ValueStore<SafetyRecord<TYPES>>,
+
+    /// The justify [`QuorumCertificate`]s of the views stored by this [`AtomicStorage`], cleaned up
+    /// together with the views.
+    qcs: DualKeyValueStore<QuorumCertificate<TYPES>>,
 }
 
 /// Persistent [`Storage`] implementation, based upon [`atomic_store`].
 #[derive(Clone)]
-pub struct AtomicStorage<STATE>
-where
-    STATE: DeserializeOwned + Serialize + StateContents,
-{
+pub struct AtomicStorage<TYPES: NodeType> {
     /// Inner state of the atomic storage
-    inner: Arc<AtomicStorageInner<STATE>>,
+    inner: Arc<AtomicStorageInner<TYPES>>,
 }
 
-impl<S: StateContents + 'static> TestableStorage<S> for AtomicStorage<S> {
-    fn construct_tmp_storage() -> StorageResult<Self> {
-        let tempdir = tempdir().map_err(|e| StorageError::InconsistencyError {
-            description: e.to_string(),
-        })?;
-        let loader = AtomicStoreLoader::create(tempdir.path(), "hotshot").map_err(|e| {
-            StorageError::InconsistencyError {
-                description: e.to_string(),
-            }
-        })?;
-        Self::init_from_loader(loader, Some(tempdir))
-            .map_err(|e| StorageError::AtomicStore { source: e })
-    }
-}
-
-impl<STATE> AtomicStorage<STATE>
-where
-    STATE: StateContents,
-{
+impl<TYPES: NodeType> AtomicStorage<TYPES> {
     /// Creates an atomic storage at a given path. If files exist, will back up existing directory before creating.
     ///
     /// # Errors
@@ -89,7 +92,7 @@ where
         Self::init_from_loader(loader, None)
     }
 
-    /// Open an atomic storage at a given path.
+    /// Open an atomic storage at a given path, creating it if it does not exist yet.
     ///
     /// # Errors
     ///
@@ -115,10 +118,12 @@ where
         mut loader: AtomicStoreLoader,
         dir: Option<TempDir>,
     ) -> atomic_store::Result<Self> {
-        let blocks = HashMapStore::load(&mut loader, "hotshot_blocks")?;
+        let stored = HashMapStore::load(&mut loader, "hotshot_stored_views")?;
+        let failed = HashMapStore::load(&mut loader, "hotshot_failed_views")?;
+        let undecided = HashMapStore::load(&mut loader, "hotshot_undecided_views")?;
+        let da_certs = HashMapStore::load(&mut loader, "hotshot_da_certs")?;
+        let safety_record = ValueStore::load(&mut loader, "hotshot_safety_record")?;
         let qcs = DualKeyValueStore::open(&mut loader, "hotshot_qcs")?;
-        let leaves = DualKeyValueStore::open(&mut loader, "hotshot_leaves")?;
-        let states = HashMapStore::load(&mut loader, "hotshot_states")?;
 
         let atomic_store = AtomicStore::open(loader)?;
 
@@ -126,134 +131,244 @@ where
             inner: Arc::new(AtomicStorageInner {
                 _temp_dir: dir,
                 atomic_store: Mutex::new(atomic_store),
-                blocks,
+                stored,
+                failed,
+                undecided,
+                da_certs,
+                safety_record,
                 qcs,
-                leaves,
-                states,
             }),
         })
     }
-}
 
-#[async_trait]
-impl<STATE: StateContents> Storage<STATE> for AtomicStorage<STATE> {
-    #[instrument(name = "AtomicStorage::get_block", skip_all)]
-    async fn get_block(
+    /// Get the QC certifying the leaf with the given commitment, if a view justified by it was stored.
+    #[instrument(name = "AtomicStorage::get_qc", skip_all)]
+    pub async fn get_qc(
         &self,
-        hash: &Commitment<STATE::BlockPayload>,
-    ) -> StorageResult<Option<STATE::BlockPayload>> {
-        Ok(self.inner.blocks.get(hash).await)
+        leaf_commitment: &Commitment<Leaf<TYPES>>,
+    ) -> Option<QuorumCertificate<TYPES>> {
+        self.inner.qcs.load_by_key_1_ref(leaf_commitment).await
     }
 
-    #[instrument(name = "AtomicStorage::get_qc", skip_all)]
-    async fn get_qc(
-        &self,
-        hash: &Commitment<STATE::BlockPayload>,
-    ) -> StorageResult<Option<QuorumCertificate<STATE>>> {
-        Ok(self.inner.qcs.load_by_key_1_ref(hash).await)
+    /// Get the QC that was formed in the given view, if any view justified by it was stored.
+    #[instrument(name = "AtomicStorage::get_qc_for_view", skip_all)]
+    pub async fn get_qc_for_view(&self, view: TYPES::Time) -> Option<QuorumCertificate<TYPES>> {
+        self.inner.qcs.load_by_key_2(view).await
     }
 
+    /// Get the QC with the highest view number this storage has seen.
     #[instrument(name = "AtomicStorage::get_newest_qc", skip_all)]
-    async fn get_newest_qc(&self) -> StorageResult<Option<QuorumCertificate<STATE>>> {
-        Ok(self.inner.qcs.load_latest(|qc| qc.get_view_number()).await)
+    pub async fn get_newest_qc(&self) -> Option<QuorumCertificate<TYPES>> {
+        self.inner.qcs.load_latest(|qc| qc.view_number).await
     }
+}
 
-    #[instrument(name = "AtomicStorage::get_qc_for_view", skip_all)]
-    async fn get_qc_for_view(
-        &self,
-        view: TYPES::Time,
-    ) -> StorageResult<Option<QuorumCertificate<STATE>>> {
-        Ok(self.inner.qcs.load_by_key_2(view).await)
+#[async_trait]
+impl<TYPES: NodeType> TestableStorage<TYPES> for AtomicStorage<TYPES> {
+    fn construct_tmp_storage() -> Result<Self> {
+        let tempdir = tempdir().map_err(|e| StorageError::InconsistencyError {
+            description: e.to_string(),
+        })?;
+        let loader =
+            AtomicStoreLoader::create(tempdir.path(), "hotshot").map_err(persistence_error)?;
+        Self::init_from_loader(loader, Some(tempdir)).map_err(persistence_error)
     }
 
-    #[instrument(name = "AtomicStorage::get_leaf", skip_all)]
-    async fn get_leaf(&self, hash: &Commitment<Leaf<STATE>>) -> StorageResult<Option<Leaf<STATE>>> {
-        Ok(self.inner.leaves.load_by_key_1_ref(hash).await)
+    async fn get_full_state(&self) -> StorageState<TYPES> {
+        StorageState {
+            stored: self.inner.stored.load_all().await.into_iter().collect(),
+            failed: self.inner.failed.load_all().await.into_keys().collect(),
+            undecided: self.inner.undecided.load_all().await.into_iter().collect(),
+            da_certs: self.inner.da_certs.load_all().await.into_iter().collect(),
+            safety_record: self.inner.safety_record.get().await.unwrap_or_default(),
+        }
     }
+}
 
-    #[instrument(name = "AtomicStorage::get_leaf_by_block", skip_all)]
-    async fn get_leaf_by_block(
-        &self,
-        hash: &Commitment<STATE::BlockPayload>,
-    ) -> StorageResult<Option<Leaf<STATE>>> {
-        Ok(self.inner.leaves.load_by_key_2_ref(hash).await)
+#[async_trait]
+impl<TYPES: NodeType> Storage<TYPES> for AtomicStorage<TYPES> {
+    #[instrument(name = "AtomicStorage::append", skip_all)]
+    async fn append(&self, views: Vec<ViewEntry<TYPES>>) -> Result {
+        for view in views {
+            match view {
+                ViewEntry::Failed(num) => {
+                    trace!(?num, "inserting failed view");
+                    self.inner.failed.insert(num, ()).await;
+                }
+                ViewEntry::Success(view) => {
+                    trace!(view_number = ?view.view_number, "inserting view");
+                    self.inner.qcs.insert(view.justify_qc.clone()).await?;
+                    // Undecided views up to this one are now either decided or abandoned
+                    let view_number = view.view_number;
+                    self.inner
+                        .undecided
+                        .retain(|undecided_view| *undecided_view > view_number)
+                        .await;
+                    self.inner.stored.insert(view.view_number, view).await;
+                }
+                ViewEntry::Undecided(view) => {
+                    trace!(view_number = ?view.view_number, "inserting undecided view");
+                    self.inner.qcs.insert(view.justify_qc.clone()).await?;
+                    self.inner.undecided.insert(view.view_number, view).await;
+                }
+            }
+        }
+        Ok(())
     }
 
-    #[instrument(name = "AtomicStorage::get_state", skip_all)]
-    async fn get_state(&self, hash: &Commitment<Leaf<STATE>>) -> StorageResult<Option<STATE>> {
-        Ok(self.inner.states.get(hash).await)
+    #[instrument(name = "AtomicStorage::cleanup_storage_up_to_view", skip_all)]
+    async fn cleanup_storage_up_to_view(&self, view: TYPES::Time) -> Result<usize> {
+        let removed_stored = self
+            .inner
+            .stored
+            .retain(|stored_view| *stored_view >= view)
+            .await;
+        let removed_failed = self
+            .inner
+            .failed
+            .retain(|failed_view| *failed_view >= view)
+            .await;
+        let removed_undecided = self
+            .inner
+            .undecided
+            .retain(|undecided_view| *undecided_view >= view)
+            .await;
+        let removed_da_certs = self
+            .inner
+            .da_certs
+            .retain(|da_cert_view| *da_cert_view >= view)
+            .await;
+        // Views that are kept may have been justified by a QC from before `view`, which has to be
+        // kept with them.
+        let mut justified: HashSet<TYPES::Time> = self
+            .inner
+            .stored
+            .map_values(|stored_view| stored_view.justify_qc.view_number)
+            .await
+            .into_iter()
+            .collect();
+        justified.extend(
+            self.inner
+                .undecided
+                .map_values(|undecided_view| undecided_view.justify_qc.view_number)
+                .await,
+        );
+        self.inner
+            .qcs
+            .retain(|qc| qc.view_number >= view || justified.contains(&qc.view_number))
+            .await;
+        Ok(removed_stored + removed_failed + removed_undecided + removed_da_certs)
     }
 
-    async fn get_internal_state(&self) -> StorageState<STATE> {
-        let mut blocks: Vec<(Commitment<STATE::BlockPayload>, STATE::BBlockPayloadlock)> =
-            self.inner.blocks.load_all().await.into_iter().collect();
-
-        blocks.sort_by_key(|(hash, _)| *hash);
-        let blocks = blocks.into_iter().map(|(_, block)| block).collect();
-
-        let mut leafs: Vec<Leaf<STATE>> = self.inner.leaves.load_all().await;
-        leafs.sort_by_cached_key(Leaf::hash);
+    #[instrument(name = "AtomicStorage::get_anchored_view", skip_all)]
+    async fn get_anchored_view(&self) -> Result<StoredView<TYPES>> {
+        self.inner
+            .stored
+            .load_all()
+            .await
+            .into_iter()
+            .max_by_key(|(view_number, _)| *view_number)
+            .map(|(_, view)| view)
+            .ok_or(StorageError::NoGenesisView)
+    }
 
-        let mut quorum_certificates = self.inner.qcs.load_all().await;
-        quorum_certificates.sort_by_key(|qc| qc.view_number());
+    #[instrument(name = "AtomicStorage::get_undecided_views", skip_all)]
+    async fn get_undecided_views(&self) -> Result<Vec<StoredView<TYPES>>> {
+        let anchor = self.inner.stored.load_all().await.into_keys().max();
+        let mut undecided: Vec<_> = self
+            .inner
+            .undecided
+            .load_all()
+            .await
+            .into_values()
+            .filter(|view| anchor < Some(view.view_number))
+            .collect();
+        undecided.sort_by_key(|view| view.view_number);
+        Ok(undecided)
+    }
 
-        let mut states: Vec<(Commitment<Leaf<STATE>>, STATE)> =
-            self.inner.states.load_all().await.into_iter().collect();
-        states.sort_by_key(|(hash, _)| *hash);
-        let states = states.into_iter().map(|(_, state)| state).collect();
+    #[instrument(name = "AtomicStorage::get_decided_views", skip_all)]
+    async fn get_decided_views(
+        &self,
+        after: TYPES::Time,
+        limit: usize,
+    ) -> Result<Vec<StoredView<TYPES>>> {
+        let mut decided = self
+            .inner
+            .stored
+            .load_where(|view_number| *view_number > after)
+            .await;
+        decided.sort_by_key(|(view_number, _)| *view_number);
+        Ok(decided
+            .into_iter()
+            .take(limit)
+            .map(|(_, view)| view)
+            .collect())
+    }
 
-        StorageState {
-            blocks,
-            quorum_certificates,
-            leafs,
-            states,
-        }
+    #[instrument(name = "AtomicStorage::append_da_cert", skip_all)]
+    async fn append_da_cert(&self, cert: DACertificate<TYPES>) -> Result {
+        self.inner.da_certs.insert(cert.view_number, cert).await;
+        Ok(())
     }
-}
 
-/// Implementation of [`StorageUpdater`] for the [`AtomicStorage`]
-struct AtomicStorageUpdater<'a, S: StateContents> {
-    /// A reference to the internals of the [`AtomicStorage`]
-    inner: &'a AtomicStorageInner<S>,
-}
+    #[instrument(name = "AtomicStorage::get_da_certs", skip_all)]
+    async fn get_da_certs(&self) -> Result<BTreeMap<TYPES::Time, DACertificate<TYPES>>> {
+        Ok(self.inner.da_certs.load_all().await.into_iter().collect())
+    }
 
-#[async_trait]
-impl<'a, STATE: StateContents + 'static> StorageUpdater<'a, STATE>
-    for AtomicStorageUpdater<'a, STATE>
-{
-    #[instrument(name = "AtomicStorage::get_block", skip_all)]
-    async fn insert_block(
-        &mut self,
-        hash: Commitment<STATE::BlockPayload>,
-        block: STATE::BlockPayload,
-    ) -> StorageResult {
-        trace!(?block, "inserting block");
+    #[instrument(name = "AtomicStorage::update_safety_record", skip_all)]
+    async fn update_safety_record(&self, record: SafetyRecord<TYPES>) -> Result {
         self.inner
-            .blocks
-            .insert(hash, block)
+            .safety_record
+            .set(record)
             .await
-            .context(AtomicStoreSnafu)?;
-        Ok(())
+            .map_err(persistence_error)
     }
 
-    #[instrument(name = "AtomicStorage::insert_leaf", skip_all)]
-    async fn insert_leaf(&mut self, leaf: Leaf<STATE>) -> StorageResult {
-        self.inner.leaves.insert(leaf).await
+    #[instrument(name = "AtomicStorage::get_safety_record", skip_all)]
+    async fn get_safety_record(&self) -> Result<SafetyRecord<TYPES>> {
+        // A record that cannot be read fails opening the storage, so no record here means this
+        // node never voted.
+        Ok(self.inner.safety_record.get().await.unwrap_or_default())
     }
 
-    #[instrument(name = "AtomicStorage::insert_qc", skip_all)]
-    async fn insert_qc(&mut self, qc: QuorumCertificate<STATE>) -> StorageResult {
-        self.inner.qcs.insert(qc).await
-    }
-
-    #[instrument(name = "AtomicStorage::insert_state", skip_all)]
-    async fn insert_state(&mut self, state: STATE, hash: Commitment<Leaf<STATE>>) -> StorageResult {
-        trace!(?hash, "Inserting state");
+    #[instrument(name = "AtomicStorage::commit", skip_all)]
+    async fn commit(&self) -> Result {
+        // Hold the lock on the atomic store for the whole commit, so concurrent commits cannot
+        // interleave their versions of the individual logs.
+        let mut atomic_store = self.inner.atomic_store.lock().await;
+        self.inner
+            .stored
+            .commit_version()
+            .await
+            .map_err(persistence_error)?;
+        self.inner
+            .failed
+            .commit_version()
+            .await
+            .map_err(persistence_error)?;
+        self.inner
+            .undecided
+            .commit_version()
+            .await
+            .map_err(persistence_error)?;
+        self.inner
+            .da_certs
+            .commit_version()
+            .await
+            .map_err(persistence_error)?;
+        self.inner
+            .safety_record
+            .commit_version()
+            .await
+            .map_err(persistence_error)?;
         self.inner
-            .states
-            .insert(hash, state)
+            .qcs
+            .commit_version()
             .await
-            .context(AtomicStoreSnafu)?;
+            .map_err(persistence_error)?;
+        atomic_store.commit_version().map_err(persistence_error)?;
         Ok(())
     }
 }
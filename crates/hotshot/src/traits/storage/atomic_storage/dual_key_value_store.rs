@@ -2,18 +2,16 @@
 //!
 //! Implementations should implement [`DualKeyValue`] before they can use [`DualKeyValueStore`].
 
-use async_std::sync::RwLock;
-use atomic_store::{load_store::BincodeLoadStore, AppendLog, AtomicStoreLoader};
-use commit::{Commitment, Committable};
+use async_lock::RwLock;
+use atomic_store::{load_store::BincodeLoadStore, AtomicStoreLoader, PersistenceError, RollingLog};
+use commit::Commitment;
 use hotshot_types::{
-    data::{Leaf, ViewNumber},
-    traits::{
-        storage::{AtomicStoreSnafu, InconsistencySnafu, StorageError},
-        StateContents,
-    },
+    data::Leaf,
+    simple_certificate::QuorumCertificate,
+    traits::{node_implementation::NodeType, storage::StorageError},
+    vote::HasViewNumber,
 };
 use serde::{de::DeserializeOwned, Serialize};
-use snafu::ResultExt;
 use std::{collections::HashMap, hash::Hash};
 
 /// A store that allows lookup of a value by 2 different keys.
@@ -25,7 +23,7 @@ pub struct DualKeyValueStore<K: DualKeyValue> {
 /// The inner struct of the [`DualKeyValueStore`]
 struct Inner<K: DualKeyValue> {
     /// The underlying store
-    store: AppendLog<BincodeLoadStore<K>>,
+    store: RollingLog<BincodeLoadStore<Vec<K>>>,
 
     /// Key 1 to index
     key_1: HashMap<K::Key1, usize>,
@@ -33,46 +31,59 @@ struct Inner<K: DualKeyValue> {
     /// Key 2 to index
     key_2: HashMap<K::Key2, usize>,
 
-    /// Actual values. This list should be append-only
+    /// Actual values
     values: Vec<K>,
+
+    /// Whether `values` changed since they were last written to `store`
+    dirty: bool,
 }
 
-impl<K: DualKeyValue> DualKeyValueStore<K> {
-    /// Open the [`DualKeyValueStore`] with the given loader and name.
-    ///
-    /// # Errors
-    ///
-    /// Returns any errors that [`AppendLog`]'s `load` returns.
-    pub fn open(
-        loader: &mut AtomicStoreLoader,
-        name: &str,
-    ) -> Result<Self, atomic_store::PersistenceError> {
-        let store = AppendLog::load(loader, BincodeLoadStore::default(), name, 1024)?;
-        let values = store
-            .iter()
-            .collect::<Result<Vec<K>, atomic_store::PersistenceError>>()
-            .unwrap_or_default();
-        let key_1 = values
+impl<K: DualKeyValue> Inner<K> {
+    /// Rebuild both indices from `values`
+    fn reindex(&mut self) {
+        self.key_1 = self
+            .values
             .iter()
             .enumerate()
             .map(|(idx, v)| (v.key_1(), idx))
             .collect();
-        let key_2 = values
+        self.key_2 = self
+            .values
             .iter()
             .enumerate()
             .map(|(idx, v)| (v.key_2(), idx))
             .collect();
+    }
+}
+
+impl<K: DualKeyValue> DualKeyValueStore<K> {
+    /// Open the [`DualKeyValueStore`] with the given loader and name.
+    ///
+    /// # Errors
+    ///
+    /// Returns any errors that [`RollingLog`]'s `load` or `load_latest` return, other than the
+    /// store being empty, so a corrupt log is never mistaken for an empty one.
+    pub fn open(loader: &mut AtomicStoreLoader, name: &str) -> Result<Self, PersistenceError> {
+        let store = RollingLog::load(loader, BincodeLoadStore::default(), name, 1024)?;
+        let values = match store.load_latest() {
+            Ok(values) => values,
+            Err(PersistenceError::FailedToFindExpectedResource { .. }) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let mut inner = Inner {
+            store,
+            key_1: HashMap::new(),
+            key_2: HashMap::new(),
+            values,
+            dirty: false,
+        };
+        inner.reindex();
         Ok(Self {
-            inner: RwLock::new(Inner {
-                store,
-                key_1,
-                key_2,
-                values,
-            }),
+            inner: RwLock::new(inner),
         })
     }
 
-    /// Load the `K` value based on the 1st key.
+    /// Load the `K` value based on a reference of the 1st key.
     pub async fn load_by_key_1_ref(&self, k: &K::Key1) -> Option<K> {
         let read = self.inner.read().await;
         let idx = read.key_1.get(k).copied()?;
@@ -101,16 +112,13 @@ impl<K: DualKeyValue> DualKeyValueStore<K> {
         read.values.iter().max_by_key::<V, F>(cb).cloned()
     }
 
-    /// Load all entries in this [`DualKeyValueStore`]
-    pub async fn load_all(&self) -> Vec<K> {
-        self.inner.read().await.values.clone()
-    }
-
-    /// Insert a value into this [`DualKeyValueStore`]
+    /// Insert a value into this [`DualKeyValueStore`]. This won't be written to disk until
+    /// `commit_version` is called.
     ///
     /// # Errors
     ///
-    /// Returns any errors that [`AppendLog`]'s `store_resource` returns.
+    /// Returns an [`StorageError::InconsistencyError`] if only one of the keys is already known,
+    /// or both are known but point at different records.
     pub async fn insert(&self, val: K) -> Result<(), StorageError> {
         let mut lock = self.inner.write().await;
 
@@ -118,46 +126,61 @@ impl<K: DualKeyValue> DualKeyValueStore<K> {
             (Some(idx), Some(key_2_idx)) if idx == key_2_idx => {
                 // updating
                 let idx = *idx;
-
-                // TODO: This still adds a duplicate `K` in the storage
-                // ideally we'd update this record instead
-                lock.store.store_resource(&val).context(AtomicStoreSnafu)?;
                 lock.values[idx] = val;
+                lock.dirty = true;
                 Ok(())
             }
-            (Some(_), Some(_)) => InconsistencySnafu {
+            (Some(_), Some(_)) => Err(StorageError::InconsistencyError {
                 description: format!("Could not insert {}, both {} and {} already exist, but point at different records", std::any::type_name::<K>(), K::KEY_1_NAME, K::KEY_2_NAME),
-            }
-            .fail(),
-            (Some(_), None) => InconsistencySnafu {
+            }),
+            (Some(_), None) => Err(StorageError::InconsistencyError {
                 description: format!("Could not insert {}, {} already exists but {} does not", std::any::type_name::<K>(), K::KEY_1_NAME, K::KEY_2_NAME),
-            }
-            .fail(),
-            (None, Some(_)) => InconsistencySnafu {
+            }),
+            (None, Some(_)) => Err(StorageError::InconsistencyError {
                 description: format!("Could not insert {}, {} already exists but {} does not", std::any::type_name::<K>(), K::KEY_2_NAME, K::KEY_1_NAME),
-            }
-            .fail(),
+            }),
             (None, None) => {
                 // inserting
-                lock.store.store_resource(&val).context(AtomicStoreSnafu)?;
-
                 let idx = lock.values.len();
                 lock.key_1.insert(val.key_1(), idx);
                 lock.key_2.insert(val.key_2(), idx);
                 lock.values.push(val);
-
+                lock.dirty = true;
                 Ok(())
             }
         }
     }
 
-    /// Commit this [`DualKeyValueStore`].
+    /// Only keep the values for which `f` returns `true`. Returns the amount of values that were
+    /// removed. This won't be written to disk until `commit_version` is called.
+    pub async fn retain<F>(&self, f: F) -> usize
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let mut lock = self.inner.write().await;
+        let old_len = lock.values.len();
+        lock.values.retain(f);
+        let removed = old_len - lock.values.len();
+        if removed > 0 {
+            lock.reindex();
+            lock.dirty = true;
+        }
+        removed
+    }
+
+    /// Commit this [`DualKeyValueStore`], writing its values first if they changed since the last
+    /// commit.
     ///
     /// # Errors
     ///
-    /// Returns any errors that [`AppendLog`]'s `commit_version` returns.
+    /// Returns any errors that [`RollingLog`]'s `store_resource` or `commit_version` return.
     pub async fn commit_version(&self) -> atomic_store::Result<()> {
         let mut lock = self.inner.write().await;
+        if lock.dirty {
+            let Inner { store, values, .. } = &mut *lock;
+            store.store_resource(values)?;
+            lock.dirty = false;
+        }
         lock.store.commit_version()?;
         Ok(())
     }
@@ -180,36 +203,17 @@ pub trait DualKeyValue: Serialize + DeserializeOwned + Clone {
     fn key_2(&self) -> Self::Key2;
 }
 
-impl<STATE: StateContents> DualKeyValue for QuorumCertificate<STATE> {
-    type Key1 = Commitment<STATE::BlockPayload>;
-    type Key2 = ViewNumber;
-
-    const KEY_1_NAME: &'static str = "payload_commitment";
-    const KEY_2_NAME: &'static str = "view_number";
-
-    fn key_1(&self) -> Self::Key1 {
-        self.payload_commitment
-    }
-    fn key_2(&self) -> Self::Key2 {
-        self.view_number
-    }
-}
-
-impl<STATE> DualKeyValue for Leaf<STATE>
-where
-    STATE: StateContents,
-{
-    type Key1 = Commitment<Leaf<STATE>>;
-    type Key2 = Commitment<STATE::BlockPayload>;
+impl<TYPES: NodeType> DualKeyValue for QuorumCertificate<TYPES> {
+    type Key1 = Commitment<Leaf<TYPES>>;
+    type Key2 = TYPES::Time;
 
     const KEY_1_NAME: &'static str = "leaf_commitment";
-    const KEY_2_NAME: &'static str = "payload_commitment";
+    const KEY_2_NAME: &'static str = "view_number";
 
     fn key_1(&self) -> Self::Key1 {
-        self.commit()
+        self.data.leaf_commit
     }
-
     fn key_2(&self) -> Self::Key2 {
-        <STATE::BlockPayload as Committable>::commit(&self.deltas)
+        self.get_view_number()
     }
 }
@@ -1,7 +1,7 @@
 //! A store based on [`RollingLog`]
 
-use async_std::sync::RwLock;
-use atomic_store::{load_store::BincodeLoadStore, AtomicStoreLoader, RollingLog};
+use async_lock::RwLock;
+use atomic_store::{load_store::BincodeLoadStore, AtomicStoreLoader, PersistenceError, RollingLog};
 use serde::{de::DeserializeOwned, Serialize};
 use std::{collections::HashMap, hash::Hash};
 
@@ -25,6 +25,8 @@ where
     store: RollingLog<BincodeLoadStore<HashMap<K, V>>>,
     /// Data currently loaded in the store
     data: HashMap<K, V>,
+    /// Whether `data` changed since it was last written to `store`
+    dirty: bool,
 }
 
 impl<K, V> HashMapStore<K, V>
@@ -37,43 +39,89 @@ where
     ///
     /// # Errors
     ///
-    /// Returns any errors that [`RollingLog`]'s `load` returns.
+    /// Returns any errors that [`RollingLog`]'s `load` or `load_latest` return, other than the
+    /// store being empty.
     pub fn load(loader: &mut AtomicStoreLoader, name: &str) -> atomic_store::Result<Self> {
         let store = RollingLog::load(loader, BincodeLoadStore::default(), name, 1024)?;
-        let data = store.load_latest().unwrap_or_default();
+        let data = match store.load_latest() {
+            Ok(data) => data,
+            Err(PersistenceError::FailedToFindExpectedResource { .. }) => HashMap::default(),
+            Err(e) => return Err(e),
+        };
         Ok(Self {
-            inner: RwLock::new(Inner { store, data }),
+            inner: RwLock::new(Inner {
+                store,
+                data,
+                dirty: false,
+            }),
         })
     }
 
-    /// Get an entry in this store. Returning `Some(V)` if it was found.
-    pub async fn get(&self, hash: &K) -> Option<V> {
-        let read = self.inner.read().await;
-        read.data.get(hash).cloned()
+    /// Insert a new key-value entry into the store. This won't be written to disk until `commit` is
+    /// called.
+    pub async fn insert(&self, key: K, val: V) {
+        let mut lock = self.inner.write().await;
+        lock.data.insert(key, val);
+        lock.dirty = true;
     }
 
-    /// Insert a new key-value entry into the store. This won't be committed until `commit` is called.
-    pub async fn insert(&self, key: K, val: V) -> atomic_store::Result<()> {
+    /// Only keep the entries for which `f` returns `true`. Returns the amount of entries that were
+    /// removed. This won't be written to disk until `commit` is called.
+    pub async fn retain<F>(&self, mut f: F) -> usize
+    where
+        F: FnMut(&K) -> bool,
+    {
         let mut lock = self.inner.write().await;
-        // Make sure to commit the store first before updating the internal value
-        // this makes sure that in a case of an error, the internal state is still correct
-        let mut data = lock.data.clone();
-        data.insert(key, val);
-        lock.store.store_resource(&data)?;
+        let old_len = lock.data.len();
+        lock.data.retain(|k, _| f(k));
+        let removed = old_len - lock.data.len();
+        if removed > 0 {
+            lock.dirty = true;
+        }
+        removed
+    }
 
-        lock.data = data;
-        Ok(())
+    /// Returns the entries for which `f` returns `true`, without copying the rest of the store.
+    pub async fn load_where<F>(&self, mut f: F) -> Vec<(K, V)>
+    where
+        K: Clone,
+        F: FnMut(&K) -> bool,
+    {
+        self.inner
+            .read()
+            .await
+            .data
+            .iter()
+            .filter(|(k, _)| f(k))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    /// Returns `f` applied to every value in the store, without copying the values themselves.
+    pub async fn map_values<T, F>(&self, f: F) -> Vec<T>
+    where
+        F: FnMut(&V) -> T,
+    {
+        self.inner.read().await.data.values().map(f).collect()
     }
 
-    /// Commit this rolling store, returning a commit lock.
+    /// Commit this rolling store, writing the entries to it first if they changed since the last
+    /// commit. A whole commit therefore writes the map at most once, no matter how many entries
+    /// were inserted or removed.
     ///
-    /// Once all stores are committed, you need to call `apply` on this lock, or else the commit will be reverted once the lock goes out of scope.
+    /// The new version will only be visible after a restart once the [`AtomicStore`](atomic_store::AtomicStore) this
+    /// store was loaded from has committed its version as well.
     ///
     /// # Errors
     ///
-    /// Returns any errors that [`RollingLog`]'s `store_resource` and `commit_version` returns.
+    /// Returns any errors that [`RollingLog`]'s `store_resource` or `commit_version` return.
     pub async fn commit_version(&self) -> atomic_store::Result<()> {
         let mut lock = self.inner.write().await;
+        if lock.dirty {
+            let Inner { store, data, .. } = &mut *lock;
+            store.store_resource(data)?;
+            lock.dirty = false;
+        }
         lock.store.commit_version()?;
         Ok(())
     }
@@ -0,0 +1,67 @@
+//! A store for a single value, based on [`RollingLog`]
+
+use async_lock::RwLock;
+use atomic_store::{load_store::BincodeLoadStore, AtomicStoreLoader, PersistenceError, RollingLog};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A store holding a single, optional value, with [`RollingLog`] as the storage system.
+pub struct ValueStore<V: Serialize + DeserializeOwned> {
+    /// Inner value
+    inner: RwLock<Inner<V>>,
+}
+
+/// The inner value of the [`ValueStore`]
+struct Inner<V: Serialize + DeserializeOwned> {
+    /// The underlying atomic_store store
+    store: RollingLog<BincodeLoadStore<V>>,
+    /// The value currently loaded in the store
+    value: Option<V>,
+}
+
+impl<V: Serialize + DeserializeOwned + Clone> ValueStore<V> {
+    /// Load a `ValueStore` with the given loader and name.
+    ///
+    /// # Errors
+    ///
+    /// Returns any errors that [`RollingLog`]'s `load` or `load_latest` return, other than the
+    /// store being empty, so a corrupt value is never mistaken for an unset one.
+    pub fn load(loader: &mut AtomicStoreLoader, name: &str) -> atomic_store::Result<Self> {
+        let store = RollingLog::load(loader, BincodeLoadStore::default(), name, 1024)?;
+        let value = match store.load_latest() {
+            Ok(value) => Some(value),
+            Err(PersistenceError::FailedToFindExpectedResource { .. }) => None,
+            Err(e) => return Err(e),
+        };
+        Ok(Self {
+            inner: RwLock::new(Inner { store, value }),
+        })
+    }
+
+    /// Get the value in this store, if one was set.
+    pub async fn get(&self) -> Option<V> {
+        self.inner.read().await.value.clone()
+    }
+
+    /// Replace the value in this store. This won't be committed until `commit` is called.
+    ///
+    /// # Errors
+    ///
+    /// Returns any errors that [`RollingLog`]'s `store_resource` returns.
+    pub async fn set(&self, value: V) -> atomic_store::Result<()> {
+        let mut lock = self.inner.write().await;
+        lock.store.store_resource(&value)?;
+        lock.value = Some(value);
+        Ok(())
+    }
+
+    /// Commit this rolling store.
+    ///
+    /// # Errors
+    ///
+    /// Returns any errors that [`RollingLog`]'s `commit_version` returns.
+    pub async fn commit_version(&self) -> atomic_store::Result<()> {
+        let mut lock = self.inner.write().await;
+        lock.store.commit_version()?;
+        Ok(())
+    }
+}
@@ -3,10 +3,14 @@
 //! This module provides a non-persisting, dummy adapter for the [`Storage`] trait
 use async_lock::RwLock;
 use async_trait::async_trait;
-use hotshot_types::traits::{
-    node_implementation::NodeType,
-    storage::{
-        Result, Storage, StorageError, StorageState, StoredView, TestableStorage, ViewEntry,
+use hotshot_types::{
+    simple_certificate::DACertificate,
+    traits::{
+        node_implementation::NodeType,
+        storage::{
+            Result, SafetyRecord, Storage, StorageError, StorageState, StoredView, TestableStorage,
+            ViewEntry,
+        },
     },
 };
 use std::{
@@ -20,6 +24,12 @@ struct MemoryStorageInternal<TYPES: NodeType> {
     stored: BTreeMap<TYPES::Time, StoredView<TYPES>>,
     /// The views that have failed
     failed: BTreeSet<TYPES::Time>,
+    /// The views that have not been decided yet
+    undecided: BTreeMap<TYPES::Time, StoredView<TYPES>>,
+    /// The DA certificates that have been stored
+    da_certs: BTreeMap<TYPES::Time, DACertificate<TYPES>>,
+    /// The votes this node has sent
+    safety_record: SafetyRecord<TYPES>,
 }
 
 /// In memory, ephemeral, storage for a [`SystemContext`](crate::SystemContext) instance
@@ -36,6 +46,9 @@ impl<TYPES: NodeType> MemoryStorage<TYPES> {
         let inner = MemoryStorageInternal {
             stored: BTreeMap::new(),
             failed: BTreeSet::new(),
+            undecided: BTreeMap::new(),
+            da_certs: BTreeMap::new(),
+            safety_record: SafetyRecord::default(),
         };
         Self {
             inner: Arc::new(RwLock::new(inner)),
@@ -54,6 +67,9 @@ impl<TYPES: NodeType> TestableStorage<TYPES> for MemoryStorage<TYPES> {
         StorageState {
             stored: inner.stored.clone(),
             failed: inner.failed.clone(),
+            undecided: inner.undecided.clone(),
+            da_certs: inner.da_certs.clone(),
+            safety_record: inner.safety_record.clone(),
         }
     }
 }
@@ -68,8 +84,13 @@ impl<TYPES: NodeType> Storage<TYPES> for MemoryStorage<TYPES> {
                     inner.failed.insert(num);
                 }
                 ViewEntry::Success(view) => {
+                    // Undecided views up to this one are now either decided or abandoned
+                    inner.undecided = inner.undecided.split_off(&(view.view_number + 1));
                     inner.stored.insert(view.view_number, view);
                 }
+                ViewEntry::Undecided(view) => {
+                    inner.undecided.insert(view.view_number, view);
+                }
             }
         }
         Ok(())
@@ -87,7 +108,13 @@ impl<TYPES: NodeType> Storage<TYPES> for MemoryStorage<TYPES> {
         let failed_after = inner.failed.split_off(&view);
         let old_failed = std::mem::replace(&mut inner.failed, failed_after);
 
-        Ok(old_stored.len() + old_failed.len())
+        let undecided_after = inner.undecided.split_off(&view);
+        let old_undecided = std::mem::replace(&mut inner.undecided, undecided_after);
+
+        let da_certs_after = inner.da_certs.split_off(&view);
+        let old_da_certs = std::mem::replace(&mut inner.da_certs, da_certs_after);
+
+        Ok(old_stored.len() + old_failed.len() + old_undecided.len() + old_da_certs.len())
     }
 
     async fn get_anchored_view(&self) -> Result<StoredView<TYPES>> {
@@ -100,6 +127,48 @@ impl<TYPES: NodeType> Storage<TYPES> for MemoryStorage<TYPES> {
         Ok(last.clone())
     }
 
+    async fn get_undecided_views(&self) -> Result<Vec<StoredView<TYPES>>> {
+        let inner = self.inner.read().await;
+        let undecided = match inner.stored.keys().next_back() {
+            Some(anchor) => inner.undecided.range((*anchor + 1)..),
+            None => inner.undecided.range(..),
+        };
+        Ok(undecided.map(|(_, view)| view.clone()).collect())
+    }
+
+    async fn get_decided_views(
+        &self,
+        after: TYPES::Time,
+        limit: usize,
+    ) -> Result<Vec<StoredView<TYPES>>> {
+        let inner = self.inner.read().await;
+        Ok(inner
+            .stored
+            .range((after + 1)..)
+            .take(limit)
+            .map(|(_, view)| view.clone())
+            .collect())
+    }
+
+    async fn append_da_cert(&self, cert: DACertificate<TYPES>) -> Result {
+        let mut inner = self.inner.write().await;
+        inner.da_certs.insert(cert.view_number, cert);
+        Ok(())
+    }
+
+    async fn get_da_certs(&self) -> Result<BTreeMap<TYPES::Time, DACertificate<TYPES>>> {
+        Ok(self.inner.read().await.da_certs.clone())
+    }
+
+    async fn update_safety_record(&self, record: SafetyRecord<TYPES>) -> Result {
+        self.inner.write().await.safety_record = record;
+        Ok(())
+    }
+
+    async fn get_safety_record(&self) -> Result<SafetyRecord<TYPES>> {
+        Ok(self.inner.read().await.safety_record.clone())
+    }
+
     async fn commit(&self) -> Result {
         Ok(()) // do nothing
     }
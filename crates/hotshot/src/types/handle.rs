@@ -6,14 +6,16 @@ use async_broadcast::{InactiveReceiver, Receiver, Sender};
 use async_lock::RwLock;
 use futures::Stream;
 
-use hotshot_task_impls::events::HotShotEvent;
+use hotshot_constants::{SUPPORTED_VERSIONS, UPGRADE_PROPOSAL_LEAD_VIEWS};
+use hotshot_task_impls::{events::HotShotEvent, helpers::broadcast_event};
 #[cfg(feature = "hotshot-testing")]
 use hotshot_types::traits::election::Membership;
 
 use hotshot_task::task::TaskRegistry;
 use hotshot_types::{boxed_sync, BoxSyncFuture};
 use hotshot_types::{
-    consensus::Consensus, data::Leaf, error::HotShotError, traits::node_implementation::NodeType,
+    consensus::Consensus, data::Leaf, error::HotShotError, simple_vote::UpgradeProposalData,
+    traits::node_implementation::NodeType,
 };
 use std::sync::Arc;
 
@@ -134,6 +136,73 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES> + 'static> SystemContextHandl
         self.hotshot.inner.networks.wait_for_networks_ready().await;
     }
 
+    /// Schedule a protocol upgrade.
+    ///
+    /// This node proposes the upgrade when it leads a view early enough before
+    /// `upgrade.new_version_first_block`, and votes for the same upgrade when other leaders propose
+    /// it. Once a certificate for the upgrade is decided, the network switches to
+    /// `upgrade.new_version` in `upgrade.new_version_first_block`.
+    ///
+    /// # Errors
+    /// Returns [`HotShotError::InvalidUpgrade`] if the old version is not the one the network runs,
+    /// the new version is the old one or one this node cannot speak, the new version starts before
+    /// the old version ends, or too soon for the upgrade to be decided in time.
+    pub async fn schedule_upgrade(
+        &self,
+        upgrade: UpgradeProposalData<TYPES>,
+    ) -> Result<(), HotShotError<TYPES>> {
+        let (cur_view, version) = {
+            let consensus = self.hotshot.inner.consensus.read().await;
+            (consensus.cur_view, consensus.version)
+        };
+        if upgrade.old_version != version {
+            return Err(HotShotError::InvalidUpgrade {
+                context: format!(
+                    "upgrade from version {:?}, but the network runs version {:?}",
+                    upgrade.old_version, version
+                ),
+            });
+        }
+        if upgrade.new_version == upgrade.old_version {
+            return Err(HotShotError::InvalidUpgrade {
+                context: format!("upgrade from version {:?} to itself", upgrade.old_version),
+            });
+        }
+        if !SUPPORTED_VERSIONS.contains(&upgrade.new_version) {
+            return Err(HotShotError::InvalidUpgrade {
+                context: format!("version {:?} is not supported", upgrade.new_version),
+            });
+        }
+        if upgrade.new_version_first_block <= upgrade.old_version_last_block {
+            return Err(HotShotError::InvalidUpgrade {
+                context: format!(
+                    "version {:?} starts in view {}, before version {:?} ends in view {}",
+                    upgrade.new_version,
+                    *upgrade.new_version_first_block,
+                    upgrade.old_version,
+                    *upgrade.old_version_last_block
+                ),
+            });
+        }
+        if *cur_view + UPGRADE_PROPOSAL_LEAD_VIEWS > *upgrade.new_version_first_block {
+            return Err(HotShotError::InvalidUpgrade {
+                context: format!(
+                    "version {:?} starts in view {}, less than {} views after the current view {}",
+                    upgrade.new_version,
+                    *upgrade.new_version_first_block,
+                    UPGRADE_PROPOSAL_LEAD_VIEWS,
+                    *cur_view
+                ),
+            });
+        }
+        broadcast_event(
+            HotShotEvent::UpgradeScheduled(upgrade),
+            &self.internal_event_stream.0,
+        )
+        .await;
+        Ok(())
+    }
+
     /// Shut down the the inner hotshot and wait until all background threads are closed.
     //     pub async fn shut_down(mut self) {
     //         self.registry.shutdown_all().await
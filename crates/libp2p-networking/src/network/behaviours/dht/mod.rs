@@ -1,6 +1,7 @@
 use std::{
     collections::{HashMap, HashSet, VecDeque},
     num::NonZeroUsize,
+    sync::Arc,
     task::Poll,
     time::Duration,
 };
@@ -13,8 +14,8 @@ use libp2p::kad::{store::RecordStore, Behaviour as KademliaBehaviour};
 use libp2p::{
     kad::{
         /* handler::KademliaHandlerIn, */ store::MemoryStore, BootstrapError, BootstrapOk,
-        GetClosestPeersOk, GetRecordOk, GetRecordResult, Mode, ProgressStep, PutRecordResult,
-        QueryId, QueryResult, Quorum, Record,
+        GetClosestPeersOk, GetRecordOk, GetRecordResult, InboundRequest, Mode, ProgressStep,
+        PutRecordResult, QueryId, QueryResult, Quorum, Record,
     },
     swarm::{NetworkBehaviour, THandlerInEvent, THandlerOutEvent, ToSwarm},
     Multiaddr,
@@ -33,6 +34,30 @@ lazy_static! {
 
 use super::exponential_backoff::ExponentialBackoff;
 
+/// Decides which records the DHT accepts, so that a record can only be published by whoever owns
+/// its key. Records received from other peers, whether stored for them or returned to our own
+/// queries, are dropped unless they are valid.
+pub trait RecordValidator: Send + Sync + 'static {
+    /// Whether `value` is a valid record for `key`
+    fn validate(&self, key: &[u8], value: &[u8]) -> bool;
+
+    /// Whether the valid record `new` should replace the record `existing` stored for `key`.
+    /// Conflicting records should be rejected.
+    fn supersedes(&self, key: &[u8], new: &[u8], existing: &[u8]) -> bool;
+
+    /// The newest of the valid `records` for `key`, i.e. the one no other record supersedes. Of
+    /// conflicting records, the first one is kept.
+    fn newest(&self, key: &[u8], records: Vec<Vec<u8>>) -> Option<Vec<u8>> {
+        records.into_iter().reduce(|newest, record| {
+            if self.supersedes(key, &record, &newest) {
+                record
+            } else {
+                newest
+            }
+        })
+    }
+}
+
 /// Behaviour wrapping libp2p's kademlia
 /// included:
 /// - publishing API
@@ -66,6 +91,8 @@ pub struct DHTBehaviour {
     pub peer_id: PeerId,
     /// replication factor
     pub replication_factor: NonZeroUsize,
+    /// validates the records received from other peers, if records are validated
+    record_validator: Option<Arc<dyn RecordValidator>>,
 }
 
 /// State of bootstrapping
@@ -121,6 +148,7 @@ impl DHTBehaviour {
         pid: PeerId,
         replication_factor: NonZeroUsize,
         _: Option<String>,
+        record_validator: Option<Arc<dyn RecordValidator>>,
     ) -> Self {
         // needed because otherwise we stay in client mode when testing locally
         // and don't publish keys stuff
@@ -149,6 +177,31 @@ impl DHTBehaviour {
             },
             in_progress_get_closest_peers: HashMap::default(),
             replication_factor,
+            record_validator,
+        }
+    }
+
+    /// Store a record another peer asked us to, if it is valid and supersedes the record we
+    /// already store for its key
+    fn store_inbound_record(&mut self, record: Record) {
+        let Some(validator) = self.record_validator.clone() else {
+            return;
+        };
+        if !validator.validate(record.key.as_ref(), &record.value) {
+            warn!("DHT: rejected invalid record for key {:?}", record.key);
+            return;
+        }
+        if let Some(existing) = self.kadem.store_mut().get(&record.key) {
+            if !validator.supersedes(record.key.as_ref(), &record.value, &existing.value) {
+                warn!(
+                    "DHT: rejected record conflicting with the one stored for key {:?}",
+                    record.key
+                );
+                return;
+            }
+        }
+        if self.kadem.store_mut().put(record).is_err() {
+            error!("Error putting inbound record into Record Store");
         }
     }
 
@@ -262,6 +315,14 @@ impl DHTBehaviour {
         let num = match self.in_progress_get_record_queries.get_mut(&id) {
             Some(query) => match record_results {
                 Ok(results) => match results {
+                    GetRecordOk::FoundRecord(record)
+                        if !self.record_validator.as_ref().map_or(true, |validator| {
+                            validator.validate(&query.key, &record.record.value)
+                        }) =>
+                    {
+                        warn!("Get DHT: dropped invalid record for key {:?}", query.key);
+                        0
+                    }
                     GetRecordOk::FoundRecord(record) => {
                         match query.records.entry(record.record.value) {
                             std::collections::hash_map::Entry::Occupied(mut o) => {
@@ -297,8 +358,10 @@ impl DHTBehaviour {
 
         // if the query has completed and we need to retry
         // or if the query has enoguh replicas to return to the client
-        // trigger retry or completion logic
-        if num >= NUM_REPLICATED_TO_TRUST || last {
+        // trigger retry or completion logic.
+        // Validated records can be trusted from a single replica, but a replica may hold an
+        // outdated version, so the query runs to completion to find the newest one.
+        if (num >= NUM_REPLICATED_TO_TRUST && self.record_validator.is_none()) || last {
             if let Some(KadGetQuery {
                 backoff,
                 progress,
@@ -318,12 +381,17 @@ impl DHTBehaviour {
                 // values is not handled because it can't be hit.
                 // We optimistically choose whichever record returns the most trusted entries first
 
-                // iterate through the records and find an value that has enough replicas
-                // to trust the value
-                if let Some((r, _)) = records
-                    .into_iter()
-                    .find(|(_, v)| *v >= NUM_REPLICATED_TO_TRUST)
-                {
+                // with a validator, every record we kept is valid, so return the newest one.
+                // Otherwise iterate through the records and find an value that has enough
+                // replicas to trust the value
+                let value = match &self.record_validator {
+                    Some(validator) => validator.newest(&key, records.into_keys().collect()),
+                    None => records
+                        .into_iter()
+                        .find(|(_, v)| *v >= NUM_REPLICATED_TO_TRUST)
+                        .map(|(r, _)| r),
+                };
+                if let Some(r) = value {
                     let record = Record {
                         key: key.into(),
                         value: r.clone(),
@@ -505,6 +573,16 @@ impl DHTBehaviour {
             KademliaEvent::UnroutablePeer { peer } => {
                 info!("on peer {:?} have unroutable peer {:?}", self.peer_id, peer);
             }
+            // only received when records are validated, as kademlia then leaves storing them to us
+            KademliaEvent::InboundRequest {
+                request:
+                    InboundRequest::PutRecord {
+                        record: Some(record),
+                        ..
+                    },
+            } => {
+                self.store_inbound_record(record);
+            }
             KademliaEvent::InboundRequest { request: _r } => {}
             KademliaEvent::RoutingUpdated {
                 peer: _,
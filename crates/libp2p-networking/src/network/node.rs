@@ -42,7 +42,7 @@ use libp2p::{
         Info as IdentifyInfo,
     },
     identity::Keypair,
-    kad::{store::MemoryStore, Behaviour, Config},
+    kad::{store::MemoryStore, Behaviour, Config, StoreInserts},
     request_response::{
         Behaviour as RequestResponse, Config as RequestResponseConfig, ProtocolSupport,
     },
@@ -255,6 +255,10 @@ impl NetworkNode {
                 .set_provider_publication_interval(Some(record_republication_interval))
                 .set_publication_interval(Some(record_republication_interval))
                 .set_record_ttl(ttl);
+            // validated records are only stored once the validator accepts them
+            if config.record_validator.is_some() {
+                kconfig.set_record_filtering(StoreInserts::FilterBoth);
+            }
 
             // allowing panic here because something is very wrong if this fales
             #[allow(clippy::panic)]
@@ -287,6 +291,7 @@ impl NetworkNode {
                         .replication_factor
                         .unwrap_or_else(|| NonZeroUsize::new(4).unwrap()),
                     config.dht_cache_location.clone(),
+                    config.record_validator.clone(),
                 ),
                 identify,
                 DMBehaviour::new(request_response),
@@ -1,7 +1,9 @@
 use crate::network::NetworkNodeType;
 use libp2p::{identity::Keypair, Multiaddr};
 use libp2p_identity::PeerId;
-use std::{collections::HashSet, num::NonZeroUsize, time::Duration};
+use std::{collections::HashSet, num::NonZeroUsize, sync::Arc, time::Duration};
+
+use crate::network::behaviours::dht::RecordValidator;
 
 /// replication factor for kademlia
 pub const DEFAULT_REPLICATION_FACTOR: Option<NonZeroUsize> = NonZeroUsize::new(20);
@@ -28,6 +30,11 @@ pub struct NetworkNodeConfig {
     #[builder(default = "None")]
     pub dht_cache_location: Option<String>,
 
+    /// location of the latest version of the records this node publishes to the dht, default is
+    /// None
+    #[builder(default = "None")]
+    pub record_version_location: Option<String>,
+
     #[builder(default)]
     /// parameters for gossipsub mesh network
     pub mesh_params: Option<MeshParams>,
@@ -40,6 +47,10 @@ pub struct NetworkNodeConfig {
     /// expiratiry for records in DHT
     #[builder(default)]
     pub ttl: Option<Duration>,
+    /// validator of the records stored in the DHT, any record is accepted if None
+    #[builder(setter(into, strip_option), default)]
+    #[debug(skip)]
+    pub record_validator: Option<Arc<dyn RecordValidator>>,
 }
 
 /// NOTE: `mesh_outbound_min <= mesh_n_low <= mesh_n <= mesh_n_high`
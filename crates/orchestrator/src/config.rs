@@ -457,6 +457,15 @@ pub struct HotShotConfigFile<KEY: SignatureKey> {
     pub propose_min_round_time: Duration,
     /// The maximum amount of time a leader can wait to start a round
     pub propose_max_round_time: Duration,
+    /// Path to the trusted KZG setup for VID
+    #[serde(default)]
+    pub vid_srs_path: Option<PathBuf>,
+    /// Whether nodes sign their messages and drop unsigned ones
+    #[serde(default)]
+    pub authenticate_messages: bool,
+    /// The number of views to keep decided leaves for, or the whole chain if unset
+    #[serde(default)]
+    pub decided_view_retention: Option<u64>,
 }
 
 /// Holds configuration for a validator node
@@ -529,6 +538,9 @@ impl<KEY: SignatureKey, E: ElectionConfig> From<HotShotConfigFile<KEY>> for HotS
             propose_min_round_time: val.propose_min_round_time,
             propose_max_round_time: val.propose_max_round_time,
             election_config: None,
+            vid_srs_path: val.vid_srs_path,
+            authenticate_messages: val.authenticate_messages,
+            decided_view_retention: val.decided_view_retention,
         }
     }
 }
@@ -581,6 +593,9 @@ impl<KEY: SignatureKey> Default for HotShotConfigFile<KEY> {
             propose_min_round_time: Duration::from_secs(0),
             propose_max_round_time: Duration::from_secs(10),
             num_bootstrap: 5,
+            vid_srs_path: None,
+            authenticate_messages: false,
+            decided_view_retention: None,
         }
     }
 }
@@ -0,0 +1,61 @@
+use async_trait::async_trait;
+use hotshot_types::{
+    light_client::{StateSignatureRequestBody, StateSignaturesBundle},
+    traits::state_relay::{StateRelay, StateRelayError},
+};
+use std::fmt::{self, Debug};
+use surf_disco::{error::ClientError, Client};
+use tide_disco::Url;
+
+/// Publishes the light client states signed by a node to a relay server
+#[derive(Clone)]
+pub struct StateRelayClient {
+    /// The address of the relay server
+    url: Url,
+    /// the client
+    client: Client<ClientError>,
+}
+
+impl StateRelayClient {
+    /// Creates the client that will connect to the relay server
+    #[must_use]
+    pub fn new(url: Url) -> Self {
+        let client = Client::<ClientError>::new(url.clone());
+        Self { url, client }
+    }
+
+    /// Get the complete bundle of the highest light client state from the relay server
+    /// # Errors
+    /// if no bundle is complete yet, or the relay server cannot be reached
+    pub async fn get_latest_bundle(&self) -> Result<StateSignaturesBundle, ClientError> {
+        self.client
+            .get::<StateSignaturesBundle>("api/bundle/latest")
+            .send()
+            .await
+    }
+}
+
+impl Debug for StateRelayClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StateRelayClient")
+            .field("url", &self.url)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl StateRelay for StateRelayClient {
+    async fn publish(&self, request: StateSignatureRequestBody) -> Result<(), StateRelayError> {
+        self.client
+            .post::<()>("api/signature")
+            .body_binary(&request)
+            .map_err(|e| StateRelayError::Delivery {
+                context: e.to_string(),
+            })?
+            .send()
+            .await
+            .map_err(|e| StateRelayError::Delivery {
+                context: e.to_string(),
+            })
+    }
+}
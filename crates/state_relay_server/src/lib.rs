@@ -0,0 +1,299 @@
+//! Relay server collecting the light client states signed by `HotShot` nodes, and serving the
+//! states signed by enough stake to a light client prover
+
+/// The client nodes publish their signed states with
+pub mod client;
+
+use async_lock::RwLock;
+use ethereum_types::U256;
+use futures::FutureExt;
+use hotshot_stake_table::vec_based::StakeTable;
+use hotshot_types::{
+    light_client::{
+        CircuitField, LightClientState, StateSignatureRequestBody, StateSignaturesBundle,
+        StateVerKey,
+    },
+    signature_key::BLSPubKey,
+    traits::stake_table::{SnapshotVersion, StakeTableError, StakeTableScheme},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    io::ErrorKind,
+};
+use tide_disco::{
+    api::ApiError,
+    error::ServerError,
+    method::{ReadState, WriteState},
+    Api, App, StatusCode, Url,
+};
+
+/// The number of complete bundles the relay keeps, starting from the highest block height
+pub const MAX_COMPLETE_BUNDLES: usize = 1000;
+
+/// How many block heights past the highest complete bundle the relay collects signatures for.
+/// Until a bundle is complete, this is the number of heights it collects signatures for.
+pub const PENDING_HEIGHT_WINDOW: usize = 100;
+
+/// The number of different states signed at the same block height the relay collects signatures
+/// for
+pub const MAX_STATES_PER_HEIGHT: usize = 4;
+
+/// The stake table of the light client, keyed by the BLS key and carrying the Schnorr key of
+/// each node
+pub type LightClientStakeTable = StakeTable<BLSPubKey, StateVerKey, CircuitField>;
+
+/// The state of the relay server
+#[derive(Debug)]
+pub struct StateRelayServerState {
+    /// The stake of each Schnorr key in the stake table
+    stake_table: HashMap<StateVerKey, U256>,
+    /// Commitment to the stake table, which every state must be signed along with
+    stake_table_comm: (CircuitField, CircuitField, CircuitField),
+    /// The stake the signers of a state need to hold for its bundle to be complete
+    threshold: U256,
+    /// Bundles short of the threshold, keyed by block height. Nodes may sign different states at
+    /// the same height, so each height may have several.
+    pending_bundles: BTreeMap<usize, HashMap<LightClientState, StateSignaturesBundle>>,
+    /// Complete bundles, keyed by block height
+    complete_bundles: BTreeMap<usize, StateSignaturesBundle>,
+}
+
+impl StateRelayServerState {
+    /// Create the state of a relay collecting signatures from the given version of the stake table.
+    ///
+    /// A bundle is complete once its signers hold more than two thirds of the stake.
+    ///
+    /// # Errors
+    /// Returns an error if the stake table does not support committing to `version`
+    pub fn new(
+        stake_table: &LightClientStakeTable,
+        version: SnapshotVersion,
+    ) -> Result<Self, StakeTableError> {
+        let stake_table_comm = stake_table.commitment(version)?;
+        let total_stake = stake_table.total_stake(version)?;
+        let stake_table = stake_table
+            .try_iter(version)?
+            .filter(|(_, amount, _)| !amount.is_zero())
+            .map(|(_, amount, schnorr_key)| (schnorr_key, amount))
+            .collect();
+        Ok(Self {
+            stake_table,
+            stake_table_comm,
+            threshold: total_stake * U256::from(2) / U256::from(3) + U256::one(),
+            pending_bundles: BTreeMap::new(),
+            complete_bundles: BTreeMap::new(),
+        })
+    }
+
+    /// The stake the signers of a state need to hold for its bundle to be complete
+    #[must_use]
+    pub fn threshold(&self) -> U256 {
+        self.threshold
+    }
+
+    /// Add a signature to the bundle of its state, completing the bundle if its signers now hold
+    /// enough stake
+    ///
+    /// # Errors
+    /// Returns an error if the state is too far ahead of the highest complete bundle, or too many
+    /// other states are signed at its height
+    fn add_signature(
+        &mut self,
+        request: StateSignatureRequestBody,
+        stake: U256,
+    ) -> Result<(), ServerError> {
+        let height = request.state.block_height;
+        // Late signatures of a complete state are still collected.
+        if let Some(bundle) = self.complete_bundles.get_mut(&height) {
+            if bundle.state == request.state
+                && bundle
+                    .signatures
+                    .insert(request.key, request.signature)
+                    .is_none()
+            {
+                bundle.accumulated_weight += stake;
+            }
+            return Ok(());
+        }
+
+        match self.complete_bundles.last_key_value() {
+            Some((&latest_height, _)) => {
+                if height > latest_height.saturating_add(PENDING_HEIGHT_WINDOW) {
+                    return Err(ServerError {
+                        status: StatusCode::BadRequest,
+                        message: format!(
+                            "State at height {height} is too far ahead of the latest complete state at height {latest_height}"
+                        ),
+                    });
+                }
+            }
+            // Until a state is complete, we do not know which heights are current, so we only
+            // keep the highest ones.
+            None => {
+                if !self.pending_bundles.contains_key(&height)
+                    && self.pending_bundles.len() >= PENDING_HEIGHT_WINDOW
+                {
+                    if self
+                        .pending_bundles
+                        .first_key_value()
+                        .is_some_and(|(&lowest_height, _)| height < lowest_height)
+                    {
+                        return Err(ServerError {
+                            status: StatusCode::BadRequest,
+                            message: format!("State at height {height} is too old"),
+                        });
+                    }
+                    self.pending_bundles.pop_first();
+                }
+            }
+        }
+
+        let states = self.pending_bundles.entry(height).or_default();
+        if !states.contains_key(&request.state) && states.len() >= MAX_STATES_PER_HEIGHT {
+            return Err(ServerError {
+                status: StatusCode::BadRequest,
+                message: format!("Too many different states are signed at height {height}"),
+            });
+        }
+        let bundle = states
+            .entry(request.state.clone())
+            .or_insert_with(|| StateSignaturesBundle {
+                state: request.state,
+                signatures: HashMap::new(),
+                accumulated_weight: U256::zero(),
+            });
+        if bundle
+            .signatures
+            .insert(request.key, request.signature)
+            .is_some()
+        {
+            return Ok(());
+        }
+        bundle.accumulated_weight += stake;
+        if bundle.accumulated_weight < self.threshold {
+            return Ok(());
+        }
+
+        let bundle = bundle.clone();
+        tracing::info!("Light client state at height {} is complete", height);
+        // Nothing at or below a complete height can be completed anymore.
+        self.pending_bundles = self.pending_bundles.split_off(&(height + 1));
+        self.complete_bundles.insert(height, bundle);
+        while self.complete_bundles.len() > MAX_COMPLETE_BUNDLES {
+            self.complete_bundles.pop_first();
+        }
+        Ok(())
+    }
+}
+
+/// An api exposed by the relay server
+pub trait StateRelayServerApi {
+    /// post endpoint for a signed light client state
+    /// # Errors
+    /// if the signer is not in the stake table, the state or its signature is invalid, or the
+    /// relay does not collect signatures for the state
+    fn post_signature(&mut self, request: StateSignatureRequestBody) -> Result<(), ServerError>;
+    /// get endpoint for the complete bundle of the highest state
+    /// # Errors
+    /// if no bundle is complete yet
+    fn get_latest_bundle(&self) -> Result<StateSignaturesBundle, ServerError>;
+    /// get endpoint for the complete bundle of the state at a block height
+    /// # Errors
+    /// if the bundle at `block_height` is not complete, or was pruned
+    fn get_bundle(&self, block_height: usize) -> Result<StateSignaturesBundle, ServerError>;
+}
+
+impl StateRelayServerApi for StateRelayServerState {
+    fn post_signature(&mut self, request: StateSignatureRequestBody) -> Result<(), ServerError> {
+        let Some(stake) = self.stake_table.get(&request.key).copied() else {
+            return Err(ServerError {
+                status: StatusCode::Unauthorized,
+                message: "Signer is not in the stake table".to_string(),
+            });
+        };
+        if request.state.stake_table_comm != self.stake_table_comm {
+            return Err(ServerError {
+                status: StatusCode::BadRequest,
+                message: "State commits to a different stake table".to_string(),
+            });
+        }
+        if !request.verify() {
+            return Err(ServerError {
+                status: StatusCode::BadRequest,
+                message: "Invalid signature of the state".to_string(),
+            });
+        }
+        self.add_signature(request, stake)
+    }
+
+    fn get_latest_bundle(&self) -> Result<StateSignaturesBundle, ServerError> {
+        self.complete_bundles
+            .last_key_value()
+            .map(|(_, bundle)| bundle.clone())
+            .ok_or_else(|| ServerError {
+                status: StatusCode::NotFound,
+                message: "No state has been signed by enough stake yet".to_string(),
+            })
+    }
+
+    fn get_bundle(&self, block_height: usize) -> Result<StateSignaturesBundle, ServerError> {
+        self.complete_bundles
+            .get(&block_height)
+            .cloned()
+            .ok_or_else(|| ServerError {
+                status: StatusCode::NotFound,
+                message: format!(
+                    "The state at height {block_height} is not signed by enough stake"
+                ),
+            })
+    }
+}
+
+/// Sets up all API routes
+fn define_api<State>() -> Result<Api<State, ServerError>, ApiError>
+where
+    State: 'static + Send + Sync + ReadState + WriteState,
+    <State as ReadState>::State: Send + Sync + StateRelayServerApi,
+{
+    let api_toml = toml::from_str::<toml::Value>(include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/api.toml"
+    )))
+    .expect("API file is not valid toml");
+    let mut api = Api::<State, ServerError>::new(api_toml)?;
+    api.post("postsignature", |req, state| {
+        async move {
+            let request = req.body_auto::<StateSignatureRequestBody>()?;
+            state.post_signature(request)
+        }
+        .boxed()
+    })?
+    .get("getlatestbundle", |_req, state| {
+        async move { state.get_latest_bundle() }.boxed()
+    })?
+    .get("getbundle", |req, state| {
+        async move {
+            let block_height = req.integer_param("block_height")?;
+            state.get_bundle(block_height)
+        }
+        .boxed()
+    })?;
+    Ok(api)
+}
+
+/// Runs the relay server
+/// # Errors
+/// This errors if tide disco runs into an issue during serving
+/// # Panics
+/// This panics if unable to register the api with tide disco
+pub async fn run_relay_server(state: StateRelayServerState, url: Url) -> io::Result<()> {
+    let relay_api =
+        define_api().map_err(|_e| io::Error::new(ErrorKind::Other, "Failed to define api"))?;
+
+    let mut app = App::<RwLock<StateRelayServerState>, ServerError>::with_state(RwLock::new(state));
+    app.register_module("api", relay_api)
+        .expect("Error registering api");
+    tracing::info!("State relay server listening on {:?}", url);
+    app.serve(url).await
+}
@@ -1,6 +1,6 @@
 use crate::{
     events::{HotShotEvent, HotShotTaskCompleted},
-    helpers::{broadcast_event, cancel_task},
+    helpers::{broadcast_event, cancel_task, record_vote},
     vote::{create_vote_accumulator, AccumulatorInfo, VoteCollectionTaskState},
 };
 use async_compatibility_layer::art::{async_sleep, async_spawn};
@@ -9,17 +9,20 @@ use async_lock::{RwLock, RwLockUpgradableReadGuard};
 use async_std::task::JoinHandle;
 use commit::Committable;
 use core::time::Duration;
-use hotshot_constants::Version;
-use hotshot_constants::LOOK_AHEAD;
+use hotshot_constants::{LOOK_AHEAD, SUPPORTED_VERSIONS, SYNC_VIEW_THRESHOLD};
 use hotshot_task::task::{Task, TaskState};
 
 use async_broadcast::Sender;
 
 use hotshot_types::{
     consensus::{Consensus, View},
-    data::{Leaf, QuorumProposal, VidCommitment, VidDisperse},
-    event::{Event, EventType},
-    message::{GeneralConsensusMessage, Proposal},
+    data::{
+        vid_scheme, Leaf, QuorumProposal, VidCommitment, VidDisperseShare, VidParams,
+        VidSchemeTrait,
+    },
+    error::HotShotError,
+    event::{Equivocation, Event, EventType},
+    message::{DataRequest, GeneralConsensusMessage, Proposal},
     simple_certificate::{QuorumCertificate, TimeoutCertificate, UpgradeCertificate},
     simple_vote::{QuorumData, QuorumVote, TimeoutData, TimeoutVote},
     traits::{
@@ -30,6 +33,7 @@ use hotshot_types::{
         node_implementation::{ConsensusTime, NodeImplementation, NodeType},
         signature_key::SignatureKey,
         states::ValidatedState,
+        storage::SafetyVote,
         BlockPayload,
     },
     utils::{Terminator, ViewInner},
@@ -128,18 +132,12 @@ pub struct ConsensusTaskState<
     /// most recent decided upgrade certificate
     pub decided_upgrade_cert: Option<UpgradeCertificate<TYPES>>,
 
-    /// The current version of the network.
-    /// Updated on view change based on the most recent decided upgrade certificate.
-    pub current_network_version: Version,
-
     /// Output events to application
     pub output_event_stream: async_broadcast::Sender<Event<TYPES>>,
 
-    /// All the VID shares we've received for current and future views.
-    /// In the future we will need a different struct similar to VidDisperse except
-    /// it stores only one share.
-    /// TODO <https://github.com/EspressoSystems/HotShot/issues/1732>
-    pub vid_shares: BTreeMap<TYPES::Time, Proposal<TYPES, VidDisperse<TYPES>>>,
+    /// The first validly signed quorum proposal we received for the current and future views, kept
+    /// to detect a leader proposing twice in the same view.
+    pub signed_proposals: BTreeMap<TYPES::Time, Proposal<TYPES, QuorumProposal<TYPES>>>,
 
     /// The most recent proposal we have, will correspond to the current view if Some()
     /// Will be none if the view advanced through timeout/view_sync
@@ -176,7 +174,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 let view = TYPES::Time::new(*proposal.view_number);
                 let justify_qc = proposal.justify_qc.clone();
                 let parent = if justify_qc.is_genesis {
-                    Some(Leaf::genesis(&consensus.instance_state))
+                    Leaf::genesis(&consensus.instance_state).ok()
                 } else {
                     consensus
                         .saved_leaves
@@ -217,6 +215,10 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 let message = GeneralConsensusMessage::<TYPES>::Vote(vote);
 
                 if let GeneralConsensusMessage::Vote(vote) = message {
+                    drop(consensus);
+                    if !record_vote(&self.api, SafetyVote::Quorum(vote.get_view_number())).await {
+                        return false;
+                    }
                     debug!(
                         "Sending vote to next quorum leader {:?}",
                         vote.get_view_number() + 1
@@ -232,7 +234,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
             }
 
             // Only vote if you has seen the VID share for this view
-            if let Some(_vid_share) = self.vid_shares.get(&proposal.view_number) {
+            if let Some(_vid_share) = consensus.saved_vid_shares.get(&proposal.view_number) {
             } else {
                 debug!(
                     "We have not seen the VID share for this view {:?} yet, so we cannot vote.",
@@ -248,7 +250,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 // TODO: do some of this logic without the vote token check, only do that when voting.
                 let justify_qc = proposal.justify_qc.clone();
                 let parent = if justify_qc.is_genesis {
-                    Some(Leaf::genesis(&consensus.instance_state))
+                    Leaf::genesis(&consensus.instance_state).ok()
                 } else {
                     consensus
                         .saved_leaves
@@ -308,6 +310,10 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 };
 
                 if let GeneralConsensusMessage::Vote(vote) = message {
+                    drop(consensus);
+                    if !record_vote(&self.api, SafetyVote::Quorum(vote.get_view_number())).await {
+                        return false;
+                    }
                     debug!(
                         "Sending vote to next quorum leader {:?}",
                         vote.get_view_number() + 1
@@ -354,6 +360,8 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 cancel_task(timeout_task).await;
             }
             self.cur_view = new_view;
+            // Proposals for older views are ignored, so we no longer need them to detect equivocation
+            self.signed_proposals = self.signed_proposals.split_off(&new_view);
 
             // Poll the future leader for lookahead
             let lookahead_view = new_view + LOOK_AHEAD;
@@ -448,6 +456,22 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                     return;
                 }
 
+                // Check whether the leader already signed a different proposal for this view
+                if let Some(earlier_proposal) = self.signed_proposals.get(&view) {
+                    let evidence =
+                        Equivocation::QuorumProposal(earlier_proposal.clone(), proposal.clone());
+                    if evidence.is_valid() {
+                        warn!("Leader {} proposed twice in view {}", sender, *view);
+                        self.api
+                            .send_event(Event {
+                                view_number: view,
+                                event: EventType::Equivocation { evidence },
+                            })
+                            .await;
+                        return;
+                    }
+                }
+
                 // Verify a timeout certificate exists and is valid
                 if proposal.data.justify_qc.get_view_number() != view - 1 {
                     let Some(timeout_cert) = proposal.data.timeout_certificate.clone() else {
@@ -507,7 +531,13 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 // Get the parent leaf and state.
                 let parent = if justify_qc.is_genesis {
                     // Send the `Decide` event for the genesis block if the justify QC is genesis.
-                    let leaf = Leaf::genesis(&consensus.instance_state);
+                    let leaf = match Leaf::genesis(&consensus.instance_state) {
+                        Ok(leaf) => leaf,
+                        Err(e) => {
+                            error!("Failed to build the genesis leaf: {}", e);
+                            return;
+                        }
+                    };
                     broadcast_event(
                         Event {
                             view_number: TYPES::Time::genesis(),
@@ -553,6 +583,19 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                         "Proposal's parent missing from storage with commitment: {:?}",
                         justify_qc.get_data().leaf_commit
                     );
+                    // The leader built on the parent, so it should be able to send it to us. If we
+                    // are far behind, sync the decided chain instead of walking back leaf by leaf.
+                    let event = if justify_qc.get_view_number()
+                        > consensus.last_decided_view + SYNC_VIEW_THRESHOLD
+                    {
+                        HotShotEvent::SyncStart(sender.clone())
+                    } else {
+                        HotShotEvent::LeafMissing(
+                            DataRequest::Leaf(justify_qc.get_data().leaf_commit),
+                            sender.clone(),
+                        )
+                    };
+                    broadcast_event(event, &event_stream).await;
                     let leaf = Leaf {
                         view_number: view,
                         justify_qc: justify_qc.clone(),
@@ -585,6 +628,14 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
 
                     drop(consensus);
 
+                    // Persist the leaf without holding the consensus lock, as it commits the storage.
+                    if let Err(e) = self.api.store_undecided_leaf(leaf).await {
+                        error!(
+                            "Could not insert undecided leaf into the storage API: {:?}",
+                            e
+                        );
+                    }
+
                     if liveness_check {
                         self.current_proposal = Some(proposal.data.clone());
                         let new_view = proposal.data.view_number + 1;
@@ -636,6 +687,9 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                     error!(?proposal.signature, "Could not verify proposal.");
                     return;
                 }
+                self.signed_proposals
+                    .entry(view)
+                    .or_insert_with(|| proposal.clone());
                 // Create a positive vote if either liveness or safety check
                 // passes.
 
@@ -691,6 +745,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 let mut new_decide_qc = None;
                 let mut leaf_views = Vec::new();
                 let mut leafs_decided = Vec::new();
+                let mut missing_payloads = Vec::new();
                 let mut included_txns = HashSet::new();
                 let old_anchor_view = consensus.last_decided_view;
                 let parent_view = leaf.justify_qc.get_view_number();
@@ -731,8 +786,16 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                                         .set(usize::try_from(leaf.get_height()).unwrap_or(0));
                                 }
                                 if let Some(ref upgrade_cert) = proposal.data.upgrade_certificate {
-                                    info!("Updating consensus state with decided upgrade certificate: {:?}", upgrade_cert);
-                                    self.decided_upgrade_cert = Some(upgrade_cert.clone());
+                                    if upgrade_cert.data.old_version == consensus.version {
+                                        if SUPPORTED_VERSIONS.contains(&upgrade_cert.data.new_version) {
+                                            info!("Updating consensus state with decided upgrade certificate: {:?}", upgrade_cert);
+                                        } else {
+                                            error!("Decided an upgrade to version {:?}, which we cannot speak, so we will halt in view {}", upgrade_cert.data.new_version, *upgrade_cert.data.new_version_first_block);
+                                        }
+                                        self.decided_upgrade_cert = Some(upgrade_cert.clone());
+                                    } else {
+                                        warn!("Ignoring decided upgrade certificate from version {:?}, we are on version {:?}", upgrade_cert.data.old_version, consensus.version);
+                                    }
                                 }
                                 // If the block payload is available for this leaf, include it in
                                 // the leaf chain that we send to the client.
@@ -747,10 +810,13 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                                     leaf.fill_block_payload_unchecked(payload);
                                 }
 
-                                let vid = self
-                                    .vid_shares
+                                let vid = consensus
+                                    .saved_vid_shares
                                     .get(&leaf.get_view_number())
                                     .map(|vid_proposal| vid_proposal.data.clone());
+                                if leaf.block_payload.is_none() {
+                                    missing_payloads.push(leaf.clone());
+                                }
 
                                 leaf_views.push((leaf.clone(), vid));
                                 leafs_decided.push(leaf.clone());
@@ -793,12 +859,25 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                     },
                 );
                 consensus.saved_leaves.insert(leaf.commit(), leaf.clone());
+                // The proposal's justify QC certifies its parent, which we are now locked on. It is
+                // persisted once the consensus lock is released, before we vote.
+                let mut new_locked_qc = None;
                 if new_commit_reached {
                     consensus.locked_view = new_locked_view;
+                    new_locked_qc = Some(justify_qc.clone());
                 }
+                let mut new_decided_leaves = None;
                 #[allow(clippy::cast_precision_loss)]
                 if new_decide_reached {
-                    broadcast_event(HotShotEvent::LeafDecided(leafs_decided), &event_stream).await;
+                    broadcast_event(
+                        HotShotEvent::LeafDecided(leafs_decided.clone()),
+                        &event_stream,
+                    )
+                    .await;
+                    // Recover the payloads we never received from the VID shares of our peers.
+                    for leaf in missing_payloads {
+                        broadcast_event(HotShotEvent::PayloadMissing(leaf), &event_stream).await;
+                    }
                     let decide_sent = broadcast_event(
                         Event {
                             view_number: consensus.last_decided_view,
@@ -814,8 +893,11 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                     consensus
                         .collect_garbage(old_anchor_view, new_anchor_view)
                         .await;
-                    self.vid_shares = self.vid_shares.split_off(&new_anchor_view);
                     consensus.last_decided_view = new_anchor_view;
+                    // Memberships whose committee changes over time move to the new anchor's epoch.
+                    self.quorum_membership.set_decided_view(new_anchor_view);
+                    self.timeout_membership.set_decided_view(new_anchor_view);
+                    self.committee_membership.set_decided_view(new_anchor_view);
                     consensus
                         .metrics
                         .last_decided_time
@@ -832,10 +914,9 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                         .number_of_views_per_decide_event
                         .add_point(cur_number_of_views_per_decide_event as f64);
 
-                    // We're only storing the last QC. We could store more but we're realistically only going to retrieve the last one.
-                    if let Err(e) = self.api.store_leaf(old_anchor_view, leaf).await {
-                        error!("Could not insert new anchor into the storage API: {:?}", e);
-                    }
+                    // Stored once the consensus lock is released, so we can serve the decided
+                    // chain to peers syncing it.
+                    new_decided_leaves = Some(leafs_decided);
 
                     debug!("Sending Decide for view {:?}", consensus.last_decided_view);
                     debug!("Decided txns len {:?}", included_txns_set.len());
@@ -853,6 +934,29 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 let qc = consensus.high_qc.clone();
 
                 drop(consensus);
+
+                // Persist without holding the consensus lock, as each of these commits the storage.
+                // The locked QC must be durable before `vote_if_able` sends a vote based on it.
+                if let Some(locked_qc) = new_locked_qc {
+                    if let Err(e) = self.api.store_locked_qc(locked_qc).await {
+                        error!("Could not store locked QC in the storage API: {:?}", e);
+                    }
+                }
+                if let Some(decided_leaves) = new_decided_leaves {
+                    if let Err(e) = self.api.store_decided_leaves(decided_leaves).await {
+                        error!(
+                            "Could not insert decided leaves into the storage API: {:?}",
+                            e
+                        );
+                    }
+                }
+                if let Err(e) = self.api.store_undecided_leaf(leaf).await {
+                    error!(
+                        "Could not insert undecided leaf into the storage API: {:?}",
+                        e
+                    );
+                }
+
                 if should_propose {
                     debug!(
                         "Attempting to publish proposal after voting; now in view: {}",
@@ -1042,6 +1146,12 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                     .await
                     .saved_da_certs
                     .insert(view, cert.clone());
+                if let Err(e) = self.api.store_da_cert(cert.clone()).await {
+                    error!(
+                        "Could not insert DA certificate into the storage API: {:?}",
+                        e
+                    );
+                }
 
                 if self.vote_if_able(&event_stream).await {
                     self.current_proposal = None;
@@ -1082,6 +1192,18 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                     return;
                 }
 
+                if disperse.data.recipient_key != self.public_key {
+                    warn!("Received a VID share intended for another node");
+                    return;
+                }
+
+                // The leader only signs the payload commitment, so check that the shares and the
+                // common data we got are consistent with it.
+                if !self.is_valid_vid_share(&disperse.data) {
+                    warn!("Invalid VID share for view {}", *view);
+                    return;
+                }
+
                 // stop polling for the received disperse after verifying it's valid
                 self.quorum_network
                     .inject_consensus_info(ConsensusIntentEvent::CancelPollForVIDDisperse(
@@ -1090,9 +1212,21 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                     .await;
 
                 // Add to the storage that we have received the VID disperse for a specific view
-                self.vid_shares.insert(view, disperse);
+                self.consensus
+                    .write()
+                    .await
+                    .saved_vid_shares
+                    .insert(view, disperse);
                 self.vote_if_able(&event_stream).await;
             }
+            HotShotEvent::Equivocation(evidence) => {
+                self.api
+                    .send_event(Event {
+                        view_number: evidence.get_view_number(),
+                        event: EventType::Equivocation { evidence },
+                    })
+                    .await;
+            }
             HotShotEvent::ViewChange(new_view) => {
                 debug!("View Change event for view {} in consensus task", *new_view);
 
@@ -1116,7 +1250,41 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 // we may need to upgrade the protocol version on a view change.
                 if let Some(ref cert) = self.decided_upgrade_cert {
                     if new_view >= cert.data.new_version_first_block {
-                        self.current_network_version = cert.data.new_version;
+                        // We cannot follow the network past the upgrade, so we stop rather than
+                        // keep running the old version on our own.
+                        if !SUPPORTED_VERSIONS.contains(&cert.data.new_version) {
+                            let error = HotShotError::InvalidUpgrade {
+                                context: format!(
+                                    "the network upgraded to version {:?} in view {}, which this node cannot speak",
+                                    cert.data.new_version, *cert.data.new_version_first_block
+                                ),
+                            };
+                            error!("Halting: {}", error);
+                            broadcast_event(
+                                Event {
+                                    view_number: new_view,
+                                    event: EventType::Error {
+                                        error: Arc::new(error),
+                                    },
+                                },
+                                &self.output_event_stream,
+                            )
+                            .await;
+                            broadcast_event(HotShotEvent::Shutdown, &event_stream).await;
+                            return;
+                        }
+                        let mut consensus = self.consensus.write().await;
+                        info!(
+                            "Switching from version {:?} to version {:?} in view {}",
+                            consensus.version, cert.data.new_version, *new_view
+                        );
+                        consensus.version = cert.data.new_version;
+                        drop(consensus);
+                        broadcast_event(
+                            HotShotEvent::VersionUpgrade(cert.data.new_version),
+                            &event_stream,
+                        )
+                        .await;
                         // Discard the old upgrade certificate, which is no longer relevant.
                         self.decided_upgrade_cert = None;
                     }
@@ -1165,6 +1333,9 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                     error!("Failed to sign TimeoutData!");
                     return;
                 };
+                if !record_vote(&self.api, SafetyVote::Timeout(vote.clone())).await {
+                    return;
+                }
 
                 broadcast_event(HotShotEvent::TimeoutVoteSend(vote), &event_stream).await;
                 debug!(
@@ -1213,6 +1384,33 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
         }
     }
 
+    /// Whether `share` holds as many shares as this node should get for its view, each consistent
+    /// with the common data and the payload commitment of the share.
+    fn is_valid_vid_share(&self, share: &VidDisperseShare<TYPES>) -> bool {
+        let view = share.view_number;
+        if VidParams::share_counts::<TYPES>(&self.quorum_membership, view).get(&self.public_key)
+            != Some(&share.shares.len())
+        {
+            return false;
+        }
+        let vid = match vid_scheme(VidParams::from_membership::<TYPES>(
+            &self.quorum_membership,
+            view,
+        )) {
+            Ok(vid) => vid,
+            Err(e) => {
+                error!("VID: {}", e);
+                return false;
+            }
+        };
+        share.shares.iter().all(|vid_share| {
+            matches!(
+                vid.verify_share(vid_share, &share.common, &share.payload_commitment),
+                Ok(Ok(()))
+            )
+        })
+    }
+
     /// Sends a proposal if possible from the high qc we have
     #[allow(clippy::too_many_lines)]
     pub async fn publish_proposal_if_able(
@@ -1377,6 +1575,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 | HotShotEvent::Timeout(_)
                 | HotShotEvent::TimeoutVoteRecv(_)
                 | HotShotEvent::VidDisperseRecv(..)
+                | HotShotEvent::Equivocation(_)
                 | HotShotEvent::Shutdown,
         )
     }
@@ -1,6 +1,6 @@
 use crate::{
     events::{HotShotEvent, HotShotTaskCompleted},
-    helpers::broadcast_event,
+    helpers::{broadcast_event, record_vote},
     vote::{create_vote_accumulator, AccumulatorInfo, VoteCollectionTaskState},
 };
 use async_broadcast::Sender;
@@ -9,7 +9,7 @@ use async_lock::RwLock;
 use hotshot_task::task::{Task, TaskState};
 use hotshot_types::{
     consensus::{Consensus, View},
-    data::DAProposal,
+    data::{DAProposal, VidParams},
     event::{Event, EventType},
     message::Proposal,
     simple_certificate::DACertificate,
@@ -21,6 +21,7 @@ use hotshot_types::{
         network::{ConnectedNetwork, ConsensusIntentEvent},
         node_implementation::{ConsensusTime, NodeImplementation, NodeType},
         signature_key::SignatureKey,
+        storage::SafetyVote,
     },
     utils::ViewInner,
     vote::HasViewNumber,
@@ -116,10 +117,16 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                     return None;
                 }
 
-                let payload_commitment = vid_commitment(
+                let payload_commitment = match vid_commitment(
                     &proposal.data.encoded_transactions,
-                    self.quorum_membership.total_nodes(),
-                );
+                    VidParams::from_membership::<TYPES>(&self.quorum_membership, view),
+                ) {
+                    Ok(payload_commitment) => payload_commitment,
+                    Err(e) => {
+                        error!("VID: {}", e);
+                        return None;
+                    }
+                };
                 let encoded_transactions_hash = Sha256::digest(&proposal.data.encoded_transactions);
 
                 // ED Is this the right leader?
@@ -168,6 +175,10 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 // ED Don't think this is necessary?
                 // self.cur_view = view;
 
+                if !record_vote(&self.api, SafetyVote::DA(view)).await {
+                    return None;
+                }
+
                 debug!("Sending vote to the DA leader {:?}", vote.get_view_number());
 
                 broadcast_event(HotShotEvent::DAVoteSend(vote), &event_stream).await;
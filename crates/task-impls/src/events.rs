@@ -1,16 +1,21 @@
 use crate::view_sync::ViewSyncPhase;
 
 use either::Either;
+use hotshot_constants::Version;
 use hotshot_types::{
-    data::{DAProposal, Leaf, QuorumProposal, UpgradeProposal, VidCommitment, VidDisperse},
-    message::Proposal,
+    data::{
+        DAProposal, Leaf, QuorumProposal, UpgradeProposal, VidCommitment, VidDisperse,
+        VidDisperseShare,
+    },
+    event::Equivocation,
+    message::{DataRequest, DataResponse, Proposal, SyncRequest, SyncResponse},
     simple_certificate::{
         DACertificate, QuorumCertificate, TimeoutCertificate, UpgradeCertificate,
         ViewSyncCommitCertificate2, ViewSyncFinalizeCertificate2, ViewSyncPreCommitCertificate2,
     },
     simple_vote::{
-        DAVote, QuorumVote, TimeoutVote, UpgradeVote, ViewSyncCommitVote, ViewSyncFinalizeVote,
-        ViewSyncPreCommitVote,
+        DAVote, QuorumVote, TimeoutVote, UpgradeProposalData, UpgradeVote, ViewSyncCommitVote,
+        ViewSyncFinalizeVote, ViewSyncPreCommitVote,
     },
     traits::{node_implementation::NodeType, BlockPayload},
 };
@@ -107,22 +112,91 @@ pub enum HotShotEvent<TYPES: NodeType> {
     BlockReady(VidDisperse<TYPES>, TYPES::Time),
     /// Event when consensus decided on a leaf
     LeafDecided(Vec<Leaf<TYPES>>),
-    /// Send VID shares to VID storage nodes; emitted by the DA leader
+    /// Send VID shares to VID storage nodes; emitted by the DA leader. The network task sends
+    /// each storage node only its own share.
     ///
     /// Like [`HotShotEvent::DAProposalSend`].
     VidDisperseSend(Proposal<TYPES, VidDisperse<TYPES>>, TYPES::SignatureKey),
-    /// Vid disperse data has been received from the network; handled by the DA task
+    /// Our VID share has been received from the network; handled by the consensus task
     ///
     /// Like [`HotShotEvent::DAProposalRecv`].
-    VidDisperseRecv(Proposal<TYPES, VidDisperse<TYPES>>, TYPES::SignatureKey),
+    VidDisperseRecv(
+        Proposal<TYPES, VidDisperseShare<TYPES>>,
+        TYPES::SignatureKey,
+    ),
     /// Upgrade proposal has been received from the network
     UpgradeProposalRecv(Proposal<TYPES, UpgradeProposal<TYPES>>, TYPES::SignatureKey),
-    /// Upgrade proposal has been sent to the network
-    UpgradeProposalSend(UpgradeProposal<TYPES>),
+    /// Send an upgrade proposal to the network; emitted by the upgrade task when we lead a view
+    /// in time to propose a scheduled upgrade
+    UpgradeProposalSend(Proposal<TYPES, UpgradeProposal<TYPES>>, TYPES::SignatureKey),
+    /// An operator scheduled an upgrade through the handle; handled by the upgrade task
+    UpgradeScheduled(UpgradeProposalData<TYPES>),
     /// Upgrade vote has been received from the network
     UpgradeVoteRecv(UpgradeVote<TYPES>),
     /// Upgrade vote has been sent to the network
     UpgradeVoteSend(UpgradeVote<TYPES>),
     /// Upgrade certificate has been sent to the network
     UpgradeCertificateFormed(UpgradeCertificate<TYPES>),
+    /// The first view of a decided upgrade has been reached, and messages we send from now on
+    /// use the new protocol version; emitted by the consensus task, handled by the network tasks
+    VersionUpgrade(Version),
+    /// Send a transaction submitted to this node to the DA committee; handled by the network task
+    TransactionSend(TYPES::Transaction, TYPES::SignatureKey),
+    /// A node signed two conflicting votes; emitted by the vote collection tasks and forwarded to
+    /// the application by the consensus task
+    Equivocation(Equivocation<TYPES>),
+    /// A leaf is missing from storage; emitted by the consensus task along with a peer that
+    /// should have it; handled by the request task
+    LeafMissing(DataRequest<TYPES>, TYPES::SignatureKey),
+    /// Send a request for missing data to a peer; emitted by the request task. Contains the
+    /// request, our key and the key of the peer
+    DataRequestSend(DataRequest<TYPES>, TYPES::SignatureKey, TYPES::SignatureKey),
+    /// A peer has requested data from us; handled by the request task
+    DataRequestRecv(DataRequest<TYPES>, TYPES::SignatureKey),
+    /// Send requested data to the requesting peer; emitted by the request task. Contains the
+    /// response, our key and the key of the requesting peer
+    DataResponseSend(
+        DataResponse<TYPES>,
+        TYPES::SignatureKey,
+        TYPES::SignatureKey,
+    ),
+    /// A peer has responded to one of our requests; handled by the request task
+    DataResponseRecv(DataResponse<TYPES>, TYPES::SignatureKey),
+    /// We are far behind the network; emitted by the consensus task along with a peer to sync
+    /// the decided chain from; handled by the sync task
+    SyncStart(TYPES::SignatureKey),
+    /// Send a request for a batch of the decided chain to a peer; emitted by the sync task.
+    /// Contains the request, our key and the key of the peer
+    SyncRequestSend(SyncRequest<TYPES>, TYPES::SignatureKey, TYPES::SignatureKey),
+    /// A peer has requested a batch of the decided chain from us; handled by the sync task
+    SyncRequestRecv(SyncRequest<TYPES>, TYPES::SignatureKey),
+    /// Send a batch of the decided chain to the requesting peer; emitted by the sync task.
+    /// Contains the response, our key and the key of the requesting peer
+    SyncResponseSend(
+        SyncResponse<TYPES>,
+        TYPES::SignatureKey,
+        TYPES::SignatureKey,
+    ),
+    /// A peer has sent us a batch of the decided chain; handled by the sync task
+    SyncResponseRecv(SyncResponse<TYPES>, TYPES::SignatureKey),
+    /// A leaf was decided without its block payload; emitted by the consensus task, handled by
+    /// the payload recovery task
+    PayloadMissing(Leaf<TYPES>),
+    /// Ask all peers for their VID share of the given view; emitted by the payload recovery task.
+    /// Contains the view and our key
+    VidShareRequestSend(TYPES::Time, TYPES::SignatureKey),
+    /// A peer has asked for our VID share of the given view; handled by the payload recovery task
+    VidShareRequestRecv(TYPES::Time, TYPES::SignatureKey),
+    /// Send our VID share to the requesting peer; emitted by the payload recovery task.
+    /// Contains the share, our key and the key of the requesting peer
+    VidShareResponseSend(
+        Proposal<TYPES, VidDisperseShare<TYPES>>,
+        TYPES::SignatureKey,
+        TYPES::SignatureKey,
+    ),
+    /// A peer has sent us its VID share; handled by the payload recovery task
+    VidShareResponseRecv(
+        Proposal<TYPES, VidDisperseShare<TYPES>>,
+        TYPES::SignatureKey,
+    ),
 }
@@ -1,6 +1,11 @@
 use async_broadcast::{SendError, Sender};
 #[cfg(async_executor_impl = "async-std")]
 use async_std::task::JoinHandle;
+use hotshot_types::traits::{
+    consensus_api::ConsensusApi,
+    node_implementation::{NodeImplementation, NodeType},
+    storage::SafetyVote,
+};
 #[cfg(async_executor_impl = "tokio")]
 use tokio::task::JoinHandle;
 
@@ -30,3 +35,31 @@ pub async fn broadcast_event<E: Clone + std::fmt::Debug>(event: E, sender: &Send
         }
     }
 }
+
+/// Check `vote` against the safety record of this node, and durably record it if it can be sent.
+///
+/// Returns `false` if the vote must not be sent, either because it contradicts a vote this node
+/// already sent, or because it could not be recorded.
+pub async fn record_vote<
+    TYPES: NodeType,
+    I: NodeImplementation<TYPES>,
+    A: ConsensusApi<TYPES, I>,
+>(
+    api: &A,
+    vote: SafetyVote<TYPES>,
+) -> bool {
+    match api.record_vote(vote.clone()).await {
+        Ok(true) => true,
+        Ok(false) => {
+            tracing::warn!(
+                "Not sending {:?}, it conflicts with a vote we already sent",
+                vote
+            );
+            false
+        }
+        Err(e) => {
+            tracing::error!("Could not record {:?} in storage: {:?}", vote, e);
+            false
+        }
+    }
+}
@@ -31,5 +31,17 @@ pub mod vote;
 /// Task for handling upgrades
 pub mod upgrade;
 
+/// Task for fetching missing leaves from peers and answering their requests
+pub mod request;
+
+/// Task for syncing the decided chain from peers when far behind, and serving it to peers
+pub mod sync;
+
+/// Task for recovering missing block payloads from the VID shares of peers
+pub mod recovery;
+
+/// Task for signing the light client state of every decided leaf
+pub mod state_signing;
+
 /// Helper functions used by any task
 pub mod helpers;
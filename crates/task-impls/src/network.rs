@@ -5,23 +5,26 @@ use crate::{
 use async_broadcast::Sender;
 use async_compatibility_layer::art::async_spawn;
 use either::Either::{self, Left, Right};
-use hotshot_constants::VERSION_0_1;
+use hotshot_constants::Version;
 use std::sync::Arc;
 
 use hotshot_task::task::{Task, TaskState};
 use hotshot_types::{
+    consensus::ConsensusMetricsValue,
+    data::{VidDisperse, VidDisperseShare},
     message::{
-        CommitteeConsensusMessage, GeneralConsensusMessage, Message, MessageKind, SequencingMessage,
+        CommitteeConsensusMessage, DataMessage, GeneralConsensusMessage, Message, MessageKind,
+        Proposal, RequestResponseMessage, SequencingMessage,
     },
     traits::{
         election::Membership,
-        network::{ConnectedNetwork, TransmitType, ViewMessage},
-        node_implementation::NodeType,
+        network::{ConnectedNetwork, ConsensusIntentEvent, TransmitType, ViewMessage},
+        node_implementation::{ConsensusTime, NodeType},
+        signature_key::SignatureKey,
     },
     vote::{HasViewNumber, Vote},
 };
-use tracing::error;
-use tracing::instrument;
+use tracing::{error, instrument, warn};
 
 /// quorum filter
 pub fn quorum_filter<TYPES: NodeType>(event: &HotShotEvent<TYPES>) -> bool {
@@ -33,6 +36,15 @@ pub fn quorum_filter<TYPES: NodeType>(event: &HotShotEvent<TYPES>) -> bool {
             | HotShotEvent::DACSend(_, _)
             | HotShotEvent::ViewChange(_)
             | HotShotEvent::TimeoutVoteSend(_)
+            | HotShotEvent::DataRequestSend(_, _, _)
+            | HotShotEvent::DataResponseSend(_, _, _)
+            | HotShotEvent::SyncRequestSend(_, _, _)
+            | HotShotEvent::SyncResponseSend(_, _, _)
+            | HotShotEvent::VidShareRequestSend(_, _)
+            | HotShotEvent::VidShareResponseSend(_, _, _)
+            | HotShotEvent::UpgradeProposalSend(_, _)
+            | HotShotEvent::UpgradeVoteSend(_)
+            | HotShotEvent::VersionUpgrade(_)
     )
 }
 
@@ -42,8 +54,10 @@ pub fn committee_filter<TYPES: NodeType>(event: &HotShotEvent<TYPES>) -> bool {
         event,
         HotShotEvent::DAProposalSend(_, _)
             | HotShotEvent::DAVoteSend(_)
+            | HotShotEvent::TransactionSend(_, _)
             | HotShotEvent::Shutdown
             | HotShotEvent::ViewChange(_)
+            | HotShotEvent::VersionUpgrade(_)
     )
 }
 
@@ -51,7 +65,10 @@ pub fn committee_filter<TYPES: NodeType>(event: &HotShotEvent<TYPES>) -> bool {
 pub fn vid_filter<TYPES: NodeType>(event: &HotShotEvent<TYPES>) -> bool {
     !matches!(
         event,
-        HotShotEvent::Shutdown | HotShotEvent::VidDisperseSend(_, _) | HotShotEvent::ViewChange(_)
+        HotShotEvent::Shutdown
+            | HotShotEvent::VidDisperseSend(_, _)
+            | HotShotEvent::ViewChange(_)
+            | HotShotEvent::VersionUpgrade(_)
     )
 }
 
@@ -67,6 +84,7 @@ pub fn view_sync_filter<TYPES: NodeType>(event: &HotShotEvent<TYPES>) -> bool {
             | HotShotEvent::ViewSyncFinalizeVoteSend(_)
             | HotShotEvent::Shutdown
             | HotShotEvent::ViewChange(_)
+            | HotShotEvent::VersionUpgrade(_)
     )
 }
 /// the network message task state
@@ -74,6 +92,11 @@ pub fn view_sync_filter<TYPES: NodeType>(event: &HotShotEvent<TYPES>) -> bool {
 pub struct NetworkMessageTaskState<TYPES: NodeType> {
     /// Sender to send internal events this task generates to other tasks
     pub event_stream: Sender<HotShotEvent<TYPES>>,
+    /// Whether to drop messages which are not signed by their sender. Messages with an invalid
+    /// signature are dropped either way.
+    pub authenticate_messages: bool,
+    /// Metrics counting the messages dropped
+    pub metrics: Arc<ConsensusMetricsValue>,
 }
 
 impl<TYPES: NodeType> TaskState for NetworkMessageTaskState<TYPES> {
@@ -98,11 +121,28 @@ impl<TYPES: NodeType> TaskState for NetworkMessageTaskState<TYPES> {
 }
 
 impl<TYPES: NodeType> NetworkMessageTaskState<TYPES> {
+    /// Whether a message can be trusted to come from its sender
+    fn is_authentic(&self, message: &Message<TYPES>) -> bool {
+        if message.signature.is_some() {
+            message.has_valid_signature()
+        } else {
+            !self.authenticate_messages
+        }
+    }
+
     /// Handle the message.
     pub async fn handle_messages(&mut self, messages: Vec<Message<TYPES>>) {
         // We will send only one event for a vector of transactions.
         let mut transactions = Vec::new();
         for message in messages {
+            if !self.is_authentic(&message) {
+                warn!(
+                    "Dropping a message which is not signed by its sender {:?}",
+                    message.sender
+                );
+                self.metrics.rejected_messages.add(1);
+                continue;
+            }
             let sender = message.sender;
             match message.kind {
                 MessageKind::Consensus(consensus_message) => {
@@ -166,10 +206,33 @@ impl<TYPES: NodeType> NetworkMessageTaskState<TYPES> {
                     broadcast_event(event, &self.event_stream).await;
                 }
                 MessageKind::Data(message) => match message {
-                    hotshot_types::message::DataMessage::SubmitTransaction(transaction, _) => {
+                    DataMessage::SubmitTransaction(transaction, _) => {
                         transactions.push(transaction);
                     }
                 },
+                MessageKind::RequestResponse(message) => {
+                    let event = match message {
+                        RequestResponseMessage::Request(request, _) => {
+                            HotShotEvent::DataRequestRecv(request, sender)
+                        }
+                        RequestResponseMessage::Response(response) => {
+                            HotShotEvent::DataResponseRecv(response, sender)
+                        }
+                        RequestResponseMessage::SyncRequest(request) => {
+                            HotShotEvent::SyncRequestRecv(request, sender)
+                        }
+                        RequestResponseMessage::SyncResponse(response) => {
+                            HotShotEvent::SyncResponseRecv(response, sender)
+                        }
+                        RequestResponseMessage::VidShareRequest(view) => {
+                            HotShotEvent::VidShareRequestRecv(view, sender)
+                        }
+                        RequestResponseMessage::VidShareResponse(share) => {
+                            HotShotEvent::VidShareResponseRecv(share, sender)
+                        }
+                    };
+                    broadcast_event(event, &self.event_stream).await;
+                }
             };
         }
         if !transactions.is_empty() {
@@ -191,6 +254,10 @@ pub struct NetworkEventTaskState<
     pub channel: Arc<COMMCHANNEL>,
     /// view number
     pub view: TYPES::Time,
+    /// The protocol version of the messages we send
+    pub version: Version,
+    /// The key to sign the messages we send with, if messages are authenticated
+    pub private_key: Option<<TYPES::SignatureKey as SignatureKey>::PrivateKey>,
     /// membership for the channel
     pub membership: TYPES::Membership,
     // TODO ED Need to add exchange so we can get the recipient key and our own key?
@@ -261,14 +328,10 @@ impl<TYPES: NodeType, COMMCHANNEL: ConnectedNetwork<Message<TYPES>, TYPES::Signa
                 TransmitType::Direct,
                 Some(membership.get_leader(vote.get_view_number() + 1)),
             ),
-            HotShotEvent::VidDisperseSend(proposal, sender) => (
-                sender,
-                MessageKind::<TYPES>::from_consensus_message(SequencingMessage(Right(
-                    CommitteeConsensusMessage::VidDisperseMsg(proposal),
-                ))), // TODO not a CommitteeConsensusMessage https://github.com/EspressoSystems/HotShot/issues/1696
-                TransmitType::Broadcast, // TODO not a broadcast https://github.com/EspressoSystems/HotShot/issues/1696
-                None,
-            ),
+            HotShotEvent::VidDisperseSend(proposal, sender) => {
+                self.vid_disperse_send(proposal, sender);
+                return None;
+            }
             HotShotEvent::DAProposalSend(proposal, sender) => (
                 sender,
                 MessageKind::<TYPES>::from_consensus_message(SequencingMessage(Right(
@@ -343,6 +406,22 @@ impl<TYPES: NodeType, COMMCHANNEL: ConnectedNetwork<Message<TYPES>, TYPES::Signa
                 TransmitType::Broadcast,
                 None,
             ),
+            HotShotEvent::UpgradeProposalSend(proposal, sender) => (
+                sender,
+                MessageKind::<TYPES>::from_consensus_message(SequencingMessage(Left(
+                    GeneralConsensusMessage::UpgradeProposal(proposal),
+                ))),
+                TransmitType::Broadcast,
+                None,
+            ),
+            HotShotEvent::UpgradeVoteSend(vote) => (
+                vote.get_signing_key(),
+                MessageKind::<TYPES>::from_consensus_message(SequencingMessage(Left(
+                    GeneralConsensusMessage::UpgradeVote(vote.clone()),
+                ))),
+                TransmitType::Direct,
+                Some(membership.get_leader(vote.get_view_number())),
+            ),
             HotShotEvent::TimeoutVoteSend(vote) => (
                 vote.get_signing_key(),
                 MessageKind::<TYPES>::from_consensus_message(SequencingMessage(Left(
@@ -351,10 +430,73 @@ impl<TYPES: NodeType, COMMCHANNEL: ConnectedNetwork<Message<TYPES>, TYPES::Signa
                 TransmitType::Direct,
                 Some(membership.get_leader(vote.get_view_number() + 1)),
             ),
+            HotShotEvent::DataRequestSend(request, sender, recipient) => (
+                sender,
+                MessageKind::RequestResponse(RequestResponseMessage::Request(request, self.view)),
+                TransmitType::Direct,
+                Some(recipient),
+            ),
+            HotShotEvent::DataResponseSend(response, sender, recipient) => (
+                sender,
+                MessageKind::RequestResponse(RequestResponseMessage::Response(response)),
+                TransmitType::Direct,
+                Some(recipient),
+            ),
+            HotShotEvent::SyncRequestSend(request, sender, recipient) => (
+                sender,
+                MessageKind::RequestResponse(RequestResponseMessage::SyncRequest(request)),
+                TransmitType::Direct,
+                Some(recipient),
+            ),
+            HotShotEvent::SyncResponseSend(response, sender, recipient) => (
+                sender,
+                MessageKind::RequestResponse(RequestResponseMessage::SyncResponse(response)),
+                TransmitType::Direct,
+                Some(recipient),
+            ),
+            HotShotEvent::VidShareRequestSend(view, sender) => (
+                sender,
+                MessageKind::RequestResponse(RequestResponseMessage::VidShareRequest(view)),
+                TransmitType::Broadcast,
+                None,
+            ),
+            HotShotEvent::VidShareResponseSend(share, sender, recipient) => (
+                sender,
+                MessageKind::RequestResponse(RequestResponseMessage::VidShareResponse(share)),
+                TransmitType::Direct,
+                Some(recipient),
+            ),
+            HotShotEvent::TransactionSend(transaction, sender) => (
+                sender,
+                MessageKind::from(DataMessage::SubmitTransaction(
+                    transaction,
+                    TYPES::Time::new(0),
+                )),
+                TransmitType::Broadcast,
+                None,
+            ),
             HotShotEvent::ViewChange(view) => {
+                // Let the network follow the committee into a new epoch. The committees are only
+                // compared when the views answer for different memberships, which is only the
+                // case across an epoch boundary.
+                if membership.for_view(self.view) != membership.for_view(view) {
+                    let previous = membership.get_committee(self.view);
+                    let current = membership.get_committee(view);
+                    if previous != current {
+                        self.channel
+                            .inject_consensus_info(ConsensusIntentEvent::CommitteeChanged(
+                                *view, previous, current,
+                            ))
+                            .await;
+                    }
+                }
                 self.view = view;
                 return None;
             }
+            HotShotEvent::VersionUpgrade(version) => {
+                self.version = version;
+                return None;
+            }
             HotShotEvent::Shutdown => {
                 error!("Networking task shutting down");
                 return Some(HotShotTaskCompleted);
@@ -364,11 +506,7 @@ impl<TYPES: NodeType, COMMCHANNEL: ConnectedNetwork<Message<TYPES>, TYPES::Signa
                 return None;
             }
         };
-        let message = Message {
-            version: VERSION_0_1,
-            sender,
-            kind: message_kind,
-        };
+        let message = self.create_message(sender, message_kind)?;
         let view = message.kind.get_view_number();
         let committee = membership.get_committee(view);
         let net = self.channel.clone();
@@ -386,4 +524,55 @@ impl<TYPES: NodeType, COMMCHANNEL: ConnectedNetwork<Message<TYPES>, TYPES::Signa
 
         None
     }
+
+    /// Create a message of the current version, signed if messages are authenticated
+    fn create_message(
+        &self,
+        sender: TYPES::SignatureKey,
+        kind: MessageKind<TYPES>,
+    ) -> Option<Message<TYPES>> {
+        let mut message = Message {
+            version: self.version,
+            sender,
+            kind,
+            signature: None,
+        };
+        if let Some(private_key) = &self.private_key {
+            if let Err(e) = message.sign(private_key) {
+                error!("Failed to sign message from network task: {:?}", e);
+                return None;
+            }
+        }
+        Some(message)
+    }
+
+    /// Send each VID storage node only its own share of the dispersal, rather than broadcasting
+    /// the whole dispersal to every node.
+    fn vid_disperse_send(
+        &self,
+        proposal: Proposal<TYPES, VidDisperse<TYPES>>,
+        sender: TYPES::SignatureKey,
+    ) {
+        let messages: Vec<_> = VidDisperseShare::to_share_proposals(proposal)
+            .into_iter()
+            .filter_map(|share| {
+                let recipient = share.data.recipient_key.clone();
+                let message = self.create_message(
+                    sender.clone(),
+                    MessageKind::<TYPES>::from_consensus_message(SequencingMessage(Right(
+                        CommitteeConsensusMessage::VidDisperseMsg(share),
+                    ))), // TODO not a CommitteeConsensusMessage https://github.com/EspressoSystems/HotShot/issues/1696
+                )?;
+                Some((recipient, message))
+            })
+            .collect();
+        let net = self.channel.clone();
+        async_spawn(async move {
+            for (recipient, message) in messages {
+                if let Err(e) = net.direct_message(message, recipient).await {
+                    error!("Failed to send VID share from network task: {:?}", e);
+                }
+            }
+        });
+    }
 }
@@ -0,0 +1,370 @@
+use crate::{
+    events::{HotShotEvent, HotShotTaskCompleted},
+    helpers::broadcast_event,
+    request::REQUEST_TIMEOUT_VIEWS,
+};
+use async_broadcast::Sender;
+use async_lock::RwLock;
+use commit::Committable;
+use hotshot_constants::VID_SHARE_RETENTION_VIEWS;
+use hotshot_task::task::{Task, TaskState};
+use hotshot_types::{
+    consensus::Consensus,
+    data::{vid_scheme, Leaf, VidDisperseShare, VidParams, VidScheme, VidSchemeTrait},
+    event::{Event, EventType},
+    message::Proposal,
+    traits::{
+        block_contents::BlockHeader,
+        consensus_api::ConsensusApi,
+        election::Membership,
+        node_implementation::{NodeImplementation, NodeType},
+        signature_key::SignatureKey,
+        BlockPayload,
+    },
+    vote::HasViewNumber,
+};
+use std::{collections::BTreeMap, marker::PhantomData, sync::Arc};
+use tracing::{debug, error, info, instrument, warn};
+
+/// Maximum number of views to wait for VID shares before asking our peers again
+const MAX_REQUEST_BACKOFF_VIEWS: u64 = 32;
+
+/// A block payload being recovered from the VID shares of our peers
+pub struct PayloadRecovery<TYPES: NodeType> {
+    /// The decided leaf missing its block payload
+    pub leaf: Leaf<TYPES>,
+    /// The parameters of the VID scheme the payload was dispersed with
+    pub vid_params: VidParams,
+    /// The VID scheme the payload was dispersed with
+    pub vid: VidScheme,
+    /// The number of shares each storage node was dispersed
+    pub share_counts: BTreeMap<TYPES::SignatureKey, usize>,
+    /// Valid VID shares collected so far, keyed by the node each share was dispersed to
+    pub shares: BTreeMap<TYPES::SignatureKey, VidDisperseShare<TYPES>>,
+    /// The view we last asked our peers for their shares in
+    pub requested: TYPES::Time,
+    /// Number of views after `requested` to wait before asking again, doubled after each request
+    pub timeout: u64,
+}
+
+/// State of the payload recovery task, which rebuilds the block payloads of decided leaves from
+/// the VID shares of peers, and serves our own VID shares to recovering peers
+pub struct PayloadRecoveryTaskState<
+    TYPES: NodeType,
+    I: NodeImplementation<TYPES>,
+    A: ConsensusApi<TYPES, I> + 'static,
+> {
+    /// Consensus api
+    pub api: A,
+
+    /// Reference to consensus. Our own VID shares are read from this.
+    pub consensus: Arc<RwLock<Consensus<TYPES>>>,
+
+    /// View number this view is executing in.
+    pub cur_view: TYPES::Time,
+
+    /// Membership for Quorum Certs/votes
+    pub quorum_membership: Arc<TYPES::Membership>,
+
+    /// Payloads being recovered, keyed by the view of their leaf
+    pub recoveries: BTreeMap<TYPES::Time, PayloadRecovery<TYPES>>,
+
+    /// This Nodes public key
+    pub public_key: TYPES::SignatureKey,
+
+    /// This state's ID
+    pub id: u64,
+
+    /// needed to typecheck
+    pub _pd: PhantomData<I>,
+}
+
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> + 'static>
+    PayloadRecoveryTaskState<TYPES, I, A>
+{
+    /// main task event handler
+    #[instrument(skip_all, fields(id = self.id, view = *self.cur_view), name = "Payload Recovery Task", level = "error")]
+    pub async fn handle(
+        &mut self,
+        event: HotShotEvent<TYPES>,
+        event_stream: Sender<HotShotEvent<TYPES>>,
+    ) -> Option<HotShotTaskCompleted> {
+        match event {
+            HotShotEvent::PayloadMissing(leaf) => {
+                let view = leaf.get_view_number();
+                if self.recoveries.contains_key(&view) {
+                    return None;
+                }
+                info!("Recovering the block payload of view {}", *view);
+                let vid_params = VidParams::from_membership::<TYPES>(&self.quorum_membership, view);
+                let vid = match vid_scheme(vid_params) {
+                    Ok(vid) => vid,
+                    Err(e) => {
+                        error!("Cannot recover the block payload of view {}: {}", *view, e);
+                        return None;
+                    }
+                };
+                let recovery = PayloadRecovery {
+                    leaf,
+                    vid_params,
+                    vid,
+                    share_counts: VidParams::share_counts::<TYPES>(&self.quorum_membership, view),
+                    shares: BTreeMap::new(),
+                    requested: self.cur_view,
+                    timeout: REQUEST_TIMEOUT_VIEWS,
+                };
+                self.recoveries.insert(view, recovery);
+                self.request_shares(view, &event_stream).await;
+            }
+            HotShotEvent::VidShareRequestRecv(view, requester) => {
+                let Some(share) = self
+                    .consensus
+                    .read()
+                    .await
+                    .saved_vid_shares
+                    .get(&view)
+                    .cloned()
+                else {
+                    debug!("No VID share of view {} for {}", *view, requester);
+                    return None;
+                };
+                broadcast_event(
+                    HotShotEvent::VidShareResponseSend(share, self.public_key.clone(), requester),
+                    &event_stream,
+                )
+                .await;
+            }
+            HotShotEvent::VidShareResponseRecv(share, sender) => {
+                self.add_share(share, sender).await;
+            }
+            HotShotEvent::ViewChange(view) => {
+                if view <= self.cur_view {
+                    return None;
+                }
+                self.cur_view = view;
+                // Give up once our peers no longer keep the shares of the payload.
+                self.recoveries.retain(|leaf_view, _| {
+                    let pending = **leaf_view + VID_SHARE_RETENTION_VIEWS >= *view;
+                    if !pending {
+                        error!(
+                            "Could not recover the block payload of view {}",
+                            **leaf_view
+                        );
+                    }
+                    pending
+                });
+                // Ask again for the payloads too few peers answered for.
+                let timed_out: Vec<_> = self
+                    .recoveries
+                    .iter()
+                    .filter(|(_, recovery)| recovery.requested + recovery.timeout < view)
+                    .map(|(leaf_view, _)| *leaf_view)
+                    .collect();
+                for leaf_view in timed_out {
+                    debug!("Requesting the VID shares of view {} again", *leaf_view);
+                    self.request_shares(leaf_view, &event_stream).await;
+                }
+            }
+            HotShotEvent::Shutdown => {
+                error!("Shutting down because of shutdown signal!");
+                return Some(HotShotTaskCompleted);
+            }
+            _ => {
+                error!("unexpected event {:?}", event);
+            }
+        }
+        None
+    }
+
+    /// Ask our peers for the VID shares of the payload of `view`, starting with our own share if we
+    /// have it, and back off before asking again.
+    async fn request_shares(
+        &mut self,
+        view: TYPES::Time,
+        event_stream: &Sender<HotShotEvent<TYPES>>,
+    ) {
+        let own_share = self
+            .consensus
+            .read()
+            .await
+            .saved_vid_shares
+            .get(&view)
+            .cloned();
+        if let Some(share) = own_share {
+            let sender = self.public_key.clone();
+            self.add_share(share, sender).await;
+        }
+        let cur_view = self.cur_view;
+        let Some(recovery) = self.recoveries.get_mut(&view) else {
+            return;
+        };
+        if recovery.requested < cur_view {
+            recovery.timeout = (recovery.timeout * 2).min(MAX_REQUEST_BACKOFF_VIEWS);
+        }
+        recovery.requested = cur_view;
+        broadcast_event(
+            HotShotEvent::VidShareRequestSend(view, self.public_key.clone()),
+            event_stream,
+        )
+        .await;
+    }
+
+    /// Check a VID share against the leaf being recovered, and recover the payload once enough
+    /// shares are collected.
+    async fn add_share(
+        &mut self,
+        share: Proposal<TYPES, VidDisperseShare<TYPES>>,
+        sender: TYPES::SignatureKey,
+    ) {
+        let view = share.data.get_view_number();
+        let Some(recovery) = self.recoveries.get_mut(&view) else {
+            return;
+        };
+        let payload_commitment = share.data.payload_commitment;
+        if share.data.recipient_key != sender {
+            warn!("Received a VID share of view {} for another node", *view);
+            return;
+        }
+        if payload_commitment != recovery.leaf.get_block_header().payload_commitment() {
+            warn!("VID share does not match the leaf of view {}", *view);
+            return;
+        }
+        if !self
+            .quorum_membership
+            .get_leader(view)
+            .validate(&share.signature, payload_commitment.as_ref())
+        {
+            warn!("Could not verify the VID share signature of view {}", *view);
+            return;
+        }
+        if recovery.share_counts.get(&sender) != Some(&share.data.shares.len()) {
+            warn!(
+                "Wrong number of VID shares of view {} from {}",
+                *view, sender
+            );
+            return;
+        }
+        if !share.data.shares.iter().all(|vid_share| {
+            matches!(
+                recovery
+                    .vid
+                    .verify_share(vid_share, &share.data.common, &payload_commitment),
+                Ok(Ok(()))
+            )
+        }) {
+            warn!("Invalid VID share of view {} from {}", *view, sender);
+            return;
+        }
+        recovery.shares.insert(sender, share.data);
+
+        let num_shares: usize = recovery
+            .shares
+            .values()
+            .map(|share| share.shares.len())
+            .sum();
+        if num_shares < recovery.vid_params.recovery_threshold {
+            return;
+        }
+        let Some(recovery) = self.recoveries.get(&view) else {
+            return;
+        };
+        if self.recover(recovery).await {
+            self.recoveries.remove(&view);
+        } else if let Some(recovery) = self.recoveries.get_mut(&view) {
+            // Collect fresh shares, which are asked for again once the request times out.
+            recovery.shares.clear();
+        }
+    }
+
+    /// Rebuild the block payload of a leaf from the collected VID shares, and store the leaf.
+    /// Returns whether the payload was recovered.
+    async fn recover(&self, recovery: &PayloadRecovery<TYPES>) -> bool {
+        let mut leaf = recovery.leaf.clone();
+        let view = leaf.get_view_number();
+        let Some(common) = recovery
+            .shares
+            .values()
+            .next()
+            .map(|share| share.common.clone())
+        else {
+            return false;
+        };
+        let shares: Vec<_> = recovery
+            .shares
+            .values()
+            .flat_map(|share| share.shares.iter().cloned())
+            .collect();
+        let encoded_transactions = match recovery.vid.recover_payload(&shares, &common) {
+            Ok(encoded_transactions) => encoded_transactions,
+            Err(e) => {
+                warn!(
+                    "Failed to recover the block payload of view {}: {:?}",
+                    *view, e
+                );
+                return false;
+            }
+        };
+        let payload = TYPES::BlockPayload::from_bytes(
+            encoded_transactions.into_iter(),
+            leaf.get_block_header().metadata(),
+        );
+        if let Err(e) = leaf.fill_block_payload(payload, recovery.vid_params) {
+            warn!(
+                "Recovered payload does not match the leaf of view {}: {:?}",
+                *view, e
+            );
+            return false;
+        }
+
+        {
+            let mut consensus = self.consensus.write().await;
+            let commitment = leaf.commit();
+            if let Some(saved_leaf) = consensus.saved_leaves.get_mut(&commitment) {
+                *saved_leaf = leaf.clone();
+            }
+        }
+        if let Err(e) = self.api.store_decided_leaves(vec![leaf.clone()]).await {
+            error!("Could not store the recovered leaf: {:?}", e);
+        }
+        info!("Recovered the block payload of view {}", *view);
+        self.api
+            .send_event(Event {
+                view_number: view,
+                event: EventType::PayloadRecovered { leaf },
+            })
+            .await;
+        true
+    }
+}
+
+/// task state implementation for the payload recovery task
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> + 'static> TaskState
+    for PayloadRecoveryTaskState<TYPES, I, A>
+{
+    type Event = HotShotEvent<TYPES>;
+
+    type Output = HotShotTaskCompleted;
+
+    async fn handle_event(
+        event: Self::Event,
+        task: &mut Task<Self>,
+    ) -> Option<HotShotTaskCompleted> {
+        let sender = task.clone_sender();
+        task.state_mut().handle(event, sender).await
+    }
+
+    fn should_shutdown(event: &Self::Event) -> bool {
+        matches!(event, HotShotEvent::Shutdown)
+    }
+
+    fn filter(&self, event: &Self::Event) -> bool {
+        !matches!(
+            event,
+            HotShotEvent::PayloadMissing(_)
+                | HotShotEvent::VidShareRequestRecv(_, _)
+                | HotShotEvent::VidShareResponseRecv(_, _)
+                | HotShotEvent::ViewChange(_)
+                | HotShotEvent::Shutdown
+        )
+    }
+}
@@ -0,0 +1,398 @@
+use crate::{
+    events::{HotShotEvent, HotShotTaskCompleted},
+    helpers::broadcast_event,
+};
+use async_broadcast::Sender;
+use async_lock::RwLock;
+use commit::Committable;
+use hotshot_task::task::{Task, TaskState};
+use hotshot_types::{
+    consensus::{Consensus, View},
+    data::{DAProposal, Leaf, VidParams},
+    message::{DataRequest, DataResponse},
+    simple_certificate::QuorumCertificate,
+    traits::{
+        block_contents::{vid_commitment, BlockHeader},
+        election::Membership,
+        node_implementation::NodeType,
+        states::ValidatedState,
+    },
+    utils::{LeafCommitment, ViewInner},
+    vote::{Certificate, HasViewNumber},
+};
+use std::{collections::HashMap, sync::Arc};
+use tracing::{debug, error, instrument, warn};
+
+/// Number of views after which an unanswered request is dropped, so the data can be requested
+/// again
+pub const REQUEST_TIMEOUT_VIEWS: u64 = 2;
+
+/// State of the request task, which fetches leaves missing from storage from peers and answers
+/// the requests of peers
+pub struct RequestTaskState<TYPES: NodeType> {
+    /// Reference to consensus. Fetched leaves are inserted with a write lock on this.
+    pub consensus: Arc<RwLock<Consensus<TYPES>>>,
+
+    /// View number this view is executing in.
+    pub cur_view: TYPES::Time,
+
+    /// Membership for Quorum Certs/votes
+    pub quorum_membership: Arc<TYPES::Membership>,
+
+    /// Requests we have sent and not yet received a valid response for, with the peer we sent
+    /// them to and the view we sent them in
+    pub outstanding_requests: HashMap<DataRequest<TYPES>, (TYPES::SignatureKey, TYPES::Time)>,
+
+    /// Fetched leaves that are not saved yet because we do not have the state of their parent to
+    /// validate them against, by their own commitment
+    pub unvalidated_leaves: HashMap<LeafCommitment<TYPES>, Leaf<TYPES>>,
+
+    /// This Nodes public key
+    pub public_key: TYPES::SignatureKey,
+
+    /// This state's ID
+    pub id: u64,
+}
+
+impl<TYPES: NodeType> RequestTaskState<TYPES> {
+    /// main task event handler
+    #[instrument(skip_all, fields(id = self.id, view = *self.cur_view), name = "Request Task", level = "error")]
+    pub async fn handle(
+        &mut self,
+        event: HotShotEvent<TYPES>,
+        event_stream: Sender<HotShotEvent<TYPES>>,
+    ) -> Option<HotShotTaskCompleted> {
+        match event {
+            HotShotEvent::LeafMissing(request, peer) => {
+                self.request(request, peer, &event_stream).await;
+            }
+            HotShotEvent::DataRequestRecv(request, requester) => {
+                let Some(response) = self.find_response(request).await else {
+                    debug!("Cannot answer request from {}", requester);
+                    return None;
+                };
+                broadcast_event(
+                    HotShotEvent::DataResponseSend(response, self.public_key.clone(), requester),
+                    &event_stream,
+                )
+                .await;
+            }
+            HotShotEvent::DataResponseRecv(response, sender) => {
+                match self.outstanding_requests.get(&response.request) {
+                    Some((peer, _)) if *peer == sender => {}
+                    _ => {
+                        warn!("Received a response we did not ask {} for", sender);
+                        return None;
+                    }
+                }
+                if !self.is_valid_response(&response) {
+                    return None;
+                }
+                self.outstanding_requests.remove(&response.request);
+
+                let DataResponse {
+                    leaf, da_proposal, ..
+                } = response;
+                let view = leaf.get_view_number();
+                let mut consensus = self.consensus.write().await;
+                if view <= consensus.last_decided_view {
+                    debug!("Fetched leaf for view {} is already decided", *view);
+                    return None;
+                }
+
+                if let Some(proposal) = da_proposal {
+                    consensus
+                        .saved_payloads
+                        .entry(view)
+                        .or_insert(proposal.encoded_transactions);
+                }
+
+                // The leaf is only saved once it is validated against the state of its parent,
+                // which we may still have to fetch.
+                let last_decided_view = consensus.last_decided_view;
+                self.unvalidated_leaves
+                    .retain(|_, pending| pending.get_view_number() > last_decided_view);
+                self.unvalidated_leaves.insert(leaf.commit(), leaf.clone());
+                Self::validate_fetched_leaves(
+                    &mut consensus,
+                    &mut self.unvalidated_leaves,
+                    leaf.parent_commitment,
+                );
+
+                // Keep walking back the chain until we reach a leaf we have.
+                let parent_missing = !leaf.justify_qc.is_genesis
+                    && leaf.justify_qc.get_view_number() > consensus.last_decided_view
+                    && !consensus.saved_leaves.contains_key(&leaf.parent_commitment)
+                    && !self
+                        .unvalidated_leaves
+                        .contains_key(&leaf.parent_commitment);
+                drop(consensus);
+                if parent_missing {
+                    self.request(
+                        DataRequest::Leaf(leaf.parent_commitment),
+                        sender,
+                        &event_stream,
+                    )
+                    .await;
+                }
+            }
+            HotShotEvent::ViewChange(view) => {
+                if view <= self.cur_view {
+                    return None;
+                }
+                self.cur_view = view;
+                self.outstanding_requests
+                    .retain(|_, (_, requested)| *requested + REQUEST_TIMEOUT_VIEWS >= view);
+            }
+            HotShotEvent::Shutdown => {
+                error!("Shutting down because of shutdown signal!");
+                return Some(HotShotTaskCompleted);
+            }
+            _ => {
+                error!("unexpected event {:?}", event);
+            }
+        }
+        None
+    }
+
+    /// Send `request` to `peer`, unless we are already waiting for a response to it.
+    async fn request(
+        &mut self,
+        request: DataRequest<TYPES>,
+        peer: TYPES::SignatureKey,
+        event_stream: &Sender<HotShotEvent<TYPES>>,
+    ) {
+        if self.outstanding_requests.contains_key(&request) {
+            return;
+        }
+        debug!("Requesting {:?} from {}", request, peer);
+        self.outstanding_requests
+            .insert(request.clone(), (peer.clone(), self.cur_view));
+        broadcast_event(
+            HotShotEvent::DataRequestSend(request, self.public_key.clone(), peer),
+            event_stream,
+        )
+        .await;
+    }
+
+    /// Validate the fetched leaves extending `parent` against the state of their parent, and then
+    /// the fetched leaves extending those in turn, saving each valid leaf and inserting it into the
+    /// state map. Leaves whose parent state we do not have are kept, invalid leaves are dropped.
+    fn validate_fetched_leaves(
+        consensus: &mut Consensus<TYPES>,
+        unvalidated_leaves: &mut HashMap<LeafCommitment<TYPES>, Leaf<TYPES>>,
+        parent: LeafCommitment<TYPES>,
+    ) {
+        let mut parents = vec![parent];
+        while let Some(parent) = parents.pop() {
+            let Some(parent_leaf) = consensus.saved_leaves.get(&parent).cloned() else {
+                continue;
+            };
+            let Some(parent_state) = consensus
+                .validated_state_map
+                .get(&parent_leaf.get_view_number())
+                .and_then(|view| view.get_leaf())
+                .filter(|(commitment, _)| *commitment == parent)
+                .map(|(_, state)| state.clone())
+            else {
+                continue;
+            };
+            // Forks can leave several fetched leaves extending the same parent.
+            let children: Vec<_> = unvalidated_leaves
+                .iter()
+                .filter(|(_, leaf)| leaf.parent_commitment == parent)
+                .map(|(commitment, _)| *commitment)
+                .collect();
+            for commitment in children {
+                let Some(leaf) = unvalidated_leaves.remove(&commitment) else {
+                    continue;
+                };
+                let view = leaf.get_view_number();
+                let state = match parent_state.validate_and_apply_header(
+                    &consensus.instance_state,
+                    &parent_leaf.block_header,
+                    &leaf.block_header,
+                ) {
+                    Ok(state) => Arc::new(state),
+                    Err(e) => {
+                        warn!("Fetched leaf of view {} is invalid: {:?}", *view, e);
+                        continue;
+                    }
+                };
+
+                // Do not overwrite a leaf we already have for this view.
+                if !matches!(
+                    consensus.validated_state_map.get(&view),
+                    Some(View {
+                        view_inner: ViewInner::Leaf { .. }
+                    })
+                ) {
+                    consensus.validated_state_map.insert(
+                        view,
+                        View {
+                            view_inner: ViewInner::Leaf {
+                                leaf: commitment,
+                                state,
+                            },
+                        },
+                    );
+                }
+                consensus.saved_leaves.insert(commitment, leaf);
+                parents.push(commitment);
+            }
+        }
+    }
+
+    /// Look up the data requested by a peer. Returns `None` if we do not have the leaf or a
+    /// certificate for it.
+    async fn find_response(&self, request: DataRequest<TYPES>) -> Option<DataResponse<TYPES>> {
+        let consensus = self.consensus.read().await;
+        let leaf = match &request {
+            DataRequest::Leaf(commitment) => consensus.saved_leaves.get(commitment),
+            DataRequest::View(view) => consensus
+                .validated_state_map
+                .get(view)
+                .and_then(|view| view.get_leaf_commitment())
+                .and_then(|commitment| consensus.saved_leaves.get(&commitment)),
+        }?
+        .clone();
+        let leaf_commitment = leaf.commit();
+
+        // The leaf is certified either by our high QC or by the justify QC of one of its children.
+        // Children follow it in the state map, usually in the very next view.
+        let qc = if consensus.high_qc.get_data().leaf_commit == leaf_commitment {
+            consensus.high_qc.clone()
+        } else {
+            consensus
+                .validated_state_map
+                .range(leaf.get_view_number() + 1..)
+                .filter_map(|(_, view)| view.get_leaf_commitment())
+                .filter_map(|commitment| consensus.saved_leaves.get(&commitment))
+                .find(|child| child.justify_qc.get_data().leaf_commit == leaf_commitment)?
+                .justify_qc
+                .clone()
+        };
+
+        let da_proposal =
+            consensus
+                .saved_payloads
+                .get(&leaf.get_view_number())
+                .map(|encoded_transactions| DAProposal {
+                    encoded_transactions: encoded_transactions.clone(),
+                    metadata: leaf.block_header.metadata().clone(),
+                    view_number: leaf.get_view_number(),
+                });
+
+        Some(DataResponse {
+            request,
+            leaf,
+            qc,
+            da_proposal,
+        })
+    }
+
+    /// Whether `qc` is a valid, non-genesis certificate for the data it claims to certify.
+    fn is_valid_qc(&self, qc: &QuorumCertificate<TYPES>) -> bool {
+        !qc.is_genesis
+            && qc.get_data_commitment() == qc.get_data().commit()
+            && qc.is_valid_cert(self.quorum_membership.as_ref())
+    }
+
+    /// Check a response against the QC chain: the leaf must be the one we requested, be certified
+    /// by a valid QC and extend its own justify QC, and the DA proposal, if any, must match the
+    /// payload commitment of the leaf.
+    fn is_valid_response(&self, response: &DataResponse<TYPES>) -> bool {
+        let DataResponse {
+            request,
+            leaf,
+            qc,
+            da_proposal,
+        } = response;
+        let leaf_commitment = leaf.commit();
+        let view = leaf.get_view_number();
+
+        let requested = match request {
+            DataRequest::Leaf(commitment) => *commitment == leaf_commitment,
+            DataRequest::View(requested_view) => *requested_view == view,
+        };
+        if !requested {
+            warn!("Response does not contain the requested leaf");
+            return false;
+        }
+
+        if qc.get_data().leaf_commit != leaf_commitment
+            || qc.get_view_number() != view
+            || !self.is_valid_qc(qc)
+        {
+            warn!("Invalid certificate for fetched leaf of view {}", *view);
+            return false;
+        }
+
+        let justify_qc = &leaf.justify_qc;
+        if justify_qc.get_view_number() >= view
+            || !(justify_qc.is_genesis
+                || (justify_qc.get_data().leaf_commit == leaf.parent_commitment
+                    && self.is_valid_qc(justify_qc)))
+        {
+            warn!(
+                "Fetched leaf of view {} does not extend its justify QC",
+                *view
+            );
+            return false;
+        }
+
+        if let Some(proposal) = da_proposal {
+            let payload_commitment = match vid_commitment(
+                &proposal.encoded_transactions,
+                VidParams::from_membership::<TYPES>(&self.quorum_membership, view),
+            ) {
+                Ok(payload_commitment) => payload_commitment,
+                Err(e) => {
+                    error!("VID: {}", e);
+                    return false;
+                }
+            };
+            if proposal.view_number != view
+                || payload_commitment != leaf.block_header.payload_commitment()
+            {
+                warn!(
+                    "Fetched DA proposal does not match the leaf of view {}",
+                    *view
+                );
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// task state implementation for the request task
+impl<TYPES: NodeType> TaskState for RequestTaskState<TYPES> {
+    type Event = HotShotEvent<TYPES>;
+
+    type Output = HotShotTaskCompleted;
+
+    async fn handle_event(
+        event: Self::Event,
+        task: &mut Task<Self>,
+    ) -> Option<HotShotTaskCompleted> {
+        let sender = task.clone_sender();
+        task.state_mut().handle(event, sender).await
+    }
+
+    fn should_shutdown(event: &Self::Event) -> bool {
+        matches!(event, HotShotEvent::Shutdown)
+    }
+
+    fn filter(&self, event: &Self::Event) -> bool {
+        !matches!(
+            event,
+            HotShotEvent::LeafMissing(_, _)
+                | HotShotEvent::DataRequestRecv(_, _)
+                | HotShotEvent::DataResponseRecv(_, _)
+                | HotShotEvent::ViewChange(_)
+                | HotShotEvent::Shutdown
+        )
+    }
+}
@@ -0,0 +1,128 @@
+use crate::events::{HotShotEvent, HotShotTaskCompleted};
+use ark_ff::PrimeField;
+use commit::Committable;
+use hotshot_task::task::{Task, TaskState};
+use hotshot_types::{
+    data::Leaf,
+    event::{Event, EventType},
+    light_client::{
+        CircuitField, LightClientState, StateKeyPair, StateSignatureRequestBody, StateSigningConfig,
+    },
+    traits::{
+        consensus_api::ConsensusApi,
+        node_implementation::{NodeImplementation, NodeType},
+    },
+};
+use std::marker::PhantomData;
+use tracing::{debug, error, instrument};
+
+/// State of the state signing task, which signs the light client state of every decided leaf
+/// and publishes it to the state relay
+pub struct StateSigningTaskState<
+    TYPES: NodeType,
+    I: NodeImplementation<TYPES>,
+    A: ConsensusApi<TYPES, I> + 'static,
+> {
+    /// Consensus api
+    pub api: A,
+
+    /// The key pair this node signs light client states with
+    pub state_key_pair: StateKeyPair,
+
+    /// The stake table commitment to sign, and the relay to publish signed states to
+    pub config: StateSigningConfig,
+
+    /// The view of the last leaf we signed the state of
+    pub last_signed_view: TYPES::Time,
+
+    /// This state's ID
+    pub id: u64,
+
+    /// needed to typecheck
+    pub _pd: PhantomData<I>,
+}
+
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> + 'static>
+    StateSigningTaskState<TYPES, I, A>
+{
+    /// The light client state after the given leaf was decided, or `None` if its view number or
+    /// height does not fit in a `usize`.
+    ///
+    /// Only the view number and block height come from the leaf. The other fields are placeholders
+    /// until the application provides them: `block_comm_root` is the leaf commitment reduced into
+    /// the field rather than the root of a block commitment tree, `fee_ledger_comm` is zero, and
+    /// `stake_table_comm` is the commitment of the config, which does not follow the stake table
+    /// across epochs.
+    #[must_use]
+    pub fn light_client_state(&self, leaf: &Leaf<TYPES>) -> Option<LightClientState> {
+        Some(LightClientState {
+            view_number: usize::try_from(*leaf.get_view_number()).ok()?,
+            block_height: usize::try_from(leaf.get_height()).ok()?,
+            block_comm_root: CircuitField::from_le_bytes_mod_order(leaf.commit().as_ref()),
+            fee_ledger_comm: CircuitField::default(),
+            stake_table_comm: self.config.stake_table_comm,
+        })
+    }
+
+    /// main task event handler
+    #[instrument(skip_all, fields(id = self.id, view = *self.last_signed_view), name = "State Signing Task", level = "error")]
+    pub async fn handle(&mut self, event: HotShotEvent<TYPES>) -> Option<HotShotTaskCompleted> {
+        match event {
+            HotShotEvent::LeafDecided(leaves) => {
+                // Only the latest state needs a signature; it commits to all the earlier ones.
+                let leaf = leaves.iter().max_by_key(|leaf| leaf.get_view_number())?;
+                let view = leaf.get_view_number();
+                if view <= self.last_signed_view {
+                    return None;
+                }
+                self.last_signed_view = view;
+
+                let Some(state) = self.light_client_state(leaf) else {
+                    error!("Cannot sign the light client state of view {}", *view);
+                    return None;
+                };
+                let signed_state = StateSignatureRequestBody::sign(&self.state_key_pair, state);
+                debug!("Signed the light client state of view {}", *view);
+                self.api
+                    .send_event(Event {
+                        view_number: view,
+                        event: EventType::StateSigned {
+                            signed_state: signed_state.clone(),
+                        },
+                    })
+                    .await;
+                if let Err(e) = self.config.relay.publish(signed_state).await {
+                    error!("Failed to publish the signed light client state: {}", e);
+                }
+            }
+            HotShotEvent::Shutdown => {
+                return Some(HotShotTaskCompleted);
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> + 'static> TaskState
+    for StateSigningTaskState<TYPES, I, A>
+{
+    type Event = HotShotEvent<TYPES>;
+
+    type Output = HotShotTaskCompleted;
+
+    async fn handle_event(
+        event: Self::Event,
+        task: &mut Task<Self>,
+    ) -> Option<HotShotTaskCompleted> {
+        task.state_mut().handle(event).await
+    }
+
+    fn should_shutdown(event: &Self::Event) -> bool {
+        matches!(event, HotShotEvent::Shutdown)
+    }
+
+    fn filter(&self, event: &Self::Event) -> bool {
+        !matches!(event, HotShotEvent::LeafDecided(_) | HotShotEvent::Shutdown)
+    }
+}
@@ -0,0 +1,439 @@
+use crate::{
+    events::{HotShotEvent, HotShotTaskCompleted},
+    helpers::broadcast_event,
+    request::REQUEST_TIMEOUT_VIEWS,
+};
+use async_broadcast::Sender;
+use async_lock::RwLock;
+use commit::Committable;
+use hotshot_constants::SYNC_BATCH_SIZE;
+use hotshot_task::task::{Task, TaskState};
+use hotshot_types::{
+    consensus::{Consensus, View},
+    data::{Leaf, VidParams},
+    event::{Event, EventType},
+    message::{SyncRequest, SyncResponse},
+    simple_certificate::QuorumCertificate,
+    traits::{
+        consensus_api::ConsensusApi,
+        election::Membership,
+        node_implementation::{ConsensusTime, NodeImplementation, NodeType},
+        states::ValidatedState,
+    },
+    utils::ViewInner,
+    vote::{Certificate, HasViewNumber},
+};
+use std::{marker::PhantomData, sync::Arc};
+use tracing::{debug, error, info, instrument, warn};
+
+/// A sync in progress
+pub struct SyncProgress<TYPES: NodeType> {
+    /// The peer we are syncing from
+    pub peer: TYPES::SignatureKey,
+    /// The request we are waiting for a response to, and the view we sent it in
+    pub request: (SyncRequest<TYPES>, TYPES::Time),
+    /// The number of decided leaves fetched so far
+    pub leaves_synced: u64,
+}
+
+/// State of the sync task, which brings a node that is far behind up to date with the decided
+/// chain of a peer, and serves the decided chain to syncing peers
+pub struct SyncTaskState<
+    TYPES: NodeType,
+    I: NodeImplementation<TYPES>,
+    A: ConsensusApi<TYPES, I> + 'static,
+> {
+    /// Consensus api
+    pub api: A,
+
+    /// Reference to consensus. The new anchor is installed with a write lock on this.
+    pub consensus: Arc<RwLock<Consensus<TYPES>>>,
+
+    /// View number this view is executing in.
+    pub cur_view: TYPES::Time,
+
+    /// Membership for Quorum Certs/votes
+    pub quorum_membership: Arc<TYPES::Membership>,
+
+    /// The sync we are running, if any
+    pub sync: Option<SyncProgress<TYPES>>,
+
+    /// This Nodes public key
+    pub public_key: TYPES::SignatureKey,
+
+    /// This state's ID
+    pub id: u64,
+
+    /// needed to typecheck
+    pub _pd: PhantomData<I>,
+}
+
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> + 'static>
+    SyncTaskState<TYPES, I, A>
+{
+    /// main task event handler
+    #[instrument(skip_all, fields(id = self.id, view = *self.cur_view), name = "Sync Task", level = "error")]
+    pub async fn handle(
+        &mut self,
+        event: HotShotEvent<TYPES>,
+        event_stream: Sender<HotShotEvent<TYPES>>,
+    ) -> Option<HotShotTaskCompleted> {
+        match event {
+            HotShotEvent::SyncStart(peer) => {
+                if self.sync.is_some() {
+                    return None;
+                }
+                let anchor = self.consensus.read().await.get_decided_leaf();
+                info!(
+                    "Syncing the decided chain from {} starting at view {}",
+                    peer,
+                    *anchor.get_view_number()
+                );
+                self.request_page(peer, &anchor, 0, &event_stream).await;
+            }
+            HotShotEvent::SyncRequestRecv(request, requester) => {
+                let Some(response) = self.find_response(request).await else {
+                    debug!("Cannot serve the decided chain to {}", requester);
+                    return None;
+                };
+                broadcast_event(
+                    HotShotEvent::SyncResponseSend(response, self.public_key.clone(), requester),
+                    &event_stream,
+                )
+                .await;
+            }
+            HotShotEvent::SyncResponseRecv(response, sender) => {
+                match &self.sync {
+                    Some(sync) if sync.peer == sender && sync.request.0 == response.request => {}
+                    _ => {
+                        warn!("Received a sync response we did not ask {} for", sender);
+                        return None;
+                    }
+                }
+                let Some(sync) = self.sync.take() else {
+                    return None;
+                };
+                self.handle_response(response, sync, &event_stream).await;
+            }
+            HotShotEvent::ViewChange(view) => {
+                if view <= self.cur_view {
+                    return None;
+                }
+                self.cur_view = view;
+                // Give up on an unresponsive peer, so the sync can be started again.
+                if self
+                    .sync
+                    .as_ref()
+                    .is_some_and(|sync| sync.request.1 + REQUEST_TIMEOUT_VIEWS < view)
+                {
+                    warn!("Sync request timed out");
+                    self.sync = None;
+                }
+            }
+            HotShotEvent::Shutdown => {
+                error!("Shutting down because of shutdown signal!");
+                return Some(HotShotTaskCompleted);
+            }
+            _ => {
+                error!("unexpected event {:?}", event);
+            }
+        }
+        None
+    }
+
+    /// Request the decided leaves following `anchor` from `peer`.
+    async fn request_page(
+        &mut self,
+        peer: TYPES::SignatureKey,
+        anchor: &Leaf<TYPES>,
+        leaves_synced: u64,
+        event_stream: &Sender<HotShotEvent<TYPES>>,
+    ) {
+        let request = SyncRequest {
+            anchor: anchor.commit(),
+            anchor_view: anchor.get_view_number(),
+            max_leaves: SYNC_BATCH_SIZE,
+        };
+        self.sync = Some(SyncProgress {
+            peer: peer.clone(),
+            request: (request.clone(), self.cur_view),
+            leaves_synced,
+        });
+        broadcast_event(
+            HotShotEvent::SyncRequestSend(request, self.public_key.clone(), peer),
+            event_stream,
+        )
+        .await;
+    }
+
+    /// Look up the decided leaves following the anchor of a syncing peer. Returns `None` if the
+    /// anchor is not on our decided chain.
+    async fn find_response(&self, request: SyncRequest<TYPES>) -> Option<SyncResponse<TYPES>> {
+        let max_leaves = usize::try_from(request.max_leaves.min(SYNC_BATCH_SIZE)).ok()?;
+        // Fetch one more leaf than we serve: its justify QC certifies the last leaf we serve.
+        let mut leaves = match self
+            .api
+            .get_decided_leaves(request.anchor_view, max_leaves + 1)
+            .await
+        {
+            Ok(leaves) => leaves,
+            Err(e) => {
+                error!(
+                    "Could not read decided leaves from the storage API: {:?}",
+                    e
+                );
+                return None;
+            }
+        };
+        if leaves
+            .first()
+            .is_some_and(|leaf| leaf.get_parent_commitment() != request.anchor)
+        {
+            return None;
+        }
+
+        let qc = if leaves.len() > max_leaves {
+            leaves.pop().map(|next| next.justify_qc)
+        } else if let Some(last) = leaves.last() {
+            // The last decided leaf is certified by our high QC or by a child we have not
+            // decided yet. Without either, serve one leaf less.
+            let consensus = self.consensus.read().await;
+            let last_commitment = last.commit();
+            if consensus.high_qc.get_data().leaf_commit == last_commitment {
+                Some(consensus.high_qc.clone())
+            } else if let Some(child) = consensus
+                .saved_leaves
+                .values()
+                .find(|child| child.justify_qc.get_data().leaf_commit == last_commitment)
+            {
+                Some(child.justify_qc.clone())
+            } else {
+                leaves.pop().map(|last| last.justify_qc)
+            }
+        } else {
+            None
+        };
+        // The justify QC of the first leaf certifies the anchor, not a leaf we serve.
+        let qc = if leaves.is_empty() { None } else { qc };
+
+        Some(SyncResponse {
+            request,
+            leaves,
+            qc,
+        })
+    }
+
+    /// Whether `qc` is a valid, non-genesis certificate for the data it claims to certify.
+    fn is_valid_qc(&self, qc: &QuorumCertificate<TYPES>) -> bool {
+        !qc.is_genesis
+            && qc.get_data_commitment() == qc.get_data().commit()
+            && qc.is_valid_cert(self.quorum_membership.as_ref())
+    }
+
+    /// Check a page of the decided chain against the QC chain, and return the leaves of it that
+    /// are proven decided.
+    ///
+    /// Every leaf must extend the previous one (the first one extends our anchor) through a valid
+    /// justify QC, the last one must be certified by the QC of the page, and payloads must match
+    /// their headers. A leaf is proven decided if it starts a chain of three certified leaves
+    /// with consecutive views; it and its ancestors are then decided.
+    fn verify_page(&self, response: SyncResponse<TYPES>) -> Option<Vec<Leaf<TYPES>>> {
+        let SyncResponse {
+            request,
+            mut leaves,
+            qc,
+        } = response;
+        if u64::try_from(leaves.len()).map_or(true, |len| len > request.max_leaves) {
+            warn!("Sync response contains more leaves than requested");
+            return None;
+        }
+        let Some(qc) = qc else {
+            return if leaves.is_empty() {
+                Some(Vec::new())
+            } else {
+                warn!("Sync response does not certify its last leaf");
+                None
+            };
+        };
+
+        let mut parent = (request.anchor, request.anchor_view);
+        for leaf in &mut leaves {
+            let view = leaf.get_view_number();
+            let justify_qc = &leaf.justify_qc;
+            // Only the leaf following the genesis leaf is justified by the genesis QC.
+            let certified_parent = if justify_qc.is_genesis {
+                parent.1 == TYPES::Time::genesis()
+            } else {
+                justify_qc.get_data().leaf_commit == parent.0
+                    && justify_qc.get_view_number() == parent.1
+                    && self.is_valid_qc(justify_qc)
+            };
+            if view <= parent.1 || leaf.get_parent_commitment() != parent.0 || !certified_parent {
+                warn!("Synced leaf of view {} does not extend its parent", *view);
+                return None;
+            }
+            if let Some(payload) = leaf.block_payload.take() {
+                if leaf
+                    .fill_block_payload(
+                        payload,
+                        VidParams::from_membership::<TYPES>(&self.quorum_membership, view),
+                    )
+                    .is_err()
+                {
+                    warn!("Synced payload does not match the leaf of view {}", *view);
+                    return None;
+                }
+            }
+            parent = (leaf.commit(), view);
+        }
+        if qc.get_data().leaf_commit != parent.0
+            || qc.get_view_number() != parent.1
+            || !self.is_valid_qc(&qc)
+        {
+            warn!("Invalid certificate for the last synced leaf");
+            return None;
+        }
+
+        let decided = leaves
+            .windows(3)
+            .rposition(|chain| {
+                chain[1].get_view_number() == chain[0].get_view_number() + 1
+                    && chain[2].get_view_number() == chain[1].get_view_number() + 1
+            })
+            .map_or(0, |index| index + 1);
+        leaves.truncate(decided);
+        Some(leaves)
+    }
+
+    /// Verify a page of the decided chain, hand the decided leaves of it over to consensus and
+    /// request the next page if there is one.
+    async fn handle_response(
+        &mut self,
+        response: SyncResponse<TYPES>,
+        sync: SyncProgress<TYPES>,
+        event_stream: &Sender<HotShotEvent<TYPES>>,
+    ) {
+        let full_page = u64::try_from(response.leaves.len())
+            .is_ok_and(|len| len == response.request.max_leaves);
+        let Some(leaves) = self.verify_page(response) else {
+            return;
+        };
+        let Some(new_anchor) = leaves.last().cloned() else {
+            self.finish(sync).await;
+            return;
+        };
+        let new_anchor_view = new_anchor.get_view_number();
+
+        if let Err(e) = self.api.store_decided_leaves(leaves.clone()).await {
+            error!(
+                "Could not insert synced leaves into the storage API: {:?}",
+                e
+            );
+            return;
+        }
+
+        let mut consensus = self.consensus.write().await;
+        if new_anchor_view > consensus.last_decided_view {
+            let old_anchor_view = consensus.last_decided_view;
+            let state = Arc::new(<TYPES::ValidatedState as ValidatedState>::from_header(
+                &new_anchor.block_header,
+            ));
+            consensus.validated_state_map.insert(
+                new_anchor_view,
+                View {
+                    view_inner: ViewInner::Leaf {
+                        leaf: new_anchor.commit(),
+                        state,
+                    },
+                },
+            );
+            consensus
+                .saved_leaves
+                .insert(new_anchor.commit(), new_anchor.clone());
+            consensus
+                .collect_garbage(old_anchor_view, new_anchor_view)
+                .await;
+            consensus.last_decided_view = new_anchor_view;
+            if consensus.locked_view < new_anchor_view {
+                consensus.locked_view = new_anchor_view;
+            }
+        }
+        drop(consensus);
+        // Memberships whose committee changes over time move through the synced epochs.
+        for leaf in &leaves {
+            self.quorum_membership
+                .set_decided_view(leaf.get_view_number());
+        }
+
+        let leaves_synced = sync.leaves_synced + leaves.len() as u64;
+        let mut leafs_decided = leaves;
+        leafs_decided.reverse();
+        broadcast_event(HotShotEvent::LeafDecided(leafs_decided), event_stream).await;
+
+        let finished = !full_page;
+        self.api
+            .send_event(Event {
+                view_number: new_anchor_view,
+                event: EventType::SyncProgress {
+                    last_synced_view: new_anchor_view,
+                    leaves_synced,
+                    finished,
+                },
+            })
+            .await;
+        if finished {
+            info!("Synced the decided chain up to view {}", *new_anchor_view);
+        } else {
+            self.request_page(sync.peer, &new_anchor, leaves_synced, event_stream)
+                .await;
+        }
+    }
+
+    /// Report a sync that has nothing more to fetch as finished.
+    async fn finish(&self, sync: SyncProgress<TYPES>) {
+        let last_synced_view = sync.request.0.anchor_view;
+        info!("Synced the decided chain up to view {}", *last_synced_view);
+        self.api
+            .send_event(Event {
+                view_number: last_synced_view,
+                event: EventType::SyncProgress {
+                    last_synced_view,
+                    leaves_synced: sync.leaves_synced,
+                    finished: true,
+                },
+            })
+            .await;
+    }
+}
+
+/// task state implementation for the sync task
+impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> + 'static> TaskState
+    for SyncTaskState<TYPES, I, A>
+{
+    type Event = HotShotEvent<TYPES>;
+
+    type Output = HotShotTaskCompleted;
+
+    async fn handle_event(
+        event: Self::Event,
+        task: &mut Task<Self>,
+    ) -> Option<HotShotTaskCompleted> {
+        let sender = task.clone_sender();
+        task.state_mut().handle(event, sender).await
+    }
+
+    fn should_shutdown(event: &Self::Event) -> bool {
+        matches!(event, HotShotEvent::Shutdown)
+    }
+
+    fn filter(&self, event: &Self::Event) -> bool {
+        !matches!(
+            event,
+            HotShotEvent::SyncStart(_)
+                | HotShotEvent::SyncRequestRecv(_, _)
+                | HotShotEvent::SyncResponseRecv(_, _)
+                | HotShotEvent::ViewChange(_)
+                | HotShotEvent::Shutdown
+        )
+    }
+}
@@ -1,14 +1,18 @@
 use crate::{
     events::{HotShotEvent, HotShotTaskCompleted},
-    helpers::broadcast_event,
+    helpers::{broadcast_event, record_vote},
     vote::{create_vote_accumulator, AccumulatorInfo, VoteCollectionTaskState},
 };
 use async_broadcast::Sender;
 use async_lock::RwLock;
 
+use commit::Committable;
+use hotshot_constants::UPGRADE_PROPOSAL_LEAD_VIEWS;
 use hotshot_task::task::TaskState;
 use hotshot_types::{
+    data::UpgradeProposal,
     event::{Event, EventType},
+    message::Proposal,
     simple_certificate::UpgradeCertificate,
     simple_vote::{UpgradeProposalData, UpgradeVote},
     traits::{
@@ -16,14 +20,15 @@ use hotshot_types::{
         election::Membership,
         node_implementation::{ConsensusTime, NodeImplementation, NodeType},
         signature_key::SignatureKey,
+        storage::SafetyVote,
     },
     vote::HasViewNumber,
 };
 
 use crate::vote::HandleVoteEvent;
 use snafu::Snafu;
-use std::sync::Arc;
-use tracing::{debug, error, instrument, warn};
+use std::{marker::PhantomData, sync::Arc};
+use tracing::{debug, error, info, instrument, warn};
 
 /// Alias for Optional type for Vote Collectors
 type VoteCollectorOption<TYPES, VOTE, CERT> = Option<VoteCollectionTaskState<TYPES, VOTE, CERT>>;
@@ -51,6 +56,10 @@ pub struct UpgradeTaskState<
     /// Whether we should vote affirmatively on a given upgrade proposal (true) or not (false)
     pub should_vote: fn(UpgradeProposalData<TYPES>) -> bool,
 
+    /// The upgrade scheduled through the handle, which we propose when we lead a view early
+    /// enough, and vote for when another leader proposes it, until it takes effect or expires
+    pub upgrade_target: Option<UpgradeProposalData<TYPES>>,
+
     /// The current vote collection task, if there is one.
     pub vote_collector:
         RwLock<VoteCollectorOption<TYPES, UpgradeVote<TYPES>, UpgradeCertificate<TYPES>>>,
@@ -79,7 +88,9 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
             HotShotEvent::UpgradeProposalRecv(proposal, sender) => {
                 let should_vote = self.should_vote;
                 // If the proposal does not match our upgrade target, we immediately exit.
-                if !should_vote(proposal.data.upgrade_proposal.clone()) {
+                if self.upgrade_target.as_ref() != Some(&proposal.data.upgrade_proposal)
+                    && !should_vote(proposal.data.upgrade_proposal.clone())
+                {
                     warn!("Received unexpected upgrade proposal:\n{:?}", proposal.data);
                     return None;
                 }
@@ -114,6 +125,28 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                     error!("Upgrade proposal doesn't have expected leader key for view {} \n Upgrade proposal is: {:?}", *view, proposal.data.clone());
                     return None;
                 }
+                if !sender.validate(
+                    &proposal.signature,
+                    proposal.data.upgrade_proposal.commit().as_ref(),
+                ) {
+                    error!(
+                        "Could not verify the upgrade proposal signature of view {}",
+                        *view
+                    );
+                    return None;
+                }
+
+                // A certificate formed this late may not be decided before the new version
+                // starts, and nodes would switch versions in different views.
+                if *view + UPGRADE_PROPOSAL_LEAD_VIEWS
+                    > *proposal.data.upgrade_proposal.new_version_first_block
+                {
+                    warn!(
+                        "Upgrade proposal of view {} is too close to the new version",
+                        *view
+                    );
+                    return None;
+                }
 
                 // At this point, we've checked that:
                 //   * the proposal was expected,
@@ -140,6 +173,9 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                     error!("Failed to sign UpgradeVote!");
                     return None;
                 };
+                if !record_vote(&self.api, SafetyVote::Upgrade(view)).await {
+                    return None;
+                }
                 debug!("Sending upgrade vote {:?}", vote.get_view_number());
                 broadcast_event(HotShotEvent::UpgradeVoteSend(vote), &tx).await;
             }
@@ -196,8 +232,38 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 }
                 self.cur_view = view;
 
+                // An upgrade that has not been decided by the time its version starts never will
+                // be, so we stop proposing and voting for it.
+                if self
+                    .upgrade_target
+                    .as_ref()
+                    .is_some_and(|upgrade| view >= upgrade.new_version_first_block)
+                {
+                    warn!("Scheduled upgrade expired before it was decided");
+                    self.upgrade_target = None;
+                }
+
+                // Propose for the next view, so that the votes reach us before we propose it.
+                self.propose_upgrade(view + 1, &tx).await;
                 return None;
             }
+            HotShotEvent::UpgradeScheduled(upgrade) => {
+                info!(
+                    "Scheduled upgrade to version {:?} in view {}",
+                    upgrade.new_version, *upgrade.new_version_first_block
+                );
+                self.upgrade_target = Some(upgrade);
+            }
+            HotShotEvent::VersionUpgrade(version) => {
+                // The decided upgrade took effect, so there is nothing left to propose.
+                if self
+                    .upgrade_target
+                    .as_ref()
+                    .is_some_and(|upgrade| upgrade.new_version == version)
+                {
+                    self.upgrade_target = None;
+                }
+            }
             HotShotEvent::Shutdown => {
                 error!("Shutting down because of shutdown signal!");
                 return Some(HotShotTaskCompleted);
@@ -208,6 +274,38 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
         }
         None
     }
+
+    /// Propose the scheduled upgrade in `view` if we lead it, and the upgrade can still be
+    /// decided before the new version starts.
+    async fn propose_upgrade(&self, view: TYPES::Time, tx: &Sender<HotShotEvent<TYPES>>) {
+        let Some(upgrade) = self.upgrade_target.clone() else {
+            return;
+        };
+        if self.quorum_membership.get_leader(view) != self.public_key
+            || *view + UPGRADE_PROPOSAL_LEAD_VIEWS > *upgrade.new_version_first_block
+        {
+            return;
+        }
+        let Ok(signature) = TYPES::SignatureKey::sign(&self.private_key, upgrade.commit().as_ref())
+        else {
+            error!("Failed to sign UpgradeProposal!");
+            return;
+        };
+        let proposal = Proposal {
+            data: UpgradeProposal {
+                upgrade_proposal: upgrade,
+                view_number: view,
+            },
+            signature,
+            _pd: PhantomData,
+        };
+        debug!("Sending upgrade proposal for view {}", *view);
+        broadcast_event(
+            HotShotEvent::UpgradeProposalSend(proposal, self.public_key.clone()),
+            tx,
+        )
+        .await;
+    }
 }
 
 /// task state implementation for DA Task
@@ -236,6 +334,8 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
             event,
             HotShotEvent::UpgradeProposalRecv(_, _)
                 | HotShotEvent::UpgradeVoteRecv(_)
+                | HotShotEvent::UpgradeScheduled(_)
+                | HotShotEvent::VersionUpgrade(_)
                 | HotShotEvent::Shutdown
                 | HotShotEvent::ViewChange(_)
         )
@@ -19,7 +19,7 @@ use hotshot_types::{
     },
 };
 use hotshot_types::{
-    data::{test_srs, VidScheme, VidSchemeTrait},
+    data::{vid_scheme, VidParams, VidSchemeTrait},
     traits::network::ConsensusIntentEvent,
 };
 #[cfg(async_executor_impl = "tokio")]
@@ -74,23 +74,20 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
     ) -> Option<HotShotTaskCompleted> {
         match event {
             HotShotEvent::TransactionsSequenced(encoded_transactions, metadata, view_number) => {
-                // get the number of quorum committee members to be used for VID calculation
-                let num_quorum_committee = self.membership.total_nodes();
-
-                // TODO <https://github.com/EspressoSystems/HotShot/issues/1686>
-                let srs = test_srs(num_quorum_committee);
-
-                // calculate the last power of two
-                // TODO change after https://github.com/EspressoSystems/jellyfish/issues/339
-                // issue: https://github.com/EspressoSystems/HotShot/issues/2152
-                let chunk_size = 1 << num_quorum_committee.ilog2();
+                // the quorum committee stores the payload, with shares assigned by stake
+                let vid_params = VidParams::from_membership::<TYPES>(&self.membership, view_number);
+                let vid = match vid_scheme(vid_params) {
+                    Ok(vid) => vid,
+                    Err(e) => {
+                        error!("VID: {}", e);
+                        return None;
+                    }
+                };
 
                 // calculate vid shares
-                let vid_disperse = spawn_blocking(move || {
-                    let vid = VidScheme::new(chunk_size, num_quorum_committee, &srs).unwrap();
-                    vid.disperse(encoded_transactions.clone()).unwrap()
-                })
-                .await;
+                let vid_disperse =
+                    spawn_blocking(move || vid.disperse(encoded_transactions.clone()).unwrap())
+                        .await;
 
                 #[cfg(async_executor_impl = "tokio")]
                 // Unwrap here will just propogate any panic from the spawned task, it's not a new place we can panic.
@@ -166,6 +163,13 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 return None;
             }
 
+            HotShotEvent::LeafDecided(leaf_chain) => {
+                // The first decided leaf is the new anchor
+                if let Some(anchor) = leaf_chain.first() {
+                    self.membership.set_decided_view(anchor.get_view_number());
+                }
+            }
+
             HotShotEvent::Shutdown => {
                 return Some(HotShotTaskCompleted);
             }
@@ -200,6 +204,7 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 | HotShotEvent::TransactionsSequenced(_, _, _)
                 | HotShotEvent::BlockReady(_, _)
                 | HotShotEvent::ViewChange(_)
+                | HotShotEvent::LeafDecided(_)
         )
     }
     fn should_shutdown(event: &Self::Event) -> bool {
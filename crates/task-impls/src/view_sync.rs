@@ -1,7 +1,7 @@
 #![allow(clippy::module_name_repetitions)]
 use crate::{
     events::{HotShotEvent, HotShotTaskCompleted},
-    helpers::{broadcast_event, cancel_task},
+    helpers::{broadcast_event, cancel_task, record_vote},
     vote::{create_vote_accumulator, AccumulatorInfo, HandleVoteEvent, VoteCollectionTaskState},
 };
 use async_broadcast::Sender;
@@ -33,6 +33,7 @@ use hotshot_types::{
         election::Membership,
         network::ConnectedNetwork,
         node_implementation::{ConsensusTime, NodeImplementation, NodeType},
+        storage::SafetyVote,
     },
 };
 use snafu::Snafu;
@@ -136,6 +137,7 @@ impl<
                 | HotShotEvent::Timeout(_)
                 | HotShotEvent::ViewSyncTimeout(_, _, _)
                 | HotShotEvent::ViewChange(_)
+                | HotShotEvent::LeafDecided(_)
         )
     }
 
@@ -457,6 +459,12 @@ impl<
                     self.last_garbage_collected_view = self.current_view - 1;
                 }
             }
+            HotShotEvent::LeafDecided(leaf_chain) => {
+                // The first decided leaf is the new anchor
+                if let Some(anchor) = leaf_chain.first() {
+                    self.membership.set_decided_view(anchor.get_view_number());
+                }
+            }
             &HotShotEvent::Timeout(view_number) => {
                 // This is an old timeout and we can ignore it
                 if view_number <= TYPES::Time::new(*self.current_view) {
@@ -587,6 +595,9 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 let message = GeneralConsensusMessage::<TYPES>::ViewSyncCommitVote(vote);
 
                 if let GeneralConsensusMessage::ViewSyncCommitVote(vote) = message {
+                    if !record_vote(&self.api, SafetyVote::ViewSync(self.next_view)).await {
+                        return None;
+                    }
                     broadcast_event(HotShotEvent::ViewSyncCommitVoteSend(vote), &event_stream)
                         .await;
                 }
@@ -660,6 +671,9 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 let message = GeneralConsensusMessage::<TYPES>::ViewSyncFinalizeVote(vote);
 
                 if let GeneralConsensusMessage::ViewSyncFinalizeVote(vote) = message {
+                    if !record_vote(&self.api, SafetyVote::ViewSync(self.next_view)).await {
+                        return None;
+                    }
                     broadcast_event(HotShotEvent::ViewSyncFinalizeVoteSend(vote), &event_stream)
                         .await;
                 }
@@ -717,6 +731,9 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                     error!("Failed to sign TimeoutData!");
                     return None;
                 };
+                if !record_vote(&self.api, SafetyVote::Timeout(vote.clone())).await {
+                    return None;
+                }
 
                 broadcast_event(HotShotEvent::TimeoutVoteSend(vote), &event_stream).await;
                 // Ignore certificate if it is for an older round
@@ -786,6 +803,9 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                 let message = GeneralConsensusMessage::<TYPES>::ViewSyncPreCommitVote(vote);
 
                 if let GeneralConsensusMessage::ViewSyncPreCommitVote(vote) = message {
+                    if !record_vote(&self.api, SafetyVote::ViewSync(self.next_view)).await {
+                        return None;
+                    }
                     broadcast_event(HotShotEvent::ViewSyncPreCommitVoteSend(vote), &event_stream)
                         .await;
                 }
@@ -838,6 +858,11 @@ impl<TYPES: NodeType, I: NodeImplementation<TYPES>, A: ConsensusApi<TYPES, I> +
                                 GeneralConsensusMessage::<TYPES>::ViewSyncPreCommitVote(vote);
 
                             if let GeneralConsensusMessage::ViewSyncPreCommitVote(vote) = message {
+                                if !record_vote(&self.api, SafetyVote::ViewSync(self.next_view))
+                                    .await
+                                {
+                                    return None;
+                                }
                                 broadcast_event(
                                     HotShotEvent::ViewSyncPreCommitVoteSend(vote),
                                     &event_stream,
@@ -10,6 +10,7 @@ use either::Either::{self, Left, Right};
 
 use hotshot_task::task::{Task, TaskState};
 use hotshot_types::{
+    event::Equivocation,
     simple_certificate::{
         DACertificate, QuorumCertificate, TimeoutCertificate, UpgradeCertificate,
         ViewSyncCommitCertificate2, ViewSyncFinalizeCertificate2, ViewSyncPreCommitCertificate2,
@@ -22,7 +23,7 @@ use hotshot_types::{
     vote::{Certificate, HasViewNumber, Vote, VoteAccumulator},
 };
 use snafu::Snafu;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
 #[derive(Snafu, Debug)]
 /// Stub of a vote error
@@ -62,11 +63,17 @@ pub trait AggregatableVote<
 
     /// return the Hotshot event for the completion of this CERT
     fn make_cert_event(certificate: CERT, key: &TYPES::SignatureKey) -> HotShotEvent<TYPES>;
+
+    /// return the evidence that the signer of two conflicting votes equivocated, if two votes of
+    /// this kind for different data in the same view are a protocol violation
+    fn make_equivocation(_first: VOTE, _second: VOTE) -> Option<Equivocation<TYPES>> {
+        None
+    }
 }
 
 impl<
         TYPES: NodeType,
-        VOTE: Vote<TYPES> + AggregatableVote<TYPES, VOTE, CERT>,
+        VOTE: Vote<TYPES> + AggregatableVote<TYPES, VOTE, CERT> + Clone,
         CERT: Certificate<TYPES, Voteable = VOTE::Commitment> + Debug,
     > VoteCollectionTaskState<TYPES, VOTE, CERT>
 {
@@ -92,6 +99,17 @@ impl<
         let Some(ref mut accumulator) = self.accumulator else {
             return None;
         };
+        if let Some(earlier_vote) = accumulator.get_conflicting_vote(vote) {
+            warn!(
+                "{} signed conflicting votes in view {}",
+                vote.get_signing_key(),
+                *self.view
+            );
+            if let Some(evidence) = VOTE::make_equivocation(earlier_vote, vote.clone()) {
+                broadcast_event(HotShotEvent::Equivocation(evidence), event_stream).await;
+            }
+            return None;
+        }
         match accumulator.accumulate(vote, &self.membership) {
             Either::Left(()) => None,
             Either::Right(cert) => {
@@ -200,6 +218,7 @@ where
     let new_accumulator = VoteAccumulator {
         vote_outcomes: HashMap::new(),
         signers: HashMap::new(),
+        signed_votes: HashMap::new(),
         phantom: PhantomData,
     };
 
@@ -260,6 +279,12 @@ impl<TYPES: NodeType> AggregatableVote<TYPES, QuorumVote<TYPES>, QuorumCertifica
     ) -> HotShotEvent<TYPES> {
         HotShotEvent::QCFormed(Left(certificate))
     }
+    fn make_equivocation(
+        first: QuorumVote<TYPES>,
+        second: QuorumVote<TYPES>,
+    ) -> Option<Equivocation<TYPES>> {
+        Some(Equivocation::QuorumVote(first, second))
+    }
 }
 
 impl<TYPES: NodeType> AggregatableVote<TYPES, UpgradeVote<TYPES>, UpgradeCertificate<TYPES>>
@@ -274,6 +299,12 @@ impl<TYPES: NodeType> AggregatableVote<TYPES, UpgradeVote<TYPES>, UpgradeCertifi
     ) -> HotShotEvent<TYPES> {
         HotShotEvent::UpgradeCertificateFormed(certificate)
     }
+    fn make_equivocation(
+        first: UpgradeVote<TYPES>,
+        second: UpgradeVote<TYPES>,
+    ) -> Option<Equivocation<TYPES>> {
+        Some(Equivocation::UpgradeVote(first, second))
+    }
 }
 
 impl<TYPES: NodeType> AggregatableVote<TYPES, DAVote<TYPES>, DACertificate<TYPES>>
@@ -288,6 +319,12 @@ impl<TYPES: NodeType> AggregatableVote<TYPES, DAVote<TYPES>, DACertificate<TYPES
     ) -> HotShotEvent<TYPES> {
         HotShotEvent::DACSend(certificate, key.clone())
     }
+    fn make_equivocation(
+        first: DAVote<TYPES>,
+        second: DAVote<TYPES>,
+    ) -> Option<Equivocation<TYPES>> {
+        Some(Equivocation::DAVote(first, second))
+    }
 }
 
 impl<TYPES: NodeType> AggregatableVote<TYPES, TimeoutVote<TYPES>, TimeoutCertificate<TYPES>>
@@ -9,7 +9,6 @@ use hotshot_example_types::{
 
 use crate::test_builder::TestMetadata;
 use commit::Committable;
-use ethereum_types::U256;
 use hotshot::{
     types::{BLSPubKey, SignatureKey, SystemContextHandle},
     HotShotConsensusApi, HotShotInitializer, Memberships, Networks, SystemContext,
@@ -17,7 +16,10 @@ use hotshot::{
 use hotshot_task_impls::events::HotShotEvent;
 use hotshot_types::{
     consensus::ConsensusMetricsValue,
-    data::{Leaf, QuorumProposal, VidScheme, ViewNumber},
+    data::{
+        vid_scheme, Leaf, QuorumProposal, VidDisperse, VidDisperseShare, VidParams, VidScheme,
+        ViewNumber,
+    },
     message::Proposal,
     simple_certificate::QuorumCertificate,
     simple_vote::SimpleVote,
@@ -87,24 +89,12 @@ pub async fn build_system_handle(
         _pd: PhantomData,
     };
 
-    let memberships = Memberships {
-        quorum_membership: <TestTypes as NodeType>::Membership::create_election(
-            known_nodes_with_stake.clone(),
-            quorum_election_config.clone(),
-        ),
-        da_membership: <TestTypes as NodeType>::Membership::create_election(
-            known_nodes_with_stake.clone(),
-            committee_election_config,
-        ),
-        vid_membership: <TestTypes as NodeType>::Membership::create_election(
-            known_nodes_with_stake.clone(),
-            quorum_election_config.clone(),
-        ),
-        view_sync_membership: <TestTypes as NodeType>::Membership::create_election(
-            known_nodes_with_stake.clone(),
-            quorum_election_config,
-        ),
-    };
+    let memberships = Memberships::create(
+        known_nodes_with_stake,
+        quorum_election_config,
+        committee_election_config,
+    )
+    .expect("Could not create the memberships");
 
     SystemContext::init(
         public_key,
@@ -116,6 +106,7 @@ pub async fn build_system_handle(
         networks_bundle,
         initializer,
         ConsensusMetricsValue::default(),
+        None,
     )
     .await
     .expect("Could not init hotshot")
@@ -167,7 +158,7 @@ pub fn build_assembled_sig<
     let real_qc_pp: <TYPES::SignatureKey as SignatureKey>::QCParams =
         <TYPES::SignatureKey as SignatureKey>::get_public_parameter(
             stake_table.clone(),
-            U256::from(CERT::threshold(membership)),
+            CERT::threshold(membership),
         );
     let total_nodes = stake_table.len();
     let signers = bitvec![1; total_nodes];
@@ -233,13 +224,12 @@ async fn build_quorum_proposal_and_signature(
     let block = <TestBlockPayload as TestableBlock>::genesis();
     let payload_commitment = vid_commitment(
         &block.encode().unwrap().collect(),
-        handle
-            .hotshot
-            .inner
-            .memberships
-            .quorum_membership
-            .total_nodes(),
-    );
+        VidParams::from_membership::<TestTypes>(
+            &handle.hotshot.inner.memberships.quorum_membership,
+            ViewNumber::genesis(),
+        ),
+    )
+    .unwrap();
     let mut parent_state = Arc::new(<TestValidatedState as ValidatedState>::from_header(
         &parent_leaf.block_header,
     ));
@@ -362,6 +352,20 @@ pub fn key_pair_for_id(node_id: u64) -> (<BLSPubKey as SignatureKey>::PrivateKey
     (private_key, public_key)
 }
 
+/// get the VID share of `public_key` from a signed VID dispersal
+/// # Panics
+/// if the dispersal has no share for `public_key`
+#[must_use]
+pub fn vid_share(
+    proposal: Proposal<TestTypes, VidDisperse<TestTypes>>,
+    public_key: &BLSPubKey,
+) -> Proposal<TestTypes, VidDisperseShare<TestTypes>> {
+    VidDisperseShare::to_share_proposals(proposal)
+        .into_iter()
+        .find(|share| share.data.recipient_key == *public_key)
+        .expect("No VID share for the key in the dispersal")
+}
+
 /// initialize VID
 /// # Panics
 /// if unable to create a [`VidScheme`]
@@ -370,15 +374,5 @@ pub fn vid_init<TYPES: NodeType>(
     membership: &TYPES::Membership,
     view_number: TYPES::Time,
 ) -> VidScheme {
-    let num_committee = membership.get_committee(view_number).len();
-
-    // calculate the last power of two
-    // TODO change after https://github.com/EspressoSystems/jellyfish/issues/339
-    // issue: https://github.com/EspressoSystems/HotShot/issues/2152
-    let chunk_size = 1 << num_committee.ilog2();
-
-    // TODO <https://github.com/EspressoSystems/HotShot/issues/1686>
-    let srs = hotshot_types::data::test_srs(num_committee);
-
-    VidScheme::new(chunk_size, num_committee, srs).unwrap()
+    vid_scheme(VidParams::from_membership::<TYPES>(membership, view_number)).unwrap()
 }
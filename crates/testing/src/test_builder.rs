@@ -1,4 +1,5 @@
 use hotshot::{traits::NetworkReliability, types::SignatureKey};
+use hotshot_constants::Version;
 use hotshot_orchestrator::config::ValidatorConfigFile;
 use hotshot_types::traits::election::Membership;
 use std::{num::NonZeroUsize, sync::Arc, time::Duration};
@@ -36,6 +37,15 @@ pub struct TimingData {
     pub propose_max_round_time: Duration,
 }
 
+/// a protocol upgrade every node schedules when the test starts
+#[derive(Clone, Debug, Copy)]
+pub struct UpgradeDescription {
+    /// The version to upgrade to
+    pub new_version: Version,
+    /// The first view of the new version
+    pub new_version_first_view: u64,
+}
+
 /// metadata describing a test
 #[derive(Clone, Debug)]
 pub struct TestMetadata {
@@ -63,6 +73,9 @@ pub struct TestMetadata {
     pub unreliable_network: Option<Box<dyn NetworkReliability>>,
     /// view sync check task
     pub view_sync_properties: ViewSyncTaskDescription,
+    /// upgrade scheduled at the start of the test, which every node must have switched to by
+    /// the end of it
+    pub upgrade: Option<UpgradeDescription>,
 }
 
 impl Default for TimingData {
@@ -193,6 +206,7 @@ impl Default for TestMetadata {
             ),
             unreliable_network: None,
             view_sync_properties: ViewSyncTaskDescription::Threshold(0, num_nodes),
+            upgrade: None,
         }
     }
 }
@@ -262,6 +276,10 @@ impl TestMetadata {
             election_config: Some(TYPES::Membership::default_election_config(
                 total_nodes as u64,
             )),
+            // The test setup is used for VID.
+            vid_srs_path: None,
+            authenticate_messages: false,
+            decided_view_retention: None,
         };
         let TimingData {
             next_view_timeout,
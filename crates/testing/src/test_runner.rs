@@ -30,6 +30,7 @@ use hotshot_types::{
 };
 use hotshot_types::{
     message::Message,
+    simple_vote::UpgradeProposalData,
     traits::{network::ConnectedNetwork, node_implementation::NodeImplementation},
 };
 use std::{
@@ -237,9 +238,28 @@ where
         }
 
         // Start hotshot
+        let mut started_handles = vec![];
         for node in nodes {
             if !late_start_nodes.contains(&node.node_id) {
+                if let Some(upgrade) = meta.upgrade {
+                    let old_version = node.handle.get_consensus().read().await.version;
+                    node.handle
+                        .schedule_upgrade(UpgradeProposalData {
+                            old_version,
+                            new_version: upgrade.new_version,
+                            new_version_hash: vec![],
+                            old_version_last_block: TYPES::Time::new(
+                                upgrade.new_version_first_view - 1,
+                            ),
+                            new_version_first_block: TYPES::Time::new(
+                                upgrade.new_version_first_view,
+                            ),
+                        })
+                        .await
+                        .expect("Could not schedule the upgrade");
+                }
                 node.handle.hotshot.start_consensus().await;
+                started_handles.push(node.handle);
             }
         }
         task_futs.push(safety_task.run());
@@ -297,6 +317,17 @@ where
             error_list.is_empty(),
             "TEST FAILED! Results: {error_list:?}"
         );
+
+        if let Some(upgrade) = meta.upgrade {
+            for handle in started_handles {
+                assert_eq!(
+                    handle.get_consensus().read().await.version,
+                    upgrade.new_version,
+                    "TEST FAILED! Node {} did not upgrade",
+                    handle.hotshot.inner.id
+                );
+            }
+        }
     }
 
     /// add nodes
@@ -372,24 +403,12 @@ where
             _pd: PhantomData,
         };
 
-        let memberships = Memberships {
-            quorum_membership: <TYPES as NodeType>::Membership::create_election(
-                known_nodes_with_stake.clone(),
-                quorum_election_config.clone(),
-            ),
-            da_membership: <TYPES as NodeType>::Membership::create_election(
-                known_nodes_with_stake.clone(),
-                committee_election_config(config.da_committee_size as u64),
-            ),
-            vid_membership: <TYPES as NodeType>::Membership::create_election(
-                known_nodes_with_stake.clone(),
-                quorum_election_config.clone(),
-            ),
-            view_sync_membership: <TYPES as NodeType>::Membership::create_election(
-                known_nodes_with_stake.clone(),
-                quorum_election_config,
-            ),
-        };
+        let memberships = Memberships::create(
+            known_nodes_with_stake,
+            quorum_election_config,
+            committee_election_config(config.da_committee_size as u64),
+        )
+        .expect("Could not create the memberships");
 
         SystemContext::new(
             public_key,
@@ -401,6 +420,7 @@ where
             network_bundle,
             initializer,
             ConsensusMetricsValue::default(),
+            None,
         )
         .await
         .expect("Could not init hotshot")
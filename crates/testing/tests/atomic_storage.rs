@@ -1,151 +1,277 @@
-#![cfg(foo)]
-use hotshot::{
-    certificate::QuorumCertificate,
-    demos::vdemo::{
-        random_quorom_certificate, random_transaction, random_validating_leaf, VDemoBlock,
-        VDemoState,
+use commit::Committable;
+use hotshot::traits::implementations::AtomicStorage;
+use hotshot::traits::Storage;
+use hotshot_example_types::{
+    block_types::{genesis_vid_commitment, TestBlockHeader, TestBlockPayload},
+    node_types::TestTypes,
+};
+use hotshot_types::{
+    data::{fake_commitment, random_commitment, Leaf, ViewNumber},
+    simple_certificate::{DACertificate, QuorumCertificate},
+    simple_vote::DAData,
+    traits::{
+        node_implementation::{ConsensusTime, NodeType},
+        signature_key::SignatureKey,
+        storage::{SafetyRecord, SafetyVote, StoredView, TestableStorage, ViewEntry},
     },
-    traits::{BlockPayload, Storage, ValidatedState},
 };
-use hotshot_types::{data::ViewNumber, traits::statesTestableState};
-use rand::thread_rng;
+use std::marker::PhantomData;
+use tracing::instrument;
 
-type AtomicStorage = hotshot::traits::implementations::AtomicStorage<DEntryState>;
+fn random_stored_view(view_number: <TestTypes as NodeType>::Time) -> StoredView<TestTypes> {
+    let payload = TestBlockPayload::genesis();
+    let header = TestBlockHeader {
+        block_number: *view_number,
+        payload_commitment: genesis_vid_commitment(),
+    };
+    let dummy_leaf_commit = fake_commitment::<Leaf<TestTypes>>();
+    // Every QC must certify a different leaf, as they are indexed by it
+    let data = hotshot_types::simple_vote::QuorumData {
+        leaf_commit: random_commitment(&mut rand::thread_rng()),
+    };
+    let commit = data.commit();
+    StoredView::from_qc_block_and_state(
+        QuorumCertificate {
+            is_genesis: view_number == <TestTypes as NodeType>::Time::genesis(),
+            data,
+            vote_commitment: commit,
+            signatures: None,
+            view_number,
+            _pd: PhantomData,
+        },
+        header,
+        Some(payload),
+        dummy_leaf_commit,
+        <<TestTypes as NodeType>::SignatureKey as SignatureKey>::genesis_proposer_pk(),
+    )
+}
 
+#[cfg(test)]
 #[cfg_attr(
     async_executor_impl = "tokio",
     tokio::test(flavor = "multi_thread", worker_threads = 2)
 )]
 #[cfg_attr(async_executor_impl = "async-std", async_std::test)]
-async fn test_happy_path_qcs() {
+#[instrument]
+async fn atomic_storage() {
+    let storage = AtomicStorage::<TestTypes>::construct_tmp_storage().unwrap();
+    let genesis = random_stored_view(ViewNumber::genesis());
+    storage
+        .append_single_view(genesis.clone())
+        .await
+        .expect("Could not append block");
+    assert_eq!(storage.get_anchored_view().await.unwrap(), genesis);
+    storage
+        .cleanup_storage_up_to_view(genesis.view_number)
+        .await
+        .unwrap();
+    assert_eq!(storage.get_anchored_view().await.unwrap(), genesis);
+    storage
+        .cleanup_storage_up_to_view(genesis.view_number + 1)
+        .await
+        .unwrap();
+    assert!(storage.get_anchored_view().await.is_err());
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn atomic_storage_reopen() {
     // This folder will be destroyed when the last handle to it closes
     let file = tempfile::tempdir().expect("Could not create temp dir");
     let path = file.path();
-    println!("Using store in {:?}", path);
-    let mut store = AtomicStorage::open(path).expect("Could not open atomic store");
-
-    // Add some certificates
-    let mut certs = Vec::<QuorumCertificate<H_256>>::new();
-    for i in 0..10 {
-        let cert = QuorumCertificate {
-            view_number: ViewNumber::new(i),
-            ..random_quorom_certificate()
-        };
-        println!("Inserting {:?}", cert);
-        store
-            .update(|mut m| {
-                let cert = cert.clone();
-                async move { m.insert_qc(cert).await }
-            })
-            .await
-            .unwrap();
-        certs.push(cert);
-    }
+    let mut storage = AtomicStorage::<TestTypes>::create(path).expect("Could not create store");
 
-    // read them all back 3 times
+    let views: Vec<_> = (0..10)
+        .map(|i| random_stored_view(ViewNumber::new(i)))
+        .collect();
+    storage
+        .append(
+            views
+                .iter()
+                .cloned()
+                .map(ViewEntry::Success)
+                .chain([ViewEntry::Failed(ViewNumber::new(10))])
+                .collect(),
+        )
+        .await
+        .unwrap();
+    storage.commit().await.unwrap();
+
+    // read everything back twice
     // 1st time: normal readback
     // 2nd: after dropping and re-opening the store
     for i in 0..2 {
         if i == 1 {
-            drop(store);
-            store = AtomicStorage::open(path).expect("Could not open atomic store");
+            drop(storage);
+            storage = AtomicStorage::open(path).expect("Could not open atomic store");
         }
 
-        for cert in &certs {
-            match store
-                .get_qc_for_view(cert.view_number)
-                .await
-                .expect("Could not read view_number")
-            {
-                Some(c) => {
-                    println!("read {:?}", c);
-                    assert_eq!(&c, cert);
-                }
-                None => panic!("Could not read {:?}: {:?}", cert.view_number, cert),
-            }
-            match store
-                .get_qc(&cert.block_hash)
-                .await
-                .expect("Could not read qc by hash")
-            {
-                Some(c) => {
-                    println!("read {:?}", c);
-                    assert_eq!(&c, cert);
-                }
-                None => panic!(
-                    "Could not read block_hash {:?}: {:?}",
-                    cert.block_hash, cert
-                ),
-            }
+        let state = storage.get_full_state().await;
+        assert_eq!(state.stored.len(), views.len());
+        assert!(state.failed.contains(&ViewNumber::new(10)));
+        for view in &views {
+            assert_eq!(state.stored.get(&view.view_number), Some(view));
+            assert_eq!(
+                storage.get_qc_for_view(view.view_number).await.as_ref(),
+                Some(&view.justify_qc)
+            );
         }
+        assert_eq!(
+            storage.get_anchored_view().await.unwrap(),
+            views.last().unwrap().clone()
+        );
     }
 }
 
+#[cfg(test)]
 #[cfg_attr(
     async_executor_impl = "tokio",
     tokio::test(flavor = "multi_thread", worker_threads = 2)
 )]
 #[cfg_attr(async_executor_impl = "async-std", async_std::test)]
-async fn test_happy_path_leaves() {
-    // This folder will be destroyed when the last handle to it closes
+#[instrument]
+async fn atomic_storage_uncommitted_is_discarded() {
+    let file = tempfile::tempdir().expect("Could not create temp dir");
+    let path = file.path();
+    let storage = AtomicStorage::<TestTypes>::create(path).expect("Could not create store");
+
+    let committed = random_stored_view(ViewNumber::new(1));
+    storage.append_single_view(committed.clone()).await.unwrap();
+    storage.commit().await.unwrap();
+
+    // Simulate the process being killed between `append` and `commit`
+    let uncommitted = random_stored_view(ViewNumber::new(2));
+    storage.append_single_view(uncommitted).await.unwrap();
+    storage
+        .cleanup_storage_up_to_view(ViewNumber::new(2))
+        .await
+        .unwrap();
+    drop(storage);
+
+    let storage = AtomicStorage::<TestTypes>::open(path).expect("Could not open atomic store");
+    let state = storage.get_full_state().await;
+    assert_eq!(state.stored.len(), 1);
+    assert_eq!(storage.get_anchored_view().await.unwrap(), committed);
+    assert!(storage.get_qc_for_view(ViewNumber::new(2)).await.is_none());
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn atomic_storage_restart_state() {
     let file = tempfile::tempdir().expect("Could not create temp dir");
     let path = file.path();
-    println!("Using store in {:?}", path);
-    let mut store = AtomicStorage::open(path).expect("Could not open atomic store");
-
-    // Add some leaves
-    let mut leaves = Vec::<Leaf<DEntryBlock, ValidatedState, H_256>>::new();
-    for _ in 0..10 {
-        let leaf = random_validating_leaf(DEntryBlock {
-            previous_block: StateHash::random(),
-            ..Default::default()
-        });
-        println!("Inserting {:?}", leaf);
-        store
-            .update(|mut m| {
-                let leaf = leaf.clone();
-                async move { m.insert_leaf(leaf).await }
-            })
+    let storage = AtomicStorage::<TestTypes>::create(path).expect("Could not create store");
+
+    let anchor = random_stored_view(ViewNumber::new(1));
+    let undecided: Vec<_> = (2..5)
+        .map(|i| random_stored_view(ViewNumber::new(i)))
+        .collect();
+    storage.append_single_view(anchor.clone()).await.unwrap();
+    storage
+        .append(
+            undecided
+                .iter()
+                .cloned()
+                .map(ViewEntry::Undecided)
+                .collect(),
+        )
+        .await
+        .unwrap();
+
+    let data = DAData {
+        payload_commit: genesis_vid_commitment(),
+    };
+    let da_cert = DACertificate::<TestTypes> {
+        is_genesis: false,
+        vote_commitment: data.commit(),
+        data,
+        signatures: None,
+        view_number: ViewNumber::new(4),
+        _pd: PhantomData,
+    };
+    storage.append_da_cert(da_cert.clone()).await.unwrap();
+    let mut safety_record = SafetyRecord::default();
+    assert!(safety_record.record_vote(SafetyVote::Quorum(ViewNumber::new(4))));
+    assert!(safety_record.update_locked_qc(undecided[2].justify_qc.clone()));
+    storage
+        .update_safety_record(safety_record.clone())
+        .await
+        .unwrap();
+    storage.commit().await.unwrap();
+    drop(storage);
+
+    let storage = AtomicStorage::<TestTypes>::open(path).expect("Could not open atomic store");
+    assert_eq!(storage.get_anchored_view().await.unwrap(), anchor);
+    assert_eq!(storage.get_undecided_views().await.unwrap(), undecided);
+    assert_eq!(
+        storage
+            .get_da_certs()
             .await
-            .unwrap();
-        leaves.push(leaf);
-    }
+            .unwrap()
+            .get(&ViewNumber::new(4)),
+        Some(&da_cert)
+    );
+    assert_eq!(storage.get_safety_record().await.unwrap(), safety_record);
+
+    // Deciding a view drops the undecided views up to it
+    storage
+        .append_single_view(undecided[1].clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        storage.get_undecided_views().await.unwrap(),
+        vec![undecided[2].clone()]
+    );
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn atomic_storage_cleanup_prunes_qcs() {
+    let file = tempfile::tempdir().expect("Could not create temp dir");
+    let path = file.path();
+    let mut storage = AtomicStorage::<TestTypes>::create(path).expect("Could not create store");
+
+    let views: Vec<_> = (0..5)
+        .map(|i| random_stored_view(ViewNumber::new(i)))
+        .collect();
+    storage
+        .append(views.iter().cloned().map(ViewEntry::Success).collect())
+        .await
+        .unwrap();
+    storage
+        .cleanup_storage_up_to_view(ViewNumber::new(3))
+        .await
+        .unwrap();
+    storage.commit().await.unwrap();
 
-    // read them all back 2 times
-    // 1st time: normal readback
-    // 2nd: after dropping and re-opening the store
     for i in 0..2 {
         if i == 1 {
-            drop(store);
-            store = AtomicStorage::open(path).expect("Could not open atomic store");
+            drop(storage);
+            storage = AtomicStorage::open(path).expect("Could not open atomic store");
         }
-
-        for leaf in &leaves {
-            match store
-                .get_leaf(&leaf.hash())
-                .await
-                .expect("Could not read leaf hash")
-            {
-                Some(l) => {
-                    println!("read {:?}", l);
-                    assert_eq!(&l, leaf);
-                }
-                None => {
-                    panic!("Could not read leaf hash {:?}: {:?}", leaf.hash(), leaf)
-                }
-            }
-            let hash = BlockContents::hash(&leaf.deltas);
-            match store
-                .get_leaf_by_block(&hash)
-                .await
-                .expect("Could not read leaf by block")
-            {
-                Some(l) => {
-                    println!("read {:?}", l);
-                    assert_eq!(&l, leaf);
-                }
-                None => panic!("Could not read leaf hash {:?}: {:?}", hash, leaf),
-            }
+        for view in &views[..3] {
+            assert!(storage.get_qc_for_view(view.view_number).await.is_none());
+        }
+        for view in &views[3..] {
+            assert_eq!(
+                storage.get_qc_for_view(view.view_number).await.as_ref(),
+                Some(&view.justify_qc)
+            );
         }
     }
 }
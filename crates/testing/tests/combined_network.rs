@@ -1,5 +1,6 @@
-use std::time::Duration;
+use std::{collections::BTreeSet, sync::Arc, time::Duration};
 
+use hotshot_constants::VERSION_0_1;
 use hotshot_example_types::node_types::{CombinedImpl, TestTypes};
 use hotshot_testing::{
     completion_task::{CompletionTaskDescription, TimeBasedCompletionTaskDescription},
@@ -10,8 +11,68 @@ use hotshot_testing::{
 use rand::Rng;
 use tracing::instrument;
 
-use hotshot::traits::implementations::{calculate_hash_of, Cache};
+use hotshot::traits::implementations::{
+    digest_of, CombinedNetworks, CombinedRoute, ConsecutiveFailures, MasterMap, MemoryNetwork,
+    MessageDedup, NetworkingMetricsValue, UnderlyingCombinedNetworks,
+};
+use hotshot::types::SignatureKey;
 use hotshot_example_types::block_types::TestTransaction;
+use hotshot_types::{
+    data::ViewNumber,
+    message::{DataMessage, Message, MessageKind, MessagePurpose},
+    signature_key::BLSPubKey,
+    traits::{
+        network::{ConnectedNetwork, NetworkMsg, TransmitType},
+        node_implementation::ConsensusTime,
+    },
+};
+
+/// A memory network carrying the messages of the test types
+type TestMemoryNetwork = MemoryNetwork<Message<TestTypes>, BLSPubKey>;
+
+/// Combine two memory networks for the node with the given key
+fn combined_memory_network(
+    pub_key: BLSPubKey,
+    primary: &Arc<MasterMap<Message<TestTypes>, BLSPubKey>>,
+    secondary: &Arc<MasterMap<Message<TestTypes>, BLSPubKey>>,
+) -> CombinedNetworks<TestTypes, TestMemoryNetwork, TestMemoryNetwork> {
+    CombinedNetworks::new(Arc::new(UnderlyingCombinedNetworks(
+        MemoryNetwork::new(
+            pub_key,
+            NetworkingMetricsValue::default(),
+            primary.clone(),
+            None,
+        ),
+        MemoryNetwork::new(
+            pub_key,
+            NetworkingMetricsValue::default(),
+            secondary.clone(),
+            None,
+        ),
+    )))
+}
+
+/// A transaction submitted by `sender`
+fn transaction_message(sender: BLSPubKey, seed: u8) -> Message<TestTypes> {
+    Message {
+        version: VERSION_0_1,
+        sender,
+        kind: MessageKind::Data(DataMessage::SubmitTransaction(
+            TestTransaction(vec![seed; 8]),
+            ViewNumber::new(0),
+        )),
+        signature: None,
+    }
+}
+
+/// Receive `count` direct messages from a network
+async fn recv_direct(network: &TestMemoryNetwork, count: usize) -> Vec<Message<TestTypes>> {
+    let mut messages = Vec::new();
+    while messages.len() < count {
+        messages.append(&mut network.recv_msgs(TransmitType::Direct).await.unwrap());
+    }
+    messages
+}
 
 #[cfg(test)]
 #[cfg_attr(
@@ -20,12 +81,13 @@ use hotshot_example_types::block_types::TestTransaction;
 )]
 #[cfg_attr(async_executor_impl = "async-std", async_std::test)]
 #[instrument]
-async fn test_hash_calculation() {
-    let message1 = TestTransaction(vec![0; 32]);
-    let message2 = TestTransaction(vec![1; 32]);
+async fn test_digest_calculation() {
+    let key = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0).0;
+    let message1 = transaction_message(key, 0).encode().unwrap();
+    let message2 = transaction_message(key, 1).encode().unwrap();
 
-    assert_eq!(calculate_hash_of(&message1), calculate_hash_of(&message1));
-    assert_ne!(calculate_hash_of(&message1), calculate_hash_of(&message2));
+    assert_eq!(digest_of(&message1), digest_of(&message1));
+    assert_ne!(digest_of(&message1), digest_of(&message2));
 }
 
 #[cfg(test)]
@@ -35,24 +97,175 @@ async fn test_hash_calculation() {
 )]
 #[cfg_attr(async_executor_impl = "async-std", async_std::test)]
 #[instrument]
-async fn test_cache_integrity() {
-    let message1 = TestTransaction(vec![0; 32]);
-    let message2 = TestTransaction(vec![1; 32]);
+async fn test_dedup_integrity() {
+    let digest1 = digest_of(&[0; 32]);
+    let digest2 = digest_of(&[1; 32]);
 
-    let mut cache = Cache::new(3);
+    let mut dedup = MessageDedup::new(2, 100);
 
     // test insertion integrity
-    cache.insert(calculate_hash_of(&message1));
-    cache.insert(calculate_hash_of(&message2));
+    assert!(dedup.insert(1, digest1));
+    assert!(dedup.insert(1, digest2));
+    assert!(dedup.contains(1, &digest1));
+    assert!(dedup.contains(1, &digest2));
+
+    // check that duplicates are dropped, unless they are for another view
+    assert!(!dedup.insert(1, digest1));
+    assert!(dedup.insert(2, digest1));
+    assert_eq!(dedup.len(), 3);
+}
+
+/// Messages are forgotten as views advance, or when too many are remembered
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn test_dedup_eviction() {
+    let mut dedup = MessageDedup::new(2, 4);
+    for view in 1..=3 {
+        assert!(dedup.insert(view, digest_of(&view.to_le_bytes())));
+    }
+    assert_eq!(dedup.len(), 3);
+
+    // the fourth view pushes the first out of the window
+    assert!(dedup.insert(4, digest_of(&4u64.to_le_bytes())));
+    assert_eq!(dedup.latest_view(), 4);
+    assert_eq!(dedup.len(), 3);
+    assert!(!dedup.contains(1, &digest_of(&1u64.to_le_bytes())));
+    assert!(dedup.contains(2, &digest_of(&2u64.to_le_bytes())));
+
+    // messages older than the window are never dropped
+    assert!(dedup.insert(1, digest_of(&1u64.to_le_bytes())));
+    assert!(dedup.insert(1, digest_of(&1u64.to_le_bytes())));
+
+    // the oldest view is forgotten once too many messages are remembered
+    assert!(dedup.insert(4, digest_of(&[4])));
+    assert!(dedup.insert(4, digest_of(&[5])));
+    assert_eq!(dedup.len(), 4);
+    assert!(!dedup.contains(2, &digest_of(&2u64.to_le_bytes())));
+    assert!(dedup.contains(3, &digest_of(&3u64.to_le_bytes())));
+}
+
+/// A message for a view far ahead does not make the dedup layer forget the current views
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn test_dedup_far_ahead_view() {
+    let mut dedup = MessageDedup::new(2, 100);
+    for view in 1..=3 {
+        assert!(dedup.insert(view, digest_of(&view.to_le_bytes())));
+    }
 
-    assert!(cache.contains(calculate_hash_of(&message1)));
-    assert!(cache.contains(calculate_hash_of(&message2)));
+    assert!(dedup.insert(u64::MAX, digest_of(&[0])));
+    assert_eq!(dedup.latest_view(), 4);
+    assert!(!dedup.insert(3, digest_of(&3u64.to_le_bytes())));
+    assert!(!dedup.insert(u64::MAX, digest_of(&[0])));
+}
+
+/// Messages without a view are deduplicated however far the views advance
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn test_dedup_viewless() {
+    let mut dedup = MessageDedup::new(2, 2);
+    assert!(dedup.insert_viewless(digest_of(&[0])));
+    for view in 1..=10 {
+        assert!(dedup.insert(view, digest_of(&view.to_le_bytes())));
+    }
+    assert!(!dedup.insert_viewless(digest_of(&[0])));
+
+    // the oldest are forgotten once too many are remembered
+    assert!(dedup.insert_viewless(digest_of(&[1])));
+    assert!(dedup.insert_viewless(digest_of(&[2])));
+    assert!(dedup.insert_viewless(digest_of(&[0])));
+}
+
+/// Messages sent on both networks of a combined network are only received once
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn test_combined_memory_networks_dedup() {
+    async_compatibility_layer::logging::setup_logging();
+    let primary = MasterMap::new();
+    let secondary = MasterMap::new();
+    let key_a = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0).0;
+    let key_b = BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0;
+    let network_a = combined_memory_network(key_a, &primary, &secondary);
+    let network_b = combined_memory_network(key_b, &primary, &secondary);
+
+    network_a
+        .broadcast_message(transaction_message(key_a, 0), BTreeSet::from([key_b]))
+        .await
+        .unwrap();
+    let messages = network_b.recv_msgs(TransmitType::Broadcast).await.unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].sender, key_a);
+}
 
-    // check that the cache is not modified on duplicate entries
-    cache.insert(calculate_hash_of(&message1));
-    assert!(cache.contains(calculate_hash_of(&message1)));
-    assert!(cache.contains(calculate_hash_of(&message2)));
-    assert_eq!(cache.len(), 2);
+/// Nodes only reachable on the secondary network still get messages, and the failover strategy
+/// and routes decide when the primary is tried
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn test_combined_memory_networks_failover() {
+    async_compatibility_layer::logging::setup_logging();
+    let primary = MasterMap::new();
+    let secondary = MasterMap::new();
+    let key_a = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0).0;
+    let key_b = BLSPubKey::generated_from_seed_indexed([0u8; 32], 1).0;
+    let strategy = Arc::new(ConsecutiveFailures::new(2, 3));
+    let network_a = combined_memory_network(key_a, &primary, &secondary)
+        .with_failover_strategy(strategy.clone());
+    // `b` is not on the primary network `a` is on
+    let network_b = combined_memory_network(key_b, &MasterMap::new(), &secondary);
+
+    // the primary is presumed down after two failures, and retried on the fifth message
+    for seed in 0..5 {
+        network_a
+            .direct_message(transaction_message(key_a, seed), key_b)
+            .await
+            .unwrap();
+    }
+    assert_eq!(strategy.consecutive_failures(), 3);
+    assert_eq!(recv_direct(network_b.secondary(), 5).await.len(), 5);
+
+    // messages routed to the primary fall back to the secondary
+    let network_a = network_a.with_route(MessagePurpose::Data, CombinedRoute::Primary);
+    network_a
+        .direct_message(transaction_message(key_a, 5), key_b)
+        .await
+        .unwrap();
+    assert_eq!(recv_direct(network_b.secondary(), 1).await.len(), 1);
+
+    // messages routed to the secondary never try the primary
+    let network_a = network_a.with_route(MessagePurpose::Data, CombinedRoute::Secondary);
+    for seed in 6..9 {
+        network_a
+            .direct_message(transaction_message(key_a, seed), key_b)
+            .await
+            .unwrap();
+    }
+    assert_eq!(strategy.consecutive_failures(), 3);
+    assert_eq!(recv_direct(network_b.secondary(), 3).await.len(), 3);
 }
 
 /// A run with both the webserver and libp2p functioning properly
@@ -187,13 +187,16 @@ async fn test_consensus_with_vid() {
     use hotshot_testing::task_helpers::build_cert;
     use hotshot_testing::task_helpers::build_system_handle;
     use hotshot_testing::task_helpers::vid_init;
+    use hotshot_testing::task_helpers::vid_share;
     use hotshot_types::data::VidSchemeTrait;
     use hotshot_types::simple_certificate::DACertificate;
     use hotshot_types::simple_vote::DAData;
     use hotshot_types::simple_vote::DAVote;
     use hotshot_types::traits::block_contents::{vid_commitment, TestableBlock};
     use hotshot_types::{
-        data::VidDisperse, message::Proposal, traits::node_implementation::NodeType,
+        data::{VidDisperse, VidParams},
+        message::Proposal,
+        traits::node_implementation::NodeType,
     };
     use std::marker::PhantomData;
 
@@ -246,8 +249,9 @@ async fn test_consensus_with_vid() {
     let block = <TestBlockPayload as TestableBlock>::genesis();
     let da_payload_commitment = vid_commitment(
         &block.encode().unwrap().collect(),
-        quorum_membership.total_nodes(),
-    );
+        VidParams::from_membership::<TestTypes>(&quorum_membership, ViewNumber::new(2)),
+    )
+    .unwrap();
     let da_data = DAData {
         payload_commit: da_payload_commitment,
     };
@@ -260,7 +264,10 @@ async fn test_consensus_with_vid() {
             &private_key_view2,
         );
     input.push(HotShotEvent::DACRecv(created_dac_view2.clone()));
-    input.push(HotShotEvent::VidDisperseRecv(vid_proposal.clone(), pub_key));
+    input.push(HotShotEvent::VidDisperseRecv(
+        vid_share(vid_proposal, &pub_key),
+        pub_key,
+    ));
 
     // Send a proposal, vote on said proposal, update view based on proposal QC, receive vote as next leader
     input.push(HotShotEvent::QuorumProposalRecv(
@@ -286,3 +293,159 @@ async fn test_consensus_with_vid() {
 
     run_harness(input, output, consensus_state, false).await;
 }
+
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_consensus_double_proposal() {
+    use hotshot::tasks::create_consensus_state;
+    use hotshot_task_impls::harness::run_harness;
+    use hotshot_testing::task_helpers::build_system_handle;
+    use hotshot_types::{
+        event::{Equivocation, EventType},
+        message::Proposal,
+        signature_key::BLSPubKey,
+        traits::signature_key::SignatureKey,
+    };
+    use std::marker::PhantomData;
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(2).await.0;
+    let mut events = handle.hotshot.inner.output_event_stream.1.activate_cloned();
+    // We assign node's key pair rather than read from config file since it's a test
+    let (private_key, public_key) = key_pair_for_id(1);
+
+    let mut input = Vec::new();
+    let mut output = HashMap::new();
+
+    // The leader of view 1 signs a second proposal for another block in the same view
+    let proposal = build_quorum_proposal(&handle, &private_key, 1).await;
+    let mut conflicting_data = proposal.data.clone();
+    conflicting_data.block_header.block_number += 1;
+    let conflicting_proposal = Proposal {
+        signature: BLSPubKey::sign(
+            &private_key,
+            Leaf::from_proposal(&conflicting_data).commit().as_ref(),
+        )
+        .expect("Failed to sign the conflicting proposal"),
+        data: conflicting_data,
+        _pd: PhantomData,
+    };
+
+    input.push(HotShotEvent::QuorumProposalRecv(
+        proposal.clone(),
+        public_key,
+    ));
+    input.push(HotShotEvent::QuorumProposalRecv(
+        conflicting_proposal,
+        public_key,
+    ));
+    input.push(HotShotEvent::Shutdown);
+
+    // We only vote on the first proposal
+    if let GeneralConsensusMessage::Vote(vote) = build_vote(&handle, proposal.data).await {
+        output.insert(HotShotEvent::QuorumVoteSend(vote.clone()), 1);
+    }
+    output.insert(HotShotEvent::ViewChange(ViewNumber::new(1)), 1);
+
+    let consensus_state =
+        create_consensus_state(handle.hotshot.inner.output_event_stream.0.clone(), &handle).await;
+
+    run_harness(input, output, consensus_state, false).await;
+
+    // and report the second one as evidence against the leader
+    let mut reported = false;
+    while let Ok(event) = events.try_recv() {
+        if let EventType::Equivocation {
+            evidence: evidence @ Equivocation::QuorumProposal(..),
+        } = event.event
+        {
+            assert!(evidence.is_valid());
+            assert_eq!(evidence.signer(), public_key);
+            reported = true;
+        }
+    }
+    assert!(reported, "The conflicting proposal was not reported");
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_consensus_rejects_invalid_vid_share() {
+    use hotshot::tasks::create_consensus_state;
+    use hotshot_example_types::block_types::TestTransaction;
+    use hotshot_task_impls::harness::run_harness;
+    use hotshot_testing::task_helpers::{build_system_handle, vid_init, vid_share};
+    use hotshot_types::{
+        data::{VidDisperse, VidSchemeTrait},
+        message::Proposal,
+        signature_key::BLSPubKey,
+        traits::signature_key::SignatureKey,
+    };
+    use std::marker::PhantomData;
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(2).await.0;
+    let (_, public_key) = key_pair_for_id(2);
+    let quorum_membership = handle.hotshot.inner.memberships.quorum_membership.clone();
+
+    // The share of this node in a dispersal signed by the leader of `view`
+    let share = |view: u64, transaction: u8| {
+        let (leader_private_key, leader_public_key) = key_pair_for_id(view);
+        let view = ViewNumber::new(view);
+        let vid = vid_init::<TestTypes>(&quorum_membership, view);
+        let encoded_transactions =
+            TestTransaction::encode(vec![TestTransaction(vec![transaction])]).unwrap();
+        let vid_disperse = vid.disperse(&encoded_transactions).unwrap();
+        let signature = BLSPubKey::sign(&leader_private_key, vid_disperse.commit.as_ref())
+            .expect("Failed to sign payload commitment");
+        let proposal = Proposal {
+            data: VidDisperse::from_membership(
+                view,
+                vid_disperse,
+                &quorum_membership.clone().into(),
+            ),
+            signature,
+            _pd: PhantomData,
+        };
+        (vid_share(proposal, &public_key), leader_public_key)
+    };
+
+    // The leader of view 1 signs one payload, but sends the common data of another.
+    let (mut tampered, leader_view1) = share(1, 0);
+    tampered.data.common = share(1, 1).0.data.common;
+    let (valid, leader_view2) = share(2, 0);
+
+    let mut input = Vec::new();
+    let mut output = HashMap::new();
+
+    input.push(HotShotEvent::ViewChange(ViewNumber::new(1)));
+    input.push(HotShotEvent::VidDisperseRecv(tampered, leader_view1));
+    input.push(HotShotEvent::VidDisperseRecv(valid.clone(), leader_view2));
+    input.push(HotShotEvent::Shutdown);
+
+    output.insert(HotShotEvent::ViewChange(ViewNumber::new(1)), 1);
+    output.insert(HotShotEvent::Shutdown, 1);
+
+    let consensus_state =
+        create_consensus_state(handle.hotshot.inner.output_event_stream.0.clone(), &handle).await;
+    run_harness(input, output, consensus_state, false).await;
+
+    let consensus = handle.get_consensus();
+    let consensus = consensus.read().await;
+    assert!(!consensus.saved_vid_shares.contains_key(&ViewNumber::new(1)));
+    assert_eq!(
+        consensus.saved_vid_shares.get(&ViewNumber::new(2)),
+        Some(&valid)
+    );
+}
@@ -5,12 +5,11 @@ use hotshot_example_types::{
 };
 use hotshot_task_impls::{da::DATaskState, events::HotShotEvent};
 use hotshot_types::{
-    data::{DAProposal, ViewNumber},
+    data::{DAProposal, VidParams, ViewNumber},
     simple_vote::{DAData, DAVote},
     traits::{
         block_contents::vid_commitment,
         consensus_api::ConsensusApi,
-        election::Membership,
         node_implementation::{ConsensusTime, NodeType},
     },
 };
@@ -40,13 +39,12 @@ async fn test_da_task() {
     let encoded_transactions = TestTransaction::encode(transactions.clone()).unwrap();
     let payload_commitment = vid_commitment(
         &encoded_transactions,
-        handle
-            .hotshot
-            .inner
-            .memberships
-            .quorum_membership
-            .total_nodes(),
-    );
+        VidParams::from_membership::<TestTypes>(
+            &handle.hotshot.inner.memberships.quorum_membership,
+            ViewNumber::new(2),
+        ),
+    )
+    .unwrap();
     let encoded_transactions_hash = Sha256::digest(&encoded_transactions);
 
     let signature =
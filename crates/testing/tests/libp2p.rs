@@ -1,14 +1,165 @@
-use std::time::Duration;
+use std::{collections::BTreeSet, time::Duration};
 
-use hotshot_example_types::node_types::{Libp2pImpl, TestTypes};
+use async_compatibility_layer::art::async_timeout;
+use futures::future::join_all;
+use hotshot::{traits::implementations::Libp2pNetwork, types::SignatureKey};
+use hotshot_constants::VERSION_0_1;
+use hotshot_example_types::{
+    block_types::TestTransaction,
+    node_types::{Libp2pImpl, TestTypes},
+};
 use hotshot_testing::{
     completion_task::{CompletionTaskDescription, TimeBasedCompletionTaskDescription},
     overall_safety_task::OverallSafetyPropertiesDescription,
     spinning_task::{ChangeNode, SpinningTaskDescription, UpDown},
     test_builder::{TestMetadata, TimingData},
 };
+use hotshot_types::{
+    data::ViewNumber,
+    message::{DataMessage, Message, MessageKind},
+    signature_key::BLSPubKey,
+    traits::{
+        network::{
+            ConnectedNetwork, ConsensusIntentEvent, TestableNetworkingImplementation, TransmitType,
+        },
+        node_implementation::ConsensusTime,
+    },
+};
 use tracing::instrument;
 
+/// The libp2p network of the test types
+type TestLibp2pNetwork = Libp2pNetwork<Message<TestTypes>, BLSPubKey>;
+
+/// Committee topics only depend on the members of the committee
+#[test]
+fn test_committee_topic() {
+    let keys: Vec<_> = (0..4)
+        .map(|i| BLSPubKey::generated_from_seed_indexed([0u8; 32], i).0)
+        .collect();
+    let committee = BTreeSet::from([keys[0], keys[1], keys[2]]);
+    let reordered = BTreeSet::from([keys[2], keys[0], keys[1]]);
+    let other = BTreeSet::from([keys[0], keys[1], keys[3]]);
+
+    assert_eq!(
+        TestLibp2pNetwork::committee_topic(&committee),
+        TestLibp2pNetwork::committee_topic(&reordered)
+    );
+    assert_ne!(
+        TestLibp2pNetwork::committee_topic(&committee),
+        TestLibp2pNetwork::committee_topic(&other)
+    );
+}
+
+/// Broadcasts to recipient sets without a topic are delivered as direct messages
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn test_libp2p_broadcast_without_topic() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+    let generator = <TestLibp2pNetwork as TestableNetworkingImplementation<TestTypes>>::generator(
+        6, 5, 0, 2, false, None,
+    );
+    let networks: Vec<_> = (0..6).map(|node_id| generator(node_id).0).collect();
+    join_all(networks.iter().map(|network| network.wait_for_ready())).await;
+
+    let sender = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0).0;
+    let recipients: BTreeSet<_> = (4..6)
+        .map(|i| BLSPubKey::generated_from_seed_indexed([0u8; 32], i).0)
+        .collect();
+    let message = Message {
+        version: VERSION_0_1,
+        sender,
+        kind: MessageKind::Data(DataMessage::SubmitTransaction(
+            TestTransaction(vec![0; 8]),
+            ViewNumber::new(0),
+        )),
+        signature: None,
+    };
+    networks[0]
+        .broadcast_message(message, recipients)
+        .await
+        .unwrap();
+
+    for network in &networks[4..] {
+        let received = network.recv_msgs(TransmitType::Direct).await.unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].sender, sender);
+    }
+}
+
+/// When the committee changes, its members subscribe to its topic and broadcasts to it are gossiped
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn test_libp2p_committee_change() {
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+    let generator = <TestLibp2pNetwork as TestableNetworkingImplementation<TestTypes>>::generator(
+        6, 5, 0, 2, false, None,
+    );
+    let networks: Vec<_> = (0..6).map(|node_id| generator(node_id).0).collect();
+    join_all(networks.iter().map(|network| network.wait_for_ready())).await;
+
+    let keys: Vec<_> = (0..6)
+        .map(|i| BLSPubKey::generated_from_seed_indexed([0u8; 32], i).0)
+        .collect();
+    let previous: BTreeSet<_> = keys.iter().copied().collect();
+    let current: BTreeSet<_> = keys[..5].iter().copied().collect();
+    join_all(networks.iter().map(|network| {
+        network.inject_consensus_info(ConsensusIntentEvent::CommitteeChanged(
+            1,
+            previous.clone(),
+            current.clone(),
+        ))
+    }))
+    .await;
+    // The new subscriptions take a few heartbeats to propagate through the mesh, so broadcast
+    // fresh messages until every member of the new committee has received one.
+    let mut pending: BTreeSet<usize> = (1..5).collect();
+    async_timeout(Duration::from_secs(30), async {
+        let mut round = 0u64;
+        while !pending.is_empty() {
+            let message = Message {
+                version: VERSION_0_1,
+                sender: keys[0],
+                kind: MessageKind::Data(DataMessage::SubmitTransaction(
+                    TestTransaction(round.to_le_bytes().to_vec()),
+                    ViewNumber::new(0),
+                )),
+                signature: None,
+            };
+            networks[0]
+                .broadcast_message(message, current.clone())
+                .await
+                .unwrap();
+            round += 1;
+
+            for node in pending.clone() {
+                let Ok(received) = async_timeout(
+                    Duration::from_millis(500),
+                    networks[node].recv_msgs(TransmitType::Broadcast),
+                )
+                .await
+                else {
+                    continue;
+                };
+                let received = received.unwrap();
+                assert!(received.iter().all(|message| message.sender == keys[0]));
+                pending.remove(&node);
+            }
+        }
+    })
+    .await
+    .expect("committee broadcast was not gossiped");
+}
+
 /// libp2p network test
 #[cfg_attr(
     async_executor_impl = "tokio",
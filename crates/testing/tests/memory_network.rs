@@ -112,6 +112,7 @@ fn gen_messages(num_messages: u64, seed: u64, pk: BLSPubKey) -> Vec<Message<Test
                 TestTransaction(bytes.to_vec()),
                 <ViewNumber as ConsensusTime>::new(0),
             )),
+            signature: None,
         };
         messages.push(message);
     }
@@ -1,7 +1,7 @@
 use hotshot::{types::SignatureKey, HotShotConsensusApi};
 use hotshot_example_types::node_types::{MemoryImpl, TestTypes};
 use hotshot_task_impls::events::HotShotEvent;
-use hotshot_testing::task_helpers::{build_quorum_proposal, vid_init};
+use hotshot_testing::task_helpers::{build_quorum_proposal, vid_init, vid_share};
 use hotshot_types::{
     data::{DAProposal, VidSchemeTrait, ViewNumber},
     traits::{consensus_api::ConsensusApi, node_implementation::ConsensusTime},
@@ -137,7 +137,10 @@ async fn test_network_task() {
         HotShotEvent::QuorumProposalRecv(quorum_proposal, pub_key),
         1,
     );
-    output.insert(HotShotEvent::VidDisperseRecv(vid_proposal, pub_key), 1);
+    output.insert(
+        HotShotEvent::VidDisperseRecv(vid_share(vid_proposal, &pub_key), pub_key),
+        1,
+    );
     output.insert(HotShotEvent::DAProposalRecv(da_proposal, pub_key), 1);
 
     // let build_fn = |task_runner, _| async { task_runner };
@@ -147,3 +150,76 @@ async fn test_network_task() {
     // `allow_extra_output` to `true` for deterministic test result.
     // run_harness(input, output, Some(event_stream), build_fn, true).await;
 }
+
+/// Create a transaction message from the given sender
+fn transaction_message(
+    sender: hotshot_types::signature_key::BLSPubKey,
+    transaction: u8,
+) -> hotshot_types::message::Message<TestTypes> {
+    use hotshot_example_types::block_types::TestTransaction;
+    use hotshot_types::message::{DataMessage, Message, MessageKind};
+
+    Message {
+        version: hotshot_constants::VERSION_0_1,
+        sender,
+        kind: MessageKind::Data(DataMessage::SubmitTransaction(
+            TestTransaction(vec![transaction]),
+            ViewNumber::new(0),
+        )),
+        signature: None,
+    }
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_network_message_authentication() {
+    use hotshot_example_types::block_types::TestTransaction;
+    use hotshot_task_impls::network::NetworkMessageTaskState;
+    use hotshot_types::{
+        consensus::ConsensusMetricsValue, message::Message, signature_key::BLSPubKey,
+    };
+    use std::sync::Arc;
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let (event_stream, mut events) = async_broadcast::broadcast(10);
+    let mut state = NetworkMessageTaskState::<TestTypes> {
+        event_stream,
+        authenticate_messages: false,
+        metrics: Arc::new(ConsensusMetricsValue::default()),
+    };
+    let (sender, private_key) = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0);
+    let (other_node, _) = BLSPubKey::generated_from_seed_indexed([0u8; 32], 1);
+
+    let mut signed = transaction_message(sender, 1);
+    signed.sign(&private_key).unwrap();
+    let unsigned = transaction_message(sender, 2);
+    // Another node claiming to have sent a message it did not sign
+    let spoofed = Message {
+        sender: other_node,
+        ..signed.clone()
+    };
+    let messages = vec![signed, unsigned, spoofed];
+
+    // Without authentication, only messages with an invalid signature are dropped.
+    state.handle_messages(messages.clone()).await;
+    assert_eq!(
+        events.try_recv().unwrap(),
+        HotShotEvent::TransactionsRecv(vec![TestTransaction(vec![1]), TestTransaction(vec![2])])
+    );
+    assert!(events.try_recv().is_err());
+
+    // With authentication, unsigned messages are dropped too.
+    state.authenticate_messages = true;
+    state.handle_messages(messages).await;
+    assert_eq!(
+        events.try_recv().unwrap(),
+        HotShotEvent::TransactionsRecv(vec![TestTransaction(vec![1])])
+    );
+    assert!(events.try_recv().is_err());
+}
@@ -0,0 +1,221 @@
+use commit::Committable;
+use hotshot::{types::SignatureKey, HotShotConsensusApi};
+use hotshot_constants::VID_SHARE_RETENTION_VIEWS;
+use hotshot_example_types::{
+    block_types::{TestBlockHeader, TestBlockPayload, TestTransaction},
+    node_types::{MemoryImpl, TestTypes},
+};
+use hotshot_task_impls::{
+    events::HotShotEvent, recovery::PayloadRecoveryTaskState, request::REQUEST_TIMEOUT_VIEWS,
+};
+use hotshot_testing::task_helpers::{build_system_handle, key_pair_for_id, vid_init};
+use hotshot_types::{
+    data::{Leaf, VidDisperse, VidDisperseShare, VidParams, VidSchemeTrait, ViewNumber},
+    message::Proposal,
+    simple_certificate::QuorumCertificate,
+    traits::{
+        consensus_api::ConsensusApi,
+        node_implementation::{ConsensusTime, NodeType},
+    },
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    marker::PhantomData,
+};
+
+/// The leaf of view 2 decided without its payload by node 2, which led view 2 and dispersed the
+/// payload. Returns the leaf with the VID share of node 2, which is saved in its consensus state,
+/// and the shares of the other nodes.
+async fn decide_without_payload(
+    handle: &hotshot::types::SystemContextHandle<TestTypes, MemoryImpl>,
+) -> (
+    Leaf<TestTypes>,
+    Proposal<TestTypes, VidDisperseShare<TestTypes>>,
+    Vec<Proposal<TestTypes, VidDisperseShare<TestTypes>>>,
+) {
+    let api: HotShotConsensusApi<TestTypes, MemoryImpl> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let pub_key = *api.public_key();
+    let view = ViewNumber::new(2);
+    let quorum_membership = handle.hotshot.inner.memberships.quorum_membership.clone();
+
+    let vid = vid_init::<TestTypes>(&quorum_membership, ViewNumber::new(0));
+    let encoded_transactions = TestTransaction::encode(vec![TestTransaction(vec![0])]).unwrap();
+    let vid_disperse = vid.disperse(&encoded_transactions).unwrap();
+    let payload_commitment = vid_disperse.commit;
+    let signature =
+        <TestTypes as NodeType>::SignatureKey::sign(api.private_key(), payload_commitment.as_ref())
+            .expect("Failed to sign block payload!");
+    let shares = VidDisperseShare::to_share_proposals(Proposal {
+        data: VidDisperse::from_membership(view, vid_disperse, &quorum_membership.clone().into()),
+        signature,
+        _pd: PhantomData,
+    });
+    let (own_share, peer_shares): (Vec<_>, Vec<_>) = shares
+        .into_iter()
+        .partition(|share| share.data.recipient_key == pub_key);
+    let own_share = own_share[0].clone();
+    handle
+        .get_consensus()
+        .write()
+        .await
+        .saved_vid_shares
+        .insert(view, own_share.clone());
+
+    // The leaf of view 2 was decided without its payload.
+    let genesis = handle.get_consensus().read().await.get_decided_leaf();
+    let leaf = Leaf {
+        view_number: view,
+        justify_qc: QuorumCertificate::<TestTypes>::genesis(),
+        parent_commitment: genesis.commit(),
+        block_header: TestBlockHeader {
+            block_number: 1,
+            payload_commitment,
+        },
+        block_payload: None,
+        proposer_id: pub_key,
+    };
+    (leaf, own_share, peer_shares)
+}
+
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_payload_recovery_task() {
+    use hotshot_task_impls::harness::run_harness;
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(2).await.0;
+    let api: HotShotConsensusApi<TestTypes, MemoryImpl> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let pub_key = *api.public_key();
+    let view = ViewNumber::new(2);
+    let quorum_membership = handle.hotshot.inner.memberships.quorum_membership.clone();
+    let (leaf, own_share, peer_shares) = decide_without_payload(&handle).await;
+    let (_, requester) = key_pair_for_id(3);
+
+    let mut input = Vec::new();
+    let mut output = HashMap::new();
+
+    input.push(HotShotEvent::VidShareRequestRecv(view, requester));
+    // We have no share of a view we were not dispersed to.
+    input.push(HotShotEvent::VidShareRequestRecv(
+        ViewNumber::new(3),
+        requester,
+    ));
+    input.push(HotShotEvent::PayloadMissing(leaf.clone()));
+    // A share relayed by a node other than its recipient is ignored.
+    input.push(HotShotEvent::VidShareResponseRecv(
+        peer_shares[0].clone(),
+        peer_shares[1].data.recipient_key,
+    ));
+    // Every node has one share, so together with our own share these recover the payload.
+    let recovery_threshold =
+        VidParams::from_membership::<TestTypes>(&quorum_membership, view).recovery_threshold;
+    for share in &peer_shares[..recovery_threshold - 1] {
+        input.push(HotShotEvent::VidShareResponseRecv(
+            share.clone(),
+            share.data.recipient_key,
+        ));
+    }
+    input.push(HotShotEvent::Shutdown);
+
+    output.insert(
+        HotShotEvent::VidShareResponseSend(own_share, pub_key, requester),
+        1,
+    );
+    output.insert(HotShotEvent::VidShareRequestSend(view, pub_key), 1);
+
+    let recovery_state = PayloadRecoveryTaskState {
+        consensus: handle.hotshot.get_consensus(),
+        cur_view: ViewNumber::new(0),
+        quorum_membership: quorum_membership.clone().into(),
+        recoveries: BTreeMap::new(),
+        public_key: pub_key,
+        id: handle.hotshot.inner.id,
+        api: api.clone(),
+        _pd: PhantomData,
+    };
+    run_harness(input, output, recovery_state, false).await;
+
+    let decided = api
+        .get_decided_leaves(ViewNumber::genesis(), 10)
+        .await
+        .unwrap();
+    assert_eq!(decided, vec![leaf]);
+    assert_eq!(
+        decided[0].get_block_payload(),
+        Some(TestBlockPayload {
+            transactions: vec![TestTransaction(vec![0])]
+        })
+    );
+}
+
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_payload_recovery_task_retries() {
+    use hotshot_task_impls::harness::run_harness;
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(2).await.0;
+    let api: HotShotConsensusApi<TestTypes, MemoryImpl> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let pub_key = *api.public_key();
+    let view = ViewNumber::new(2);
+    let quorum_membership = handle.hotshot.inner.memberships.quorum_membership.clone();
+    let (leaf, _, peer_shares) = decide_without_payload(&handle).await;
+
+    let mut input = Vec::new();
+    let mut output = HashMap::new();
+
+    input.push(HotShotEvent::PayloadMissing(leaf));
+    // Nobody answers, so the shares are asked for again once the request times out, and then
+    // after twice as many views.
+    let first_retry = REQUEST_TIMEOUT_VIEWS + 1;
+    let second_retry = first_retry + 2 * REQUEST_TIMEOUT_VIEWS + 1;
+    for cur_view in 1..=second_retry {
+        input.push(HotShotEvent::ViewChange(ViewNumber::new(cur_view)));
+    }
+    // Once our peers no longer keep the shares, the recovery is given up and late shares are
+    // ignored.
+    input.push(HotShotEvent::ViewChange(
+        view + VID_SHARE_RETENTION_VIEWS + 1,
+    ));
+    for share in peer_shares {
+        let recipient = share.data.recipient_key;
+        input.push(HotShotEvent::VidShareResponseRecv(share, recipient));
+    }
+    input.push(HotShotEvent::Shutdown);
+
+    output.insert(HotShotEvent::VidShareRequestSend(view, pub_key), 3);
+
+    let recovery_state = PayloadRecoveryTaskState {
+        consensus: handle.hotshot.get_consensus(),
+        cur_view: ViewNumber::new(0),
+        quorum_membership: quorum_membership.clone().into(),
+        recoveries: BTreeMap::new(),
+        public_key: pub_key,
+        id: handle.hotshot.inner.id,
+        api: api.clone(),
+        _pd: PhantomData,
+    };
+    run_harness(input, output, recovery_state, false).await;
+
+    assert!(api
+        .get_decided_leaves(ViewNumber::genesis(), 10)
+        .await
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,211 @@
+#![allow(clippy::panic)]
+use commit::Committable;
+use hotshot::HotShotConsensusApi;
+use hotshot_example_types::node_types::{MemoryImpl, TestTypes};
+use hotshot_task_impls::{events::HotShotEvent, request::RequestTaskState};
+use hotshot_testing::task_helpers::{build_quorum_proposal, build_system_handle, key_pair_for_id};
+use hotshot_types::{
+    data::{Leaf, ViewNumber},
+    message::{DataRequest, DataResponse},
+    simple_certificate::QuorumCertificate,
+    traits::{consensus_api::ConsensusApi, node_implementation::ConsensusTime},
+    vote::Certificate,
+};
+use std::collections::HashMap;
+
+/// Build a chain of leaves for views 1 and 2 in the consensus state of `handle`. Returns both
+/// leaves along with the certificates for them.
+async fn build_chain(
+    handle: &hotshot::types::SystemContextHandle<TestTypes, MemoryImpl>,
+) -> (
+    (Leaf<TestTypes>, QuorumCertificate<TestTypes>),
+    (Leaf<TestTypes>, QuorumCertificate<TestTypes>),
+) {
+    let (private_key, _) = key_pair_for_id(1);
+    let proposal = build_quorum_proposal(handle, &private_key, 3).await;
+    let consensus = handle.get_consensus();
+    let consensus = consensus.read().await;
+    let leaf_2 = consensus
+        .saved_leaves
+        .get(&proposal.data.justify_qc.get_data().leaf_commit)
+        .unwrap()
+        .clone();
+    let leaf_1 = consensus
+        .saved_leaves
+        .get(&leaf_2.parent_commitment)
+        .unwrap()
+        .clone();
+    let qc_1 = leaf_2.justify_qc.clone();
+    ((leaf_1, qc_1), (leaf_2, proposal.data.justify_qc))
+}
+
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_request_task_answers_requests() {
+    use hotshot_task_impls::harness::run_harness;
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(2).await.0;
+    let api: HotShotConsensusApi<TestTypes, MemoryImpl> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let ((leaf_1, qc_1), _) = build_chain(&handle).await;
+    let (_, requester) = key_pair_for_id(3);
+
+    let mut input = Vec::new();
+    let mut output = HashMap::new();
+
+    let request = DataRequest::Leaf(leaf_1.commit());
+    input.push(HotShotEvent::DataRequestRecv(request.clone(), requester));
+    // The same leaf, requested by view.
+    input.push(HotShotEvent::DataRequestRecv(
+        DataRequest::View(ViewNumber::new(1)),
+        requester,
+    ));
+    // We do not have a certificate for the last leaf of the chain, so cannot answer.
+    input.push(HotShotEvent::DataRequestRecv(
+        DataRequest::View(ViewNumber::new(2)),
+        requester,
+    ));
+    input.push(HotShotEvent::Shutdown);
+
+    output.insert(
+        HotShotEvent::DataResponseSend(
+            DataResponse {
+                request,
+                leaf: leaf_1.clone(),
+                qc: qc_1.clone(),
+                da_proposal: None,
+            },
+            *api.public_key(),
+            requester,
+        ),
+        1,
+    );
+    output.insert(
+        HotShotEvent::DataResponseSend(
+            DataResponse {
+                request: DataRequest::View(ViewNumber::new(1)),
+                leaf: leaf_1,
+                qc: qc_1,
+                da_proposal: None,
+            },
+            *api.public_key(),
+            requester,
+        ),
+        1,
+    );
+
+    let request_state = RequestTaskState {
+        consensus: handle.hotshot.get_consensus(),
+        cur_view: ViewNumber::new(0),
+        quorum_membership: api.inner.memberships.quorum_membership.clone().into(),
+        outstanding_requests: HashMap::new(),
+        unvalidated_leaves: HashMap::new(),
+        public_key: *api.public_key(),
+        id: handle.hotshot.inner.id,
+    };
+    run_harness(input, output, request_state, false).await;
+}
+
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_request_task_fetches_missing_leaves() {
+    use hotshot_task_impls::harness::run_harness;
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    // Node 2 has the chain, node 3 is missing it.
+    let peer_handle = build_system_handle(2).await.0;
+    let ((leaf_1, qc_1), (leaf_2, qc_2)) = build_chain(&peer_handle).await;
+    let peer = *HotShotConsensusApi::<TestTypes, MemoryImpl> {
+        inner: peer_handle.hotshot.inner.clone(),
+    }
+    .public_key();
+
+    let handle = build_system_handle(3).await.0;
+    let api: HotShotConsensusApi<TestTypes, MemoryImpl> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+
+    let mut input = Vec::new();
+    let mut output = HashMap::new();
+
+    let request_2 = DataRequest::Leaf(leaf_2.commit());
+    input.push(HotShotEvent::LeafMissing(request_2.clone(), peer));
+    // Asking again while waiting for the response does not send a second request.
+    input.push(HotShotEvent::LeafMissing(request_2.clone(), peer));
+    // The certificate does not certify the leaf, so the response is rejected.
+    input.push(HotShotEvent::DataResponseRecv(
+        DataResponse {
+            request: request_2.clone(),
+            leaf: leaf_2.clone(),
+            qc: qc_1.clone(),
+            da_proposal: None,
+        },
+        peer,
+    ));
+    // Once the leaf of view 2 is in, its parent is requested as well.
+    input.push(HotShotEvent::DataResponseRecv(
+        DataResponse {
+            request: request_2.clone(),
+            leaf: leaf_2.clone(),
+            qc: qc_2,
+            da_proposal: None,
+        },
+        peer,
+    ));
+    let request_1 = DataRequest::Leaf(leaf_1.commit());
+    input.push(HotShotEvent::DataResponseRecv(
+        DataResponse {
+            request: request_1.clone(),
+            leaf: leaf_1.clone(),
+            qc: qc_1,
+            da_proposal: None,
+        },
+        peer,
+    ));
+    input.push(HotShotEvent::Shutdown);
+
+    output.insert(
+        HotShotEvent::DataRequestSend(request_2, *api.public_key(), peer),
+        1,
+    );
+    output.insert(
+        HotShotEvent::DataRequestSend(request_1, *api.public_key(), peer),
+        1,
+    );
+
+    let request_state = RequestTaskState {
+        consensus: handle.hotshot.get_consensus(),
+        cur_view: ViewNumber::new(0),
+        quorum_membership: api.inner.memberships.quorum_membership.clone().into(),
+        outstanding_requests: HashMap::new(),
+        unvalidated_leaves: HashMap::new(),
+        public_key: *api.public_key(),
+        id: handle.hotshot.inner.id,
+    };
+    run_harness(input, output, request_state, false).await;
+
+    let consensus = handle.get_consensus();
+    let consensus = consensus.read().await;
+    for leaf in [leaf_1, leaf_2] {
+        assert_eq!(consensus.saved_leaves.get(&leaf.commit()), Some(&leaf));
+        assert_eq!(
+            consensus
+                .validated_state_map
+                .get(&leaf.view_number)
+                .and_then(|view| view.get_leaf_commitment()),
+            Some(leaf.commit())
+        );
+    }
+}
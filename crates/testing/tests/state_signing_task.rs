@@ -0,0 +1,132 @@
+use commit::Committable;
+use ethereum_types::U256;
+use hotshot::{traits::implementations::InProcessStateRelay, HotShotConsensusApi};
+use hotshot_example_types::{
+    block_types::TestBlockHeader,
+    node_types::{MemoryImpl, TestTypes},
+};
+use hotshot_task_impls::{events::HotShotEvent, state_signing::StateSigningTaskState};
+use hotshot_testing::task_helpers::build_system_handle;
+use hotshot_types::{
+    data::{Leaf, VidParams, ViewNumber},
+    event::EventType,
+    light_client::{CircuitField, StateKeyPair, StateSignatureRequestBody, StateSigningConfig},
+    simple_certificate::QuorumCertificate,
+    traits::{
+        block_contents::vid_commitment, consensus_api::ConsensusApi,
+        node_implementation::ConsensusTime, state_relay::StateRelay,
+    },
+};
+use std::{collections::HashMap, marker::PhantomData, sync::Arc};
+
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_state_signing_task() {
+    use hotshot_task_impls::harness::run_harness;
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(2).await.0;
+    let api: HotShotConsensusApi<TestTypes, MemoryImpl> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let mut output_events = handle.get_event_stream_known_impl();
+    let state_key_pair = api
+        .inner
+        .config
+        .my_own_validator_config
+        .state_key_pair
+        .clone();
+    let other_key_pair = StateKeyPair::generate_from_seed_indexed([1u8; 32], 0);
+    let relay = Arc::new(InProcessStateRelay::new(HashMap::from([
+        (state_key_pair.ver_key(), U256::from(3)),
+        (other_key_pair.ver_key(), U256::from(5)),
+    ])));
+    let config = StateSigningConfig {
+        stake_table_comm: (
+            CircuitField::from(1u64),
+            CircuitField::from(2u64),
+            CircuitField::from(3u64),
+        ),
+        relay: relay.clone(),
+    };
+
+    let genesis = handle.get_consensus().read().await.get_decided_leaf();
+    let leaf = Leaf {
+        view_number: ViewNumber::new(2),
+        justify_qc: QuorumCertificate::<TestTypes>::genesis(),
+        parent_commitment: genesis.commit(),
+        block_header: TestBlockHeader {
+            block_number: 1,
+            payload_commitment: vid_commitment(&vec![], VidParams::for_num_nodes(4)).unwrap(),
+        },
+        block_payload: None,
+        proposer_id: *api.public_key(),
+    };
+
+    let state_signing_state = StateSigningTaskState {
+        api: api.clone(),
+        state_key_pair: state_key_pair.clone(),
+        config,
+        last_signed_view: ViewNumber::genesis(),
+        id: handle.hotshot.inner.id,
+        _pd: PhantomData,
+    };
+    let state = state_signing_state.light_client_state(&leaf).unwrap();
+    assert_eq!(state.view_number, 2);
+    assert_eq!(state.block_height, 1);
+
+    // Only the latest decided leaf is signed, and only once.
+    let input = vec![
+        HotShotEvent::LeafDecided(vec![leaf.clone(), genesis]),
+        HotShotEvent::LeafDecided(vec![leaf]),
+        HotShotEvent::Shutdown,
+    ];
+    run_harness(input, HashMap::new(), state_signing_state, false).await;
+
+    let bundle = relay.get_bundle(&state).await.expect("No signed state");
+    assert_eq!(bundle.accumulated_weight, U256::from(3));
+    assert_eq!(bundle.signatures.len(), 1);
+
+    let mut signed_states = Vec::new();
+    while let Ok(event) = output_events.try_recv() {
+        if let EventType::StateSigned { signed_state } = event.event {
+            signed_states.push(signed_state);
+        }
+    }
+    assert_eq!(signed_states.len(), 1);
+    assert!(signed_states[0].verify());
+    assert_eq!(signed_states[0].state, state);
+
+    // Signatures from other members of the stake table add to the weight of the state.
+    relay
+        .publish(StateSignatureRequestBody::sign(
+            &other_key_pair,
+            state.clone(),
+        ))
+        .await
+        .unwrap();
+    let bundle = relay.get_bundle(&state).await.unwrap();
+    assert_eq!(bundle.accumulated_weight, U256::from(8));
+
+    // Signatures from outside the stake table, or of another state, are rejected.
+    let unknown_key_pair = StateKeyPair::generate_from_seed_indexed([2u8; 32], 0);
+    assert!(relay
+        .publish(StateSignatureRequestBody::sign(
+            &unknown_key_pair,
+            state.clone()
+        ))
+        .await
+        .is_err());
+    let mut forged = StateSignatureRequestBody::sign(&other_key_pair, state.clone());
+    forged.state.block_height += 1;
+    assert!(relay.publish(forged).await.is_err());
+    assert_eq!(
+        relay.get_bundle(&state).await.unwrap().accumulated_weight,
+        U256::from(8)
+    );
+}
@@ -1,14 +1,17 @@
 use commit::Committable;
 use hotshot::traits::implementations::MemoryStorage;
 use hotshot::traits::Storage;
+use hotshot::HotShotConsensusApi;
 use hotshot_example_types::{
     block_types::{genesis_vid_commitment, TestBlockHeader, TestBlockPayload},
-    node_types::TestTypes,
+    node_types::{MemoryImpl, TestTypes},
 };
+use hotshot_testing::task_helpers::build_system_handle;
 use hotshot_types::{
     data::{fake_commitment, Leaf},
     simple_certificate::QuorumCertificate,
     traits::{
+        consensus_api::ConsensusApi,
         node_implementation::{ConsensusTime, NodeType},
         signature_key::SignatureKey,
         storage::{StoredView, TestableStorage},
@@ -70,3 +73,79 @@ async fn memory_storage() {
         .unwrap();
     assert!(storage.get_anchored_view().await.is_err());
 }
+
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn memory_storage_decided_views() {
+    let storage = MemoryStorage::construct_tmp_storage().unwrap();
+    let views: Vec<_> = (0..5)
+        .map(|view| random_stored_view(<TestTypes as NodeType>::Time::new(view)))
+        .collect();
+    for view in &views {
+        storage
+            .append_single_view(view.clone())
+            .await
+            .expect("Could not append block");
+    }
+    assert_eq!(
+        storage
+            .get_decided_views(<TestTypes as NodeType>::Time::new(1), 2)
+            .await
+            .unwrap(),
+        views[2..4]
+    );
+    assert_eq!(
+        storage
+            .get_decided_views(<TestTypes as NodeType>::Time::new(3), 10)
+            .await
+            .unwrap(),
+        views[4..]
+    );
+}
+
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+#[instrument]
+async fn decided_leaves_are_retained_by_default() {
+    let handle = build_system_handle(2).await.0;
+    let api: HotShotConsensusApi<TestTypes, MemoryImpl> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let genesis = handle.get_consensus().read().await.get_decided_leaf();
+    let leaf_at = |view| {
+        let mut leaf = genesis.clone();
+        leaf.view_number = <TestTypes as NodeType>::Time::new(view);
+        leaf
+    };
+
+    api.store_decided_leaves(vec![leaf_at(1), leaf_at(2)])
+        .await
+        .unwrap();
+    assert_eq!(
+        api.get_decided_leaves(<TestTypes as NodeType>::Time::genesis(), 10)
+            .await
+            .unwrap(),
+        vec![leaf_at(1), leaf_at(2)]
+    );
+
+    // Without a retention window, deciding a view far ahead keeps the whole chain for syncing peers.
+    let newest = leaf_at(1002);
+    api.store_decided_leaves(vec![newest.clone()])
+        .await
+        .unwrap();
+    assert_eq!(
+        api.get_decided_leaves(<TestTypes as NodeType>::Time::genesis(), 10)
+            .await
+            .unwrap(),
+        vec![leaf_at(1), leaf_at(2), newest]
+    );
+}
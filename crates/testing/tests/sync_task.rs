@@ -0,0 +1,195 @@
+#![allow(clippy::panic)]
+use commit::Committable;
+use hotshot::HotShotConsensusApi;
+use hotshot_constants::SYNC_BATCH_SIZE;
+use hotshot_example_types::node_types::{MemoryImpl, TestTypes};
+use hotshot_task_impls::{events::HotShotEvent, sync::SyncTaskState};
+use hotshot_testing::task_helpers::{build_quorum_proposal, build_system_handle, key_pair_for_id};
+use hotshot_types::{
+    data::{Leaf, ViewNumber},
+    message::{SyncRequest, SyncResponse},
+    simple_certificate::QuorumCertificate,
+    traits::{consensus_api::ConsensusApi, node_implementation::ConsensusTime},
+    vote::Certificate,
+};
+use std::{collections::HashMap, marker::PhantomData};
+
+/// Build a chain of leaves for views 1 to 4 in the consensus state of `handle` and store it as
+/// decided. Returns the genesis leaf, the chain and the certificate for its last leaf.
+async fn build_decided_chain(
+    handle: &hotshot::types::SystemContextHandle<TestTypes, MemoryImpl>,
+) -> (
+    Leaf<TestTypes>,
+    Vec<Leaf<TestTypes>>,
+    QuorumCertificate<TestTypes>,
+) {
+    let (private_key, _) = key_pair_for_id(1);
+    let proposal = build_quorum_proposal(handle, &private_key, 5).await;
+    let consensus = handle.get_consensus();
+    let consensus = consensus.read().await;
+    let genesis = consensus.get_decided_leaf();
+    let mut chain = vec![consensus
+        .saved_leaves
+        .get(&proposal.data.justify_qc.get_data().leaf_commit)
+        .unwrap()
+        .clone()];
+    while let Some(parent) = consensus.saved_leaves.get(&chain[0].parent_commitment) {
+        if parent.view_number == ViewNumber::genesis() {
+            break;
+        }
+        chain.insert(0, parent.clone());
+    }
+    drop(consensus);
+
+    let api: HotShotConsensusApi<TestTypes, MemoryImpl> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    api.store_decided_leaves(chain.clone()).await.unwrap();
+    (genesis, chain, proposal.data.justify_qc)
+}
+
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_sync_task_serves_decided_chain() {
+    use hotshot_task_impls::harness::run_harness;
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    let handle = build_system_handle(2).await.0;
+    let api: HotShotConsensusApi<TestTypes, MemoryImpl> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let (genesis, chain, _) = build_decided_chain(&handle).await;
+    let (_, requester) = key_pair_for_id(3);
+
+    let mut input = Vec::new();
+    let mut output = HashMap::new();
+
+    let request = SyncRequest {
+        anchor: genesis.commit(),
+        anchor_view: ViewNumber::genesis(),
+        max_leaves: 3,
+    };
+    input.push(HotShotEvent::SyncRequestRecv(request.clone(), requester));
+    // An anchor that is not on our decided chain cannot be served.
+    input.push(HotShotEvent::SyncRequestRecv(
+        SyncRequest {
+            anchor: chain[0].commit(),
+            anchor_view: ViewNumber::genesis(),
+            max_leaves: 3,
+        },
+        requester,
+    ));
+    input.push(HotShotEvent::Shutdown);
+
+    // The next decided leaf certifies the last leaf of the page.
+    output.insert(
+        HotShotEvent::SyncResponseSend(
+            SyncResponse {
+                request,
+                leaves: chain[..3].to_vec(),
+                qc: Some(chain[3].justify_qc.clone()),
+            },
+            *api.public_key(),
+            requester,
+        ),
+        1,
+    );
+
+    let sync_state = SyncTaskState {
+        consensus: handle.hotshot.get_consensus(),
+        cur_view: ViewNumber::new(0),
+        quorum_membership: api.inner.memberships.quorum_membership.clone().into(),
+        sync: None,
+        public_key: *api.public_key(),
+        id: handle.hotshot.inner.id,
+        api,
+        _pd: PhantomData,
+    };
+    run_harness(input, output, sync_state, false).await;
+}
+
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_sync_task_syncs_decided_chain() {
+    use hotshot_task_impls::harness::run_harness;
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    // Node 2 has decided the chain, node 3 is far behind.
+    let peer_handle = build_system_handle(2).await.0;
+    let (genesis, chain, qc) = build_decided_chain(&peer_handle).await;
+    let peer = *HotShotConsensusApi::<TestTypes, MemoryImpl> {
+        inner: peer_handle.hotshot.inner.clone(),
+    }
+    .public_key();
+    let (_, other_peer) = key_pair_for_id(4);
+
+    let handle = build_system_handle(3).await.0;
+    let api: HotShotConsensusApi<TestTypes, MemoryImpl> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+
+    let mut input = Vec::new();
+    let mut output = HashMap::new();
+
+    let request = SyncRequest {
+        anchor: genesis.commit(),
+        anchor_view: ViewNumber::genesis(),
+        max_leaves: SYNC_BATCH_SIZE,
+    };
+    let response = SyncResponse {
+        request: request.clone(),
+        leaves: chain.clone(),
+        qc: Some(qc),
+    };
+    input.push(HotShotEvent::SyncStart(peer));
+    // Only one sync runs at a time.
+    input.push(HotShotEvent::SyncStart(other_peer));
+    // A response from a peer we did not ask is ignored.
+    input.push(HotShotEvent::SyncResponseRecv(response.clone(), other_peer));
+    input.push(HotShotEvent::SyncResponseRecv(response, peer));
+    input.push(HotShotEvent::Shutdown);
+
+    output.insert(
+        HotShotEvent::SyncRequestSend(request, *api.public_key(), peer),
+        1,
+    );
+    // The certified chain of views 2, 3 and 4 proves views 1 and 2 decided, but not views 3 and 4.
+    output.insert(
+        HotShotEvent::LeafDecided(vec![chain[1].clone(), chain[0].clone()]),
+        1,
+    );
+
+    let sync_state = SyncTaskState {
+        consensus: handle.hotshot.get_consensus(),
+        cur_view: ViewNumber::new(0),
+        quorum_membership: api.inner.memberships.quorum_membership.clone().into(),
+        sync: None,
+        public_key: *api.public_key(),
+        id: handle.hotshot.inner.id,
+        api: api.clone(),
+        _pd: PhantomData,
+    };
+    run_harness(input, output, sync_state, false).await;
+
+    let consensus = handle.get_consensus();
+    let consensus = consensus.read().await;
+    assert_eq!(consensus.last_decided_view, ViewNumber::new(2));
+    assert_eq!(consensus.get_decided_leaf(), chain[1]);
+    drop(consensus);
+    assert_eq!(
+        api.get_decided_leaves(ViewNumber::genesis(), 10)
+            .await
+            .unwrap(),
+        chain[..2]
+    );
+}
@@ -1,4 +1,11 @@
 mod unit {
+    mod equivocation;
     mod message;
+    mod peer_record;
+    mod safety_record;
+    mod stake_table_committee;
+    mod state_relay_server;
+    mod static_committee;
     mod version;
+    mod vid_params;
 }
@@ -0,0 +1,77 @@
+#[cfg(test)]
+use std::{collections::HashMap, marker::PhantomData};
+
+use either::Either;
+use hotshot_example_types::node_types::TestTypes;
+use hotshot_types::{
+    data::{random_commitment, ViewNumber},
+    event::Equivocation,
+    signature_key::BLSPubKey,
+    simple_certificate::QuorumCertificate,
+    simple_vote::{QuorumData, QuorumVote},
+    traits::{
+        election::Membership,
+        node_implementation::{ConsensusTime, NodeType},
+        signature_key::SignatureKey,
+    },
+    vote::VoteAccumulator,
+};
+
+/// Sign a quorum vote for a random leaf in view 3, with the key of the given index
+fn random_quorum_vote(index: u64) -> QuorumVote<TestTypes> {
+    let (public_key, private_key) = BLSPubKey::generated_from_seed_indexed([0u8; 32], index);
+    QuorumVote::create_signed_vote(
+        QuorumData {
+            leaf_commit: random_commitment(&mut rand::thread_rng()),
+        },
+        ViewNumber::new(3),
+        &public_key,
+        &private_key,
+    )
+    .expect("Failed to sign QuorumData!")
+}
+
+#[test]
+/// Check that two votes are only evidence of equivocation if the same key signed different data
+fn equivocation_evidence_is_valid() {
+    let first = random_quorum_vote(0);
+    let second = random_quorum_vote(0);
+    assert!(Equivocation::QuorumVote(first.clone(), second).is_valid());
+    assert!(!Equivocation::QuorumVote(first.clone(), first.clone()).is_valid());
+    assert!(!Equivocation::QuorumVote(first, random_quorum_vote(1)).is_valid());
+}
+
+#[test]
+/// Check that the accumulator reports a conflicting vote, and does not count it
+fn accumulator_detects_conflicting_votes() {
+    let keys: Vec<_> = (0..10)
+        .map(|i| {
+            BLSPubKey::generated_from_seed_indexed([0u8; 32], i)
+                .0
+                .get_stake_table_entry(1)
+        })
+        .collect();
+    let membership = <TestTypes as NodeType>::Membership::create_election(
+        keys,
+        <TestTypes as NodeType>::Membership::default_election_config(10),
+    )
+    .unwrap();
+    let mut accumulator =
+        VoteAccumulator::<TestTypes, QuorumVote<TestTypes>, QuorumCertificate<TestTypes>> {
+            vote_outcomes: HashMap::new(),
+            signers: HashMap::new(),
+            signed_votes: HashMap::new(),
+            phantom: PhantomData,
+        };
+
+    let first = random_quorum_vote(0);
+    let second = random_quorum_vote(0);
+    assert!(accumulator.accumulate(&first, &membership).is_left());
+    assert!(accumulator.get_conflicting_vote(&first).is_none());
+    assert_eq!(accumulator.get_conflicting_vote(&second), Some(first));
+    assert!(matches!(
+        accumulator.accumulate(&second, &membership),
+        Either::Left(())
+    ));
+    assert_eq!(accumulator.vote_outcomes.len(), 1);
+}
@@ -0,0 +1,178 @@
+#[cfg(test)]
+use bincode::Options;
+use hotshot::traits::implementations::{
+    PeerRecordValidator, RecordVersionCounter, SignedPeerRecord,
+};
+use hotshot_orchestrator::libp2p_generate_indexed_identity;
+use hotshot_types::{signature_key::BLSPubKey, traits::signature_key::SignatureKey};
+use hotshot_utils::bincode::bincode_opts;
+use libp2p_networking::network::behaviours::dht::RecordValidator;
+
+/// Sign the record of the node of the given index at the given version
+fn signed_record(index: u64, version: u64) -> SignedPeerRecord<BLSPubKey> {
+    let (_, private_key) = BLSPubKey::generated_from_seed_indexed([0u8; 32], index);
+    let identity = libp2p_generate_indexed_identity([0u8; 32], index);
+    SignedPeerRecord::new(&private_key, &identity, version).expect("Failed to sign the record!")
+}
+
+#[test]
+/// Check that only records signed by the key they map are valid
+fn peer_record_signature() {
+    let record = signed_record(0, 1);
+    assert!(record.is_valid());
+
+    // pointing the key to another peer id invalidates the signature
+    let mut redirected = record.clone();
+    redirected.record.peer_id = signed_record(1, 1).record.peer_id;
+    assert!(!redirected.is_valid());
+
+    // as does claiming the record of another key
+    let mut stolen = record.clone();
+    stolen.record.public_key = signed_record(1, 1).record.public_key;
+    assert!(!stolen.is_valid());
+
+    let mut bumped = record;
+    bumped.record.version = 2;
+    assert!(!bumped.is_valid());
+}
+
+#[test]
+/// Check that records may only be stored under their consensus key and their peer id
+fn peer_record_keys() {
+    let record = signed_record(0, 1);
+    let other = signed_record(1, 1);
+    let key_of = |record: &SignedPeerRecord<BLSPubKey>| {
+        bincode_opts().serialize(&record.record.public_key).unwrap()
+    };
+    let peer_id_of = |record: &SignedPeerRecord<BLSPubKey>| {
+        bincode_opts().serialize(&record.record.peer_id).unwrap()
+    };
+
+    assert!(record.is_stored_under(&key_of(&record)));
+    assert!(record.is_stored_under(&peer_id_of(&record)));
+    assert!(!record.is_stored_under(&key_of(&other)));
+    assert!(!record.is_stored_under(&peer_id_of(&other)));
+}
+
+#[test]
+/// Check that records of the same key are ordered by version, and that a peer id only maps to
+/// another key after a rotation
+fn peer_record_supersedes() {
+    let record = signed_record(0, 2);
+
+    assert!(signed_record(0, 3).supersedes(&record));
+    assert!(record.supersedes(&record.clone()));
+    assert!(!signed_record(0, 1).supersedes(&record));
+
+    // a record of the same version mapping the key to another peer id conflicts with it
+    let (_, private_key) = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0);
+    let identity = libp2p_generate_indexed_identity([0u8; 32], 0);
+    let other_identity = libp2p_generate_indexed_identity([0u8; 32], 1);
+    let conflicting = SignedPeerRecord::new(&private_key, &other_identity, 2).unwrap();
+    assert!(!conflicting.supersedes(&record));
+
+    // another key cannot take over the peer id without rotating from the current key
+    let (_, new_private_key) = BLSPubKey::generated_from_seed_indexed([0u8; 32], 1);
+    let takeover = SignedPeerRecord::new(&new_private_key, &identity, 3).unwrap();
+    assert!(!takeover.supersedes(&record));
+
+    let rotated = SignedPeerRecord::rotated(&new_private_key, &private_key, &identity, 3).unwrap();
+    assert!(rotated.is_valid());
+    assert!(rotated.supersedes(&record));
+
+    let stale_rotation =
+        SignedPeerRecord::rotated(&new_private_key, &private_key, &identity, 2).unwrap();
+    assert!(!stale_rotation.supersedes(&record));
+}
+
+#[test]
+/// Check that the DHT validator only accepts valid records stored under their keys
+fn peer_record_validator() {
+    let validator = PeerRecordValidator::<BLSPubKey>::default();
+    let record = signed_record(0, 1);
+    let key = bincode_opts().serialize(&record.record.public_key).unwrap();
+    let value = bincode_opts().serialize(&record).unwrap();
+
+    assert!(validator.validate(&key, &value));
+    assert!(!validator.validate(
+        &bincode_opts()
+            .serialize(&signed_record(1, 1).record.public_key)
+            .unwrap(),
+        &value
+    ));
+    assert!(!validator.validate(&key, b"not a record"));
+
+    let mut tampered = record.clone();
+    tampered.record.version = 2;
+    assert!(!validator.validate(&key, &bincode_opts().serialize(&tampered).unwrap()));
+
+    let newer = bincode_opts().serialize(&signed_record(0, 2)).unwrap();
+    assert!(validator.supersedes(&key, &newer, &value));
+    assert!(!validator.supersedes(&key, &value, &newer));
+    assert!(validator.supersedes(&key, &value, b"not a record"));
+}
+
+#[test]
+/// Check that a lookup resolves to the newest of the records returned by the replicas
+fn peer_record_newest() {
+    let validator = PeerRecordValidator::<BLSPubKey>::default();
+    let record = signed_record(0, 1);
+    let key = bincode_opts().serialize(&record.record.public_key).unwrap();
+    let versions: Vec<_> = [2, 1, 3, 1]
+        .into_iter()
+        .map(|version| {
+            bincode_opts()
+                .serialize(&signed_record(0, version))
+                .unwrap()
+        })
+        .collect();
+
+    assert_eq!(
+        validator.newest(&key, versions.clone()),
+        Some(versions[2].clone())
+    );
+    assert_eq!(validator.newest(&key, Vec::new()), None);
+}
+
+#[test]
+/// Check that a node cannot publish a record claiming the peer id of another node
+fn peer_record_cross_key_squat() {
+    let validator = PeerRecordValidator::<BLSPubKey>::default();
+    let victim = signed_record(0, 1);
+    let peer_id_key = bincode_opts().serialize(&victim.record.peer_id).unwrap();
+
+    // the squatter signs a record mapping its own key to the peer id of the victim
+    let (_, private_key) = BLSPubKey::generated_from_seed_indexed([0u8; 32], 1);
+    let identity = libp2p_generate_indexed_identity([0u8; 32], 1);
+    let mut squat = signed_record(1, 1);
+    squat.record.peer_id = victim.record.peer_id;
+    let bytes = bincode_opts().serialize(&squat.record).unwrap();
+    squat.signature = BLSPubKey::sign(&private_key, &bytes).unwrap();
+    squat.peer_signature = identity.sign(&bytes).unwrap();
+    assert!(!squat.is_valid());
+    assert!(!validator.validate(&peer_id_key, &bincode_opts().serialize(&squat).unwrap()));
+
+    // nor can it pass off the public key of the victim's peer as its own
+    squat.peer_public_key = victim.peer_public_key.clone();
+    assert!(!squat.is_valid());
+    assert!(!validator.validate(&peer_id_key, &bincode_opts().serialize(&squat).unwrap()));
+
+    assert!(validator.validate(&peer_id_key, &bincode_opts().serialize(&victim).unwrap()));
+}
+
+#[test]
+/// Check that record versions keep increasing across restarts when persisted
+fn record_version_counter() {
+    let dir = tempfile::tempdir().unwrap();
+    let location = dir.path().join("record_version");
+
+    let counter = RecordVersionCounter::new(Some(location.clone())).unwrap();
+    assert_eq!(counter.next_version().unwrap(), 1);
+    assert_eq!(counter.next_version().unwrap(), 2);
+
+    let restarted = RecordVersionCounter::new(Some(location)).unwrap();
+    assert_eq!(restarted.next_version().unwrap(), 3);
+
+    let unpersisted = RecordVersionCounter::new(None).unwrap();
+    assert_eq!(unpersisted.next_version().unwrap(), 1);
+}
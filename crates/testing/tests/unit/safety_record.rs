@@ -0,0 +1,65 @@
+#[cfg(test)]
+use hotshot_example_types::node_types::TestTypes;
+use hotshot_types::{
+    data::ViewNumber,
+    signature_key::BLSPubKey,
+    simple_vote::{TimeoutData, TimeoutVote},
+    traits::{
+        node_implementation::ConsensusTime,
+        signature_key::SignatureKey,
+        storage::{SafetyRecord, SafetyVote},
+    },
+};
+
+/// Sign a timeout vote for the given view
+fn timeout_vote(view: u64) -> TimeoutVote<TestTypes> {
+    let (public_key, private_key) = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0);
+    TimeoutVote::create_signed_vote(
+        TimeoutData {
+            view: ViewNumber::new(view),
+        },
+        ViewNumber::new(view),
+        &public_key,
+        &private_key,
+    )
+    .expect("Failed to sign TimeoutData!")
+}
+
+#[test]
+/// Check that quorum, DA and upgrade votes can only be sent once per view, in increasing views.
+fn safety_record_once_per_view() {
+    let mut record = SafetyRecord::<TestTypes>::default();
+    for vote in [SafetyVote::Quorum, SafetyVote::DA, SafetyVote::Upgrade] {
+        assert!(record.record_vote(vote(ViewNumber::new(2))));
+        assert!(!record.record_vote(vote(ViewNumber::new(2))));
+        assert!(!record.record_vote(vote(ViewNumber::new(1))));
+        assert!(record.record_vote(vote(ViewNumber::new(3))));
+    }
+    assert_eq!(record.highest_voted_view, Some(ViewNumber::new(3)));
+    assert_eq!(record.highest_da_voted_view, Some(ViewNumber::new(3)));
+    assert_eq!(record.highest_upgrade_voted_view, Some(ViewNumber::new(3)));
+}
+
+#[test]
+/// Check that a node does not vote for a proposal in a view it has timed out in.
+fn safety_record_no_quorum_vote_after_timeout() {
+    let mut record = SafetyRecord::<TestTypes>::default();
+    assert!(record.record_vote(SafetyVote::Quorum(ViewNumber::new(3))));
+    // Timing out in a view we voted in is fine, and may be repeated
+    assert!(record.record_vote(SafetyVote::Timeout(timeout_vote(3))));
+    assert!(record.record_vote(SafetyVote::Timeout(timeout_vote(4))));
+    assert!(record.record_vote(SafetyVote::Timeout(timeout_vote(4))));
+    assert!(!record.record_vote(SafetyVote::Timeout(timeout_vote(3))));
+    assert!(!record.record_vote(SafetyVote::Quorum(ViewNumber::new(4))));
+    assert!(record.record_vote(SafetyVote::Quorum(ViewNumber::new(5))));
+}
+
+#[test]
+/// Check that view sync votes are never sent for an older round.
+fn safety_record_view_sync_rounds() {
+    let mut record = SafetyRecord::<TestTypes>::default();
+    assert!(record.record_vote(SafetyVote::ViewSync(ViewNumber::new(5))));
+    // Votes for several phases and relays of the same round
+    assert!(record.record_vote(SafetyVote::ViewSync(ViewNumber::new(5))));
+    assert!(!record.record_vote(SafetyVote::ViewSync(ViewNumber::new(4))));
+}
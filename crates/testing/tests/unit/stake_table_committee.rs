@@ -0,0 +1,190 @@
+#[cfg(test)]
+use std::{collections::HashMap, marker::PhantomData};
+
+use ethereum_types::U256;
+use hotshot::traits::election::stake_table_committee::{
+    StakeTableCommittee, StakeTableElectionConfig,
+};
+use hotshot_example_types::{
+    block_types::{TestBlockHeader, TestBlockPayload, TestTransaction},
+    state_types::{TestInstanceState, TestValidatedState},
+};
+use hotshot_stake_table::vec_based::{
+    config::{FieldType, StateVerKey},
+    StakeTable,
+};
+use hotshot_types::{
+    data::{random_commitment, ViewNumber},
+    signature_key::BLSPubKey,
+    simple_certificate::QuorumCertificate,
+    simple_vote::{QuorumData, QuorumVote},
+    stake_table::StakeTableEntry,
+    traits::{
+        election::{ElectionError, Membership},
+        node_implementation::{ConsensusTime, NodeType},
+        signature_key::SignatureKey,
+        stake_table::StakeTableError,
+    },
+    vote::{Certificate, VoteAccumulator},
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Copy, Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+/// Node type whose membership is read from a stake table
+struct EpochTypes;
+
+impl NodeType for EpochTypes {
+    type Time = ViewNumber;
+    type BlockHeader = TestBlockHeader;
+    type BlockPayload = TestBlockPayload;
+    type SignatureKey = BLSPubKey;
+    type Transaction = TestTransaction;
+    type ElectionConfigType = StakeTableElectionConfig;
+    type ValidatedState = TestValidatedState;
+    type InstanceState = TestInstanceState;
+    type Membership =
+        StakeTableCommittee<EpochTypes, BLSPubKey, StakeTable<BLSPubKey, StateVerKey, FieldType>>;
+}
+
+/// Number of views in an epoch for these tests
+const EPOCH_LENGTH: u64 = 10;
+
+/// Create a membership of 4 nodes with 10 stake each
+fn membership() -> <EpochTypes as NodeType>::Membership {
+    let entries = (0..4)
+        .map(|i| StakeTableEntry {
+            stake_key: BLSPubKey::generated_from_seed_indexed([0u8; 32], i).0,
+            stake_amount: U256::from(10),
+        })
+        .collect();
+    <EpochTypes as NodeType>::Membership::create_election(
+        entries,
+        StakeTableElectionConfig {
+            num_nodes: 10,
+            epoch_length: EPOCH_LENGTH,
+        },
+    )
+    .unwrap()
+}
+
+/// Form a quorum certificate for `view` from the votes of the keys with the given indices
+fn form_qc(
+    membership: &<EpochTypes as NodeType>::Membership,
+    view: u64,
+    signers: &[u64],
+) -> Option<QuorumCertificate<EpochTypes>> {
+    let mut accumulator =
+        VoteAccumulator::<EpochTypes, QuorumVote<EpochTypes>, QuorumCertificate<EpochTypes>> {
+            vote_outcomes: HashMap::new(),
+            signers: HashMap::new(),
+            signed_votes: HashMap::new(),
+            phantom: PhantomData,
+        };
+    let leaf_commit = random_commitment(&mut rand::thread_rng());
+    signers.iter().find_map(|index| {
+        let (public_key, private_key) = BLSPubKey::generated_from_seed_indexed([0u8; 32], *index);
+        let vote = QuorumVote::create_signed_vote(
+            QuorumData { leaf_commit },
+            ViewNumber::new(view),
+            &public_key,
+            &private_key,
+        )
+        .expect("Failed to sign QuorumData!");
+        accumulator.accumulate(&vote, membership).right()
+    })
+}
+
+#[test]
+/// Check that stake table changes take effect at the epoch after the next decided epoch
+fn committee_switches_at_epoch_boundaries() {
+    let membership = membership();
+    let joining = BLSPubKey::generated_from_seed_indexed([0u8; 32], 4).0;
+    let leaving = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0).0;
+    membership
+        .register(joining, U256::from(10), StateVerKey::default())
+        .unwrap();
+    membership.deregister(&leaving).unwrap();
+
+    // The first two epochs use the genesis committee.
+    for view in [5, 15, 25] {
+        let committee = membership.get_committee(ViewNumber::new(view));
+        assert!(committee.contains(&leaving) && !committee.contains(&joining));
+    }
+
+    // Deciding a leaf of epoch 1 makes the changes effective from epoch 2.
+    membership.set_decided_view(ViewNumber::new(12));
+    assert_eq!(membership.current_epoch(), 1);
+    assert!(membership
+        .get_committee(ViewNumber::new(15))
+        .contains(&leaving));
+    let committee = membership.get_committee(ViewNumber::new(25));
+    assert!(!committee.contains(&leaving) && committee.contains(&joining));
+    assert_eq!(committee.len(), 4);
+
+    membership.set_decided_view(ViewNumber::new(21));
+    assert!(membership.has_stake(&joining));
+    assert!(!membership.has_stake(&leaving));
+    assert_eq!(membership.success_threshold(), U256::from(27));
+}
+
+#[test]
+/// Check that certificates are checked against the committee of their own epoch
+fn certificates_use_their_epoch_committee() {
+    let membership = membership();
+    let leaving = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0).0;
+    membership.deregister(&leaving).unwrap();
+    membership.set_decided_view(ViewNumber::new(12));
+
+    let epoch_one_qc = form_qc(&membership, 15, &[0, 1, 2]).expect("QC should be formed");
+    let epoch_two_qc = form_qc(&membership, 25, &[1, 2, 3]).expect("QC should be formed");
+    // A node which left the committee no longer counts towards a certificate.
+    assert!(form_qc(&membership, 25, &[0, 1, 2]).is_none());
+
+    membership.set_decided_view(ViewNumber::new(21));
+    assert!(epoch_one_qc.is_valid_cert(&membership));
+    assert!(epoch_two_qc.is_valid_cert(&membership));
+}
+
+#[test]
+/// Check that a committee cannot be created without any stake, as it would have no leader
+fn empty_committee_is_rejected() {
+    let membership = <EpochTypes as NodeType>::Membership::create_election(
+        Vec::new(),
+        StakeTableElectionConfig {
+            num_nodes: 10,
+            epoch_length: EPOCH_LENGTH,
+        },
+    );
+    assert!(matches!(
+        membership,
+        Err(ElectionError::InvalidStakeTable {
+            error: StakeTableError::EmptyCommittee
+        })
+    ));
+}
+
+#[test]
+/// Check that a derived membership follows the changes made through the one it was derived from
+fn derived_membership_shares_the_stake_table() {
+    let membership = membership();
+    let da_membership = membership
+        .derive_election(
+            Vec::new(),
+            StakeTableElectionConfig {
+                num_nodes: 2,
+                epoch_length: EPOCH_LENGTH,
+            },
+        )
+        .unwrap();
+    let first = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0).0;
+    assert_eq!(da_membership.total_nodes(), 2);
+    assert!(da_membership.has_stake(&first));
+
+    membership.deregister(&first).unwrap();
+    membership.set_decided_view(ViewNumber::new(21));
+    assert_eq!(da_membership.current_epoch(), 2);
+    assert!(!da_membership.has_stake(&first));
+    assert_eq!(da_membership.total_nodes(), 2);
+}
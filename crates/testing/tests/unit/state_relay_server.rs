@@ -0,0 +1,224 @@
+#[cfg(test)]
+use ethereum_types::U256;
+
+use hotshot_stake_table::vec_based::config::FieldType;
+use hotshot_state_relay_server::{
+    LightClientStakeTable, StateRelayServerApi, StateRelayServerState, MAX_STATES_PER_HEIGHT,
+    PENDING_HEIGHT_WINDOW,
+};
+use hotshot_types::{
+    light_client::{LightClientState, StateKeyPair, StateSignatureRequestBody},
+    signature_key::BLSPubKey,
+    traits::{
+        signature_key::SignatureKey,
+        stake_table::{SnapshotVersion, StakeTableScheme},
+    },
+};
+
+/// The state key of node `i`
+fn state_key_pair(i: u64) -> StateKeyPair {
+    StateKeyPair::generate_from_seed_indexed([0u8; 32], i)
+}
+
+/// Create a relay for a stake table of 4 nodes with 10 stake each
+fn relay() -> (StateRelayServerState, LightClientStakeTable) {
+    let mut stake_table = LightClientStakeTable::new(10);
+    for i in 0..4 {
+        stake_table
+            .register(
+                BLSPubKey::generated_from_seed_indexed([0u8; 32], i).0,
+                U256::from(10),
+                state_key_pair(i).ver_key(),
+            )
+            .unwrap();
+    }
+    stake_table.advance();
+    let relay = StateRelayServerState::new(&stake_table, SnapshotVersion::EpochStart).unwrap();
+    (relay, stake_table)
+}
+
+/// A light client state at the given block height, committing to the stake table
+fn state(stake_table: &LightClientStakeTable, block_height: usize) -> LightClientState {
+    LightClientState {
+        view_number: block_height + 1,
+        block_height,
+        block_comm_root: FieldType::from(block_height as u64),
+        fee_ledger_comm: FieldType::default(),
+        stake_table_comm: stake_table.commitment(SnapshotVersion::EpochStart).unwrap(),
+    }
+}
+
+#[test]
+/// Check that a bundle is only served once its signers hold more than two thirds of the stake
+fn bundle_completes_at_threshold() {
+    let (mut relay, stake_table) = relay();
+    let state = state(&stake_table, 1);
+    assert_eq!(relay.threshold(), U256::from(27));
+
+    for i in 0..2 {
+        relay
+            .post_signature(StateSignatureRequestBody::sign(
+                &state_key_pair(i),
+                state.clone(),
+            ))
+            .unwrap();
+    }
+    // A node signing twice does not count twice.
+    relay
+        .post_signature(StateSignatureRequestBody::sign(
+            &state_key_pair(1),
+            state.clone(),
+        ))
+        .unwrap();
+    assert!(relay.get_latest_bundle().is_err());
+
+    relay
+        .post_signature(StateSignatureRequestBody::sign(
+            &state_key_pair(2),
+            state.clone(),
+        ))
+        .unwrap();
+    let bundle = relay.get_latest_bundle().unwrap();
+    assert_eq!(bundle.state, state);
+    assert_eq!(bundle.accumulated_weight, U256::from(30));
+    assert_eq!(bundle.signatures.len(), 3);
+    assert_eq!(relay.get_bundle(1).unwrap().state, state);
+    assert!(relay.get_bundle(2).is_err());
+
+    // Late signatures are added to the complete bundle.
+    relay
+        .post_signature(StateSignatureRequestBody::sign(
+            &state_key_pair(3),
+            state.clone(),
+        ))
+        .unwrap();
+    assert_eq!(
+        relay.get_latest_bundle().unwrap().accumulated_weight,
+        U256::from(40)
+    );
+}
+
+#[test]
+/// Check that signatures of different states at the same height are not aggregated together
+fn conflicting_states_are_bundled_separately() {
+    let (mut relay, stake_table) = relay();
+    let state = state(&stake_table, 1);
+    let other_state = LightClientState {
+        block_comm_root: FieldType::from(100u64),
+        ..state.clone()
+    };
+
+    for i in 0..2 {
+        relay
+            .post_signature(StateSignatureRequestBody::sign(
+                &state_key_pair(i),
+                state.clone(),
+            ))
+            .unwrap();
+    }
+    for i in 2..4 {
+        relay
+            .post_signature(StateSignatureRequestBody::sign(
+                &state_key_pair(i),
+                other_state.clone(),
+            ))
+            .unwrap();
+    }
+    assert!(relay.get_latest_bundle().is_err());
+}
+
+#[test]
+/// Check that signatures from outside the stake table, over another stake table, or which do not
+/// verify are rejected
+fn invalid_signatures_are_rejected() {
+    let (mut relay, stake_table) = relay();
+    let state = state(&stake_table, 1);
+
+    assert!(relay
+        .post_signature(StateSignatureRequestBody::sign(
+            &state_key_pair(4),
+            state.clone(),
+        ))
+        .is_err());
+
+    let other_stake_table_state = LightClientState {
+        stake_table_comm: (
+            FieldType::from(1u64),
+            FieldType::from(2u64),
+            FieldType::from(3u64),
+        ),
+        ..state.clone()
+    };
+    assert!(relay
+        .post_signature(StateSignatureRequestBody::sign(
+            &state_key_pair(0),
+            other_stake_table_state,
+        ))
+        .is_err());
+
+    let mut forged = StateSignatureRequestBody::sign(&state_key_pair(0), state.clone());
+    forged.state.block_height += 1;
+    assert!(relay.post_signature(forged).is_err());
+}
+
+#[test]
+/// Check that the relay only collects signatures of states shortly after the latest complete state
+fn far_ahead_states_are_rejected() {
+    let (mut relay, stake_table) = relay();
+    for i in 0..3 {
+        relay
+            .post_signature(StateSignatureRequestBody::sign(
+                &state_key_pair(i),
+                state(&stake_table, 1),
+            ))
+            .unwrap();
+    }
+    assert!(relay.get_bundle(1).is_ok());
+
+    relay
+        .post_signature(StateSignatureRequestBody::sign(
+            &state_key_pair(0),
+            state(&stake_table, 1 + PENDING_HEIGHT_WINDOW),
+        ))
+        .unwrap();
+    assert!(relay
+        .post_signature(StateSignatureRequestBody::sign(
+            &state_key_pair(0),
+            state(&stake_table, 2 + PENDING_HEIGHT_WINDOW),
+        ))
+        .is_err());
+}
+
+#[test]
+/// Check that the relay collects signatures for a bounded number of states at each height
+fn states_per_height_are_capped() {
+    let (mut relay, stake_table) = relay();
+    let states: Vec<_> = (0..=MAX_STATES_PER_HEIGHT)
+        .map(|i| LightClientState {
+            block_comm_root: FieldType::from(100 + i as u64),
+            ..state(&stake_table, 1)
+        })
+        .collect();
+
+    for state in &states[..MAX_STATES_PER_HEIGHT] {
+        relay
+            .post_signature(StateSignatureRequestBody::sign(
+                &state_key_pair(0),
+                state.clone(),
+            ))
+            .unwrap();
+    }
+    assert!(relay
+        .post_signature(StateSignatureRequestBody::sign(
+            &state_key_pair(0),
+            states[MAX_STATES_PER_HEIGHT].clone(),
+        ))
+        .is_err());
+    // Signatures of the states already collected are still accepted.
+    relay
+        .post_signature(StateSignatureRequestBody::sign(
+            &state_key_pair(1),
+            states[0].clone(),
+        ))
+        .unwrap();
+}
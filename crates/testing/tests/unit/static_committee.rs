@@ -0,0 +1,165 @@
+#[cfg(test)]
+use std::{collections::HashMap, marker::PhantomData};
+
+use ethereum_types::U256;
+use hotshot::traits::election::static_committee::{
+    GeneralStaticCommittee, LeaderElection, RoundRobin, StakeWeighted,
+};
+use hotshot_example_types::node_types::TestTypes;
+use hotshot_types::{
+    data::{random_commitment, ViewNumber},
+    signature_key::BLSPubKey,
+    simple_certificate::QuorumCertificate,
+    simple_vote::{QuorumData, QuorumVote},
+    traits::{
+        election::Membership,
+        node_implementation::{ConsensusTime, NodeType},
+        signature_key::{SignatureKey, StakeTableEntryType},
+    },
+    vote::{Certificate, VoteAccumulator},
+};
+
+/// Create a committee where node 0 has 10 stake, and nodes 1 to 3 have 1 stake each
+fn unequal_stake_membership() -> <TestTypes as NodeType>::Membership {
+    membership_with_stakes(&[10, 1, 1, 1])
+}
+
+/// Create a committee of nodes with the given stakes, choosing leaders with `LEADER`
+fn membership_with_stakes<LEADER: LeaderElection>(
+    stakes: &[u64],
+) -> GeneralStaticCommittee<TestTypes, BLSPubKey, LEADER> {
+    let entries = stakes
+        .iter()
+        .zip(0..)
+        .map(|(stake, i)| {
+            BLSPubKey::generated_from_seed_indexed([0u8; 32], i)
+                .0
+                .get_stake_table_entry(*stake)
+        })
+        .collect();
+    <GeneralStaticCommittee<TestTypes, BLSPubKey, LEADER> as Membership<TestTypes>>::create_election(
+        entries,
+        <TestTypes as NodeType>::Membership::default_election_config(stakes.len() as u64),
+    )
+    .unwrap()
+}
+
+/// Count how many of the first `views` views each node leads
+fn leader_counts<LEADER: LeaderElection>(
+    membership: &GeneralStaticCommittee<TestTypes, BLSPubKey, LEADER>,
+    views: u64,
+) -> HashMap<BLSPubKey, u64> {
+    let mut counts = HashMap::new();
+    for view in 0..views {
+        *counts
+            .entry(membership.get_leader(ViewNumber::new(view)))
+            .or_default() += 1;
+    }
+    counts
+}
+
+#[test]
+/// Check that stake lookups return the registered stake and thresholds are in stake units
+fn thresholds_are_in_stake_units() {
+    let membership = unequal_stake_membership();
+    let heavy = BLSPubKey::generated_from_seed_indexed([0u8; 32], 0).0;
+    let outsider = BLSPubKey::generated_from_seed_indexed([0u8; 32], 4).0;
+
+    assert!(membership.has_stake(&heavy));
+    assert_eq!(
+        membership.get_stake(&heavy).map(|entry| entry.get_stake()),
+        Some(U256::from(10))
+    );
+    assert!(!membership.has_stake(&outsider));
+
+    // The total stake is 13.
+    assert_eq!(membership.success_threshold(), U256::from(9));
+    assert_eq!(membership.failure_threshold(), U256::from(5));
+    assert_eq!(membership.upgrade_threshold(), U256::from(12));
+}
+
+#[test]
+/// Check that thresholds stay exact when the total stake does not fit in a `u64`
+fn thresholds_do_not_saturate() {
+    let membership = membership_with_stakes::<RoundRobin>(&[u64::MAX; 3]);
+    let total_stake = U256::from(u64::MAX) * 3;
+
+    assert_eq!(membership.success_threshold(), total_stake * 2 / 3 + 1);
+    assert_eq!(membership.failure_threshold(), total_stake / 3 + 1);
+    assert!(membership.success_threshold() > U256::from(u64::MAX));
+}
+
+#[test]
+/// Check that a certificate is formed once the votes carry enough stake, regardless of their count
+fn certificate_is_formed_by_stake() {
+    let membership = unequal_stake_membership();
+    let leaf_commit = random_commitment(&mut rand::thread_rng());
+    let vote = |index| {
+        let (public_key, private_key) = BLSPubKey::generated_from_seed_indexed([0u8; 32], index);
+        QuorumVote::<TestTypes>::create_signed_vote(
+            QuorumData { leaf_commit },
+            ViewNumber::new(1),
+            &public_key,
+            &private_key,
+        )
+        .expect("Failed to sign QuorumData!")
+    };
+    let mut accumulator =
+        VoteAccumulator::<TestTypes, QuorumVote<TestTypes>, QuorumCertificate<TestTypes>> {
+            vote_outcomes: HashMap::new(),
+            signers: HashMap::new(),
+            signed_votes: HashMap::new(),
+            phantom: PhantomData,
+        };
+
+    // Three light nodes do not have enough stake.
+    for index in 1..4 {
+        assert!(accumulator.accumulate(&vote(index), &membership).is_left());
+    }
+    let qc = accumulator
+        .accumulate(&vote(0), &membership)
+        .right()
+        .expect("QC should be formed");
+    assert!(qc.is_valid_cert(&membership));
+}
+
+#[test]
+/// Check that stake weighted leaders lead in proportion to their stake
+fn stake_weighted_leader_distribution() {
+    let membership = membership_with_stakes::<StakeWeighted>(&[10, 1, 1, 1]);
+    let counts = leader_counts(&membership, 13_000);
+
+    for (index, expected) in [(0, 10_000), (1, 1_000), (2, 1_000), (3, 1_000)] {
+        let key = BLSPubKey::generated_from_seed_indexed([0u8; 32], index).0;
+        let count = counts.get(&key).copied().unwrap_or_default();
+        assert!(
+            count.abs_diff(expected) * 10 <= expected,
+            "node {index} led {count} views, expected about {expected}"
+        );
+    }
+    // Every node agrees on the leader of a view.
+    assert_eq!(
+        membership.get_leader(ViewNumber::new(42)),
+        membership_with_stakes::<StakeWeighted>(&[10, 1, 1, 1]).get_leader(ViewNumber::new(42))
+    );
+}
+
+#[test]
+/// Check that nodes take turns when there is no stake to weigh them by
+fn stake_weighted_leader_without_stake() {
+    let membership = membership_with_stakes::<StakeWeighted>(&[0, 0, 0]);
+    for view in 0..6 {
+        assert_eq!(
+            membership.get_leader(ViewNumber::new(view)),
+            BLSPubKey::generated_from_seed_indexed([0u8; 32], view % 3).0
+        );
+    }
+}
+
+#[test]
+/// Check that round robin leaders take turns regardless of their stake
+fn round_robin_leader_ignores_stake() {
+    let counts = leader_counts(&unequal_stake_membership(), 400);
+    assert_eq!(counts.len(), 4);
+    assert!(counts.values().all(|count| *count == 100));
+}
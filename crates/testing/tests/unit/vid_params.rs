@@ -0,0 +1,105 @@
+#[cfg(test)]
+use std::{collections::BTreeMap, sync::Arc};
+
+use hotshot_example_types::node_types::TestTypes;
+use hotshot_types::{
+    data::{vid_scheme, VidDisperse, VidParams, VidSchemeTrait, ViewNumber},
+    signature_key::BLSPubKey,
+    traits::{
+        election::Membership,
+        node_implementation::{ConsensusTime, NodeType},
+        signature_key::SignatureKey,
+    },
+};
+
+/// Create a committee with the given stake for each node
+fn membership(stakes: &[u64]) -> <TestTypes as NodeType>::Membership {
+    let entries = stakes
+        .iter()
+        .enumerate()
+        .map(|(i, stake)| key(i as u64).get_stake_table_entry(*stake))
+        .collect();
+    <TestTypes as NodeType>::Membership::create_election(
+        entries,
+        <TestTypes as NodeType>::Membership::default_election_config(stakes.len() as u64),
+    )
+    .unwrap()
+}
+
+/// The key of node `i`
+fn key(i: u64) -> BLSPubKey {
+    BLSPubKey::generated_from_seed_indexed([0u8; 32], i).0
+}
+
+#[test]
+/// Check that a committee of equal stake needs the shares of more than a third of its nodes,
+/// even when its size is not a power of two
+fn equal_stake_needs_one_third_plus_one() {
+    let membership = membership(&[1; 10]);
+    let params = VidParams::from_membership::<TestTypes>(&membership, ViewNumber::genesis());
+    assert_eq!(params, VidParams::for_num_nodes(10));
+    assert_eq!(params.recovery_threshold, 4);
+    assert_eq!(params.num_shares, 10);
+    assert!(
+        VidParams::share_counts::<TestTypes>(&membership, ViewNumber::genesis())
+            .values()
+            .all(|count| *count == 1)
+    );
+}
+
+#[test]
+/// Check that shares are assigned in proportion to stake, and the recovery threshold matches the
+/// failure threshold of stake
+fn shares_are_assigned_by_stake() {
+    let membership = membership(&[20, 10, 10, 10]);
+    let params = VidParams::from_membership::<TestTypes>(&membership, ViewNumber::genesis());
+    // Stakes are multiples of 10, and more than 50 / 3 stake takes 2 units of 10.
+    assert_eq!(params.num_shares, 5);
+    assert_eq!(params.recovery_threshold, 2);
+    assert_eq!(
+        VidParams::share_counts::<TestTypes>(&membership, ViewNumber::genesis()),
+        BTreeMap::from([(key(0), 2), (key(1), 1), (key(2), 1), (key(3), 1)])
+    );
+}
+
+#[test]
+/// Check that a stake table too fine-grained for stake-weighted shares gets one share per node
+fn fine_grained_stake_falls_back_to_one_share_per_node() {
+    let membership = membership(&[u64::MAX, 1, 1]);
+    assert_eq!(
+        VidParams::from_membership::<TestTypes>(&membership, ViewNumber::genesis()),
+        VidParams::for_num_nodes(3)
+    );
+    assert!(
+        VidParams::share_counts::<TestTypes>(&membership, ViewNumber::genesis())
+            .values()
+            .all(|count| *count == 1)
+    );
+}
+
+#[test]
+/// Check that a dispersal gives each node its shares, and that the shares of nodes holding just
+/// over a third of the stake recover the payload
+fn dispersal_follows_share_counts() {
+    let membership = membership(&[10, 1, 1, 1]);
+    let view = ViewNumber::genesis();
+    let params = VidParams::from_membership::<TestTypes>(&membership, view);
+    assert_eq!(params.num_shares, 13);
+    assert_eq!(params.recovery_threshold, 5);
+
+    let vid = vid_scheme(params).unwrap();
+    let payload = vec![7u8; 100];
+    let disperse = VidDisperse::<TestTypes>::from_membership(
+        view,
+        vid.disperse(&payload).unwrap(),
+        &Arc::new(membership),
+    );
+    assert_eq!(disperse.shares[&key(0)].len(), 10);
+    assert_eq!(disperse.shares[&key(1)].len(), 1);
+
+    // The heavy node alone holds more than a third of the stake.
+    let recovered = vid
+        .recover_payload(&disperse.shares[&key(0)], &disperse.common)
+        .unwrap();
+    assert_eq!(recovered, payload);
+}
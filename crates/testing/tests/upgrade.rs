@@ -0,0 +1,44 @@
+#[cfg(test)]
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_version_upgrade() {
+    use std::time::Duration;
+
+    use hotshot_constants::VERSION_0_2;
+    use hotshot_example_types::node_types::{MemoryImpl, TestTypes};
+    use hotshot_testing::{
+        completion_task::{CompletionTaskDescription, TimeBasedCompletionTaskDescription},
+        overall_safety_task::OverallSafetyPropertiesDescription,
+        test_builder::{TestMetadata, UpgradeDescription},
+    };
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    // Every node schedules the upgrade, which is proposed, certified and decided in the first
+    // views, and the network keeps deciding views with the new version after it takes effect.
+    let metadata = TestMetadata {
+        upgrade: Some(UpgradeDescription {
+            new_version: VERSION_0_2,
+            new_version_first_view: 20,
+        }),
+        overall_safety_properties: OverallSafetyPropertiesDescription {
+            num_successful_views: 30,
+            ..Default::default()
+        },
+        completion_task_description: CompletionTaskDescription::TimeBasedCompletionTaskBuilder(
+            TimeBasedCompletionTaskDescription {
+                duration: Duration::from_secs(60),
+            },
+        ),
+        ..TestMetadata::default()
+    };
+
+    metadata
+        .gen_launcher::<TestTypes, MemoryImpl>(0)
+        .launch()
+        .run_test()
+        .await;
+}
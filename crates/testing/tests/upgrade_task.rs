@@ -0,0 +1,170 @@
+use commit::Committable;
+use hotshot::{types::SignatureKey, HotShotConsensusApi};
+use hotshot_constants::{Version, VERSION_0_1, VERSION_0_2};
+use hotshot_example_types::node_types::{MemoryImpl, TestTypes};
+use hotshot_task_impls::{events::HotShotEvent, upgrade::UpgradeTaskState};
+use hotshot_testing::task_helpers::{build_system_handle, key_pair_for_id};
+use hotshot_types::{
+    data::{UpgradeProposal, ViewNumber},
+    message::Proposal,
+    simple_vote::{UpgradeProposalData, UpgradeVote},
+    traits::{
+        consensus_api::ConsensusApi,
+        node_implementation::{ConsensusTime, NodeType},
+    },
+};
+use std::{collections::HashMap, marker::PhantomData};
+
+/// Sign an upgrade proposal for the given view with the given key
+fn upgrade_proposal(
+    upgrade: &UpgradeProposalData<TestTypes>,
+    view: u64,
+    private_key: &<<TestTypes as NodeType>::SignatureKey as SignatureKey>::PrivateKey,
+) -> Proposal<TestTypes, UpgradeProposal<TestTypes>> {
+    let signature =
+        <TestTypes as NodeType>::SignatureKey::sign(private_key, upgrade.commit().as_ref())
+            .expect("Failed to sign upgrade proposal!");
+    Proposal {
+        data: UpgradeProposal {
+            upgrade_proposal: upgrade.clone(),
+            view_number: ViewNumber::new(view),
+        },
+        signature,
+        _pd: PhantomData,
+    }
+}
+
+#[cfg_attr(
+    async_executor_impl = "tokio",
+    tokio::test(flavor = "multi_thread", worker_threads = 2)
+)]
+#[cfg_attr(async_executor_impl = "async-std", async_std::test)]
+async fn test_upgrade_task() {
+    use hotshot_task_impls::harness::run_harness;
+
+    async_compatibility_layer::logging::setup_logging();
+    async_compatibility_layer::logging::setup_backtrace();
+
+    // Build the API for node 2, the leader of view 2.
+    let handle = build_system_handle(2).await.0;
+    let api: HotShotConsensusApi<TestTypes, MemoryImpl> = HotShotConsensusApi {
+        inner: handle.hotshot.inner.clone(),
+    };
+    let pub_key = *api.public_key();
+
+    let upgrade = UpgradeProposalData {
+        old_version: VERSION_0_1,
+        new_version: VERSION_0_2,
+        new_version_hash: vec![1, 2, 3],
+        old_version_last_block: ViewNumber::new(19),
+        new_version_first_block: ViewNumber::new(20),
+    };
+    let other_upgrade = UpgradeProposalData {
+        new_version_hash: vec![4, 5, 6],
+        ..upgrade.clone()
+    };
+    let (leader_private_key, leader_key) = key_pair_for_id(3);
+
+    let mut input = Vec::new();
+    let mut output = HashMap::new();
+
+    input.push(HotShotEvent::UpgradeScheduled(upgrade.clone()));
+    // In view 1, we are the next leader, so we propose the upgrade for view 2.
+    input.push(HotShotEvent::ViewChange(ViewNumber::new(1)));
+    // We only vote for the upgrade we scheduled, signed by the leader of the view.
+    input.push(HotShotEvent::UpgradeProposalRecv(
+        upgrade_proposal(&other_upgrade, 3, &leader_private_key),
+        leader_key,
+    ));
+    input.push(HotShotEvent::UpgradeProposalRecv(
+        upgrade_proposal(&upgrade, 3, api.private_key()),
+        leader_key,
+    ));
+    input.push(HotShotEvent::UpgradeProposalRecv(
+        upgrade_proposal(&upgrade, 3, &leader_private_key),
+        leader_key,
+    ));
+    // Once the upgrade takes effect, we no longer vote for it.
+    let (next_leader_private_key, next_leader_key) = key_pair_for_id(4);
+    input.push(HotShotEvent::VersionUpgrade(upgrade.new_version));
+    input.push(HotShotEvent::UpgradeProposalRecv(
+        upgrade_proposal(&upgrade, 4, &next_leader_private_key),
+        next_leader_key,
+    ));
+    input.push(HotShotEvent::Shutdown);
+
+    output.insert(
+        HotShotEvent::UpgradeProposalSend(
+            upgrade_proposal(&upgrade, 2, api.private_key()),
+            pub_key,
+        ),
+        1,
+    );
+    output.insert(
+        HotShotEvent::UpgradeVoteSend(
+            UpgradeVote::create_signed_vote(
+                upgrade.clone(),
+                ViewNumber::new(3),
+                &pub_key,
+                api.private_key(),
+            )
+            .expect("Failed to sign upgrade vote!"),
+        ),
+        1,
+    );
+
+    let upgrade_state = UpgradeTaskState {
+        api: api.clone(),
+        cur_view: ViewNumber::new(0),
+        quorum_membership: handle
+            .hotshot
+            .inner
+            .memberships
+            .quorum_membership
+            .clone()
+            .into(),
+        quorum_network: handle.hotshot.inner.networks.quorum_network.clone(),
+        should_vote: |_upgrade_proposal| false,
+        upgrade_target: None,
+        vote_collector: None.into(),
+        public_key: pub_key,
+        private_key: api.private_key().clone(),
+        id: handle.hotshot.inner.id,
+    };
+    run_harness(input, output, upgrade_state, false).await;
+
+    // We cannot schedule an upgrade to a version we cannot speak, nor to the version we run.
+    assert!(handle
+        .schedule_upgrade(UpgradeProposalData {
+            new_version: Version { major: 0, minor: 3 },
+            ..upgrade.clone()
+        })
+        .await
+        .is_err());
+    assert!(handle
+        .schedule_upgrade(UpgradeProposalData {
+            new_version: VERSION_0_1,
+            ..upgrade.clone()
+        })
+        .await
+        .is_err());
+    assert!(handle.schedule_upgrade(upgrade.clone()).await.is_ok());
+    // An upgrade from a version the network does not run is rejected.
+    assert!(handle
+        .schedule_upgrade(UpgradeProposalData {
+            old_version: VERSION_0_2,
+            new_version: VERSION_0_1,
+            ..upgrade.clone()
+        })
+        .await
+        .is_err());
+    // An upgrade that starts too soon is rejected.
+    assert!(handle
+        .schedule_upgrade(UpgradeProposalData {
+            old_version_last_block: ViewNumber::new(1),
+            new_version_first_block: ViewNumber::new(2),
+            ..upgrade
+        })
+        .await
+        .is_err());
+}
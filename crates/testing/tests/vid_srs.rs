@@ -0,0 +1,62 @@
+use ark_serialize::CanonicalSerialize;
+use hotshot_types::{
+    data::{
+        check_vid_srs, load_vid_srs, test_srs, vid_scheme, VidParams, VidSchemeTrait, VidSrsError,
+    },
+    traits::block_contents::vid_commitment,
+};
+
+/// The loaded setup is process-wide, so all steps run in order in a single test.
+#[test]
+fn test_load_vid_srs() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let missing = dir.path().join("missing.bin");
+    assert!(matches!(
+        load_vid_srs(&missing),
+        Err(VidSrsError::ReadSrs { .. })
+    ));
+
+    let invalid = dir.path().join("invalid.bin");
+    std::fs::write(&invalid, [0u8; 16]).unwrap();
+    assert!(matches!(
+        load_vid_srs(&invalid),
+        Err(VidSrsError::DeserializeSrs { .. })
+    ));
+
+    // Without a loaded setup, the test setup is used.
+    check_vid_srs(64).unwrap();
+
+    let path = dir.path().join("srs.bin");
+    let mut bytes = Vec::new();
+    test_srs(8).serialize_uncompressed(&mut bytes).unwrap();
+    std::fs::write(&path, bytes).unwrap();
+    load_vid_srs(&path).unwrap();
+
+    // Loading the same setup again does nothing, but another one cannot replace it.
+    load_vid_srs(&path).unwrap();
+    let other = dir.path().join("other.bin");
+    std::fs::copy(&path, &other).unwrap();
+    assert!(matches!(
+        load_vid_srs(&other),
+        Err(VidSrsError::SrsAlreadyLoaded { .. })
+    ));
+
+    check_vid_srs(8).unwrap();
+    assert!(matches!(
+        check_vid_srs(64),
+        Err(VidSrsError::SrsTooSmall { num_shares: 64, .. })
+    ));
+    assert!(vid_scheme(VidParams::for_num_nodes(64)).is_err());
+    assert!(vid_commitment(&vec![1, 2, 3], VidParams::for_num_nodes(64)).is_err());
+
+    // The loaded setup yields the same commitments as the test setup it was written from.
+    let payload = vec![1, 2, 3];
+    let params = VidParams::for_num_nodes(8);
+    let loaded = vid_scheme(params).unwrap().commit_only(&payload).unwrap();
+    let expected = hotshot_types::data::VidScheme::new(params.recovery_threshold, 8, test_srs(8))
+        .unwrap()
+        .commit_only(&payload)
+        .unwrap();
+    assert_eq!(loaded, expected);
+}
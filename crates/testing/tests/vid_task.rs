@@ -4,7 +4,7 @@ use hotshot_example_types::{
     node_types::{MemoryImpl, TestTypes},
 };
 use hotshot_task_impls::{events::HotShotEvent, vid::VIDTaskState};
-use hotshot_testing::task_helpers::{build_system_handle, vid_init};
+use hotshot_testing::task_helpers::{build_system_handle, vid_init, vid_share};
 use hotshot_types::traits::node_implementation::{ConsensusTime, NodeType};
 use hotshot_types::{
     data::{DAProposal, VidDisperse, VidSchemeTrait, ViewNumber},
@@ -83,7 +83,10 @@ async fn test_vid_task() {
         ViewNumber::new(2),
     ));
     input.push(HotShotEvent::VidDisperseSend(vid_proposal.clone(), pub_key));
-    input.push(HotShotEvent::VidDisperseRecv(vid_proposal.clone(), pub_key));
+    input.push(HotShotEvent::VidDisperseRecv(
+        vid_share(vid_proposal.clone(), &pub_key),
+        pub_key,
+    ));
     input.push(HotShotEvent::Shutdown);
 
     output.insert(
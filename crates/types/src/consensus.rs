@@ -4,16 +4,18 @@ pub use crate::utils::{View, ViewInner};
 use displaydoc::Display;
 
 use crate::{
-    data::Leaf,
+    data::{Leaf, VidDisperseShare},
     error::HotShotError,
+    message::Proposal,
     simple_certificate::{DACertificate, QuorumCertificate},
     traits::{
         metrics::{Counter, Gauge, Histogram, Label, Metrics, NoMetrics},
-        node_implementation::NodeType,
+        node_implementation::{ConsensusTime, NodeType},
     },
     utils::Terminator,
 };
 use commit::Commitment;
+use hotshot_constants::{Version, VID_SHARE_RETENTION_VIEWS};
 
 use std::{
     collections::{BTreeMap, HashMap},
@@ -55,12 +57,22 @@ pub struct Consensus<TYPES: NodeType> {
     /// Encoded transactions for every view if we got a payload for that view.
     pub saved_payloads: BTreeMap<TYPES::Time, Vec<u8>>,
 
+    /// Our VID shares for current and future views.
+    ///
+    /// Shares are kept for a while after their view is decided, so peers that missed the payload
+    /// can recover it from them.
+    pub saved_vid_shares: BTreeMap<TYPES::Time, Proposal<TYPES, VidDisperseShare<TYPES>>>,
+
     /// The `locked_qc` view number
     pub locked_view: TYPES::Time,
 
     /// the highqc per spec
     pub high_qc: QuorumCertificate<TYPES>,
 
+    /// The current version of the network.
+    /// Updated on view change based on the most recent decided upgrade certificate.
+    pub version: Version,
+
     /// A reference to the metrics trait
     pub metrics: Arc<ConsensusMetricsValue>,
 }
@@ -88,6 +100,8 @@ pub struct ConsensusMetricsValue {
     pub outstanding_transactions_memory_size: Box<dyn Gauge>,
     /// Number of views that timed out
     pub number_of_timeouts: Box<dyn Counter>,
+    /// Number of messages dropped because they are not signed by their sender
+    pub rejected_messages: Box<dyn Counter>,
 }
 
 /// The wrapper with a string name for the networking metrics
@@ -224,6 +238,7 @@ impl ConsensusMetricsValue {
             outstanding_transactions_memory_size: metrics
                 .create_gauge(String::from("outstanding_transactions_memory_size"), None),
             number_of_timeouts: metrics.create_counter(String::from("number_of_timeouts"), None),
+            rejected_messages: metrics.create_counter(String::from("rejected_messages"), None),
         }
     }
 }
@@ -294,7 +309,8 @@ impl<TYPES: NodeType> Consensus<TYPES> {
     }
 
     /// Garbage collects based on state change right now, this removes from both the
-    /// `saved_payloads` and `validated_state_map` fields of `Consensus`.
+    /// `saved_payloads` and `validated_state_map` fields of `Consensus`. VID shares are kept for
+    /// [`VID_SHARE_RETENTION_VIEWS`] views before the new anchor.
     /// # Panics
     /// On inconsistent stored entries
     #[allow(clippy::unused_async)] // async for API compatibility reasons
@@ -325,6 +341,9 @@ impl<TYPES: NodeType> Consensus<TYPES> {
             });
         self.validated_state_map = self.validated_state_map.split_off(&new_anchor_view);
         self.saved_payloads = self.saved_payloads.split_off(&new_anchor_view);
+        let oldest_vid_share =
+            TYPES::Time::new(new_anchor_view.saturating_sub(VID_SHARE_RETENTION_VIEWS));
+        self.saved_vid_shares = self.saved_vid_shares.split_off(&oldest_vid_share);
     }
 
     /// Gets the last decided leaf.
@@ -4,13 +4,14 @@
 //! `HotShot`'s version of a block, and proposals, messages upon which to reach the consensus.
 
 use crate::{
+    message::Proposal,
     simple_certificate::{QuorumCertificate, TimeoutCertificate, UpgradeCertificate},
     simple_vote::UpgradeProposalData,
     traits::{
         block_contents::{vid_commitment, BlockHeader, TestableBlock},
         election::Membership,
         node_implementation::{ConsensusTime, NodeType},
-        signature_key::SignatureKey,
+        signature_key::{SignatureKey, StakeTableEntryType},
         states::{TestableState, ValidatedState},
         storage::StoredView,
         BlockPayload,
@@ -18,23 +19,30 @@ use crate::{
     vote::{Certificate, HasViewNumber},
 };
 use ark_bls12_381::Bls12_381;
-use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, SerializationError};
 use bincode::Options;
 use commit::{Commitment, Committable, RawCommitmentBuilder};
 use derivative::Derivative;
+use ethereum_types::U256;
+use hotshot_constants::VID_MAX_SHARES;
 use hotshot_utils::bincode::bincode_opts;
 use jf_primitives::{
-    pcs::{checked_fft_size, prelude::UnivariateKzgPCS, PolynomialCommitmentScheme},
+    pcs::{
+        checked_fft_size, prelude::UnivariateKzgPCS, PolynomialCommitmentScheme,
+        StructuredReferenceString,
+    },
     vid::VidDisperse as JfVidDisperse,
 };
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use snafu::Snafu;
+use snafu::{ResultExt, Snafu};
 use std::{
     collections::BTreeMap,
     fmt::{Debug, Display},
     hash::Hash,
-    sync::Arc,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
 };
 
 /// Type-safe wrapper around `u64` so we know the thing we're talking about is a view number.
@@ -150,25 +158,26 @@ pub struct VidDisperse<TYPES: NodeType> {
     pub view_number: TYPES::Time,
     /// Block payload commitment
     pub payload_commitment: VidCommitment,
-    /// A storage node's key and its corresponding VID share
-    pub shares: BTreeMap<TYPES::SignatureKey, <VidScheme as VidSchemeTrait>::Share>,
+    /// A storage node's key and its corresponding VID shares
+    pub shares: BTreeMap<TYPES::SignatureKey, Vec<<VidScheme as VidSchemeTrait>::Share>>,
     /// VID common data sent to all storage nodes
     pub common: <VidScheme as VidSchemeTrait>::Common,
 }
 
 impl<TYPES: NodeType> VidDisperse<TYPES> {
     /// Create VID dispersal from a specified membership
-    /// Uses the specified function to calculate share dispersal
-    /// Allows for more complex stake table functionality
+    ///
+    /// Each storage node gets as many consecutive shares as [`VidParams::share_counts`] assigns
+    /// to it, so the dispersal must have been computed with [`VidParams::from_membership`].
     pub fn from_membership(
         view_number: TYPES::Time,
-        mut vid_disperse: JfVidDisperse<VidScheme>,
+        vid_disperse: JfVidDisperse<VidScheme>,
         membership: &Arc<TYPES::Membership>,
     ) -> Self {
-        let shares = membership
-            .get_committee(view_number)
-            .iter()
-            .map(|node| (node.clone(), vid_disperse.shares.remove(0)))
+        let mut shares = vid_disperse.shares.into_iter();
+        let shares = VidParams::share_counts::<TYPES>(membership, view_number)
+            .into_iter()
+            .map(|(node, count)| (node, shares.by_ref().take(count).collect()))
             .collect();
 
         Self {
@@ -180,17 +189,320 @@ impl<TYPES: NodeType> VidDisperse<TYPES> {
     }
 }
 
+/// A single storage node's part of a [`VidDisperse`]
+///
+/// The leader sends each storage node only its own share, rather than the whole dispersal.
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+pub struct VidDisperseShare<TYPES: NodeType> {
+    /// The view number for which this VID data is intended
+    pub view_number: TYPES::Time,
+    /// Block payload commitment
+    pub payload_commitment: VidCommitment,
+    /// The VID shares of the recipient
+    pub shares: Vec<<VidScheme as VidSchemeTrait>::Share>,
+    /// VID common data sent to all storage nodes
+    pub common: <VidScheme as VidSchemeTrait>::Common,
+    /// The storage node this share is intended for
+    pub recipient_key: TYPES::SignatureKey,
+}
+
+impl<TYPES: NodeType> VidDisperseShare<TYPES> {
+    /// Split a VID dispersal into the share of each storage node
+    #[must_use]
+    pub fn from_vid_disperse(vid_disperse: VidDisperse<TYPES>) -> Vec<Self> {
+        let VidDisperse {
+            view_number,
+            payload_commitment,
+            shares,
+            common,
+        } = vid_disperse;
+        shares
+            .into_iter()
+            .map(|(recipient_key, shares)| Self {
+                view_number,
+                payload_commitment,
+                shares,
+                common: common.clone(),
+                recipient_key,
+            })
+            .collect()
+    }
+
+    /// Split a signed VID dispersal into a signed share for each storage node
+    ///
+    /// The leader signs the payload commitment, which every share carries, so the signature of
+    /// the dispersal is valid for each of its shares. The signature does not cover the shares or
+    /// the common data, so recipients must verify those against the commitment.
+    #[must_use]
+    pub fn to_share_proposals(
+        proposal: Proposal<TYPES, VidDisperse<TYPES>>,
+    ) -> Vec<Proposal<TYPES, Self>> {
+        let Proposal {
+            data, signature, ..
+        } = proposal;
+        Self::from_vid_disperse(data)
+            .into_iter()
+            .map(|share| Proposal {
+                data: share,
+                signature: signature.clone(),
+                _pd: PhantomData,
+            })
+            .collect()
+    }
+}
+
+/// Parameters of the VID scheme a payload is dispersed with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct VidParams {
+    /// The number of shares needed to recover the payload
+    pub recovery_threshold: usize,
+    /// The number of shares the payload is dispersed into
+    pub num_shares: usize,
+}
+
+impl VidParams {
+    /// Parameters for `num_storage_nodes` storage nodes of equal stake, with one share each.
+    ///
+    /// The shares of any `f + 1` nodes recover the payload, where `f` is the number of faulty
+    /// nodes tolerated.
+    #[must_use]
+    pub fn for_num_nodes(num_storage_nodes: usize) -> Self {
+        Self {
+            recovery_threshold: num_storage_nodes / 3 + 1,
+            num_shares: num_storage_nodes,
+        }
+    }
+
+    /// Parameters for the storage nodes of the committee of `view_number`.
+    ///
+    /// The shares of any set of nodes holding more than the failure threshold of stake recover
+    /// the payload, and the shares of any set holding less do not.
+    #[must_use]
+    pub fn from_membership<TYPES: NodeType>(
+        membership: &TYPES::Membership,
+        view_number: TYPES::Time,
+    ) -> Self {
+        let membership = membership.for_view(view_number);
+        let Some((units, unit)) = stake_units::<TYPES>(&membership, view_number) else {
+            return Self::for_num_nodes(membership.get_committee(view_number).len());
+        };
+        // The stake of a set of nodes is a multiple of `unit`, so it reaches the failure
+        // threshold exactly when its number of shares reaches the threshold rounded up to a unit.
+        let failure_threshold = membership.failure_threshold();
+        let recovery_threshold = (failure_threshold + unit - 1) / unit;
+        Self {
+            recovery_threshold: recovery_threshold.as_usize(),
+            num_shares: units.values().sum(),
+        }
+    }
+
+    /// The number of shares each storage node of the committee of `view_number` gets.
+    ///
+    /// Nodes get shares in proportion to their stake, unless the stake table is too fine-grained
+    /// for that, in which case each node gets one share.
+    #[must_use]
+    pub fn share_counts<TYPES: NodeType>(
+        membership: &TYPES::Membership,
+        view_number: TYPES::Time,
+    ) -> BTreeMap<TYPES::SignatureKey, usize> {
+        let membership = membership.for_view(view_number);
+        match stake_units::<TYPES>(&membership, view_number) {
+            Some((units, _)) => units.into_iter().filter(|(_, count)| *count > 0).collect(),
+            None => membership
+                .get_committee(view_number)
+                .into_iter()
+                .map(|node| (node, 1))
+                .collect(),
+        }
+    }
+}
+
+/// The stake of each member of the committee of `view_number`, in units of the greatest common
+/// divisor of all stakes, along with that unit.
+///
+/// Returns `None` if the committee has no stake, or the stakes add up to more than
+/// [`VID_MAX_SHARES`] units.
+fn stake_units<TYPES: NodeType>(
+    membership: &TYPES::Membership,
+    view_number: TYPES::Time,
+) -> Option<(BTreeMap<TYPES::SignatureKey, usize>, U256)> {
+    let stakes: BTreeMap<_, _> = membership
+        .get_committee(view_number)
+        .into_iter()
+        .map(|node| {
+            let stake = membership
+                .get_stake(&node)
+                .map_or(U256::zero(), |entry| entry.get_stake());
+            (node, stake)
+        })
+        .collect();
+    let unit = stakes.values().fold(U256::zero(), |a, b| {
+        let (mut a, mut b) = (a, *b);
+        while !b.is_zero() {
+            (a, b) = (b, a % b);
+        }
+        a
+    });
+    if unit.is_zero() {
+        return None;
+    }
+    let mut total = 0;
+    let mut units = BTreeMap::new();
+    for (node, stake) in stakes {
+        let count = stake / unit;
+        total += count.min(U256::from(VID_MAX_SHARES + 1)).as_usize();
+        if total > VID_MAX_SHARES {
+            return None;
+        }
+        units.insert(node, count.as_usize());
+    }
+    Some((units, unit))
+}
+
 /// Trusted KZG setup for VID.
+pub type VidSrs = <UnivariateKzgPCS<Bls12_381> as PolynomialCommitmentScheme>::SRS;
+
+/// The trusted KZG setup loaded for this process and the file it was loaded from, see
+/// [`load_vid_srs`].
+static VID_SRS: OnceLock<(PathBuf, VidSrs)> = OnceLock::new();
+
+/// Errors when loading or using the trusted KZG setup for VID.
+#[derive(Snafu, Debug)]
+#[snafu(visibility(pub))]
+pub enum VidSrsError {
+    /// No setup was loaded, and the test setup is not available.
+    #[snafu(display("No KZG SRS for VID was loaded, set `vid_srs_path` in the HotShot config"))]
+    MissingSrs,
+    /// The setup file could not be read.
+    #[snafu(display("Failed to read the KZG SRS for VID from {}: {source}", path.display()))]
+    ReadSrs {
+        /// Path of the setup file
+        path: PathBuf,
+        /// The underlying IO error
+        source: std::io::Error,
+    },
+    /// The setup file does not contain a valid setup.
+    #[snafu(display("Failed to deserialize the KZG SRS for VID from {}: {source}", path.display()))]
+    DeserializeSrs {
+        /// Path of the setup file
+        path: PathBuf,
+        /// The underlying deserialization error
+        source: SerializationError,
+    },
+    /// Another setup was already loaded for this process.
+    #[snafu(display(
+        "Cannot load the KZG SRS for VID from {}, one was already loaded from {}",
+        path.display(),
+        loaded.display()
+    ))]
+    SrsAlreadyLoaded {
+        /// Path of the setup file
+        path: PathBuf,
+        /// Path of the setup file that was loaded
+        loaded: PathBuf,
+    },
+    /// The setup does not support the number of shares.
+    #[snafu(display(
+        "The KZG SRS for VID supports degree {max_degree}, but {num_shares} shares need degree {required_degree}"
+    ))]
+    SrsTooSmall {
+        /// The maximum degree supported by the setup
+        max_degree: usize,
+        /// The degree needed for the number of shares
+        required_degree: usize,
+        /// The number of shares payloads are dispersed into
+        num_shares: usize,
+    },
+}
+
+/// Load the trusted KZG setup for VID from a file of uncompressed, canonically serialized
+/// [`VidSrs`], and use it for every VID computation in this process.
 ///
-/// TESTING ONLY: don't use this in production
-/// TODO <https://github.com/EspressoSystems/HotShot/issues/1686>
+/// The setup is loaded once per process: loading it again from the same file does nothing.
+///
+/// # Errors
+/// If the file cannot be read or does not contain a valid setup, or a setup was already loaded
+/// from another file.
+pub fn load_vid_srs(path: &Path) -> Result<(), VidSrsError> {
+    let check_loaded = |(loaded, _): &(PathBuf, VidSrs)| {
+        if loaded == path {
+            Ok(())
+        } else {
+            Err(VidSrsError::SrsAlreadyLoaded {
+                path: path.to_path_buf(),
+                loaded: loaded.clone(),
+            })
+        }
+    };
+    if let Some(loaded) = VID_SRS.get() {
+        return check_loaded(loaded);
+    }
+    let bytes = std::fs::read(path).context(ReadSrsSnafu { path })?;
+    let srs =
+        VidSrs::deserialize_uncompressed(bytes.as_slice()).context(DeserializeSrsSnafu { path })?;
+    // Another node of this process may have loaded a setup in the meantime; keep the first one.
+    let _ = VID_SRS.set((path.to_path_buf(), srs));
+    check_loaded(VID_SRS.get().unwrap())
+}
+
+/// Check that a trusted KZG setup for VID is available for dispersing payloads into `num_shares`
+/// shares.
+///
+/// # Errors
+/// If no setup was loaded and the `test-srs` feature is off, or the loaded setup is too small.
+pub fn check_vid_srs(num_shares: usize) -> Result<(), VidSrsError> {
+    let Some((_, srs)) = VID_SRS.get() else {
+        return if cfg!(feature = "test-srs") {
+            Ok(())
+        } else {
+            Err(VidSrsError::MissingSrs)
+        };
+    };
+    let required_degree = checked_fft_size(num_shares).unwrap_or(usize::MAX);
+    let max_degree = srs.max_degree();
+    if max_degree < required_degree {
+        return Err(VidSrsError::SrsTooSmall {
+            max_degree,
+            required_degree,
+            num_shares,
+        });
+    }
+    Ok(())
+}
+
+/// Build the VID scheme with the given parameters from the loaded trusted KZG setup, or from the
+/// test setup if none was loaded and the `test-srs` feature is on.
+///
+/// # Errors
+/// If no suitable setup is available, see [`check_vid_srs`].
+///
+/// # Panics
+/// If the VID scheme cannot be built from a suitable setup.
+pub fn vid_scheme(params: VidParams) -> Result<VidScheme, VidSrsError> {
+    let VidParams {
+        recovery_threshold,
+        num_shares,
+    } = params;
+    check_vid_srs(num_shares)?;
+
+    match VID_SRS.get() {
+        Some((_, srs)) => Ok(VidScheme::new(recovery_threshold, num_shares, srs).unwrap()),
+        #[cfg(feature = "test-srs")]
+        None => Ok(VidScheme::new(recovery_threshold, num_shares, test_srs(num_shares)).unwrap()),
+        #[cfg(not(feature = "test-srs"))]
+        None => Err(VidSrsError::MissingSrs),
+    }
+}
+
+/// Trusted KZG setup for VID.
+///
+/// TESTING ONLY: don't use this in production, load a setup with [`load_vid_srs`] instead.
 ///
 /// # Panics
 /// ...because this is only for tests. This comment exists to pacify clippy.
+#[cfg(feature = "test-srs")]
 #[must_use]
-pub fn test_srs(
-    num_storage_nodes: usize,
-) -> <UnivariateKzgPCS<Bls12_381> as PolynomialCommitmentScheme>::SRS {
+pub fn test_srs(num_storage_nodes: usize) -> VidSrs {
     let mut rng = jf_utils::test_rng();
     UnivariateKzgPCS::<ark_bls12_381::Bls12_381>::gen_srs_for_testing(
         &mut rng,
@@ -234,6 +546,12 @@ impl<TYPES: NodeType> HasViewNumber<TYPES> for VidDisperse<TYPES> {
     }
 }
 
+impl<TYPES: NodeType> HasViewNumber<TYPES> for VidDisperseShare<TYPES> {
+    fn get_view_number(&self) -> TYPES::Time {
+        self.view_number
+    }
+}
+
 impl<TYPES: NodeType> HasViewNumber<TYPES> for QuorumProposal<TYPES> {
     fn get_view_number(&self) -> TYPES::Time {
         self.view_number
@@ -255,6 +573,8 @@ pub enum BlockError {
     InvalidTransactionLength,
     /// Inconsistent payload commitment.
     InconsistentPayloadCommitment,
+    /// The payload commitment cannot be checked without a trusted setup for VID.
+    MissingVidSrs,
 }
 
 /// Additional functions required to use a [`Leaf`] with hotshot-testing.
@@ -329,18 +649,22 @@ impl<TYPES: NodeType> Display for Leaf<TYPES> {
 }
 
 impl<TYPES: NodeType> Leaf<TYPES> {
-    /// Create a new leaf from its components.
-    #[must_use]
-    pub fn genesis(instance_state: &TYPES::InstanceState) -> Self {
+    /// Create the genesis leaf.
+    ///
+    /// # Errors
+    /// If no trusted setup for VID is available, which the genesis header needs to commit to the
+    /// genesis payload.
+    pub fn genesis(instance_state: &TYPES::InstanceState) -> Result<Self, VidSrsError> {
+        check_vid_srs(1)?;
         let (block_header, block_payload, _) = TYPES::BlockHeader::genesis(instance_state);
-        Self {
+        Ok(Self {
             view_number: TYPES::Time::genesis(),
             justify_qc: QuorumCertificate::<TYPES>::genesis(),
             parent_commitment: fake_commitment(),
             block_header: block_header.clone(),
             block_payload: Some(block_payload),
             proposer_id: <<TYPES as NodeType>::SignatureKey as SignatureKey>::genesis_proposer_pk(),
-        }
+        })
     }
 
     /// Time when this leaf was created.
@@ -370,11 +694,11 @@ impl<TYPES: NodeType> Leaf<TYPES> {
     /// # Errors
     ///
     /// Fails if the payload commitment doesn't match `self.block_header.payload_commitment()`
-    /// or if the transactions are of invalid length
+    /// or if the transactions are of invalid length, or if no trusted setup for VID is available
     pub fn fill_block_payload(
         &mut self,
         block_payload: TYPES::BlockPayload,
-        num_storage_nodes: usize,
+        vid_params: VidParams,
     ) -> Result<(), BlockError> {
         let encoded_txns = match block_payload.encode() {
             // TODO (Keyao) [VALIDATED_STATE] - Avoid collect/copy on the encoded transaction bytes.
@@ -382,7 +706,8 @@ impl<TYPES: NodeType> Leaf<TYPES> {
             Ok(encoded) => encoded.into_iter().collect(),
             Err(_) => return Err(BlockError::InvalidTransactionLength),
         };
-        let commitment = vid_commitment(&encoded_txns, num_storage_nodes);
+        let commitment =
+            vid_commitment(&encoded_txns, vid_params).map_err(|_| BlockError::MissingVidSrs)?;
         if commitment != self.block_header.payload_commitment() {
             return Err(BlockError::InconsistentPayloadCommitment);
         }
@@ -422,6 +747,21 @@ impl<TYPES: NodeType> Leaf<TYPES> {
             proposer_id: stored_view.proposer_id,
         }
     }
+
+    /// Create the leaf a quorum proposal proposes, without its block payload.
+    ///
+    /// The commitment of this leaf is what the leader signs when making the proposal.
+    #[must_use]
+    pub fn from_proposal(proposal: &QuorumProposal<TYPES>) -> Self {
+        Self {
+            view_number: proposal.view_number,
+            justify_qc: proposal.justify_qc.clone(),
+            parent_commitment: proposal.justify_qc.get_data().leaf_commit,
+            block_header: proposal.block_header.clone(),
+            block_payload: None,
+            proposer_id: proposal.proposer_id.clone(),
+        }
+    }
 }
 
 impl<TYPES: NodeType> TestableLeaf for Leaf<TYPES>
@@ -78,6 +78,18 @@ pub enum HotShotError<TYPES: NodeType> {
         /// Threshold of signatures needed for a quorum
         threshold: NonZeroU64,
     },
+    /// The trusted KZG setup for VID is missing or unusable
+    #[snafu(display("Failed to set up VID: {source}"))]
+    VidSrs {
+        /// The underlying setup error
+        source: crate::data::VidSrsError,
+    },
+    /// A protocol upgrade could not be scheduled
+    #[snafu(display("Invalid upgrade: {context}"))]
+    InvalidUpgrade {
+        /// why the upgrade was rejected
+        context: String,
+    },
     /// Miscelaneous error
     /// TODO fix this with
     /// #181 <https://github.com/EspressoSystems/HotShot/issues/181>
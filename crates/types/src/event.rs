@@ -1,12 +1,17 @@
 //! Events that a `HotShot` instance can emit
 
 use crate::{
-    data::{DAProposal, Leaf, QuorumProposal, UpgradeProposal, VidDisperse},
+    data::{DAProposal, Leaf, QuorumProposal, UpgradeProposal, VidDisperseShare},
     error::HotShotError,
+    light_client::StateSignatureRequestBody,
     message::Proposal,
     simple_certificate::QuorumCertificate,
-    traits::node_implementation::NodeType,
+    simple_vote::{DAVote, QuorumVote, UpgradeVote},
+    traits::{node_implementation::NodeType, signature_key::SignatureKey},
+    vote::{HasViewNumber, Vote},
 };
+use commit::Committable;
+use serde::{Deserialize, Serialize};
 
 use std::sync::Arc;
 /// A status event emitted by a `HotShot` instance
@@ -22,7 +27,7 @@ pub struct Event<TYPES: NodeType> {
 }
 
 /// The chain of leafs decided on with corresponding VID info if we have it
-pub type LeafChain<TYPES> = Vec<(Leaf<TYPES>, Option<VidDisperse<TYPES>>)>;
+pub type LeafChain<TYPES> = Vec<(Leaf<TYPES>, Option<VidDisperseShare<TYPES>>)>;
 /// The type and contents of a status event emitted by a `HotShot` instance
 ///
 /// This enum does not include metadata shared among all variants, such as the stage and view
@@ -98,4 +103,107 @@ pub enum EventType<TYPES: NodeType> {
         /// Public key of the leader submitting the proposal
         sender: TYPES::SignatureKey,
     },
+    /// A node signed two conflicting messages for the same view
+    Equivocation {
+        /// Both signed messages, which can be checked with [`Equivocation::is_valid`]
+        evidence: Equivocation<TYPES>,
+    },
+    /// A batch of the decided chain was fetched from a peer and stored
+    SyncProgress {
+        /// The view of the last decided leaf fetched so far
+        last_synced_view: TYPES::Time,
+        /// The number of decided leaves fetched so far
+        leaves_synced: u64,
+        /// Whether the node has caught up with the peer and handed over to live consensus
+        finished: bool,
+    },
+    /// The block payload of a decided leaf was recovered from the VID shares of peers and stored
+    PayloadRecovered {
+        /// The decided leaf, with its block payload filled in
+        leaf: Leaf<TYPES>,
+    },
+    /// This node signed the light client state of the latest decided leaf
+    StateSigned {
+        /// The state, along with our signature of it and our state key
+        signed_state: StateSignatureRequestBody,
+    },
+}
+
+/// Two conflicting messages signed by the same key for the same view.
+///
+/// Each variant holds both signed messages, so anyone who trusts the signer's public key can
+/// verify the evidence without any other context.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(bound(deserialize = ""))]
+pub enum Equivocation<TYPES: NodeType> {
+    /// Two quorum votes for different leaves
+    QuorumVote(QuorumVote<TYPES>, QuorumVote<TYPES>),
+    /// Two DA votes for different block payloads
+    DAVote(DAVote<TYPES>, DAVote<TYPES>),
+    /// Two upgrade votes for different upgrades
+    UpgradeVote(UpgradeVote<TYPES>, UpgradeVote<TYPES>),
+    /// Two quorum proposals for different leaves
+    QuorumProposal(
+        Proposal<TYPES, QuorumProposal<TYPES>>,
+        Proposal<TYPES, QuorumProposal<TYPES>>,
+    ),
+}
+
+impl<TYPES: NodeType> Equivocation<TYPES> {
+    /// The key which signed both messages
+    #[must_use]
+    pub fn signer(&self) -> TYPES::SignatureKey {
+        match self {
+            Self::QuorumVote(vote, _) => vote.get_signing_key(),
+            Self::DAVote(vote, _) => vote.get_signing_key(),
+            Self::UpgradeVote(vote, _) => vote.get_signing_key(),
+            Self::QuorumProposal(proposal, _) => proposal.data.proposer_id.clone(),
+        }
+    }
+
+    /// Check that both messages are for the same view, carry different data, and are validly
+    /// signed by [`Self::signer`].
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Self::QuorumVote(first, second) => Self::are_conflicting_votes(first, second),
+            Self::DAVote(first, second) => Self::are_conflicting_votes(first, second),
+            Self::UpgradeVote(first, second) => Self::are_conflicting_votes(first, second),
+            Self::QuorumProposal(first, second) => {
+                let signer = &first.data.proposer_id;
+                let first_commitment = Leaf::from_proposal(&first.data).commit();
+                let second_commitment = Leaf::from_proposal(&second.data).commit();
+                *signer == second.data.proposer_id
+                    && first.data.get_view_number() == second.data.get_view_number()
+                    && first_commitment != second_commitment
+                    && signer.validate(&first.signature, first_commitment.as_ref())
+                    && signer.validate(&second.signature, second_commitment.as_ref())
+            }
+        }
+    }
+
+    /// Check that two votes are for the same view, carry different data, and are validly signed by
+    /// the same key.
+    fn are_conflicting_votes<VOTE: Vote<TYPES>>(first: &VOTE, second: &VOTE) -> bool {
+        let signer = first.get_signing_key();
+        signer == second.get_signing_key()
+            && first.get_view_number() == second.get_view_number()
+            && first.get_data_commitment() != second.get_data_commitment()
+            && signer.validate(&first.get_signature(), first.get_data_commitment().as_ref())
+            && signer.validate(
+                &second.get_signature(),
+                second.get_data_commitment().as_ref(),
+            )
+    }
+}
+
+impl<TYPES: NodeType> HasViewNumber<TYPES> for Equivocation<TYPES> {
+    fn get_view_number(&self) -> TYPES::Time {
+        match self {
+            Self::QuorumVote(vote, _) => vote.get_view_number(),
+            Self::DAVote(vote, _) => vote.get_view_number(),
+            Self::UpgradeVote(vote, _) => vote.get_view_number(),
+            Self::QuorumProposal(proposal, _) => proposal.data.get_view_number(),
+        }
+    }
 }
@@ -1,6 +1,6 @@
 //! Types and Traits for the `HotShot` consensus module
 use displaydoc::Display;
-use std::{future::Future, num::NonZeroUsize, pin::Pin, time::Duration};
+use std::{future::Future, num::NonZeroUsize, path::PathBuf, pin::Pin, time::Duration};
 use traits::{election::ElectionConfig, signature_key::SignatureKey};
 pub mod consensus;
 pub mod data;
@@ -116,4 +116,16 @@ pub struct HotShotConfig<KEY: SignatureKey, ELECTIONCONFIG: ElectionConfig> {
     pub propose_max_round_time: Duration,
     /// the election configuration
     pub election_config: Option<ELECTIONCONFIG>,
+    /// Path to the trusted KZG setup for VID, see [`data::load_vid_srs`]. Required unless the
+    /// `test-srs` feature is enabled. The setup is loaded when the node is created, so it must be
+    /// loaded with [`data::load_vid_srs`] beforehand if the genesis block is built earlier.
+    pub vid_srs_path: Option<PathBuf>,
+    /// Whether to sign every message this node sends, and drop the messages it receives which are
+    /// not signed by their sender. Needed on networks which do not authenticate the sender, such
+    /// as the web server network. Messages with an invalid signature are always dropped.
+    pub authenticate_messages: bool,
+    /// The number of views before the newest decided view whose decided leaves this node keeps in
+    /// its storage. `None` keeps the whole decided chain, which fresh nodes need to sync from
+    /// genesis, so only nodes which do not serve the chain to their peers should set it.
+    pub decided_view_retention: Option<u64>,
 }
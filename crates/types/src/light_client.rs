@@ -1,14 +1,15 @@
 //! Types and structs associated with light client state
 
+use crate::traits::state_relay::StateRelay;
 use ark_ed_on_bn254::EdwardsConfig as Config;
 use ark_ff::PrimeField;
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
 use ethereum_types::U256;
-use jf_primitives::signatures::schnorr;
+use jf_primitives::signatures::{schnorr, SignatureScheme};
 use rand::SeedableRng;
 use rand_chacha::ChaCha20Rng;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 use tagged_base64::tagged;
 
 /// Base field in the prover circuit
@@ -41,6 +42,42 @@ pub struct StateSignatureRequestBody {
     pub signature: StateSignature,
 }
 
+impl StateSignatureRequestBody {
+    /// Sign a light client state with the given key pair.
+    ///
+    /// # Panics
+    /// Panics if signing fails, which cannot happen for a state of the expected length
+    #[must_use]
+    pub fn sign(key_pair: &StateKeyPair, state: LightClientState) -> Self {
+        let msg: [CircuitField; 7] = (&state).into();
+        let signature =
+            StateSignatureScheme::sign(&(), key_pair.sign_key_ref(), msg, &mut rand::thread_rng())
+                .expect("Failed to sign the light client state");
+        Self {
+            key: key_pair.ver_key(),
+            state,
+            signature,
+        }
+    }
+
+    /// Whether the signature is a valid signature of the state by the key
+    #[must_use]
+    pub fn verify(&self) -> bool {
+        let msg: [CircuitField; 7] = (&self.state).into();
+        StateSignatureScheme::verify(&(), &self.key, msg, &self.signature).is_ok()
+    }
+}
+
+/// How a node signs a light client state on every decide, and where it publishes it
+#[derive(Clone, Debug)]
+pub struct StateSigningConfig {
+    /// Commitment to the stake table of the light client, which is signed along with each state.
+    /// It is fixed for the lifetime of the node, even when the stake table changes across epochs.
+    pub stake_table_comm: (CircuitField, CircuitField, CircuitField),
+    /// Where the signed states are published
+    pub relay: Arc<dyn StateRelay>,
+}
+
 /// The state signatures bundle is a light client state and its signatures collected
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StateSignaturesBundle {
@@ -3,9 +3,9 @@
 //! This module contains types used to represent the various types of messages that
 //! `HotShot` nodes can send among themselves.
 
-use crate::data::{QuorumProposal, UpgradeProposal};
+use crate::data::{Leaf, QuorumProposal, UpgradeProposal};
 use crate::simple_certificate::{
-    DACertificate, ViewSyncCommitCertificate2, ViewSyncFinalizeCertificate2,
+    DACertificate, QuorumCertificate, ViewSyncCommitCertificate2, ViewSyncFinalizeCertificate2,
     ViewSyncPreCommitCertificate2,
 };
 use crate::simple_vote::{
@@ -15,19 +15,28 @@ use crate::simple_vote::{
 use crate::traits::signature_key::SignatureKey;
 use crate::vote::HasViewNumber;
 use crate::{
-    data::{DAProposal, VidDisperse},
+    data::{DAProposal, VidDisperseShare},
     simple_vote::QuorumVote,
     traits::{
-        network::{NetworkMsg, ViewMessage},
+        network::{
+            FailedToDeserializeSnafu, FailedToSerializeSnafu, NetworkError, NetworkMsg, ViewMessage,
+        },
         node_implementation::NodeType,
     },
 };
 
+use bincode::Options;
+use commit::Commitment;
 use derivative::Derivative;
 use either::Either::{self, Left, Right};
-use hotshot_constants::Version;
+use hotshot_constants::{Version, VERSION_0_1, VERSION_0_2};
+use hotshot_utils::{
+    bincode::bincode_opts,
+    version::{read_version, VERSION_BYTES},
+};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
 use std::{fmt::Debug, marker::PhantomData};
 
 /// Incoming message
@@ -42,9 +51,109 @@ pub struct Message<TYPES: NodeType> {
 
     /// The message kind
     pub kind: MessageKind<TYPES>,
+
+    /// The signature of the sender over the kind, view and version of this message, which binds
+    /// the message to its sender on networks that do not authenticate the sender themselves
+    pub signature: Option<<TYPES::SignatureKey as SignatureKey>::PureAssembledSignatureType>,
 }
 
-impl<TYPES: NodeType> NetworkMsg for Message<TYPES> {}
+impl<TYPES: NodeType> Message<TYPES> {
+    /// The bytes the sender of a message signs
+    fn signed_bytes(&self) -> Result<Vec<u8>, NetworkError> {
+        bincode_opts()
+            .serialize(&(self.version, self.kind.get_view_number(), &self.kind))
+            .context(FailedToSerializeSnafu)
+    }
+
+    /// Sign this message as its sender.
+    ///
+    /// # Errors
+    /// Returns an error if the message cannot be serialized or signed
+    pub fn sign(
+        &mut self,
+        private_key: &<TYPES::SignatureKey as SignatureKey>::PrivateKey,
+    ) -> Result<(), NetworkError> {
+        let signature = TYPES::SignatureKey::sign(private_key, &self.signed_bytes()?)
+            .map_err(|_| NetworkError::FailedToSign)?;
+        self.signature = Some(signature);
+        Ok(())
+    }
+
+    /// Whether this message carries a valid signature of its sender
+    #[must_use]
+    pub fn has_valid_signature(&self) -> bool {
+        let Some(signature) = &self.signature else {
+            return false;
+        };
+        self.signed_bytes()
+            .is_ok_and(|bytes| self.sender.validate(signature, &bytes))
+    }
+}
+
+/// On the wire, a message is its version followed by a body whose encoding depends on that
+/// version, so that nodes can decode messages of every version they support while a rolling
+/// upgrade is under way.
+///
+/// The version 0.1 body is the bincode of the sender and kind, so the envelope of an unsigned
+/// message is the bincode of the message as sent by nodes that predate the envelope. The
+/// signature of a signed message follows the body. Version 0.2 bodies are encoded the same way.
+impl<TYPES: NodeType> NetworkMsg for Message<TYPES> {
+    fn encode(&self) -> Result<Vec<u8>, NetworkError> {
+        let mut bytes = bincode_opts()
+            .serialize(&self.version)
+            .context(FailedToSerializeSnafu)?;
+        match self.version {
+            VERSION_0_1 | VERSION_0_2 => {
+                bincode_opts()
+                    .serialize_into(&mut bytes, &(&self.sender, &self.kind))
+                    .context(FailedToSerializeSnafu)?;
+                if let Some(signature) = &self.signature {
+                    bincode_opts()
+                        .serialize_into(&mut bytes, signature)
+                        .context(FailedToSerializeSnafu)?;
+                }
+            }
+            version => return Err(NetworkError::UnsupportedVersion { version }),
+        }
+        Ok(bytes)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, NetworkError> {
+        let version = read_version(bytes).ok_or(NetworkError::UnreadableVersion)?;
+        let body = &bytes[VERSION_BYTES..];
+        let (sender, kind, signature) = match version {
+            VERSION_0_1 | VERSION_0_2 => {
+                let (sender, kind) = bincode_opts()
+                    .allow_trailing_bytes()
+                    .deserialize::<(TYPES::SignatureKey, MessageKind<TYPES>)>(body)
+                    .context(FailedToDeserializeSnafu)?;
+                let body_len = bincode_opts()
+                    .serialized_size(&(&sender, &kind))
+                    .context(FailedToDeserializeSnafu)?;
+                let trailer = body
+                    .get(usize::try_from(body_len).unwrap_or(usize::MAX)..)
+                    .unwrap_or_default();
+                let signature = if trailer.is_empty() {
+                    None
+                } else {
+                    Some(
+                        bincode_opts()
+                            .deserialize(trailer)
+                            .context(FailedToDeserializeSnafu)?,
+                    )
+                };
+                (sender, kind, signature)
+            }
+            version => return Err(NetworkError::UnsupportedVersion { version }),
+        };
+        Ok(Message {
+            version,
+            sender,
+            kind,
+            signature,
+        })
+    }
+}
 
 impl<TYPES: NodeType> ViewMessage<TYPES> for Message<TYPES> {
     /// get the view number out of a message
@@ -61,7 +170,7 @@ impl<TYPES: NodeType> ViewMessage<TYPES> for Message<TYPES> {
 pub struct Messages<TYPES: NodeType>(pub Vec<Message<TYPES>>);
 
 /// A message type agnostic description of a message's purpose
-#[derive(PartialEq, Copy, Clone)]
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
 pub enum MessagePurpose {
     /// Message with a [quorum/DA] proposal.
     Proposal,
@@ -85,6 +194,8 @@ pub enum MessagePurpose {
     VidDisperse,
     /// Message with an upgrade proposal.
     Upgrade,
+    /// Request for or response with data a node is missing.
+    RequestResponse,
 }
 
 // TODO (da) make it more customized to the consensus layer, maybe separating the specific message
@@ -97,6 +208,8 @@ pub enum MessageKind<TYPES: NodeType> {
     Consensus(SequencingMessage<TYPES>),
     /// Messages relating to sharing data between nodes
     Data(DataMessage<TYPES>),
+    /// Messages requesting data a node is missing from a peer, and the peer's responses
+    RequestResponse(RequestResponseMessage<TYPES>),
 }
 
 impl<TYPES: NodeType> MessageKind<TYPES> {
@@ -118,7 +231,21 @@ impl<TYPES: NodeType> ViewMessage<TYPES> for MessageKind<TYPES> {
     fn get_view_number(&self) -> TYPES::Time {
         match &self {
             MessageKind::Consensus(message) => message.view_number(),
-            MessageKind::Data(DataMessage::SubmitTransaction(_, v)) => *v,
+            MessageKind::Data(DataMessage::SubmitTransaction(_, v))
+            | MessageKind::RequestResponse(RequestResponseMessage::Request(_, v)) => *v,
+            MessageKind::RequestResponse(RequestResponseMessage::Response(response)) => {
+                response.leaf.get_view_number()
+            }
+            MessageKind::RequestResponse(RequestResponseMessage::SyncRequest(request)) => {
+                request.anchor_view
+            }
+            MessageKind::RequestResponse(RequestResponseMessage::SyncResponse(response)) => {
+                response.request.anchor_view
+            }
+            MessageKind::RequestResponse(RequestResponseMessage::VidShareRequest(view)) => *view,
+            MessageKind::RequestResponse(RequestResponseMessage::VidShareResponse(share)) => {
+                share.data.get_view_number()
+            }
         }
     }
 
@@ -128,6 +255,7 @@ impl<TYPES: NodeType> ViewMessage<TYPES> for MessageKind<TYPES> {
             MessageKind::Data(message) => match message {
                 DataMessage::SubmitTransaction(_, _) => MessagePurpose::Data,
             },
+            MessageKind::RequestResponse(_) => MessagePurpose::RequestResponse,
         }
     }
 }
@@ -189,8 +317,11 @@ pub enum ProcessedCommitteeConsensusMessage<TYPES: NodeType> {
     DAVote(DAVote<TYPES>, TYPES::SignatureKey),
     /// Certificate for the DA.
     DACertificate(DACertificate<TYPES>, TYPES::SignatureKey),
-    /// VID dispersal data. Like [`DAProposal`]
-    VidDisperseMsg(Proposal<TYPES, VidDisperse<TYPES>>, TYPES::SignatureKey),
+    /// A storage node's VID share. Like [`DAProposal`]
+    VidDisperseMsg(
+        Proposal<TYPES, VidDisperseShare<TYPES>>,
+        TYPES::SignatureKey,
+    ),
 }
 
 impl<TYPES: NodeType> From<ProcessedCommitteeConsensusMessage<TYPES>>
@@ -306,11 +437,11 @@ pub enum CommitteeConsensusMessage<TYPES: NodeType> {
     /// Certificate data is available
     DACertificate(DACertificate<TYPES>),
 
-    /// Initiate VID dispersal.
+    /// The VID share of the recipient, sent directly to each storage node.
     ///
-    /// Like [`DAProposal`]. Use `Msg` suffix to distinguish from [`VidDisperse`].
+    /// Like [`DAProposal`]. Use `Msg` suffix to distinguish from [`VidDisperseShare`].
     /// TODO this variant should not be a [`CommitteeConsensusMessage`] because <https://github.com/EspressoSystems/HotShot/issues/1696>
-    VidDisperseMsg(Proposal<TYPES, VidDisperse<TYPES>>),
+    VidDisperseMsg(Proposal<TYPES, VidDisperseShare<TYPES>>),
 }
 
 /// Messages for sequencing consensus.
@@ -421,6 +552,75 @@ pub enum DataMessage<TYPES: NodeType> {
     SubmitTransaction(TYPES::Transaction, TYPES::Time),
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(bound(deserialize = "", serialize = ""))]
+/// Messages for fetching data a node is missing, e.g. the parent of a proposal, from a peer
+pub enum RequestResponseMessage<TYPES: NodeType> {
+    /// Request for data, along with the view the requesting node is in
+    Request(DataRequest<TYPES>, TYPES::Time),
+    /// Response with the requested data
+    Response(DataResponse<TYPES>),
+    /// Request for a batch of the decided chain
+    SyncRequest(SyncRequest<TYPES>),
+    /// Response with a batch of the decided chain
+    SyncResponse(SyncResponse<TYPES>),
+    /// Request for the VID share of the given view, to recover a missing block payload
+    VidShareRequest(TYPES::Time),
+    /// Response with the VID share of the responding node
+    VidShareResponse(Proposal<TYPES, VidDisperseShare<TYPES>>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(bound(deserialize = "", serialize = ""))]
+/// Data a node can request from a peer
+pub enum DataRequest<TYPES: NodeType> {
+    /// The leaf with the given commitment
+    Leaf(Commitment<Leaf<TYPES>>),
+    /// The leaf of the given view
+    View(TYPES::Time),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(bound(deserialize = "", serialize = ""))]
+/// A requested leaf, along with what is needed to check it against the QC chain
+pub struct DataResponse<TYPES: NodeType> {
+    /// The request this responds to
+    pub request: DataRequest<TYPES>,
+    /// The requested leaf
+    pub leaf: Leaf<TYPES>,
+    /// A certificate for the requested leaf
+    pub qc: QuorumCertificate<TYPES>,
+    /// The DA proposal with the leaf's payload, if the responding node has it
+    pub da_proposal: Option<DAProposal<TYPES>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(bound(deserialize = "", serialize = ""))]
+/// Request for the decided leaves following the anchor of the requesting node
+pub struct SyncRequest<TYPES: NodeType> {
+    /// The last decided leaf of the requesting node
+    pub anchor: Commitment<Leaf<TYPES>>,
+    /// The view of the last decided leaf of the requesting node
+    pub anchor_view: TYPES::Time,
+    /// The maximum number of leaves to send
+    pub max_leaves: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(bound(deserialize = "", serialize = ""))]
+/// A batch of the decided chain, along with what is needed to check it against the QC chain
+pub struct SyncResponse<TYPES: NodeType> {
+    /// The request this responds to
+    pub request: SyncRequest<TYPES>,
+    /// Decided leaves following the requested anchor, ordered by view number. Each leaf carries
+    /// its block payload if the responding node has it.
+    ///
+    /// Fewer leaves than requested means the responding node has no more decided leaves to send.
+    pub leaves: Vec<Leaf<TYPES>>,
+    /// A certificate for the last leaf in `leaves`, or `None` if `leaves` is empty
+    pub qc: Option<QuorumCertificate<TYPES>>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
 #[serde(bound(deserialize = ""))]
 /// Prepare qc from the leader
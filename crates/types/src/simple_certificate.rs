@@ -27,7 +27,7 @@ use serde::{Deserialize, Serialize};
 /// Trait which allows use to inject different threshold calculations into a Certificate type
 pub trait Threshold<TYPES: NodeType> {
     /// Calculate a threshold based on the membership
-    fn threshold<MEMBERSHIP: Membership<TYPES>>(membership: &MEMBERSHIP) -> u64;
+    fn threshold<MEMBERSHIP: Membership<TYPES>>(membership: &MEMBERSHIP) -> U256;
 }
 
 /// Defines a threshold which is 2f + 1 (Amount needed for Quorum)
@@ -35,8 +35,8 @@ pub trait Threshold<TYPES: NodeType> {
 pub struct SuccessThreshold {}
 
 impl<TYPES: NodeType> Threshold<TYPES> for SuccessThreshold {
-    fn threshold<MEMBERSHIP: Membership<TYPES>>(membership: &MEMBERSHIP) -> u64 {
-        membership.success_threshold().into()
+    fn threshold<MEMBERSHIP: Membership<TYPES>>(membership: &MEMBERSHIP) -> U256 {
+        membership.success_threshold()
     }
 }
 
@@ -45,8 +45,8 @@ impl<TYPES: NodeType> Threshold<TYPES> for SuccessThreshold {
 pub struct OneHonestThreshold {}
 
 impl<TYPES: NodeType> Threshold<TYPES> for OneHonestThreshold {
-    fn threshold<MEMBERSHIP: Membership<TYPES>>(membership: &MEMBERSHIP) -> u64 {
-        membership.failure_threshold().into()
+    fn threshold<MEMBERSHIP: Membership<TYPES>>(membership: &MEMBERSHIP) -> U256 {
+        membership.failure_threshold()
     }
 }
 
@@ -55,8 +55,8 @@ impl<TYPES: NodeType> Threshold<TYPES> for OneHonestThreshold {
 pub struct UpgradeThreshold {}
 
 impl<TYPES: NodeType> Threshold<TYPES> for UpgradeThreshold {
-    fn threshold<MEMBERSHIP: Membership<TYPES>>(membership: &MEMBERSHIP) -> u64 {
-        membership.upgrade_threshold().into()
+    fn threshold<MEMBERSHIP: Membership<TYPES>>(membership: &MEMBERSHIP) -> U256 {
+        membership.upgrade_threshold()
     }
 }
 
@@ -102,9 +102,11 @@ impl<TYPES: NodeType, VOTEABLE: Voteable + 'static, THRESHOLD: Threshold<TYPES>>
         if self.is_genesis && self.view_number == TYPES::Time::genesis() {
             return true;
         }
+        // Check against the committee in effect for the view of this certificate.
+        let membership = membership.for_view(self.view_number);
         let real_qc_pp = <TYPES::SignatureKey as SignatureKey>::get_public_parameter(
             membership.get_committee_qc_stake_table(),
-            U256::from(Self::threshold(membership)),
+            Self::threshold(&*membership),
         );
         <TYPES::SignatureKey as SignatureKey>::check(
             &real_qc_pp,
@@ -112,7 +114,7 @@ impl<TYPES: NodeType, VOTEABLE: Voteable + 'static, THRESHOLD: Threshold<TYPES>>
             self.signatures.as_ref().unwrap(),
         )
     }
-    fn threshold<MEMBERSHIP: Membership<TYPES>>(membership: &MEMBERSHIP) -> u64 {
+    fn threshold<MEMBERSHIP: Membership<TYPES>>(membership: &MEMBERSHIP) -> U256 {
         THRESHOLD::threshold(membership)
     }
     fn get_data(&self) -> &Self::Voteable {
@@ -8,6 +8,7 @@ pub mod node_implementation;
 pub mod qc;
 pub mod signature_key;
 pub mod stake_table;
+pub mod state_relay;
 pub mod states;
 pub mod storage;
 
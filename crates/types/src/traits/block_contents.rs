@@ -4,7 +4,7 @@
 //! describe the behaviors that a block is expected to have.
 
 use crate::{
-    data::{test_srs, VidCommitment, VidScheme, VidSchemeTrait},
+    data::{vid_scheme, VidCommitment, VidParams, VidScheme, VidSchemeTrait, VidSrsError},
     traits::ValidatedState,
     utils::BuilderCommitment,
 };
@@ -93,20 +93,16 @@ pub trait TestableBlock: BlockPayload + Debug {
 }
 
 /// Compute the VID payload commitment.
+/// # Errors
+/// If no trusted setup for VID is available, see [`vid_scheme`].
 /// # Panics
 /// If the VID computation fails.
-#[must_use]
 pub fn vid_commitment(
     encoded_transactions: &Vec<u8>,
-    num_storage_nodes: usize,
-) -> <VidScheme as VidSchemeTrait>::Commit {
-    let num_chunks = 1 << num_storage_nodes.ilog2();
-
-    // TODO <https://github.com/EspressoSystems/HotShot/issues/1686>
-    let srs = test_srs(num_storage_nodes);
-
-    let vid = VidScheme::new(num_chunks, num_storage_nodes, srs).unwrap();
-    vid.commit_only(encoded_transactions).unwrap()
+    vid_params: VidParams,
+) -> Result<<VidScheme as VidSchemeTrait>::Commit, VidSrsError> {
+    let vid = vid_scheme(vid_params)?;
+    Ok(vid.commit_only(encoded_transactions).unwrap())
 }
 
 /// Header of a block, which commits to a [`BlockPayload`].
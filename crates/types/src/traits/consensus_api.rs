@@ -3,10 +3,11 @@
 use crate::{
     data::Leaf,
     event::Event,
+    simple_certificate::{DACertificate, QuorumCertificate},
     traits::{
         node_implementation::{NodeImplementation, NodeType},
         signature_key::SignatureKey,
-        storage::StorageError,
+        storage::{SafetyVote, StorageError},
     },
 };
 use async_trait::async_trait;
@@ -42,10 +43,37 @@ pub trait ConsensusApi<TYPES: NodeType, I: NodeImplementation<TYPES>>: Send + Sy
     /// Notify the system of an event within `hotshot-consensus`.
     async fn send_event(&self, event: Event<TYPES>);
 
-    /// Store a leaf in the storage
-    async fn store_leaf(
+    /// Store decided leaves in the storage.
+    ///
+    /// The whole decided chain is kept, so this node can serve it to peers syncing from genesis,
+    /// unless the config sets a `decided_view_retention`, in which case older views are cleaned
+    /// up from the storage.
+    ///
+    /// This commits the storage, so callers should not hold the consensus lock while awaiting it.
+    async fn store_decided_leaves(&self, leaves: Vec<Leaf<TYPES>>) -> Result<(), StorageError>;
+
+    /// Get up to `limit` decided leaves after view `after` from the storage, ordered by view
+    /// number.
+    async fn get_decided_leaves(
         &self,
-        old_anchor_view: TYPES::Time,
-        leaf: Leaf<TYPES>,
-    ) -> Result<(), StorageError>;
+        after: TYPES::Time,
+        limit: usize,
+    ) -> Result<Vec<Leaf<TYPES>>, StorageError>;
+
+    /// Store a leaf that has been accepted, but not decided yet.
+    ///
+    /// This commits the storage, so callers should not hold the consensus lock while awaiting it.
+    async fn store_undecided_leaf(&self, leaf: Leaf<TYPES>) -> Result<(), StorageError>;
+
+    /// Store a DA certificate in the storage
+    async fn store_da_cert(&self, cert: DACertificate<TYPES>) -> Result<(), StorageError>;
+
+    /// Check `vote` against the safety record of this node, and durably record it if it can be sent.
+    ///
+    /// Returns `Ok(false)` if the vote would contradict a vote this node already sent. A vote must
+    /// only be sent if this returns `Ok(true)`, so the node cannot equivocate after a restart.
+    async fn record_vote(&self, vote: SafetyVote<TYPES>) -> Result<bool, StorageError>;
+
+    /// Durably record that this node is locked on the given QC.
+    async fn store_locked_qc(&self, qc: QuorumCertificate<TYPES>) -> Result<(), StorageError>;
 }
@@ -5,10 +5,11 @@
 
 use super::node_implementation::NodeType;
 
-use crate::traits::signature_key::SignatureKey;
+use crate::traits::{signature_key::SignatureKey, stake_table::StakeTableError};
 
+use ethereum_types::U256;
 use snafu::Snafu;
-use std::{collections::BTreeSet, fmt::Debug, hash::Hash, num::NonZeroU64};
+use std::{borrow::Cow, collections::BTreeSet, fmt::Debug, hash::Hash};
 
 /// Error for election problems
 #[derive(Snafu, Debug)]
@@ -19,6 +20,12 @@ pub enum ElectionError {
     /// NOTE: it would be better to make Election polymorphic over
     /// the election error and then have specific math errors
     MathError,
+    /// The stake table cannot form a committee
+    #[snafu(display("Invalid stake table: {error}"))]
+    InvalidStakeTable {
+        /// why the stake table was rejected
+        error: StakeTableError,
+    },
 }
 
 /// election config
@@ -42,10 +49,29 @@ pub trait Membership<TYPES: NodeType>:
 
     /// create an election
     /// TODO may want to move this to a testableelection trait
+    ///
+    /// # Errors
+    /// If `entries` cannot form a committee.
     fn create_election(
         entries: Vec<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry>,
         config: TYPES::ElectionConfigType,
-    ) -> Self;
+    ) -> Result<Self, ElectionError>;
+
+    /// Create an election with `config` over the same nodes as this one.
+    ///
+    /// Memberships whose stake table can change share it with the returned membership, so changes
+    /// made through either are seen by both; the default creates an independent election from
+    /// `entries`.
+    ///
+    /// # Errors
+    /// If `entries` cannot form a committee.
+    fn derive_election(
+        &self,
+        entries: Vec<<TYPES::SignatureKey as SignatureKey>::StakeTableEntry>,
+        config: TYPES::ElectionConfigType,
+    ) -> Result<Self, ElectionError> {
+        Self::create_election(entries, config)
+    }
 
     /// Clone the public key and corresponding stake table for current elected committee
     fn get_committee_qc_stake_table(
@@ -71,12 +97,29 @@ pub trait Membership<TYPES: NodeType>:
     /// Returns the number of total nodes in the committee
     fn total_nodes(&self) -> usize;
 
-    /// Returns the threshold for a specific `Membership` implementation
-    fn success_threshold(&self) -> NonZeroU64;
-
-    /// Returns the threshold for a specific `Membership` implementation
-    fn failure_threshold(&self) -> NonZeroU64;
-
-    /// Returns the threshold required to upgrade the network protocol
-    fn upgrade_threshold(&self) -> NonZeroU64;
+    /// Returns the stake needed to form a quorum certificate, in the same units as the stake table
+    fn success_threshold(&self) -> U256;
+
+    /// Returns the stake that guarantees at least one honest node, in the same units as the stake
+    /// table
+    fn failure_threshold(&self) -> U256;
+
+    /// Returns the stake required to upgrade the network protocol, in the same units as the stake
+    /// table
+    fn upgrade_threshold(&self) -> U256;
+
+    /// The membership in effect for view `view_number`.
+    ///
+    /// Votes and certificates for `view_number` are checked against the returned membership.
+    /// Memberships whose committee changes over time return the committee of the epoch containing
+    /// `view_number`; the default returns `self`.
+    fn for_view(&self, _view_number: TYPES::Time) -> Cow<'_, Self> {
+        Cow::Borrowed(self)
+    }
+
+    /// Notify the membership that the leaf of view `view_number` has been decided.
+    ///
+    /// Memberships whose committee changes over time use this to move into a new epoch; the
+    /// default does nothing.
+    fn set_decided_view(&self, _view_number: TYPES::Time) {}
 }
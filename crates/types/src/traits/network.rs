@@ -15,12 +15,15 @@ use super::{node_implementation::NodeType, signature_key::SignatureKey};
 use crate::{data::ViewNumber, message::MessagePurpose, BoxSyncFuture};
 use async_compatibility_layer::channel::UnboundedSendError;
 use async_trait::async_trait;
+use bincode::Options;
+use hotshot_constants::Version;
+use hotshot_utils::bincode::bincode_opts;
 use rand::{
     distributions::{Bernoulli, Uniform},
     prelude::Distribution,
 };
 use serde::{Deserialize, Serialize};
-use snafu::Snafu;
+use snafu::{ResultExt, Snafu};
 use std::{collections::BTreeSet, fmt::Debug, sync::Arc, time::Duration};
 
 impl From<NetworkNodeHandleError> for NetworkError {
@@ -119,6 +122,22 @@ pub enum NetworkError {
         /// originating bincode error
         source: bincode::Error,
     },
+    /// Failed to sign a network message with the key of this node
+    FailedToSign,
+    /// A DHT record mapping a node to its peer is not signed by the node, or maps another node
+    InvalidPeerRecord,
+    /// Failed to read or write the persisted version of the peer records of this node
+    PeerRecordVersion {
+        /// source of error
+        source: std::io::Error,
+    },
+    /// A network message is too short to carry a protocol version
+    UnreadableVersion,
+    /// A network message uses a protocol version this node cannot encode or decode
+    UnsupportedVersion {
+        /// the version of the message
+        version: Version,
+    },
     /// A timeout occurred
     Timeout {
         /// Source of error
@@ -175,6 +194,8 @@ pub enum ConsensusIntentEvent<K: SignatureKey> {
     CancelPollForVIDDisperse(u64),
     /// Cancel polling for transactions
     CancelPollForTransactions(u64),
+    /// The committee changed in a view, from the first set of members to the second
+    CommitteeChanged(u64, BTreeSet<K>, BTreeSet<K>),
 }
 
 impl<K: SignatureKey> ConsensusIntentEvent<K> {
@@ -198,7 +219,8 @@ impl<K: SignatureKey> ConsensusIntentEvent<K> {
             | ConsensusIntentEvent::PollForViewSyncCertificate(view_number)
             | ConsensusIntentEvent::PollForTransactions(view_number)
             | ConsensusIntentEvent::CancelPollForTransactions(view_number)
-            | ConsensusIntentEvent::PollFutureLeader(view_number, _) => *view_number,
+            | ConsensusIntentEvent::PollFutureLeader(view_number, _)
+            | ConsensusIntentEvent::CommitteeChanged(view_number, _, _) => *view_number,
             ConsensusIntentEvent::PollForLatestProposal
             | ConsensusIntentEvent::PollForLatestViewSyncCertificate => 1,
         }
@@ -209,6 +231,25 @@ impl<K: SignatureKey> ConsensusIntentEvent<K> {
 pub trait NetworkMsg:
     Serialize + for<'a> Deserialize<'a> + Clone + Sync + Send + Debug + 'static
 {
+    /// Encode the message for the wire.
+    ///
+    /// # Errors
+    /// Returns an error if the message cannot be serialized
+    fn encode(&self) -> Result<Vec<u8>, NetworkError> {
+        bincode_opts()
+            .serialize(self)
+            .context(FailedToSerializeSnafu)
+    }
+
+    /// Decode a message received from the wire.
+    ///
+    /// # Errors
+    /// Returns an error if the bytes are not a message of a version this node supports
+    fn decode(bytes: &[u8]) -> Result<Self, NetworkError> {
+        bincode_opts()
+            .deserialize(bytes)
+            .context(FailedToDeserializeSnafu)
+    }
 }
 
 impl NetworkMsg for Vec<u8> {}
@@ -7,6 +7,7 @@ use jf_plonk::errors::PlonkError;
 use jf_primitives::errors::PrimitivesError;
 
 /// Snapshots of the stake table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SnapshotVersion {
     /// the latest "Head" where all new changes are applied to
     Head,
@@ -194,6 +195,17 @@ pub trait StakeTableScheme {
     /// # Errors
     /// Return err if the `version` is not supported.
     fn try_iter(&self, version: SnapshotVersion) -> Result<Self::IntoIter, StakeTableError>;
+
+    /// Update the stake table when the epoch number advances, should be manually called.
+    /// `LastEpochStart` becomes the previous `EpochStart`, and `EpochStart` becomes the current head.
+    fn advance(&mut self);
+
+    /// Record the current head as the `BlockNum(block_num)` snapshot of the stake table, pruning
+    /// the snapshots which fall out of the retention window.
+    ///
+    /// # Errors
+    /// Return err if `block_num` is not after the last recorded block.
+    fn record_block(&mut self, block_num: u64) -> Result<(), StakeTableError>;
 }
 
 /// Error type for [`StakeTableScheme`]
@@ -217,6 +229,12 @@ pub enum StakeTableError {
     StakeOverflow,
     /// The historical snapshot requested is not supported.
     SnapshotUnsupported,
+    /// The block number is not after the last recorded block.
+    StaleBlockNum,
+    /// The serialized stake table is inconsistent.
+    MalformedSnapshot,
+    /// No key in the stake table has stake.
+    EmptyCommittee,
 }
 
 impl ark_std::error::Error for StakeTableError {}
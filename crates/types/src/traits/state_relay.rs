@@ -0,0 +1,33 @@
+//! Abstraction over where a node publishes the light client states it signs
+
+use crate::light_client::StateSignatureRequestBody;
+use async_trait::async_trait;
+use snafu::Snafu;
+use std::fmt::Debug;
+
+/// Error publishing a signed light client state
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum StateRelayError {
+    /// The signature does not verify against the state and key it was sent with
+    InvalidSignature,
+    /// The signing key is not in the stake table the relay collects signatures for
+    UnknownSigner,
+    /// The signed state could not be delivered to the relay
+    #[snafu(display("Failed to deliver the signed state: {context}"))]
+    Delivery {
+        /// why the delivery failed
+        context: String,
+    },
+}
+
+/// Collects the light client states signed by the nodes of the stake table, so that a light
+/// client prover can fetch a state along with enough signatures for it.
+#[async_trait]
+pub trait StateRelay: Send + Sync + Debug + 'static {
+    /// Publish a light client state signed by this node.
+    ///
+    /// # Errors
+    /// Returns an error if the signature is invalid or cannot be delivered
+    async fn publish(&self, request: StateSignatureRequestBody) -> Result<(), StateRelayError>;
+}
@@ -1,10 +1,16 @@
 //! Abstraction over on-disk storage of node state
 
 use super::node_implementation::NodeType;
-use crate::{data::Leaf, simple_certificate::QuorumCertificate, vote::HasViewNumber};
+use crate::{
+    data::Leaf,
+    simple_certificate::{DACertificate, QuorumCertificate},
+    simple_vote::TimeoutVote,
+    vote::HasViewNumber,
+};
 use async_trait::async_trait;
 use commit::Commitment;
 use derivative::Derivative;
+use serde::{Deserialize, Serialize};
 use snafu::Snafu;
 use std::collections::{BTreeMap, BTreeSet};
 /// Errors that can occur in the storage layer.
@@ -13,6 +19,18 @@ use std::collections::{BTreeMap, BTreeSet};
 pub enum StorageError {
     /// No genesis view was inserted
     NoGenesisView,
+    /// The underlying persistent store failed to read or write
+    #[snafu(display("Persistent store failure: {description}"))]
+    PersistenceError {
+        /// Description of the underlying failure
+        description: String,
+    },
+    /// The data in the store is inconsistent with what was requested
+    #[snafu(display("Storage inconsistency: {description}"))]
+    InconsistencyError {
+        /// Description of the inconsistency
+        description: String,
+    },
 }
 
 /// Result for a storage type
@@ -35,6 +53,24 @@ where
     async fn cleanup_storage_up_to_view(&self, view: TYPES::Time) -> Result<usize>;
     /// Get the latest anchored view
     async fn get_anchored_view(&self) -> Result<StoredView<TYPES>>;
+    /// Get the undecided views stored after the latest anchored view, ordered by view number.
+    async fn get_undecided_views(&self) -> Result<Vec<StoredView<TYPES>>>;
+    /// Get up to `limit` decided views after view `after`, ordered by view number.
+    async fn get_decided_views(
+        &self,
+        after: TYPES::Time,
+        limit: usize,
+    ) -> Result<Vec<StoredView<TYPES>>>;
+    /// Store a DA certificate, so the node can still vote on the matching proposal after a restart.
+    async fn append_da_cert(&self, cert: DACertificate<TYPES>) -> Result;
+    /// Get all the DA certificates in this storage, by view number.
+    async fn get_da_certs(&self) -> Result<BTreeMap<TYPES::Time, DACertificate<TYPES>>>;
+    /// Replace the [`SafetyRecord`] of this node.
+    ///
+    /// This must be committed before any vote it records leaves the node.
+    async fn update_safety_record(&self, record: SafetyRecord<TYPES>) -> Result;
+    /// Get the [`SafetyRecord`] of this node, or an empty record if it never voted.
+    async fn get_safety_record(&self) -> Result<SafetyRecord<TYPES>>;
     /// Commit this storage.
     async fn commit(&self) -> Result;
 
@@ -78,6 +114,12 @@ pub struct StorageState<TYPES: NodeType> {
     pub stored: BTreeMap<TYPES::Time, StoredView<TYPES>>,
     /// The views that have failed
     pub failed: BTreeSet<TYPES::Time>,
+    /// The views that have not been decided yet
+    pub undecided: BTreeMap<TYPES::Time, StoredView<TYPES>>,
+    /// The DA certificates that have been stored
+    pub da_certs: BTreeMap<TYPES::Time, DACertificate<TYPES>>,
+    /// The votes this node has sent
+    pub safety_record: SafetyRecord<TYPES>,
 }
 
 /// An entry to `Storage::append`. This makes it possible to commit both succeeded and failed views at the same time
@@ -90,8 +132,8 @@ where
     Success(StoredView<TYPES>),
     /// A failed view
     Failed(TYPES::Time),
-    // future improvement:
-    // InProgress(InProgressView),
+    /// A view with a valid proposal, which has not been decided yet
+    Undecided(StoredView<TYPES>),
 }
 
 impl<TYPES> From<StoredView<TYPES>> for ViewEntry<TYPES>
@@ -113,8 +155,9 @@ where
 }
 
 /// A view stored in the [`Storage`]
-#[derive(Clone, Debug, Derivative)]
+#[derive(Clone, Debug, Derivative, Serialize, Deserialize)]
 #[derivative(PartialEq)]
+#[serde(bound(deserialize = ""))]
 pub struct StoredView<TYPES: NodeType> {
     /// The view number of this view
     pub view_number: TYPES::Time,
@@ -158,3 +201,110 @@ where
         }
     }
 }
+
+/// The votes a node has sent and the QC it is locked on.
+///
+/// A node must never send a vote which contradicts this record, even after a restart, so the
+/// record is persisted in the [`Storage`] before a vote leaves the node.
+#[derive(Clone, Debug, Derivative, Serialize, Deserialize)]
+#[derivative(PartialEq, Default(bound = ""))]
+#[serde(bound(deserialize = ""))]
+pub struct SafetyRecord<TYPES: NodeType> {
+    /// The highest view this node has sent a quorum vote in
+    pub highest_voted_view: Option<TYPES::Time>,
+    /// The highest view this node has sent a DA vote in
+    pub highest_da_voted_view: Option<TYPES::Time>,
+    /// The highest view this node has sent an upgrade vote in
+    pub highest_upgrade_voted_view: Option<TYPES::Time>,
+    /// The highest round this node has sent a view sync vote in
+    pub highest_view_sync_round: Option<TYPES::Time>,
+    /// The QC of the leaf this node is locked on
+    pub locked_qc: Option<QuorumCertificate<TYPES>>,
+    /// The last timeout vote this node has sent
+    pub last_timeout_vote: Option<TimeoutVote<TYPES>>,
+}
+
+/// A vote this node is about to send, to be checked against its [`SafetyRecord`].
+#[derive(Clone, Debug)]
+pub enum SafetyVote<TYPES: NodeType> {
+    /// A quorum vote in the given view
+    Quorum(TYPES::Time),
+    /// A DA vote in the given view
+    DA(TYPES::Time),
+    /// A timeout vote
+    Timeout(TimeoutVote<TYPES>),
+    /// A view sync vote of any phase and relay, in the given round
+    ViewSync(TYPES::Time),
+    /// An upgrade vote in the given view
+    Upgrade(TYPES::Time),
+}
+
+impl<TYPES: NodeType> SafetyRecord<TYPES> {
+    /// The view of the timeout vote this node has sent last, if any
+    #[must_use]
+    pub fn last_timeout_view(&self) -> Option<TYPES::Time> {
+        self.last_timeout_vote
+            .as_ref()
+            .map(HasViewNumber::get_view_number)
+    }
+
+    /// The highest view this node has sent a quorum or timeout vote in, if any
+    #[must_use]
+    pub fn highest_view(&self) -> Option<TYPES::Time> {
+        self.highest_voted_view.max(self.last_timeout_view())
+    }
+
+    /// Check whether `vote` can be sent without contradicting a vote this node already sent, and
+    /// record it if so.
+    ///
+    /// Quorum, DA and upgrade votes may only be sent once per view. A quorum vote may not be sent
+    /// in a view this node has timed out in. Timeout and view sync votes are the same for a given
+    /// view, so they may be sent again, but never for an older view than the last one.
+    pub fn record_vote(&mut self, vote: SafetyVote<TYPES>) -> bool {
+        match vote {
+            SafetyVote::Quorum(view) => {
+                if Some(view) <= self.highest_view() {
+                    return false;
+                }
+                self.highest_voted_view = Some(view);
+            }
+            SafetyVote::DA(view) => {
+                if Some(view) <= self.highest_da_voted_view {
+                    return false;
+                }
+                self.highest_da_voted_view = Some(view);
+            }
+            SafetyVote::Timeout(vote) => {
+                if Some(vote.get_view_number()) < self.last_timeout_view() {
+                    return false;
+                }
+                self.last_timeout_vote = Some(vote);
+            }
+            SafetyVote::ViewSync(round) => {
+                if Some(round) < self.highest_view_sync_round {
+                    return false;
+                }
+                self.highest_view_sync_round = Some(round);
+            }
+            SafetyVote::Upgrade(view) => {
+                if Some(view) <= self.highest_upgrade_voted_view {
+                    return false;
+                }
+                self.highest_upgrade_voted_view = Some(view);
+            }
+        }
+        true
+    }
+
+    /// Lock on `qc`, unless this node is already locked on a QC from the same or a later view.
+    ///
+    /// Returns whether the record changed.
+    pub fn update_locked_qc(&mut self, qc: QuorumCertificate<TYPES>) -> bool {
+        let locked_view = self.locked_qc.as_ref().map(HasViewNumber::get_view_number);
+        if locked_view >= Some(qc.get_view_number()) {
+            return false;
+        }
+        self.locked_qc = Some(qc);
+        true
+    }
+}
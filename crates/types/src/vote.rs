@@ -67,7 +67,7 @@ pub trait Certificate<TYPES: NodeType>: HasViewNumber<TYPES> {
     fn is_valid_cert<MEMBERSHIP: Membership<TYPES>>(&self, membership: &MEMBERSHIP) -> bool;
     /// Returns the amount of stake needed to create this certificate
     // TODO: Make this a static ratio of the total stake of `Membership`
-    fn threshold<MEMBERSHIP: Membership<TYPES>>(membership: &MEMBERSHIP) -> u64;
+    fn threshold<MEMBERSHIP: Membership<TYPES>>(membership: &MEMBERSHIP) -> U256;
     /// Get the commitment which was voted on
     fn get_data(&self) -> &Self::Voteable;
     /// Get the vote commitment which the votes commit to
@@ -96,13 +96,34 @@ pub struct VoteAccumulator<
     /// A bitvec to indicate which node is active and send out a valid signature for certificate aggregation, this automatically do uniqueness check
     /// And a list of valid signatures for certificate aggregation
     pub signers: SignersMap<Commitment<VOTE::Commitment>, TYPES::SignatureKey>,
+    /// The vote counted for each key, kept as evidence in case the key signs a conflicting vote
+    pub signed_votes: HashMap<TYPES::SignatureKey, VOTE>,
     /// Phantom data to specify the types this accumulator is for
     pub phantom: PhantomData<(TYPES, VOTE, CERT)>,
 }
 
-impl<TYPES: NodeType, VOTE: Vote<TYPES>, CERT: Certificate<TYPES, Voteable = VOTE::Commitment>>
-    VoteAccumulator<TYPES, VOTE, CERT>
+impl<
+        TYPES: NodeType,
+        VOTE: Vote<TYPES> + Clone,
+        CERT: Certificate<TYPES, Voteable = VOTE::Commitment>,
+    > VoteAccumulator<TYPES, VOTE, CERT>
 {
+    /// If `vote` is validly signed, and its signer already had a vote for different data counted
+    /// by this accumulator, return that earlier vote.
+    ///
+    /// Together the two votes are evidence that the signer equivocated.
+    #[must_use]
+    pub fn get_conflicting_vote(&self, vote: &VOTE) -> Option<VOTE> {
+        let key = vote.get_signing_key();
+        let earlier_vote = self.signed_votes.get(&key)?;
+        if earlier_vote.get_data_commitment() == vote.get_data_commitment()
+            || !key.validate(&vote.get_signature(), vote.get_data_commitment().as_ref())
+        {
+            return None;
+        }
+        Some(earlier_vote.clone())
+    }
+
     /// Add a vote to the total accumulated votes.  Returns the accumulator or the certificate if we
     /// have accumulated enough votes to exceed the threshold for creating a certificate.
     ///
@@ -117,6 +138,9 @@ impl<TYPES: NodeType, VOTE: Vote<TYPES>, CERT: Certificate<TYPES, Voteable = VOT
             return Either::Left(());
         }
 
+        // The vote is counted by the committee in effect for its view.
+        let membership = membership.for_view(vote.get_view_number());
+        let membership = &*membership;
         let Some(stake_table_entry) = membership.get_stake(&key) else {
             return Either::Left(());
         };
@@ -129,6 +153,11 @@ impl<TYPES: NodeType, VOTE: Vote<TYPES>, CERT: Certificate<TYPES, Voteable = VOT
         let original_signature: <TYPES::SignatureKey as SignatureKey>::PureAssembledSignatureType =
             vote.get_signature();
 
+        // A key's vote only counts towards one commitment
+        if self.signed_votes.contains_key(&key) {
+            return Either::Left(());
+        }
+
         let (total_stake_casted, total_vote_map) = self
             .vote_outcomes
             .entry(vote_commitment)
@@ -151,14 +180,18 @@ impl<TYPES: NodeType, VOTE: Vote<TYPES>, CERT: Certificate<TYPES, Voteable = VOT
 
         // TODO: Get the stake from the stake table entry.
         *total_stake_casted += stake_table_entry.get_stake();
-        total_vote_map.insert(key, (vote.get_signature(), vote.get_data_commitment()));
+        total_vote_map.insert(
+            key.clone(),
+            (vote.get_signature(), vote.get_data_commitment()),
+        );
+        self.signed_votes.insert(key, vote.clone());
 
-        if *total_stake_casted >= CERT::threshold(membership).into() {
+        if *total_stake_casted >= CERT::threshold(membership) {
             // Assemble QC
             let real_qc_pp: <<TYPES as NodeType>::SignatureKey as SignatureKey>::QCParams =
                 <TYPES::SignatureKey as SignatureKey>::get_public_parameter(
                     stake_table,
-                    U256::from(CERT::threshold(membership)),
+                    CERT::threshold(membership),
                 );
 
             let real_qc_sig = <TYPES::SignatureKey as SignatureKey>::assemble(
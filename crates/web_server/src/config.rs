@@ -60,16 +60,16 @@ pub fn post_vote_route(view_number: u64) -> String {
     format!("api/votes/{view_number}")
 }
 
-/// get vid dispersal
+/// get the vid share of a storage node
 #[must_use]
-pub fn get_vid_disperse_route(view_number: u64) -> String {
-    format!("api/vid_disperse/{view_number}")
+pub fn get_vid_disperse_route(view_number: u64, recipient: &str) -> String {
+    format!("api/vid_disperse/{view_number}/{recipient}")
 }
 
-/// post vid dispersal
+/// post the vid share of a storage node
 #[must_use]
-pub fn post_vid_disperse_route(view_number: u64) -> String {
-    format!("api/vid_disperse/{view_number}")
+pub fn post_vid_disperse_route(view_number: u64, recipient: &str) -> String {
+    format!("api/vid_disperse/{view_number}/{recipient}")
 }
 
 /// get upgrade route
@@ -60,8 +60,8 @@ struct WebServerState<KEY> {
     oldest_vote: u64,
     /// view sync: view number of oldest votes in memory
     oldest_view_sync_vote: u64,
-    /// view number -> (secret, string)
-    vid_disperses: HashMap<u64, (String, Vec<u8>)>,
+    /// view number -> storage node key -> VID share
+    vid_disperses: HashMap<u64, HashMap<String, Vec<u8>>>,
     /// view for the oldest vid disperal
     oldest_vid_disperse: u64,
     /// view of most recent vid dispersal
@@ -235,10 +235,15 @@ pub trait WebServerDataSource<KEY> {
     /// # Errors
     /// Error if unable to serve.
     fn proposal(&self, view_number: u64) -> Option<(String, Vec<u8>)>;
-    /// Post vid disperal
+    /// Post the vid share of a storage node
     /// # Errors
     /// Error if unable to serve.
-    fn post_vid_disperse(&mut self, view_number: u64, disperse: Vec<u8>) -> Result<(), Error>;
+    fn post_vid_disperse(
+        &mut self,
+        view_number: u64,
+        recipient: &str,
+        disperse: Vec<u8>,
+    ) -> Result<(), Error>;
     /// Post vid vote
     /// # Errors
     /// Error if unable to serve.
@@ -248,10 +253,14 @@ pub trait WebServerDataSource<KEY> {
     /// Error if unable to serve.
     fn post_vid_certificate(&mut self, view_number: u64, certificate: Vec<u8>)
         -> Result<(), Error>;
-    /// Get vid dispersal
+    /// Get the vid share of a storage node
     /// # Errors
     /// Error if unable to serve.
-    fn get_vid_disperse(&self, view_number: u64) -> Result<Option<Vec<Vec<u8>>>, Error>;
+    fn get_vid_disperse(
+        &self,
+        view_number: u64,
+        recipient: &str,
+    ) -> Result<Option<Vec<Vec<u8>>>, Error>;
     /// Get vid votes
     /// # Errors
     /// Error if unable to serve.
@@ -286,22 +295,21 @@ impl<KEY: SignatureKey> WebServerDataSource<KEY> for WebServerState<KEY> {
         }
     }
 
-    /// Return the VID disperse data that the server has received for a particular view
-    fn get_vid_disperse(&self, view_number: u64) -> Result<Option<Vec<Vec<u8>>>, Error> {
-        match self.vid_disperses.get(&view_number) {
-            Some(disperse) => {
-                if disperse.1.is_empty() {
-                    Err(ServerError {
-                        status: StatusCode::NotImplemented,
-                        message: format!("VID disperse not found for view {view_number}"),
-                    })
-                } else {
-                    Ok(Some(vec![disperse.1.clone()]))
-                }
-            }
+    /// Return the VID share that the server has received for a storage node for a particular view
+    fn get_vid_disperse(
+        &self,
+        view_number: u64,
+        recipient: &str,
+    ) -> Result<Option<Vec<Vec<u8>>>, Error> {
+        match self
+            .vid_disperses
+            .get(&view_number)
+            .and_then(|shares| shares.get(recipient))
+        {
+            Some(share) => Ok(Some(vec![share.clone()])),
             None => Err(ServerError {
                 status: StatusCode::NotImplemented,
-                message: format!("VID disperse not found for view {view_number}"),
+                message: format!("VID share not found for view {view_number}"),
             }),
         }
     }
@@ -571,8 +579,13 @@ impl<KEY: SignatureKey> WebServerDataSource<KEY> for WebServerState<KEY> {
         Ok(())
     }
 
-    fn post_vid_disperse(&mut self, view_number: u64, mut disperse: Vec<u8>) -> Result<(), Error> {
-        info!("Received VID disperse for view {}", view_number);
+    fn post_vid_disperse(
+        &mut self,
+        view_number: u64,
+        recipient: &str,
+        disperse: Vec<u8>,
+    ) -> Result<(), Error> {
+        info!("Received VID share for view {}", view_number);
         if view_number > self.recent_vid_disperse {
             self.recent_vid_disperse = view_number;
         }
@@ -586,8 +599,9 @@ impl<KEY: SignatureKey> WebServerDataSource<KEY> for WebServerState<KEY> {
         }
         self.vid_disperses
             .entry(view_number)
-            .and_modify(|(_, empty_proposal)| empty_proposal.append(&mut disperse))
-            .or_insert_with(|| (String::new(), disperse));
+            .or_default()
+            .entry(recipient.to_string())
+            .or_insert(disperse);
         Ok(())
     }
 
@@ -777,7 +791,8 @@ where
     .get("getviddisperse", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
-            state.get_vid_disperse(view_number)
+            let recipient: &str = req.string_param("recipient")?;
+            state.get_vid_disperse(view_number, recipient)
         }
         .boxed()
     })?
@@ -854,8 +869,9 @@ where
     .post("postviddisperse", |req, state| {
         async move {
             let view_number: u64 = req.integer_param("view_number")?;
+            let recipient: &str = req.string_param("recipient")?;
             let disperse = req.body_bytes();
-            state.post_vid_disperse(view_number, disperse)
+            state.post_vid_disperse(view_number, recipient, disperse)
         }
         .boxed()
     })?